@@ -1,25 +1,94 @@
+mod classification;
+pub use classification::*;
+
+mod convention;
+pub use convention::*;
+
+mod demangle;
+pub use demangle::*;
+
+mod disassembly;
+pub use disassembly::*;
+
+mod loader;
+pub use loader::*;
+
+mod pattern;
+pub use pattern::*;
+
+mod protection;
+pub use protection::*;
+
+mod prototypes;
+pub use prototypes::*;
+
+mod rtti;
+pub use rtti::*;
+
+mod seh;
+pub use seh::*;
+
+mod signatures;
+pub use signatures::*;
+
+mod stack;
+pub use stack::*;
+
+mod switch;
+pub use switch::*;
+
+mod toolchain;
+pub use toolchain::*;
+
+mod unwind;
+pub use unwind::*;
+
+use crate::parsers::SharedBytes;
+
 use std::collections::BTreeMap;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
-struct MemorySlice {
+pub struct MemorySlice {
     /// relative virtual address
-    rva: u64,
+    pub rva: u64,
     /// length of the memory slice
-    length: u64,
+    pub length: u64,
 }
 
-type MemoryMap = BTreeMap<MemorySlice, MemorySection>;
+impl MemorySlice {
+    pub fn end(&self) -> u64 {
+        self.rva + self.length
+    }
 
-enum MemorySection {
-    Code(Box<[u8]>),
-    InitializedData(Box<[u8]>),
+    pub fn contains(&self, rva: u64) -> bool {
+        rva >= self.rva && rva < self.end()
+    }
+}
+
+pub type MemoryMap = BTreeMap<MemorySlice, MemorySection>;
+
+/// A section of a memory map. The data may share the buffer of the parsed file.
+pub enum MemorySection {
+    Code(SharedBytes),
+    InitializedData(SharedBytes),
     UninitializedData,
 }
 
+impl MemorySection {
+    /// The backing data of the section, if it has any.
+    pub fn data(&self) -> Option<&[u8]> {
+        match self {
+            MemorySection::Code(data) | MemorySection::InitializedData(data) => Some(data),
+            MemorySection::UninitializedData => None,
+        }
+    }
+}
+
 enum AnalysisItemType {
     /// Interpret the item as another type than the default for the section
     ReinterpretItem,
-    
+
+    /// A word of a data section, see `Disassembly::data_classification`
     DataType,
     Comment,
     Function,
@@ -0,0 +1,477 @@
+use crate::analysis::{
+    is_code, Addressing, Architecture, Disassembly, LoadedImage, MemorySection, SymbolKind,
+};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::convert::TryInto;
+use zydis::{InstructionCategory, OperandType, Register};
+
+/// The smallest number of consecutive pointers that form a pointer array.
+const MIN_POINTER_ARRAY_LENGTH: usize = 3;
+/// The shortest string a string pointer may point to, in characters.
+const MIN_STRING_LENGTH: usize = 4;
+
+/// What a word in a data section holds.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DataType {
+    /// A pointer to code, such as a callback or a virtual function.
+    CodePointer(u64),
+    /// A pointer to other data of the image.
+    DataPointer(u64),
+    /// A pointer to a NUL-terminated ASCII or UTF-16 string.
+    StringPointer(u64),
+    /// A single precision value, or a double precision value for 8-byte words.
+    Float,
+    Integer,
+}
+
+impl DataType {
+    /// The address pointed to, for pointers.
+    pub fn target(self) -> Option<u64> {
+        match self {
+            DataType::CodePointer(target)
+            | DataType::DataPointer(target)
+            | DataType::StringPointer(target) => Some(target),
+            DataType::Float | DataType::Integer => None,
+        }
+    }
+
+    pub fn is_pointer(self) -> bool {
+        self.target().is_some()
+    }
+}
+
+/// Consecutive pointers in a data section, such as a table of callbacks or of strings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PointerArray {
+    pub address: u64,
+    /// The number of pointers.
+    pub length: usize,
+    /// Whether every entry points to code, as in a function pointer table or a virtual
+    /// function table.
+    pub is_function_table: bool,
+}
+
+/// The words of the data sections of an image, classified by what they most likely hold.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DataClassification {
+    /// The size of the classified words: 4 bytes in 32-bit images, 8 bytes in x64 images.
+    pub word_size: u64,
+    /// The classified words, keyed by address. Only aligned words are classified. Zero words
+    /// are left out, as they are as often padding as they are integers or null pointers, and
+    /// so are import address table slots.
+    pub words: BTreeMap<u64, DataType>,
+    /// The pointer arrays, keyed by address.
+    pub pointer_arrays: BTreeMap<u64, PointerArray>,
+}
+
+/// How the code refers to a data address.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+struct DataReference {
+    /// Whether an x87 or SSE instruction accesses the address.
+    float: bool,
+}
+
+impl DataClassification {
+    /// Classifies the words of the data sections of a flat image.
+    ///
+    /// Words the loader relocates are pointers. In images without relocation information,
+    /// words pointing into the image are taken as pointers, except that pointers to code that
+    /// was not disassembled yet need a reference from the code or neighboring pointers to back
+    /// them up. Other words are floats if the code accesses them as such or their exponent is
+    /// in a common range, and integers otherwise.
+    pub fn classify(image: &LoadedImage, disassembly: &Disassembly) -> Self {
+        let DataWords {
+            word_size,
+            references,
+            values,
+            pointers,
+        } = match DataWords::read(image, disassembly) {
+            Some(data_words) => data_words,
+            None => return Self::default(),
+        };
+
+        let mut words = BTreeMap::new();
+        for (&address, &value) in &values {
+            let reference = references.get(&address);
+            let data_type = if pointers.contains(&address) {
+                if is_code(image, value) {
+                    DataType::CodePointer(value)
+                } else if is_string(image, value) {
+                    DataType::StringPointer(value)
+                } else {
+                    DataType::DataPointer(value)
+                }
+            } else if matches!(reference, Some(reference) if reference.float)
+                || (reference.is_none() && looks_like_float(value, word_size))
+            {
+                DataType::Float
+            } else {
+                DataType::Integer
+            };
+            words.insert(address, data_type);
+        }
+
+        let pointers = words
+            .iter()
+            .filter(|(_, data_type)| data_type.is_pointer())
+            .map(|(&address, _)| address);
+        let mut pointer_arrays = BTreeMap::new();
+        for (address, length) in runs(pointers, word_size, &references) {
+            if length < MIN_POINTER_ARRAY_LENGTH {
+                continue;
+            }
+            let is_function_table = (0..length as u64).all(|index| {
+                matches!(
+                    words.get(&(address + index * word_size)),
+                    Some(DataType::CodePointer(_))
+                )
+            });
+            pointer_arrays.insert(
+                address,
+                PointerArray {
+                    address,
+                    length,
+                    is_function_table,
+                },
+            );
+        }
+
+        Self {
+            word_size,
+            words,
+            pointer_arrays,
+        }
+    }
+
+    /// The targets of the code pointers, in address order and without duplicates.
+    pub fn code_pointers(&self) -> impl Iterator<Item = u64> {
+        let targets: BTreeSet<u64> = self
+            .words
+            .values()
+            .filter_map(|data_type| match data_type {
+                DataType::CodePointer(target) => Some(*target),
+                _ => None,
+            })
+            .collect();
+        targets.into_iter()
+    }
+}
+
+/// The targets of the words `DataClassification::classify` takes as pointers to code, in
+/// address order and without duplicates. The disassembler follows them without classifying the
+/// other words.
+pub(crate) fn code_pointer_targets(
+    image: &LoadedImage,
+    disassembly: &Disassembly,
+) -> BTreeSet<u64> {
+    let data_words = match DataWords::read(image, disassembly) {
+        Some(data_words) => data_words,
+        None => return BTreeSet::new(),
+    };
+    data_words
+        .pointers
+        .iter()
+        .map(|address| data_words.values[address])
+        .filter(|&target| is_code(image, target))
+        .collect()
+}
+
+/// The words of the data sections of a flat image, and which of them are pointers.
+struct DataWords {
+    word_size: u64,
+    references: HashMap<u64, DataReference>,
+    /// The nonzero words other than import address table slots, keyed by address.
+    values: BTreeMap<u64, u64>,
+    /// The addresses of the words taken as pointers.
+    pointers: BTreeSet<u64>,
+}
+
+impl DataWords {
+    fn read(image: &LoadedImage, disassembly: &Disassembly) -> Option<Self> {
+        let word_size = match (image.architecture, &image.addressing) {
+            (Architecture::X86_32, Addressing::Flat) => 4,
+            (Architecture::X86_64, Addressing::Flat) => 8,
+            _ => return None,
+        };
+        let references = data_references(image, disassembly);
+        let import_slots: HashSet<u64> = image
+            .symbols
+            .iter()
+            .filter(|symbol| matches!(symbol.kind, SymbolKind::Import { .. }))
+            .map(|symbol| symbol.address)
+            .collect();
+
+        // Pointer candidates, and whether they are backed up by relocations or valid targets
+        let mut candidates = BTreeMap::new();
+        let mut values = BTreeMap::new();
+        for (slice, section) in &image.memory_map {
+            let data = match section {
+                MemorySection::InitializedData(data) => data,
+                _ => continue,
+            };
+            let start = image.base_address + slice.rva;
+            let first = (start + word_size - 1) & !(word_size - 1);
+            for address in (first..start + data.len() as u64).step_by(word_size as usize) {
+                let offset = (address - start) as usize;
+                let value = match data.get(offset..offset + word_size as usize) {
+                    Some(bytes) if word_size == 4 => {
+                        u64::from(u32::from_le_bytes(bytes.try_into().unwrap()))
+                    }
+                    Some(bytes) => u64::from_le_bytes(bytes.try_into().unwrap()),
+                    None => break,
+                };
+                if value == 0 || import_slots.contains(&address) {
+                    continue;
+                }
+                values.insert(address, value);
+                if let Some(confirmed) = pointer_candidate(image, disassembly, address, value) {
+                    candidates.insert(address, confirmed);
+                }
+            }
+        }
+
+        // Unconfirmed candidates count if the code refers to them, or if they are part of a
+        // run of pointers
+        let candidate_runs = runs(candidates.keys().copied(), word_size, &references);
+        let pointers = values
+            .keys()
+            .copied()
+            .filter(|&address| match candidates.get(&address) {
+                Some(true) => true,
+                Some(false) => {
+                    references.contains_key(&address)
+                        || run_length(&candidate_runs, address, word_size)
+                            >= MIN_POINTER_ARRAY_LENGTH
+                }
+                None => false,
+            })
+            .collect();
+        Some(Self {
+            word_size,
+            references,
+            values,
+            pointers,
+        })
+    }
+}
+
+/// Whether a word may be a pointer, and if so, whether the relocations or its target confirm
+/// it.
+fn pointer_candidate(
+    image: &LoadedImage,
+    disassembly: &Disassembly,
+    address: u64,
+    value: u64,
+) -> Option<bool> {
+    if !image.relocations.is_empty() {
+        return if image.relocations.contains(&address) {
+            Some(true)
+        } else {
+            None
+        };
+    }
+
+    image.slice_at(value)?;
+    if !is_code(image, value) {
+        return Some(true);
+    }
+    if disassembly.instructions.contains_key(&value) {
+        return Some(true);
+    }
+    // Pointing into the middle of an instruction
+    match disassembly.instructions.range(..value).next_back() {
+        Some((_, instruction)) if instruction.end() > value => None,
+        _ => Some(false),
+    }
+}
+
+/// Finds the runs of consecutive words among the given ascending addresses, and returns their
+/// lengths keyed by start address. Referenced words start new runs, as the code treats them as
+/// items of their own.
+fn runs(
+    addresses: impl Iterator<Item = u64>,
+    word_size: u64,
+    references: &HashMap<u64, DataReference>,
+) -> BTreeMap<u64, usize> {
+    let mut runs = BTreeMap::new();
+    let mut current: Option<(u64, usize)> = None;
+    for address in addresses {
+        current = match current {
+            Some((start, length))
+                if start + length as u64 * word_size == address
+                    && !references.contains_key(&address) =>
+            {
+                Some((start, length + 1))
+            }
+            _ => {
+                if let Some((start, length)) = current {
+                    runs.insert(start, length);
+                }
+                Some((address, 1))
+            }
+        };
+    }
+    if let Some((start, length)) = current {
+        runs.insert(start, length);
+    }
+    runs
+}
+
+/// The length of the run the word at the given address is part of, or zero.
+fn run_length(runs: &BTreeMap<u64, usize>, address: u64, word_size: u64) -> usize {
+    match runs.range(..=address).next_back() {
+        Some((&start, &length)) if address < start + length as u64 * word_size => length,
+        _ => 0,
+    }
+}
+
+/// The data addresses the instructions refer to, through memory operands or immediates.
+fn data_references(image: &LoadedImage, disassembly: &Disassembly) -> HashMap<u64, DataReference> {
+    let mut references = HashMap::new();
+    for instruction in disassembly.instructions.values() {
+        let decoded = &instruction.decoded;
+        let float = matches!(
+            decoded.meta.category,
+            InstructionCategory::X87_ALU | InstructionCategory::SSE
+        );
+        for operand in &decoded.operands[..decoded.operand_count as usize] {
+            let (address, float) = match operand.ty {
+                OperandType::MEMORY if operand.mem.base == Register::NONE => {
+                    (operand.mem.disp.displacement as u64, float)
+                }
+                OperandType::MEMORY if operand.mem.base == Register::RIP => {
+                    let ip = image.instruction_pointer(instruction.address);
+                    match decoded.calc_absolute_address(ip, operand) {
+                        Ok(address) => (address, float),
+                        Err(_) => continue,
+                    }
+                }
+                OperandType::IMMEDIATE if !operand.imm.is_relative => (operand.imm.value, false),
+                _ => continue,
+            };
+            let address = match image.architecture {
+                Architecture::X86_64 => address,
+                _ => address & 0xFFFF_FFFF,
+            };
+            if matches!(
+                image.slice_at(address),
+                Some((_, MemorySection::InitializedData(_)))
+                    | Some((_, MemorySection::UninitializedData))
+            ) {
+                let reference: &mut DataReference = references.entry(address).or_default();
+                reference.float |= float;
+            }
+        }
+    }
+    references
+}
+
+/// Whether a NUL-terminated string of printable ASCII or UTF-16 characters starts at the
+/// given address.
+fn is_string(image: &LoadedImage, address: u64) -> bool {
+    let bytes = match image.bytes_at(address) {
+        Some(bytes) => bytes,
+        None => return false,
+    };
+    let is_printable = |byte: u8| (0x20..0x7F).contains(&byte) || b"\t\r\n".contains(&byte);
+
+    let ascii = bytes.iter().take_while(|&&byte| is_printable(byte)).count();
+    if ascii >= MIN_STRING_LENGTH && bytes.get(ascii) == Some(&0) {
+        return true;
+    }
+    let utf16 = bytes
+        .chunks_exact(2)
+        .take_while(|pair| is_printable(pair[0]) && pair[1] == 0)
+        .count();
+    utf16 >= MIN_STRING_LENGTH && bytes.get(utf16 * 2..utf16 * 2 + 2) == Some(&[0, 0][..])
+}
+
+/// Whether the exponent of a word taken as floating point value is in the range common for
+/// constants, roughly 2^-15 to 2^16, which few integers hit by chance.
+fn looks_like_float(value: u64, word_size: u64) -> bool {
+    if word_size == 4 {
+        let exponent = (value >> 23) & 0xFF;
+        (0x70..=0x8F).contains(&exponent)
+    } else {
+        let exponent = (value >> 52) & 0x7FF;
+        (0x3F0..=0x40F).contains(&exponent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::loader::tests::test_image;
+
+    fn image(words: &[u32], relocations: BTreeSet<u64>) -> LoadedImage {
+        let mut data: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+        data.resize(0x100, 0);
+        data[0x80..0x86].copy_from_slice(b"Hello\0");
+
+        let mut image = test_image(&[], data);
+        image.relocations = relocations;
+        image
+    }
+
+    #[test]
+    fn classifies_relocated_words() {
+        let words = [
+            0x0040_1000, // code
+            0x0040_1010, // code
+            0x0040_1020, // code
+            0x0040_2080, // string
+            0x0040_2000, // data
+            0x3FC0_0000, // 1.5
+            0x0040_1030, // code, but not relocated
+            1234,
+        ];
+        let relocations = (0..5).map(|index| 0x0040_2000 + index * 4).collect();
+        let image = image(&words, relocations);
+
+        let classification = DataClassification::classify(&image, &Disassembly::default());
+        let types: Vec<_> = classification.words.values().take(8).copied().collect();
+        assert_eq!(
+            types,
+            vec![
+                DataType::CodePointer(0x0040_1000),
+                DataType::CodePointer(0x0040_1010),
+                DataType::CodePointer(0x0040_1020),
+                DataType::StringPointer(0x0040_2080),
+                DataType::DataPointer(0x0040_2000),
+                DataType::Float,
+                DataType::Integer,
+                DataType::Integer,
+            ]
+        );
+        assert_eq!(
+            classification.pointer_arrays.values().collect::<Vec<_>>(),
+            vec![&PointerArray {
+                address: 0x0040_2000,
+                length: 5,
+                is_function_table: false,
+            }]
+        );
+        assert_eq!(
+            classification.code_pointers().collect::<Vec<_>>(),
+            vec![0x0040_1000, 0x0040_1010, 0x0040_1020]
+        );
+    }
+
+    #[test]
+    fn needs_neighbors_for_unrelocated_code_pointers() {
+        let words = [0x0040_1000, 0x0040_1010, 0x0040_1020, 0, 0x0040_1030];
+        let image = image(&words, BTreeSet::new());
+
+        let classification = DataClassification::classify(&image, &Disassembly::default());
+        assert_eq!(
+            classification.code_pointers().collect::<Vec<_>>(),
+            vec![0x0040_1000, 0x0040_1010, 0x0040_1020]
+        );
+        assert_eq!(
+            classification.words.get(&0x0040_2010),
+            Some(&DataType::Integer)
+        );
+        assert!(classification.pointer_arrays[&0x0040_2000].is_function_table);
+    }
+}
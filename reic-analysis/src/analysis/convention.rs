@@ -0,0 +1,558 @@
+use crate::analysis::{CallingConvention, Disassembly, FlowKind, Function, StackFrame};
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use zydis::{DecodedOperand, Mnemonic, OperandAction, OperandType, Register};
+
+/// How many instructions before a call are searched for a load of ECX or EDX.
+const MAX_ARGUMENT_SETUP_DISTANCE: usize = 16;
+
+/// How many call sites are counted as evidence for one convention at most.
+const MAX_CALL_SITE_WEIGHT: u32 = 3;
+
+/// The calling convention of a function, as inferred by the analysis or set by the user.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ConventionInference {
+    pub convention: CallingConvention,
+    /// The share of the evidence that agrees with the convention, from 0 to 1. User-defined
+    /// conventions have a confidence of 1.
+    pub confidence: f32,
+    /// The bytes of arguments passed on the stack, if known.
+    pub stack_arguments: Option<u32>,
+    pub user_defined: bool,
+}
+
+impl ConventionInference {
+    /// The number of arguments, counting those passed in registers, assuming dword arguments.
+    pub fn argument_count(&self) -> Option<usize> {
+        let stack_arguments = self.stack_arguments? as usize / 4;
+        Some(stack_arguments + self.convention.register_parameters())
+    }
+}
+
+/// A calling convention set by the user.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CallingConventionOverride {
+    /// The entry of the function.
+    pub function: u64,
+    pub convention: CallingConvention,
+    /// The bytes of arguments passed on the stack, if the user specified them.
+    pub stack_arguments: Option<u32>,
+}
+
+/// Evidence for the conventions it is consistent with.
+struct Evidence {
+    supports: &'static [CallingConvention],
+    weight: u32,
+}
+
+const ALL: &[CallingConvention] = &[
+    CallingConvention::Cdecl,
+    CallingConvention::Stdcall,
+    CallingConvention::Thiscall,
+    CallingConvention::Fastcall,
+];
+const CALLEE_CLEANUP: &[CallingConvention] = &[
+    CallingConvention::Stdcall,
+    CallingConvention::Thiscall,
+    CallingConvention::Fastcall,
+];
+const CDECL: &[CallingConvention] = &[CallingConvention::Cdecl];
+const FASTCALL: &[CallingConvention] = &[CallingConvention::Fastcall];
+const ECX_ARGUMENT: &[CallingConvention] =
+    &[CallingConvention::Thiscall, CallingConvention::Fastcall];
+const NO_ECX_ARGUMENT: &[CallingConvention] =
+    &[CallingConvention::Cdecl, CallingConvention::Stdcall];
+const NO_EDX_ARGUMENT: &[CallingConvention] = &[
+    CallingConvention::Cdecl,
+    CallingConvention::Stdcall,
+    CallingConvention::Thiscall,
+];
+
+/// ECX and EDX, the registers that pass arguments in `__thiscall` and `__fastcall`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+struct ArgumentRegisters {
+    ecx: bool,
+    edx: bool,
+}
+
+impl ArgumentRegisters {
+    const BOTH: ArgumentRegisters = ArgumentRegisters {
+        ecx: true,
+        edx: true,
+    };
+
+    fn intersection(self, other: Self) -> Self {
+        Self {
+            ecx: self.ecx && other.ecx,
+            edx: self.edx && other.edx,
+        }
+    }
+
+    fn union(self, other: Self) -> Self {
+        Self {
+            ecx: self.ecx || other.ecx,
+            edx: self.edx || other.edx,
+        }
+    }
+
+    /// The argument registers among the given register, including its partial registers.
+    fn of(register: Register) -> Self {
+        match register {
+            Register::ECX | Register::CX | Register::CL | Register::CH => Self {
+                ecx: true,
+                edx: false,
+            },
+            Register::EDX | Register::DX | Register::DL | Register::DH => Self {
+                ecx: false,
+                edx: true,
+            },
+            _ => Self::default(),
+        }
+    }
+
+    fn read_by(operand: &DecodedOperand) -> Self {
+        match operand.ty {
+            OperandType::REGISTER if operand.action.intersects(OperandAction::MASK_READ) => {
+                Self::of(operand.reg)
+            }
+            OperandType::MEMORY => Self::of(operand.mem.base).union(Self::of(operand.mem.index)),
+            _ => Self::default(),
+        }
+    }
+
+    fn written_by(operand: &DecodedOperand) -> Self {
+        if operand.ty == OperandType::REGISTER
+            && operand.action.intersects(OperandAction::MASK_WRITE)
+        {
+            Self::of(operand.reg)
+        } else {
+            Self::default()
+        }
+    }
+}
+
+impl Disassembly {
+    /// Infers the calling convention of every function that has none set by the user.
+    ///
+    /// The evidence is the `ret imm16` of the function, whether ECX and EDX are read before
+    /// being written, the stack arguments found by the stack frame analysis, and at the call
+    /// sites, whether the caller removes the arguments and whether it loads ECX and EDX before
+    /// the call.
+    pub fn infer_calling_conventions(&mut self, frames: &BTreeMap<u64, StackFrame>) {
+        let callers = self.call_sites();
+        let inferences: Vec<(u64, ConventionInference)> = self
+            .functions
+            .values()
+            .filter(|function| match function.calling_convention {
+                Some(inference) => !inference.user_defined,
+                None => true,
+            })
+            .map(|function| {
+                let call_sites = callers.get(&function.entry).map_or(&[][..], Vec::as_slice);
+                let inference = self.infer_calling_convention(
+                    function,
+                    frames.get(&function.entry),
+                    call_sites,
+                );
+                (function.entry, inference)
+            })
+            .collect();
+
+        for (entry, inference) in inferences {
+            self.functions.get_mut(&entry).unwrap().calling_convention = Some(inference);
+        }
+    }
+
+    /// Sets the calling convention of a function, which is kept when inferring conventions.
+    /// Returns false if there is no function at the address.
+    pub fn set_calling_convention(
+        &mut self,
+        convention_override: &CallingConventionOverride,
+    ) -> bool {
+        let function = match self.functions.get_mut(&convention_override.function) {
+            Some(function) => function,
+            None => return false,
+        };
+        function.calling_convention = Some(ConventionInference {
+            convention: convention_override.convention,
+            confidence: 1.0,
+            stack_arguments: convention_override.stack_arguments,
+            user_defined: true,
+        });
+        true
+    }
+
+    /// The direct calls of every function, keyed by the entry of the called function.
+    fn call_sites(&self) -> HashMap<u64, Vec<u64>> {
+        let mut call_sites: HashMap<u64, Vec<u64>> = HashMap::new();
+        for (from, to, kind) in self.flow.all_edges() {
+            if *kind == FlowKind::Call {
+                call_sites.entry(to).or_default().push(from);
+            }
+        }
+        call_sites
+    }
+
+    fn infer_calling_convention(
+        &self,
+        function: &Function,
+        frame: Option<&StackFrame>,
+        call_sites: &[u64],
+    ) -> ConventionInference {
+        let mut evidence = Vec::new();
+
+        let callee_cleanup = function
+            .instructions
+            .iter()
+            .filter_map(|address| {
+                let decoded = &self.instructions[address].decoded;
+                if decoded.mnemonic != Mnemonic::RET {
+                    return None;
+                }
+                let operand = &decoded.operands[0];
+                Some(if operand.ty == OperandType::IMMEDIATE {
+                    operand.imm.value as u32
+                } else {
+                    0
+                })
+            })
+            .max();
+        let frame_arguments = frame.and_then(|frame| {
+            frame
+                .arguments()
+                .map(|argument| (argument.offset - 4) as u32 + argument.size.max(4))
+                .max()
+        });
+        match callee_cleanup {
+            Some(bytes) if bytes > 0 => evidence.push(Evidence {
+                supports: CALLEE_CLEANUP,
+                weight: 3,
+            }),
+            // A plain `ret` in a function using stack arguments
+            Some(_) if frame_arguments.is_some() => evidence.push(Evidence {
+                supports: CDECL,
+                weight: 3,
+            }),
+            _ => {}
+        }
+
+        let read = self.registers_read_before_written(function);
+        evidence.push(if read.ecx {
+            Evidence {
+                supports: ECX_ARGUMENT,
+                weight: 2,
+            }
+        } else {
+            Evidence {
+                supports: NO_ECX_ARGUMENT,
+                weight: 1,
+            }
+        });
+        evidence.push(if read.edx {
+            Evidence {
+                supports: FASTCALL,
+                weight: 2,
+            }
+        } else {
+            Evidence {
+                supports: NO_EDX_ARGUMENT,
+                weight: 1,
+            }
+        });
+
+        let caller_cleanups: Vec<u32> = call_sites
+            .iter()
+            .filter_map(|&call| self.caller_cleanup(call))
+            .collect();
+        let (mut ecx_loads, mut edx_loads) = (0, 0);
+        for &call in call_sites {
+            let loaded = self.registers_loaded_before(call);
+            ecx_loads += loaded.ecx as u32;
+            edx_loads += loaded.edx as u32;
+        }
+        for &(supports, count) in &[
+            (CDECL, caller_cleanups.len() as u32),
+            (ECX_ARGUMENT, ecx_loads),
+            (FASTCALL, edx_loads),
+        ] {
+            if count > 0 {
+                evidence.push(Evidence {
+                    supports,
+                    weight: count.min(MAX_CALL_SITE_WEIGHT),
+                });
+            }
+        }
+
+        // Ties go to the more common convention, in the order of `ALL`
+        let total: u32 = evidence.iter().map(|evidence| evidence.weight).sum();
+        let support = |convention: CallingConvention| -> u32 {
+            evidence
+                .iter()
+                .filter(|evidence| evidence.supports.contains(&convention))
+                .map(|evidence| evidence.weight)
+                .sum()
+        };
+        let mut convention = ALL[0];
+        for &candidate in &ALL[1..] {
+            if support(candidate) > support(convention) {
+                convention = candidate;
+            }
+        }
+
+        let stack_arguments = match callee_cleanup {
+            Some(bytes) if bytes > 0 => Some(bytes),
+            _ => caller_cleanups.iter().copied().max().or(frame_arguments),
+        };
+        ConventionInference {
+            convention,
+            confidence: support(convention) as f32 / total as f32,
+            stack_arguments,
+            user_defined: false,
+        }
+    }
+
+    /// Finds whether ECX or EDX are read on some path from the entry before being written.
+    /// Calls are assumed to overwrite both, and `push ecx` is not counted as a read, as
+    /// compilers use it to reserve space for a local variable.
+    fn registers_read_before_written(&self, function: &Function) -> ArgumentRegisters {
+        let mut read = ArgumentRegisters::default();
+        // The registers written on every path to each instruction
+        let mut written: HashMap<u64, ArgumentRegisters> = HashMap::new();
+        let mut queue = VecDeque::new();
+        written.insert(function.entry, ArgumentRegisters::default());
+        queue.push_back(function.entry);
+
+        while let Some(address) = queue.pop_front() {
+            let decoded = match self.instructions.get(&address) {
+                Some(instruction) => &instruction.decoded,
+                None => continue,
+            };
+            let operands = &decoded.operands[..decoded.operand_count as usize];
+            let mut state = written[&address];
+
+            let zeroing = matches!(decoded.mnemonic, Mnemonic::XOR | Mnemonic::SUB)
+                && operands.len() >= 2
+                && operands[0].ty == OperandType::REGISTER
+                && operands[1].ty == OperandType::REGISTER
+                && operands[0].reg == operands[1].reg;
+            if decoded.mnemonic != Mnemonic::PUSH && !zeroing {
+                for operand in operands {
+                    let operand_read = ArgumentRegisters::read_by(operand);
+                    read.ecx |= operand_read.ecx && !state.ecx;
+                    read.edx |= operand_read.edx && !state.edx;
+                }
+            }
+            if decoded.mnemonic == Mnemonic::CALL {
+                state = ArgumentRegisters::BOTH;
+            }
+            for operand in operands {
+                state = state.union(ArgumentRegisters::written_by(operand));
+            }
+
+            for (_, to, kind) in self.flow.edges(address) {
+                if *kind == FlowKind::Call || !function.instructions.contains(&to) {
+                    continue;
+                }
+                let merged = match written.get(&to) {
+                    Some(&existing) => existing.intersection(state),
+                    None => state,
+                };
+                if written.get(&to) != Some(&merged) {
+                    written.insert(to, merged);
+                    queue.push_back(to);
+                }
+            }
+        }
+
+        read
+    }
+
+    /// The bytes removed by an `add esp, imm` right after a call.
+    fn caller_cleanup(&self, call: u64) -> Option<u32> {
+        let (&next, instruction) = self.instructions.range(call + 1..).next()?;
+        if self.flow.edge_weight(call, next) != Some(&FlowKind::Fallthrough) {
+            return None;
+        }
+        let decoded = &instruction.decoded;
+        let (destination, source) = (&decoded.operands[0], &decoded.operands[1]);
+        if decoded.mnemonic == Mnemonic::ADD
+            && destination.ty == OperandType::REGISTER
+            && destination.reg == Register::ESP
+            && source.ty == OperandType::IMMEDIATE
+        {
+            Some(source.imm.value as u32)
+        } else {
+            None
+        }
+    }
+
+    /// The argument registers written between the previous call and the given call.
+    fn registers_loaded_before(&self, call: u64) -> ArgumentRegisters {
+        let mut loaded = ArgumentRegisters::default();
+        let mut address = call;
+        for _ in 0..MAX_ARGUMENT_SETUP_DISTANCE {
+            let decoded = match self.fallthrough_predecessor(address) {
+                Some(instruction) => {
+                    address = instruction.address.address;
+                    &instruction.decoded
+                }
+                None => break,
+            };
+            if decoded.mnemonic == Mnemonic::CALL {
+                break;
+            }
+            if matches!(decoded.mnemonic, Mnemonic::MOV | Mnemonic::LEA) {
+                loaded = loaded.union(ArgumentRegisters::written_by(&decoded.operands[0]));
+            }
+        }
+        loaded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::disassembly::tests::disassemble_functions;
+    use crate::analysis::TypeLibrary;
+
+    const FUNCTIONS: &[(u64, &[u8])] = &[
+        // push 1; call 0x401120; add esp, 4; ret
+        (
+            0x0040_1000,
+            &[
+                0x6A, 0x01, 0xE8, 0x19, 0x01, 0x00, 0x00, 0x83, 0xC4, 0x04, 0xC3,
+            ],
+        ),
+        // mov eax, [esp + 4]; add eax, [esp + 8]; ret 8
+        (
+            0x0040_1100,
+            &[
+                0x8B, 0x44, 0x24, 0x04, 0x03, 0x44, 0x24, 0x08, 0xC2, 0x08, 0x00,
+            ],
+        ),
+        // mov eax, [esp + 4]; ret
+        (0x0040_1120, &[0x8B, 0x44, 0x24, 0x04, 0xC3]),
+        // mov eax, [ecx]; add eax, [esp + 4]; ret 4
+        (
+            0x0040_1140,
+            &[0x8B, 0x01, 0x03, 0x44, 0x24, 0x04, 0xC2, 0x04, 0x00],
+        ),
+        // lea eax, [ecx + edx]; ret
+        (0x0040_1160, &[0x8D, 0x04, 0x11, 0xC3]),
+        // ret
+        (0x0040_11A0, &[0xC3]),
+        // mov ecx, 0x403000; call 0x4011A0; lea ecx, [esi + 4]; call 0x4011A0; ret
+        (
+            0x0040_11B0,
+            &[
+                0xB9, 0x00, 0x30, 0x40, 0x00, 0xE8, 0xE6, 0xFF, 0xFF, 0xFF, 0x8D, 0x4E, 0x04, 0xE8,
+                0xDE, 0xFF, 0xFF, 0xFF, 0xC3,
+            ],
+        ),
+    ];
+
+    fn inferred_conventions() -> BTreeMap<u64, ConventionInference> {
+        let (image, mut disassembly) = disassemble_functions(FUNCTIONS);
+        let frames = StackFrame::analyze_all(&image, &disassembly, &TypeLibrary::bundled());
+        disassembly.infer_calling_conventions(&frames);
+        disassembly
+            .functions
+            .values()
+            .map(|function| (function.entry, function.calling_convention.unwrap()))
+            .collect()
+    }
+
+    fn inference(
+        convention: CallingConvention,
+        confidence: f32,
+        stack_arguments: Option<u32>,
+    ) -> ConventionInference {
+        ConventionInference {
+            convention,
+            confidence,
+            stack_arguments,
+            user_defined: false,
+        }
+    }
+
+    #[test]
+    fn keeps_user_defined_conventions() {
+        let mut disassembly = Disassembly::default();
+        for &entry in &[0x401000, 0x402000] {
+            disassembly.functions.insert(
+                entry,
+                Function {
+                    entry,
+                    ..Function::default()
+                },
+            );
+        }
+        assert!(
+            disassembly.set_calling_convention(&CallingConventionOverride {
+                function: 0x402000,
+                convention: CallingConvention::Thiscall,
+                stack_arguments: Some(8),
+            })
+        );
+        assert!(
+            !disassembly.set_calling_convention(&CallingConventionOverride {
+                function: 0x403000,
+                convention: CallingConvention::Stdcall,
+                stack_arguments: None,
+            })
+        );
+
+        disassembly.infer_calling_conventions(&BTreeMap::new());
+        let inferred = disassembly.functions[&0x401000].calling_convention.unwrap();
+        assert_eq!(inferred.convention, CallingConvention::Cdecl);
+        assert!(!inferred.user_defined);
+        let user_defined = disassembly.functions[&0x402000].calling_convention.unwrap();
+        assert_eq!(user_defined.convention, CallingConvention::Thiscall);
+        assert_eq!(user_defined.argument_count(), Some(3));
+    }
+
+    #[test]
+    fn infers_callee_cleanup_from_returns() {
+        let inferred = inferred_conventions();
+        assert_eq!(
+            inferred[&0x0040_1100],
+            inference(CallingConvention::Stdcall, 1.0, Some(8))
+        );
+        assert_eq!(inferred[&0x0040_1100].argument_count(), Some(2));
+    }
+
+    #[test]
+    fn infers_caller_cleanup_from_call_sites() {
+        let inferred = inferred_conventions();
+        assert_eq!(
+            inferred[&0x0040_1120],
+            inference(CallingConvention::Cdecl, 1.0, Some(4))
+        );
+    }
+
+    #[test]
+    fn infers_register_arguments_from_reads() {
+        let inferred = inferred_conventions();
+        // ECX is read before it is written, and `ret 4` removes the stack argument
+        assert_eq!(
+            inferred[&0x0040_1140],
+            inference(CallingConvention::Thiscall, 1.0, Some(4))
+        );
+        assert_eq!(inferred[&0x0040_1140].argument_count(), Some(2));
+        assert_eq!(
+            inferred[&0x0040_1160],
+            inference(CallingConvention::Fastcall, 1.0, None)
+        );
+    }
+
+    #[test]
+    fn infers_register_arguments_from_call_sites() {
+        // Both callers load ECX, but the function itself never reads it
+        let inferred = inferred_conventions();
+        assert_eq!(
+            inferred[&0x0040_11A0],
+            inference(CallingConvention::Thiscall, 0.75, None)
+        );
+    }
+}
@@ -0,0 +1,413 @@
+mod borland;
+mod itanium;
+mod msvc;
+
+pub use msvc::demangle_msvc_type;
+
+use crate::analysis::{CallingConvention, Parameter, Prototype};
+
+use std::fmt;
+
+/// How deeply types may nest in a symbol, which keeps crafted symbols from overflowing the stack.
+const MAX_TYPE_DEPTH: usize = 64;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ManglingScheme {
+    /// Microsoft Visual C++, as in `?Run@CWinApp@@UAEHXZ`
+    Msvc,
+    /// The Itanium C++ ABI used by GCC and Clang, as in `_ZN3Foo3BarEi`
+    Itanium,
+    /// Borland C++ and C++Builder, as in `@Foo@Bar$qi`
+    Borland,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Qualifiers {
+    pub is_const: bool,
+    pub is_volatile: bool,
+}
+
+impl Qualifiers {
+    pub fn is_empty(self) -> bool {
+        !self.is_const && !self.is_volatile
+    }
+}
+
+impl fmt::Display for Qualifiers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match (self.is_const, self.is_volatile) {
+            (false, false) => "",
+            (true, false) => "const",
+            (false, true) => "volatile",
+            (true, true) => "const volatile",
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Access {
+    Public,
+    Protected,
+    Private,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Access::Public => "public",
+            Access::Protected => "protected",
+            Access::Private => "private",
+        })
+    }
+}
+
+/// A C++ type, as decoded from a mangled name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Type {
+    /// A fundamental type, such as `int` or `unsigned __int64`.
+    Builtin(&'static str),
+    /// A class, struct, union or enum by qualified name, including template arguments. The
+    /// keyword is only known for MSVC names.
+    Named {
+        keyword: Option<&'static str>,
+        name: String,
+    },
+    Qualified(Box<Type>, Qualifiers),
+    Pointer(Box<Type>),
+    Reference(Box<Type>),
+    RvalueReference(Box<Type>),
+    /// A pointer to a member of the given class.
+    MemberPointer {
+        class: String,
+        member: Box<Type>,
+    },
+    Array {
+        element: Box<Type>,
+        length: Option<u64>,
+    },
+    Function(Box<FunctionType>),
+}
+
+impl Type {
+    fn qualified(self, qualifiers: Qualifiers) -> Self {
+        if qualifiers.is_empty() {
+            self
+        } else {
+            Type::Qualified(Box::new(self), qualifiers)
+        }
+    }
+
+    /// Formats a declaration of the given declarator with this type, such as `int (*name)[4]`.
+    /// The type alone is formatted with an empty declarator.
+    pub fn declare(&self, declarator: &str) -> String {
+        match self {
+            Type::Builtin(name) => join(name, declarator),
+            Type::Named {
+                keyword: Some(keyword),
+                name,
+            } => join(&format!("{} {}", keyword, name), declarator),
+            Type::Named {
+                keyword: None,
+                name,
+            } => join(name, declarator),
+            Type::Qualified(inner, qualifiers) => match &**inner {
+                Type::Pointer(_)
+                | Type::Reference(_)
+                | Type::RvalueReference(_)
+                | Type::MemberPointer { .. } => inner.declare_indirection(*qualifiers, declarator),
+                _ => join(&format!("{} {}", inner.declare(""), qualifiers), declarator),
+            },
+            Type::Pointer(_)
+            | Type::Reference(_)
+            | Type::RvalueReference(_)
+            | Type::MemberPointer { .. } => {
+                self.declare_indirection(Qualifiers::default(), declarator)
+            }
+            Type::Array { element, length } => {
+                let declarator = match declarator.chars().next() {
+                    Some('*') | Some('&') => format!("({})", declarator),
+                    _ => declarator.to_owned(),
+                };
+                let length = length.map(|length| length.to_string()).unwrap_or_default();
+                element.declare(&format!("{}[{}]", declarator, length))
+            }
+            Type::Function(function) => function.declare(declarator),
+        }
+    }
+
+    fn declare_indirection(&self, qualifiers: Qualifiers, declarator: &str) -> String {
+        let (symbol, target) = match self {
+            Type::Pointer(target) => ("*".to_owned(), target),
+            Type::Reference(target) => ("&".to_owned(), target),
+            Type::RvalueReference(target) => ("&&".to_owned(), target),
+            Type::MemberPointer { class, member } => (format!("{}::*", class), member),
+            _ => unreachable!(),
+        };
+
+        let mut inner = symbol;
+        if !qualifiers.is_empty() {
+            inner.push_str(&qualifiers.to_string());
+            if !declarator.is_empty() {
+                inner.push(' ');
+            }
+        }
+        inner.push_str(declarator);
+        match &**target {
+            Type::Function(_) | Type::Array { .. } => target.declare(&format!("({})", inner)),
+            _ => target.declare(&inner),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.declare(""))
+    }
+}
+
+/// Joins a type and a declarator, without a space before array bounds.
+fn join(type_name: &str, declarator: &str) -> String {
+    if declarator.is_empty() {
+        type_name.to_owned()
+    } else if declarator.starts_with('[') {
+        format!("{}{}", type_name, declarator)
+    } else {
+        format!("{} {}", type_name, declarator)
+    }
+}
+
+/// The type of a function, or of a member function together with the qualifiers of `this`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FunctionType {
+    /// The calling convention, if the name tells it and it is one the analysis models.
+    pub calling_convention: Option<CallingConvention>,
+    /// The return type, which constructors, destructors and the functions of Itanium names
+    /// that are not template instances have none of.
+    pub return_type: Option<Type>,
+    pub parameters: Vec<Type>,
+    pub variadic: bool,
+    /// The cv-qualifiers of a member function, as in `int size() const`.
+    pub qualifiers: Qualifiers,
+}
+
+impl FunctionType {
+    fn declare(&self, declarator: &str) -> String {
+        let declarator = match (self.calling_convention, declarator.strip_prefix('(')) {
+            (Some(convention), Some(inner)) => format!("({} {}", convention, inner),
+            (Some(convention), None) => join(&convention.to_string(), declarator),
+            (None, _) => declarator.to_owned(),
+        };
+
+        let mut parameters: Vec<String> = self.parameters.iter().map(Type::to_string).collect();
+        if self.variadic {
+            parameters.push("...".to_owned());
+        }
+        if parameters.is_empty() {
+            parameters.push("void".to_owned());
+        }
+        let mut declarator = format!("{}({})", declarator, parameters.join(", "));
+        if !self.qualifiers.is_empty() {
+            declarator = format!("{} {}", declarator, self.qualifiers);
+        }
+
+        match &self.return_type {
+            Some(return_type) => join(&return_type.to_string(), &declarator),
+            None => declarator,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DemangledKind {
+    Function {
+        access: Option<Access>,
+        is_static: bool,
+        is_virtual: bool,
+        signature: FunctionType,
+    },
+    Variable {
+        access: Option<Access>,
+        is_static: bool,
+        /// The type of the variable, which Itanium and Borland names do not tell.
+        data_type: Option<Type>,
+    },
+    /// Data generated by the compiler, such as virtual function tables and RTTI structures.
+    Special,
+}
+
+/// A decoded symbol name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Demangled {
+    pub scheme: ManglingScheme,
+    /// The enclosing namespaces and classes, outermost first.
+    pub scope: Vec<String>,
+    /// The unqualified name, such as `Run`, `~CWnd`, `operator+` or `` `vftable' ``.
+    pub name: String,
+    pub kind: DemangledKind,
+}
+
+impl Demangled {
+    pub fn qualified_name(&self) -> String {
+        let mut components = self.scope.clone();
+        components.push(self.name.clone());
+        components.join("::")
+    }
+
+    /// The class a member belongs to. MSVC names tell whether a function is a member. Itanium
+    /// and Borland names do not tell namespaces and classes apart, so for them this is the
+    /// enclosing scope of any function.
+    pub fn class(&self) -> Option<String> {
+        if self.scope.is_empty() {
+            return None;
+        }
+        let is_member = match &self.kind {
+            DemangledKind::Function { access, .. } | DemangledKind::Variable { access, .. } => {
+                self.scheme != ManglingScheme::Msvc || access.is_some()
+            }
+            DemangledKind::Special => true,
+        };
+        if is_member {
+            Some(self.scope.join("::"))
+        } else {
+            None
+        }
+    }
+
+    /// Builds the prototype of a function for the type library, named by its mangled
+    /// `symbol` so imports can be looked up by name. The `this` pointer of non-static member
+    /// functions is the first parameter where the name tells it is one.
+    pub fn prototype(&self, module: &str, symbol: &str) -> Option<Prototype> {
+        let (access, is_static, signature) = match &self.kind {
+            DemangledKind::Function {
+                access,
+                is_static,
+                signature,
+                ..
+            } => (access, *is_static, signature),
+            _ => return None,
+        };
+        let calling_convention = match (signature.calling_convention, self.scheme) {
+            (Some(convention), _) => convention,
+            // GCC uses cdecl for everything, including member functions
+            (None, ManglingScheme::Itanium) => CallingConvention::Cdecl,
+            (None, _) => return None,
+        };
+
+        let mut parameters = Vec::new();
+        if access.is_some() && !is_static {
+            parameters.push(Parameter {
+                name: "this".to_owned(),
+                type_name: format!("{} *", self.scope.join("::")),
+                constants: None,
+            });
+        }
+        for parameter in &signature.parameters {
+            parameters.push(Parameter {
+                name: format!("arg{}", parameters.len() + 1),
+                type_name: parameter.to_string(),
+                constants: None,
+            });
+        }
+
+        Some(Prototype {
+            module: module.to_owned(),
+            name: symbol.to_owned(),
+            ordinal: None,
+            return_type: signature
+                .return_type
+                .as_ref()
+                .map(Type::to_string)
+                .unwrap_or_else(|| "void".to_owned()),
+            calling_convention,
+            parameters,
+            variadic: signature.variadic,
+        })
+    }
+}
+
+impl fmt::Display for Demangled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            DemangledKind::Function {
+                access,
+                is_static,
+                is_virtual,
+                signature,
+            } => {
+                if let Some(access) = access {
+                    write!(f, "{}: ", access)?;
+                }
+                if *is_static {
+                    f.write_str("static ")?;
+                }
+                if *is_virtual {
+                    f.write_str("virtual ")?;
+                }
+                f.write_str(&signature.declare(&self.qualified_name()))
+            }
+            DemangledKind::Variable {
+                access,
+                is_static,
+                data_type,
+            } => {
+                if let Some(access) = access {
+                    write!(f, "{}: ", access)?;
+                }
+                if *is_static {
+                    f.write_str("static ")?;
+                }
+                match data_type {
+                    Some(data_type) => f.write_str(&data_type.declare(&self.qualified_name())),
+                    None => f.write_str(&self.qualified_name()),
+                }
+            }
+            DemangledKind::Special => f.write_str(&self.qualified_name()),
+        }
+    }
+}
+
+/// Decodes a symbol name mangled by MSVC, by a compiler following the Itanium C++ ABI or by
+/// Borland C++. Returns `None` for names that are not mangled, or use constructs that are not
+/// supported.
+pub fn demangle(symbol: &str) -> Option<Demangled> {
+    if symbol.starts_with('?') {
+        msvc::demangle(symbol)
+    } else if symbol.starts_with("_Z") || symbol.starts_with("__Z") {
+        itanium::demangle(symbol)
+    } else if symbol.starts_with('@') {
+        borland::demangle(symbol)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_prototypes() {
+        let symbol = "?SetWindowTextA@CWnd@@QAEXPBD@Z";
+        let demangled = demangle(symbol).unwrap();
+        assert_eq!(demangled.class().as_deref(), Some("CWnd"));
+        let prototype = demangled.prototype("MFC42.DLL", symbol).unwrap();
+        assert_eq!(
+            prototype.to_string(),
+            format!("void __thiscall {}(CWnd * this, char const * arg2)", symbol)
+        );
+
+        let symbol = "_ZN3Foo3BarEi";
+        let prototype = demangle(symbol)
+            .unwrap()
+            .prototype("libfoo.so", symbol)
+            .unwrap();
+        assert_eq!(prototype.calling_convention, CallingConvention::Cdecl);
+        assert_eq!(prototype.parameters.len(), 1);
+
+        assert_eq!(demangle("GetProcAddress"), None);
+        assert_eq!(
+            demangle("@Foo@count").unwrap().qualified_name(),
+            "Foo::count"
+        );
+    }
+}
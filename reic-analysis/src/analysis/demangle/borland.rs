@@ -0,0 +1,369 @@
+use super::{
+    Demangled, DemangledKind, FunctionType, ManglingScheme, Qualifiers, Type, MAX_TYPE_DEPTH,
+};
+use crate::analysis::CallingConvention;
+
+pub(super) fn demangle(symbol: &str) -> Option<Demangled> {
+    let mut parser = Parser {
+        input: symbol,
+        parameters: Vec::new(),
+        depth: 0,
+    };
+    parser.symbol()
+}
+
+/// The names of the operators, constructors and destructors following `$b`.
+fn operator_name(code: &str) -> Option<&'static str> {
+    Some(match code {
+        "add" => "operator+",
+        "sub" => "operator-",
+        "mul" => "operator*",
+        "div" => "operator/",
+        "mod" => "operator%",
+        "and" => "operator&",
+        "or" => "operator|",
+        "xor" => "operator^",
+        "not" => "operator!",
+        "cmp" => "operator~",
+        "asg" => "operator=",
+        "eql" => "operator==",
+        "neq" => "operator!=",
+        "lss" => "operator<",
+        "gtr" => "operator>",
+        "leq" => "operator<=",
+        "geq" => "operator>=",
+        "land" => "operator&&",
+        "lor" => "operator||",
+        "inc" => "operator++",
+        "dec" => "operator--",
+        "lsh" => "operator<<",
+        "rsh" => "operator>>",
+        "rplu" => "operator+=",
+        "rmin" => "operator-=",
+        "rmul" => "operator*=",
+        "rdiv" => "operator/=",
+        "rmod" => "operator%=",
+        "rand" => "operator&=",
+        "ror" => "operator|=",
+        "rxor" => "operator^=",
+        "rlsh" => "operator<<=",
+        "rrsh" => "operator>>=",
+        "subs" => "operator[]",
+        "call" => "operator()",
+        "arow" => "operator->",
+        "arwm" => "operator->*",
+        "coma" => "operator,",
+        "new" => "operator new",
+        "dele" => "operator delete",
+        "nwa" => "operator new[]",
+        "dla" => "operator delete[]",
+        "ind" => "operator*",
+        "adr" => "operator&",
+        _ => return None,
+    })
+}
+
+fn builtin_type(code: char) -> Option<&'static str> {
+    Some(match code {
+        'v' => "void",
+        'c' => "char",
+        's' => "short",
+        'i' => "int",
+        'l' => "long",
+        'j' => "__int64",
+        'f' => "float",
+        'd' => "double",
+        'g' => "long double",
+        'o' => "bool",
+        'b' => "wchar_t",
+        _ => return None,
+    })
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    /// The parameters decoded so far, which `t1` to `t9` and `ta` onwards refer back to.
+    parameters: Vec<Type>,
+    /// How many types being decoded enclose the current one.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.input.chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let next = self.peek()?;
+        self.input = &self.input[next.len_utf8()..];
+        Some(next)
+    }
+
+    fn consume(&mut self, prefix: &str) -> bool {
+        if self.input.starts_with(prefix) {
+            self.input = &self.input[prefix.len()..];
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, prefix: &str) -> Option<()> {
+        if self.consume(prefix) {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// Decodes `@Scope@Name$q<parameters>` for functions, or `@Scope@Name` for variables.
+    fn symbol(&mut self) -> Option<Demangled> {
+        self.expect("@")?;
+        let mut scope = Vec::new();
+        let name = loop {
+            let component = if self.input.starts_with('%') {
+                self.template_name()?
+            } else if self.consume("$b") {
+                let end = self.input.find('$').unwrap_or(self.input.len());
+                let code = &self.input[..end];
+                let name = match code {
+                    "ctr" => scope
+                        .last()
+                        .map(|class: &String| template_name_stem(class))?,
+                    "dtr" => format!("~{}", template_name_stem(scope.last()?)),
+                    _ => operator_name(code)?.to_owned(),
+                };
+                self.input = &self.input[end..];
+                name
+            } else if self.consume("$o") {
+                format!("operator {}", self.data_type()?)
+            } else {
+                let end = self.input.find(&['@', '$'][..]).unwrap_or(self.input.len());
+                let identifier = &self.input[..end];
+                if identifier.is_empty() {
+                    return None;
+                }
+                self.input = &self.input[end..];
+                identifier.to_owned()
+            };
+            if !self.consume("@") {
+                break component;
+            }
+            scope.push(component);
+        };
+
+        if self.input.is_empty() {
+            return Some(Demangled {
+                scheme: ManglingScheme::Borland,
+                scope,
+                name,
+                kind: DemangledKind::Variable {
+                    access: None,
+                    is_static: false,
+                    data_type: None,
+                },
+            });
+        }
+
+        self.expect("$")?;
+        let mut qualifiers = Qualifiers::default();
+        loop {
+            if self.consume("x") {
+                qualifiers.is_const = true;
+            } else if self.consume("w") {
+                qualifiers.is_volatile = true;
+            } else {
+                break;
+            }
+        }
+        self.expect("q")?;
+        // Borland's `__fastcall` passes arguments in EAX, EDX and ECX, which none of the
+        // modelled conventions do, so it decodes as an unknown convention
+        let calling_convention = if self.consume("qr") {
+            None
+        } else if self.consume("qs") {
+            Some(CallingConvention::Stdcall)
+        } else {
+            Some(CallingConvention::Cdecl)
+        };
+
+        let mut signature = self.function_parameters()?;
+        if !self.input.is_empty() {
+            return None;
+        }
+        signature.calling_convention = calling_convention;
+        signature.qualifiers = qualifiers;
+        Some(Demangled {
+            scheme: ManglingScheme::Borland,
+            scope,
+            name,
+            kind: DemangledKind::Function {
+                access: None,
+                is_static: false,
+                is_virtual: false,
+                signature,
+            },
+        })
+    }
+
+    /// Decodes a class template instance, `%Name$<arguments>%`.
+    fn template_name(&mut self) -> Option<String> {
+        self.expect("%")?;
+        let end = self.input.find('$')?;
+        let name = self.input[..end].to_owned();
+        self.input = &self.input[end + 1..];
+
+        let mut arguments = Vec::new();
+        while !self.consume("%") {
+            arguments.push(self.data_type()?.to_string());
+        }
+        Some(format!("{}<{}>", name, arguments.join(", ")))
+    }
+
+    /// Decodes parameter types up to the end of the input or a `$`, with a single `void` for
+    /// no parameters.
+    fn function_parameters(&mut self) -> Option<FunctionType> {
+        let mut signature = FunctionType::default();
+        if self.input == "v" || self.input.starts_with("v$") {
+            self.next();
+            return Some(signature);
+        }
+        while !self.input.is_empty() && !self.input.starts_with('$') {
+            if self.consume("e") {
+                signature.variadic = true;
+                continue;
+            }
+            let parameter = self.data_type()?;
+            self.parameters.push(parameter.clone());
+            signature.parameters.push(parameter);
+        }
+        Some(signature)
+    }
+
+    fn data_type(&mut self) -> Option<Type> {
+        if self.depth == MAX_TYPE_DEPTH {
+            return None;
+        }
+        self.depth += 1;
+        let data_type = self.decode_data_type();
+        self.depth -= 1;
+        data_type
+    }
+
+    fn decode_data_type(&mut self) -> Option<Type> {
+        let mut qualifiers = Qualifiers::default();
+        loop {
+            if self.consume("x") {
+                qualifiers.is_const = true;
+            } else if self.consume("w") {
+                qualifiers.is_volatile = true;
+            } else {
+                break;
+            }
+        }
+
+        let code = self.next()?;
+        let data_type = if let Some(builtin) = builtin_type(code) {
+            Type::Builtin(builtin)
+        } else {
+            match code {
+                'u' => Type::Builtin(match self.next()? {
+                    'c' => "unsigned char",
+                    's' => "unsigned short",
+                    'i' => "unsigned int",
+                    'l' => "unsigned long",
+                    'j' => "unsigned __int64",
+                    _ => return None,
+                }),
+                'z' if self.consume("c") => Type::Builtin("signed char"),
+                'p' => Type::Pointer(Box::new(self.data_type()?)),
+                'r' => Type::Reference(Box::new(self.data_type()?)),
+                'a' => {
+                    let end = self.input.find('$')?;
+                    let length = self.input[..end].parse().ok()?;
+                    self.input = &self.input[end + 1..];
+                    Type::Array {
+                        element: Box::new(self.data_type()?),
+                        length: Some(length),
+                    }
+                }
+                'q' => {
+                    // Parameters first, then `$` and the return type
+                    let saved = std::mem::take(&mut self.parameters);
+                    let function = self.function_parameters();
+                    self.parameters = saved;
+                    let mut function = function?;
+                    self.expect("$")?;
+                    function.return_type = Some(self.data_type()?);
+                    function.calling_convention = Some(CallingConvention::Cdecl);
+                    Type::Function(Box::new(function))
+                }
+                't' => {
+                    let index = self.next()?.to_digit(36)? as usize;
+                    self.parameters.get(index.checked_sub(1)?)?.clone()
+                }
+                '0'..='9' => {
+                    let mut digits = code.to_string();
+                    while let Some(digit) = self.peek().filter(char::is_ascii_digit) {
+                        digits.push(digit);
+                        self.next();
+                    }
+                    let length: usize = digits.parse().ok()?;
+                    let name = self.input.get(..length)?;
+                    self.input = &self.input[length..];
+                    Type::Named {
+                        keyword: None,
+                        name: name.replace('@', "::"),
+                    }
+                }
+                _ => return None,
+            }
+        };
+        Some(data_type.qualified(qualifiers))
+    }
+}
+
+/// The name of a class template without its arguments, which constructors are named after.
+fn template_name_stem(class: &str) -> String {
+    let end = class.find('<').unwrap_or(class.len());
+    class[..end].to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn undecorated(symbol: &str) -> String {
+        demangle(symbol).unwrap().to_string()
+    }
+
+    #[test]
+    fn demangles_functions() {
+        assert_eq!(undecorated("@Foo@Bar$qi"), "__cdecl Foo::Bar(int)");
+        assert_eq!(
+            undecorated("@TForm1@Button1Click$qqrp14System@TObject"),
+            "TForm1::Button1Click(System::TObject *)"
+        );
+        assert_eq!(undecorated("@Foo@$bctr$qv"), "__cdecl Foo::Foo(void)");
+        assert_eq!(undecorated("@Foo@$bdtr$qqsv"), "__stdcall Foo::~Foo(void)");
+        assert_eq!(
+            undecorated("@Foo@get$xqpxct1"),
+            "__cdecl Foo::get(char const *, char const *) const"
+        );
+        assert_eq!(
+            undecorated("@sort$qpvuiuipqpxvpxv$i"),
+            "__cdecl sort(void *, unsigned int, unsigned int, int (__cdecl *)(void const *, void const *))"
+        );
+        assert_eq!(
+            undecorated("@%Vector$i%@size$xqv"),
+            "__cdecl Vector<int>::size(void) const"
+        );
+    }
+
+    #[test]
+    fn rejects_deeply_nested_types() {
+        let nested = |depth| format!("@f$q{}i", "p".repeat(depth));
+        assert_eq!(undecorated(&nested(3)), "__cdecl f(int ***)");
+        assert_eq!(demangle(&nested(1000)), None);
+    }
+}
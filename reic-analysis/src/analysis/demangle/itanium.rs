@@ -0,0 +1,712 @@
+use super::{
+    Demangled, DemangledKind, FunctionType, ManglingScheme, Qualifiers, Type, MAX_TYPE_DEPTH,
+};
+
+pub(super) fn demangle(symbol: &str) -> Option<Demangled> {
+    // Mach-O symbols carry an extra leading underscore
+    let encoding = symbol
+        .strip_prefix("__Z")
+        .or_else(|| symbol.strip_prefix("_Z"))?;
+    let mut parser = Parser {
+        input: encoding,
+        substitutions: Vec::new(),
+        template_arguments: Vec::new(),
+        depth: 0,
+    };
+    let demangled = parser.encoding()?;
+    if parser.input.is_empty() {
+        Some(demangled)
+    } else {
+        None
+    }
+}
+
+fn builtin_type(code: char) -> Option<&'static str> {
+    Some(match code {
+        'v' => "void",
+        'w' => "wchar_t",
+        'b' => "bool",
+        'c' => "char",
+        'a' => "signed char",
+        'h' => "unsigned char",
+        's' => "short",
+        't' => "unsigned short",
+        'i' => "int",
+        'j' => "unsigned int",
+        'l' => "long",
+        'm' => "unsigned long",
+        'x' => "long long",
+        'y' => "unsigned long long",
+        'n' => "__int128",
+        'o' => "unsigned __int128",
+        'f' => "float",
+        'd' => "double",
+        'e' => "long double",
+        'g' => "__float128",
+        _ => return None,
+    })
+}
+
+/// The fundamental types whose code follows a `D`.
+fn extended_builtin_type(code: char) -> Option<&'static str> {
+    Some(match code {
+        'n' => "std::nullptr_t",
+        'i' => "char32_t",
+        's' => "char16_t",
+        'u' => "char8_t",
+        'a' => "auto",
+        _ => return None,
+    })
+}
+
+fn operator_name(code: &str) -> Option<&'static str> {
+    Some(match code {
+        "nw" => "operator new",
+        "na" => "operator new[]",
+        "dl" => "operator delete",
+        "da" => "operator delete[]",
+        "ps" | "pl" => "operator+",
+        "ng" | "mi" => "operator-",
+        "ad" | "an" => "operator&",
+        "de" | "ml" => "operator*",
+        "co" => "operator~",
+        "dv" => "operator/",
+        "rm" => "operator%",
+        "or" => "operator|",
+        "eo" => "operator^",
+        "aS" => "operator=",
+        "pL" => "operator+=",
+        "mI" => "operator-=",
+        "mL" => "operator*=",
+        "dV" => "operator/=",
+        "rM" => "operator%=",
+        "aN" => "operator&=",
+        "oR" => "operator|=",
+        "eO" => "operator^=",
+        "ls" => "operator<<",
+        "rs" => "operator>>",
+        "lS" => "operator<<=",
+        "rS" => "operator>>=",
+        "eq" => "operator==",
+        "ne" => "operator!=",
+        "lt" => "operator<",
+        "gt" => "operator>",
+        "le" => "operator<=",
+        "ge" => "operator>=",
+        "nt" => "operator!",
+        "aa" => "operator&&",
+        "oo" => "operator||",
+        "pp" => "operator++",
+        "mm" => "operator--",
+        "cm" => "operator,",
+        "pm" => "operator->*",
+        "pt" => "operator->",
+        "cl" => "operator()",
+        "ix" => "operator[]",
+        "qu" => "operator?",
+        _ => return None,
+    })
+}
+
+/// Splits a qualified name at the `::` that are not part of template arguments.
+fn split_qualified(name: &str) -> Vec<String> {
+    let mut components = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let bytes = name.as_bytes();
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'<' => depth += 1,
+            b'>' => depth -= 1,
+            b':' if depth == 0 && bytes.get(index + 1) == Some(&b':') => {
+                components.push(name[start..index].to_owned());
+                start = index + 2;
+                index += 1;
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+    components.push(name[start..].to_owned());
+    components
+}
+
+/// The class name a constructor or destructor is named after: the last component of the
+/// enclosing scope, without template arguments.
+fn structor_name(scope: &str) -> Option<String> {
+    let class = split_qualified(scope).pop()?;
+    let end = class.find('<').unwrap_or(class.len());
+    Some(class[..end].to_owned())
+}
+
+fn format_template_arguments(arguments: &[Type]) -> String {
+    let arguments: Vec<String> = arguments.iter().map(Type::to_string).collect();
+    format!("<{}>", arguments.join(", "))
+}
+
+/// A name, as it is encoded before the type of the function or in a class type.
+struct Name {
+    qualified: String,
+    /// The template arguments of the last component, if it is a template instance.
+    template_arguments: Option<Vec<Type>>,
+    /// The cv-qualifiers of a member function.
+    qualifiers: Qualifiers,
+    /// Whether the name is a constructor, destructor or conversion operator, which have no
+    /// return type even as template instances.
+    is_structor: bool,
+}
+
+impl Name {
+    fn plain(qualified: String) -> Self {
+        Self {
+            qualified,
+            template_arguments: None,
+            qualifiers: Qualifiers::default(),
+            is_structor: false,
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    /// The prefixes and types that `S_` and `S<id>_` refer back to.
+    substitutions: Vec<Type>,
+    /// The arguments of the function template instance, which `T_` and `T<id>_` refer to.
+    template_arguments: Vec<Type>,
+    /// How many types being decoded enclose the current one.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.input.chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let next = self.peek()?;
+        self.input = &self.input[next.len_utf8()..];
+        Some(next)
+    }
+
+    fn consume(&mut self, prefix: &str) -> bool {
+        if self.input.starts_with(prefix) {
+            self.input = &self.input[prefix.len()..];
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, prefix: &str) -> Option<()> {
+        if self.consume(prefix) {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn substitute(&mut self, substitution: Type) {
+        self.substitutions.push(substitution);
+    }
+
+    fn encoding(&mut self) -> Option<Demangled> {
+        let special = if self.consume("TV") {
+            Some("`vtable'")
+        } else if self.consume("TI") {
+            Some("`RTTI Type Descriptor'")
+        } else if self.consume("TS") {
+            Some("`RTTI Type Descriptor Name'")
+        } else if self.consume("TT") {
+            Some("`VTT'")
+        } else {
+            None
+        };
+        if let Some(special) = special {
+            let data_type = self.data_type()?;
+            return Some(Demangled {
+                scheme: ManglingScheme::Itanium,
+                scope: split_qualified(&data_type.to_string()),
+                name: special.to_owned(),
+                kind: DemangledKind::Special,
+            });
+        }
+        if self.input.starts_with('T') {
+            // Thunks and other special names
+            return None;
+        }
+        if self.consume("GV") {
+            let name = self.name()?;
+            return Some(Demangled {
+                scheme: ManglingScheme::Itanium,
+                scope: split_qualified(&name.qualified),
+                name: "`guard variable'".to_owned(),
+                kind: DemangledKind::Special,
+            });
+        }
+
+        let name = self.name()?;
+        if let Some(arguments) = &name.template_arguments {
+            self.template_arguments = arguments.clone();
+        }
+        let mut scope = split_qualified(&name.qualified);
+        let unqualified = scope.pop()?;
+
+        let kind = if self.input.is_empty() {
+            // The type of variables is not encoded
+            DemangledKind::Variable {
+                access: None,
+                is_static: false,
+                data_type: None,
+            }
+        } else {
+            let return_type = if name.template_arguments.is_some() && !name.is_structor {
+                Some(self.data_type()?)
+            } else {
+                None
+            };
+            let (parameters, variadic) = self.parameters()?;
+            DemangledKind::Function {
+                access: None,
+                is_static: false,
+                is_virtual: false,
+                signature: FunctionType {
+                    calling_convention: None,
+                    return_type,
+                    parameters,
+                    variadic,
+                    qualifiers: name.qualifiers,
+                },
+            }
+        };
+
+        Some(Demangled {
+            scheme: ManglingScheme::Itanium,
+            scope,
+            name: unqualified,
+            kind,
+        })
+    }
+
+    /// Decodes the parameter types of a function up to the end of the input or an `E`. A
+    /// single `void` stands for no parameters.
+    fn parameters(&mut self) -> Option<(Vec<Type>, bool)> {
+        let mut parameters = Vec::new();
+        let mut variadic = false;
+        if self.input == "v" || self.input.starts_with("vE") {
+            self.next();
+            return Some((parameters, variadic));
+        }
+        while !self.input.is_empty() && !self.input.starts_with('E') {
+            if self.consume("z") {
+                variadic = true;
+            } else {
+                parameters.push(self.data_type()?);
+            }
+        }
+        Some((parameters, variadic))
+    }
+
+    fn name(&mut self) -> Option<Name> {
+        match self.peek()? {
+            'N' => self.nested_name(),
+            // Names local to a function are not supported
+            'Z' => None,
+            _ => {
+                let qualified = if self.consume("St") {
+                    format!("std::{}", self.unqualified_name(None)?.0)
+                } else if self.input.starts_with('S') {
+                    // Only a template can be named by a substitution
+                    let substitution = self.substitution()?;
+                    if !self.input.starts_with('I') {
+                        return None;
+                    }
+                    substitution.to_string()
+                } else {
+                    self.unqualified_name(None)?.0
+                };
+
+                let mut name = Name::plain(qualified);
+                if self.input.starts_with('I') {
+                    self.substitute(Type::Named {
+                        keyword: None,
+                        name: name.qualified.clone(),
+                    });
+                    let arguments = self.template_arguments()?;
+                    name.qualified
+                        .push_str(&format_template_arguments(&arguments));
+                    name.template_arguments = Some(arguments);
+                }
+                Some(name)
+            }
+        }
+    }
+
+    /// Decodes `N [qualifiers] prefix... E`. Every prefix but the complete name becomes a
+    /// substitution.
+    fn nested_name(&mut self) -> Option<Name> {
+        self.expect("N")?;
+        let qualifiers = self.qualifiers();
+        // Reference qualifiers of member functions
+        let _ = self.consume("R") || self.consume("O");
+
+        let mut name = Name::plain(String::new());
+        name.qualifiers = qualifiers;
+        while !self.consume("E") {
+            let mut substitutable = true;
+            if self.consume("St") {
+                name.qualified.push_str("std");
+                substitutable = false;
+            } else if self.input.starts_with('S') {
+                name.qualified = self.substitution()?.to_string();
+                substitutable = false;
+            } else if self.input.starts_with('I') {
+                let arguments = self.template_arguments()?;
+                name.qualified
+                    .push_str(&format_template_arguments(&arguments));
+                name.template_arguments = Some(arguments);
+            } else if self.input.starts_with('T') {
+                name.qualified = self.template_parameter()?.to_string();
+            } else {
+                let previous = name.qualified.clone();
+                let (component, is_structor) = self.unqualified_name(Some(&previous))?;
+                if !name.qualified.is_empty() {
+                    name.qualified.push_str("::");
+                }
+                name.qualified.push_str(&component);
+                name.template_arguments = None;
+                name.is_structor = is_structor;
+            }
+
+            if substitutable && !self.input.starts_with('E') {
+                self.substitute(Type::Named {
+                    keyword: None,
+                    name: name.qualified.clone(),
+                });
+            }
+        }
+
+        if name.qualified.is_empty() {
+            None
+        } else {
+            Some(name)
+        }
+    }
+
+    /// Decodes a source name, operator name, constructor or destructor. Returns the name and
+    /// whether it is a constructor, destructor or conversion operator.
+    fn unqualified_name(&mut self, scope: Option<&str>) -> Option<(String, bool)> {
+        let first = self.peek()?;
+        if first.is_ascii_digit() {
+            return Some((self.source_name()?, false));
+        }
+        if first == 'L' {
+            // Names with internal linkage
+            self.next();
+            return Some((self.source_name()?, false));
+        }
+        if first == 'C' || first == 'D' {
+            let code = self.input.get(..2)?;
+            let class = structor_name(scope?)?;
+            let name = match code {
+                "C1" | "C2" | "C3" | "C4" | "C5" => class,
+                "D0" | "D1" | "D2" | "D4" | "D5" => format!("~{}", class),
+                _ => return None,
+            };
+            self.input = &self.input[2..];
+            return Some((name, true));
+        }
+        if self.consume("cv") {
+            let target = self.data_type()?;
+            return Some((format!("operator {}", target), true));
+        }
+        if self.consume("li") {
+            return Some((format!("operator\"\" {}", self.source_name()?), false));
+        }
+
+        let code = self.input.get(..2)?;
+        let name = operator_name(code)?;
+        self.input = &self.input[2..];
+        Some((name.to_owned(), false))
+    }
+
+    /// Decodes an identifier prefixed with its length.
+    fn source_name(&mut self) -> Option<String> {
+        let digits = self
+            .input
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.input.len());
+        let length: usize = self.input[..digits].parse().ok()?;
+        let end = digits.checked_add(length)?;
+        let identifier = self.input.get(digits..end)?;
+        self.input = &self.input[end..];
+        if identifier.starts_with("_GLOBAL__N") {
+            Some("`anonymous namespace'".to_owned())
+        } else {
+            Some(identifier.to_owned())
+        }
+    }
+
+    /// Decodes an optional sequence number terminated by `_`: `_` for 0, then base 36 numbers
+    /// plus one.
+    fn sequence_number(&mut self) -> Option<usize> {
+        if self.consume("_") {
+            return Some(0);
+        }
+        let mut value: usize = 0;
+        loop {
+            let digit = self.next()?;
+            if digit == '_' {
+                return Some(value + 1);
+            }
+            value = value.checked_mul(36)? + digit.to_digit(36)? as usize;
+        }
+    }
+
+    fn substitution(&mut self) -> Option<Type> {
+        self.expect("S")?;
+        let abbreviation = match self.peek()? {
+            'a' => Some("std::allocator"),
+            'b' => Some("std::basic_string"),
+            's' => Some("std::string"),
+            'i' => Some("std::istream"),
+            'o' => Some("std::ostream"),
+            'd' => Some("std::iostream"),
+            _ => None,
+        };
+        if let Some(name) = abbreviation {
+            self.next();
+            return Some(Type::Named {
+                keyword: None,
+                name: name.to_owned(),
+            });
+        }
+
+        let index = self.sequence_number()?;
+        self.substitutions.get(index).cloned()
+    }
+
+    fn template_parameter(&mut self) -> Option<Type> {
+        self.expect("T")?;
+        let index = self.sequence_number()?;
+        self.template_arguments.get(index).cloned()
+    }
+
+    fn template_arguments(&mut self) -> Option<Vec<Type>> {
+        self.expect("I")?;
+        let mut arguments = Vec::new();
+        while !self.consume("E") {
+            if self.consume("L") {
+                arguments.push(self.literal()?);
+            } else if self.consume("J") {
+                // An argument pack
+                while !self.consume("E") {
+                    arguments.push(self.data_type()?);
+                }
+            } else if self.input.starts_with('X') {
+                // Expressions are not supported
+                return None;
+            } else {
+                arguments.push(self.data_type()?);
+            }
+        }
+        Some(arguments)
+    }
+
+    /// Decodes an integer template argument following `L`. The value is returned as a type
+    /// named like the value, as template arguments are formatted like types.
+    fn literal(&mut self) -> Option<Type> {
+        let data_type = self.data_type()?;
+        let negative = self.consume("n");
+        let end = self.input.find('E')?;
+        let digits = &self.input[..end];
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let value = match (&data_type, digits) {
+            (Type::Builtin("bool"), "0") => "false".to_owned(),
+            (Type::Builtin("bool"), "1") => "true".to_owned(),
+            _ if negative => format!("-{}", digits),
+            _ => digits.to_owned(),
+        };
+        self.input = &self.input[end + 1..];
+        Some(Type::Named {
+            keyword: None,
+            name: value,
+        })
+    }
+
+    fn qualifiers(&mut self) -> Qualifiers {
+        // `restrict` is not represented
+        self.consume("r");
+        let is_volatile = self.consume("V");
+        let is_const = self.consume("K");
+        Qualifiers {
+            is_const,
+            is_volatile,
+        }
+    }
+
+    fn data_type(&mut self) -> Option<Type> {
+        if self.depth == MAX_TYPE_DEPTH {
+            return None;
+        }
+        self.depth += 1;
+        let data_type = self.decode_data_type();
+        self.depth -= 1;
+        data_type
+    }
+
+    fn decode_data_type(&mut self) -> Option<Type> {
+        let first = self.peek()?;
+        if let Some(builtin) = builtin_type(first) {
+            self.next();
+            return Some(Type::Builtin(builtin));
+        }
+
+        let data_type = match first {
+            'D' => {
+                self.next();
+                if self.consume("p") {
+                    // A pack expansion
+                    return self.data_type();
+                }
+                return Some(Type::Builtin(extended_builtin_type(self.next()?)?));
+            }
+            'r' | 'V' | 'K' => {
+                let qualifiers = self.qualifiers();
+                match self.data_type()? {
+                    Type::Function(mut function) => {
+                        function.qualifiers = qualifiers;
+                        Type::Function(function)
+                    }
+                    data_type => data_type.qualified(qualifiers),
+                }
+            }
+            'P' => {
+                self.next();
+                Type::Pointer(Box::new(self.data_type()?))
+            }
+            'R' => {
+                self.next();
+                Type::Reference(Box::new(self.data_type()?))
+            }
+            'O' => {
+                self.next();
+                Type::RvalueReference(Box::new(self.data_type()?))
+            }
+            'F' => {
+                self.next();
+                self.consume("Y");
+                let return_type = self.data_type()?;
+                let (parameters, variadic) = self.parameters()?;
+                self.expect("E")?;
+                Type::Function(Box::new(FunctionType {
+                    calling_convention: None,
+                    return_type: Some(return_type),
+                    parameters,
+                    variadic,
+                    qualifiers: Qualifiers::default(),
+                }))
+            }
+            'A' => {
+                self.next();
+                let end = self.input.find('_')?;
+                let length = match &self.input[..end] {
+                    "" => None,
+                    digits => Some(digits.parse().ok()?),
+                };
+                self.input = &self.input[end + 1..];
+                Type::Array {
+                    element: Box::new(self.data_type()?),
+                    length,
+                }
+            }
+            'M' => {
+                self.next();
+                let class = self.data_type()?.to_string();
+                let member = self.data_type()?;
+                Type::MemberPointer {
+                    class,
+                    member: Box::new(member),
+                }
+            }
+            'T' => self.template_parameter()?,
+            'S' if !self.input.starts_with("St") => {
+                let substitution = self.substitution()?;
+                if !self.input.starts_with('I') {
+                    return Some(substitution);
+                }
+                let arguments = self.template_arguments()?;
+                Type::Named {
+                    keyword: None,
+                    name: format!("{}{}", substitution, format_template_arguments(&arguments)),
+                }
+            }
+            'u' => {
+                self.next();
+                Type::Named {
+                    keyword: None,
+                    name: self.source_name()?,
+                }
+            }
+            _ => Type::Named {
+                keyword: None,
+                name: self.name()?.qualified,
+            },
+        };
+
+        self.substitute(data_type.clone());
+        Some(data_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn undecorated(symbol: &str) -> String {
+        demangle(symbol).unwrap().to_string()
+    }
+
+    #[test]
+    fn demangles_functions() {
+        assert_eq!(undecorated("_ZN3Foo3BarEi"), "Foo::Bar(int)");
+        assert_eq!(undecorated("_ZNK3Foo4sizeEv"), "Foo::size(void) const");
+        assert_eq!(undecorated("_ZN3FooC2Ev"), "Foo::Foo(void)");
+        assert_eq!(undecorated("_ZN3FooD1Ev"), "Foo::~Foo(void)");
+        assert_eq!(undecorated("_Z3maxIiET_S0_S0_"), "int max<int>(int, int)");
+        assert_eq!(
+            undecorated("_ZNSt6vectorIiSaIiEE9push_backERKi"),
+            "std::vector<int, std::allocator<int>>::push_back(int const &)"
+        );
+        assert_eq!(
+            undecorated("_Z1fPFviEPA3_iz"),
+            "f(void (*)(int), int (*)[3], ...)"
+        );
+        assert_eq!(
+            undecorated("_ZN1N1fERKNS_1CES2_"),
+            "N::f(N::C const &, N::C const &)"
+        );
+        assert_eq!(
+            undecorated("_ZplRK1AS1_"),
+            "operator+(A const &, A const &)"
+        );
+    }
+
+    #[test]
+    fn demangles_special_names() {
+        assert_eq!(undecorated("_ZTV3Foo"), "Foo::`vtable'");
+        assert_eq!(undecorated("_ZN3Foo5countE"), "Foo::count");
+        let demangled = demangle("_ZNK3Foo3getEv").unwrap();
+        assert_eq!(demangled.class().as_deref(), Some("Foo"));
+    }
+
+    #[test]
+    fn rejects_deeply_nested_types() {
+        let nested = |depth| format!("_Z1f{}i", "P".repeat(depth));
+        assert_eq!(undecorated(&nested(3)), "f(int ***)");
+        assert_eq!(demangle(&nested(1000)), None);
+        assert_eq!(demangle("_Z18446744073709551615f"), None);
+    }
+}
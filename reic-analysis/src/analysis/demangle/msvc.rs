@@ -0,0 +1,759 @@
+use super::{
+    Access, Demangled, DemangledKind, FunctionType, ManglingScheme, Qualifiers, Type,
+    MAX_TYPE_DEPTH,
+};
+use crate::analysis::CallingConvention;
+
+/// How many names and parameter types are remembered to be referred back to by a digit.
+const MAX_BACK_REFERENCES: usize = 10;
+
+pub(super) fn demangle(symbol: &str) -> Option<Demangled> {
+    let mut parser = Parser::new(symbol);
+    let demangled = parser.symbol()?;
+    if parser.input.is_empty() {
+        Some(demangled)
+    } else {
+        None
+    }
+}
+
+/// Decodes the type named by an RTTI type descriptor, such as `.?AVCWnd@@` for a class or
+/// `.PAD` for the `char *` of a catch clause.
+pub fn demangle_msvc_type(mangled: &str) -> Option<Type> {
+    let mut parser = Parser::new(mangled);
+    parser.expect(".")?;
+    let qualifiers = if parser.consume("?") {
+        parser.qualifiers()?
+    } else {
+        Qualifiers::default()
+    };
+    let data_type = parser.data_type()?.qualified(qualifiers);
+    if parser.input.is_empty() {
+        Some(data_type)
+    } else {
+        None
+    }
+}
+
+/// The names an operator code stands for, after `?`.
+fn operator_name(code: char) -> Option<&'static str> {
+    Some(match code {
+        '2' => "operator new",
+        '3' => "operator delete",
+        '4' => "operator=",
+        '5' => "operator>>",
+        '6' => "operator<<",
+        '7' => "operator!",
+        '8' => "operator==",
+        '9' => "operator!=",
+        'A' => "operator[]",
+        'C' => "operator->",
+        'D' => "operator*",
+        'E' => "operator++",
+        'F' => "operator--",
+        'G' => "operator-",
+        'H' => "operator+",
+        'I' => "operator&",
+        'J' => "operator->*",
+        'K' => "operator/",
+        'L' => "operator%",
+        'M' => "operator<",
+        'N' => "operator<=",
+        'O' => "operator>",
+        'P' => "operator>=",
+        'Q' => "operator,",
+        'R' => "operator()",
+        'S' => "operator~",
+        'T' => "operator^",
+        'U' => "operator|",
+        'V' => "operator&&",
+        'W' => "operator||",
+        'X' => "operator*=",
+        'Y' => "operator+=",
+        'Z' => "operator-=",
+        _ => return None,
+    })
+}
+
+/// The names an operator code stands for, after `?_`.
+fn extended_operator_name(code: char) -> Option<&'static str> {
+    Some(match code {
+        '0' => "operator/=",
+        '1' => "operator%=",
+        '2' => "operator>>=",
+        '3' => "operator<<=",
+        '4' => "operator&=",
+        '5' => "operator|=",
+        '6' => "operator^=",
+        '7' => "`vftable'",
+        '8' => "`vbtable'",
+        '9' => "`vcall'",
+        'A' => "`typeof'",
+        'B' => "`local static guard'",
+        'D' => "`vbase dtor'",
+        'E' => "`vector deleting dtor'",
+        'F' => "`default ctor closure'",
+        'G' => "`scalar deleting dtor'",
+        'H' => "`vector ctor iterator'",
+        'I' => "`vector dtor iterator'",
+        'J' => "`vector vbase ctor iterator'",
+        'K' => "`virtual displacement map'",
+        'L' => "`eh vector ctor iterator'",
+        'M' => "`eh vector dtor iterator'",
+        'N' => "`eh vector vbase ctor iterator'",
+        'O' => "`copy ctor closure'",
+        'S' => "`local vftable'",
+        'T' => "`local vftable ctor closure'",
+        'U' => "operator new[]",
+        'V' => "operator delete[]",
+        'X' => "`placement delete closure'",
+        'Y' => "`placement delete[] closure'",
+        _ => return None,
+    })
+}
+
+/// The unqualified name of a symbol, as far as it can be told before the scope is known.
+enum SymbolName {
+    Named(String),
+    Constructor,
+    Destructor,
+    /// `operator T`, with `T` being the return type
+    Conversion,
+    /// Compiler generated data, with the name already complete.
+    Special(String),
+}
+
+/// Decodes the access and kind of a function from its code.
+fn function_kind(code: char) -> Option<(Option<Access>, bool, bool)> {
+    let access = match code {
+        'A'..='F' => Access::Private,
+        'I'..='N' => Access::Protected,
+        'Q'..='V' => Access::Public,
+        'Y' | 'Z' => return Some((None, false, false)),
+        _ => return None,
+    };
+    let (is_static, is_virtual) = match code {
+        'A' | 'B' | 'I' | 'J' | 'Q' | 'R' => (false, false),
+        'C' | 'D' | 'K' | 'L' | 'S' | 'T' => (true, false),
+        _ => (false, true),
+    };
+    Some((Some(access), is_static, is_virtual))
+}
+
+/// Decodes a calling convention code. Conventions the analysis does not model, such as
+/// `__pascal`, decode as `Some(None)`.
+fn calling_convention(code: char) -> Option<Option<CallingConvention>> {
+    match code {
+        'A' | 'B' => Some(Some(CallingConvention::Cdecl)),
+        'E' | 'F' => Some(Some(CallingConvention::Thiscall)),
+        'G' | 'H' => Some(Some(CallingConvention::Stdcall)),
+        'I' | 'J' => Some(Some(CallingConvention::Fastcall)),
+        'C' | 'D' | 'K' | 'L' | 'M' | 'N' | 'O' | 'Q' => Some(None),
+        _ => None,
+    }
+}
+
+fn builtin_type(code: char) -> Option<&'static str> {
+    Some(match code {
+        'C' => "signed char",
+        'D' => "char",
+        'E' => "unsigned char",
+        'F' => "short",
+        'G' => "unsigned short",
+        'H' => "int",
+        'I' => "unsigned int",
+        'J' => "long",
+        'K' => "unsigned long",
+        'M' => "float",
+        'N' => "double",
+        'O' => "long double",
+        'X' => "void",
+        _ => return None,
+    })
+}
+
+/// The fundamental types whose code follows an underscore.
+fn extended_builtin_type(code: char) -> Option<&'static str> {
+    Some(match code {
+        'D' => "__int8",
+        'E' => "unsigned __int8",
+        'F' => "__int16",
+        'G' => "unsigned __int16",
+        'H' => "__int32",
+        'I' => "unsigned __int32",
+        'J' => "__int64",
+        'K' => "unsigned __int64",
+        'L' => "__int128",
+        'M' => "unsigned __int128",
+        'N' => "bool",
+        'Q' => "char8_t",
+        'S' => "char16_t",
+        'U' => "char32_t",
+        'W' => "wchar_t",
+        _ => return None,
+    })
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    /// The names that digits refer back to.
+    names: Vec<String>,
+    /// The parameter types that digits refer back to.
+    types: Vec<Type>,
+    /// How many types being decoded enclose the current one.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            names: Vec::new(),
+            types: Vec::new(),
+            depth: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input.chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let next = self.peek()?;
+        self.input = &self.input[next.len_utf8()..];
+        Some(next)
+    }
+
+    fn consume(&mut self, prefix: &str) -> bool {
+        if self.input.starts_with(prefix) {
+            self.input = &self.input[prefix.len()..];
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, prefix: &str) -> Option<()> {
+        if self.consume(prefix) {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn symbol(&mut self) -> Option<Demangled> {
+        self.expect("?")?;
+        if self.consume("?_C@") {
+            // String literals encode a hash and a prefix of the string
+            self.input = "";
+            return Some(Demangled {
+                scheme: ManglingScheme::Msvc,
+                scope: Vec::new(),
+                name: "`string'".to_owned(),
+                kind: DemangledKind::Special,
+            });
+        }
+
+        let name = if self.input.starts_with('?') && !self.input.starts_with("?$") {
+            self.next();
+            self.special_name()?
+        } else {
+            SymbolName::Named(self.name_fragment()?)
+        };
+        let scope = self.scope()?;
+
+        let is_conversion = matches!(name, SymbolName::Conversion);
+        let mut name = match name {
+            SymbolName::Named(name) | SymbolName::Special(name) => name,
+            SymbolName::Constructor => scope.last()?.clone(),
+            SymbolName::Destructor => format!("~{}", scope.last()?),
+            SymbolName::Conversion => String::new(),
+        };
+        let code = self.next()?;
+        let kind = match code {
+            '0'..='4' => {
+                let data_type = self.data_type()?;
+                let data_type = data_type.qualified(self.storage_qualifiers()?);
+                let access = match code {
+                    '0' => Some(Access::Private),
+                    '1' => Some(Access::Protected),
+                    '2' => Some(Access::Public),
+                    _ => None,
+                };
+                DemangledKind::Variable {
+                    access,
+                    is_static: code != '3',
+                    data_type: Some(data_type),
+                }
+            }
+            // Virtual function and base tables, optionally for one of the bases
+            '6' | '7' => {
+                self.storage_qualifiers()?;
+                if !self.consume("@") {
+                    let base = self.scope()?.join("::");
+                    self.expect("@")?;
+                    name = format!("{}{{for `{}'}}", name, base);
+                }
+                DemangledKind::Special
+            }
+            '8' => DemangledKind::Special,
+            _ => {
+                let (access, is_static, is_virtual) = function_kind(code)?;
+                let qualifiers = if access.is_some() && !is_static {
+                    self.storage_qualifiers()?
+                } else {
+                    Qualifiers::default()
+                };
+                let mut signature = self.function_type()?;
+                signature.qualifiers = qualifiers;
+                if is_conversion {
+                    name = format!("operator {}", signature.return_type.as_ref()?);
+                }
+                DemangledKind::Function {
+                    access,
+                    is_static,
+                    is_virtual,
+                    signature,
+                }
+            }
+        };
+
+        Some(Demangled {
+            scheme: ManglingScheme::Msvc,
+            scope,
+            name,
+            kind,
+        })
+    }
+
+    /// Decodes the name following `??`: constructors, destructors, operators and the
+    /// compiler generated functions and data.
+    fn special_name(&mut self) -> Option<SymbolName> {
+        let code = self.next()?;
+        match code {
+            '0' => return Some(SymbolName::Constructor),
+            '1' => return Some(SymbolName::Destructor),
+            'B' => return Some(SymbolName::Conversion),
+            '_' => {}
+            _ => return operator_name(code).map(|name| SymbolName::Named(name.to_owned())),
+        }
+
+        let code = self.next()?;
+        if code != 'R' {
+            return extended_operator_name(code).map(|name| SymbolName::Special(name.to_owned()));
+        }
+        let name = match self.next()? {
+            '0' => {
+                self.expect("?")?;
+                let qualifiers = self.qualifiers()?;
+                let data_type = self.data_type()?.qualified(qualifiers);
+                format!("{} `RTTI Type Descriptor'", data_type)
+            }
+            '1' => {
+                let mut displacements = Vec::new();
+                for _ in 0..4 {
+                    displacements.push(self.number()?.to_string());
+                }
+                format!(
+                    "`RTTI Base Class Descriptor at ({})'",
+                    displacements.join(", ")
+                )
+            }
+            '2' => "`RTTI Base Class Array'".to_owned(),
+            '3' => "`RTTI Class Hierarchy Descriptor'".to_owned(),
+            '4' => "`RTTI Complete Object Locator'".to_owned(),
+            _ => return None,
+        };
+        Some(SymbolName::Special(name))
+    }
+
+    /// Decodes the names of the enclosing namespaces and classes up to the terminating `@`,
+    /// outermost first.
+    fn scope(&mut self) -> Option<Vec<String>> {
+        let mut scope = Vec::new();
+        while !self.consume("@") {
+            scope.push(self.name_fragment()?);
+        }
+        scope.reverse();
+        Some(scope)
+    }
+
+    fn name_fragment(&mut self) -> Option<String> {
+        let first = self.peek()?;
+        if let Some(index) = first.to_digit(10) {
+            self.next();
+            return self.names.get(index as usize).cloned();
+        }
+
+        let name = if self.consume("?$") {
+            // Template arguments have their own back references
+            let names = std::mem::take(&mut self.names);
+            let types = std::mem::take(&mut self.types);
+            let name = self.identifier();
+            if let Some(name) = &name {
+                self.remember_name(name.clone());
+            }
+            let arguments = name.as_ref().and_then(|_| self.template_arguments());
+            self.names = names;
+            self.types = types;
+            format!("{}<{}>", name?, arguments?.join(", "))
+        } else if self.consume("?A") {
+            self.identifier()?;
+            "`anonymous namespace'".to_owned()
+        } else if first == '?' {
+            // Nested names of local classes and statics are not supported
+            return None;
+        } else {
+            self.identifier()?
+        };
+        self.remember_name(name.clone());
+        Some(name)
+    }
+
+    fn identifier(&mut self) -> Option<String> {
+        let end = self.input.find('@')?;
+        let identifier = &self.input[..end];
+        if identifier.is_empty() {
+            return None;
+        }
+        self.input = &self.input[end + 1..];
+        Some(identifier.to_owned())
+    }
+
+    fn remember_name(&mut self, name: String) {
+        if self.names.len() < MAX_BACK_REFERENCES && !self.names.contains(&name) {
+            self.names.push(name);
+        }
+    }
+
+    fn template_arguments(&mut self) -> Option<Vec<String>> {
+        let mut arguments = Vec::new();
+        while !self.consume("@") {
+            if self.consume("$0") {
+                arguments.push(self.number()?.to_string());
+            } else if self.consume("$$V") || self.consume("$$Z") {
+                // An empty parameter pack
+            } else {
+                arguments.push(self.remembered_type()?.to_string());
+            }
+        }
+        Some(arguments)
+    }
+
+    /// Decodes a number: a digit for 1 to 10, or hexadecimal digits from `A` to `P` terminated
+    /// by `@`, optionally negated by a leading `?`.
+    fn number(&mut self) -> Option<i64> {
+        let negative = self.consume("?");
+        let first = self.next()?;
+        let value = match first.to_digit(10) {
+            Some(digit) => digit as i64 + 1,
+            None => {
+                let mut value: i64 = 0;
+                let mut digit = first;
+                while digit != '@' {
+                    if !('A'..='P').contains(&digit) {
+                        return None;
+                    }
+                    value = value.checked_mul(16)? + (digit as i64 - 'A' as i64);
+                    digit = self.next()?;
+                }
+                value
+            }
+        };
+        Some(if negative { -value } else { value })
+    }
+
+    /// Decodes the cv-qualifiers of data, of pointed-to types and of `this`.
+    fn qualifiers(&mut self) -> Option<Qualifiers> {
+        let (is_const, is_volatile) = match self.next()? {
+            'A' => (false, false),
+            'B' => (true, false),
+            'C' => (false, true),
+            'D' => (true, true),
+            _ => return None,
+        };
+        Some(Qualifiers {
+            is_const,
+            is_volatile,
+        })
+    }
+
+    /// Decodes qualifiers that may be preceded by the `__ptr64` marker of 64-bit images.
+    fn storage_qualifiers(&mut self) -> Option<Qualifiers> {
+        self.consume("E");
+        self.qualifiers()
+    }
+
+    /// Decodes a parameter type, remembering it if it is longer than a single character.
+    fn remembered_type(&mut self) -> Option<Type> {
+        let length = self.input.len();
+        let parameter = self.data_type()?;
+        if length - self.input.len() > 1 && self.types.len() < MAX_BACK_REFERENCES {
+            self.types.push(parameter.clone());
+        }
+        Some(parameter)
+    }
+
+    fn data_type(&mut self) -> Option<Type> {
+        if self.depth == MAX_TYPE_DEPTH {
+            return None;
+        }
+        self.depth += 1;
+        let data_type = self.decode_data_type();
+        self.depth -= 1;
+        data_type
+    }
+
+    fn decode_data_type(&mut self) -> Option<Type> {
+        let code = self.next()?;
+        if let Some(builtin) = builtin_type(code) {
+            return Some(Type::Builtin(builtin));
+        }
+        if let Some(index) = code.to_digit(10) {
+            return self.types.get(index as usize).cloned();
+        }
+
+        let data_type = match code {
+            '_' => Type::Builtin(extended_builtin_type(self.next()?)?),
+            'T' | 'U' | 'V' => Type::Named {
+                keyword: Some(match code {
+                    'T' => "union",
+                    'U' => "struct",
+                    _ => "class",
+                }),
+                name: self.scope()?.join("::"),
+            },
+            'W' => {
+                // The underlying type of the enum, always `4` for `int`
+                self.next()?;
+                Type::Named {
+                    keyword: Some("enum"),
+                    name: self.scope()?.join("::"),
+                }
+            }
+            'P' | 'Q' | 'R' | 'S' => {
+                let qualifiers = Qualifiers {
+                    is_const: code == 'Q' || code == 'S',
+                    is_volatile: code == 'R' || code == 'S',
+                };
+                self.indirection(Type::Pointer)?.qualified(qualifiers)
+            }
+            'A' => self.indirection(Type::Reference)?,
+            'B' => self.indirection(Type::Reference)?.qualified(Qualifiers {
+                is_const: false,
+                is_volatile: true,
+            }),
+            'Y' => {
+                let dimensions = self.number()?;
+                let mut lengths = Vec::new();
+                for _ in 0..dimensions {
+                    lengths.push(self.number()? as u64);
+                }
+                let mut data_type = self.data_type()?;
+                for length in lengths.into_iter().rev() {
+                    data_type = Type::Array {
+                        element: Box::new(data_type),
+                        length: Some(length),
+                    };
+                }
+                data_type
+            }
+            '?' => {
+                let qualifiers = self.qualifiers()?;
+                self.data_type()?.qualified(qualifiers)
+            }
+            '$' if self.consume("$Q") => self.indirection(Type::RvalueReference)?,
+            '$' if self.consume("$A6") => Type::Function(Box::new(self.function_type()?)),
+            '$' if self.consume("$C") => {
+                let qualifiers = self.qualifiers()?;
+                self.data_type()?.qualified(qualifiers)
+            }
+            '$' if self.consume("$T") => Type::Builtin("std::nullptr_t"),
+            _ => return None,
+        };
+        Some(data_type)
+    }
+
+    /// Decodes what a pointer or reference refers to, and wraps it with `wrap`.
+    fn indirection(&mut self, wrap: fn(Box<Type>) -> Type) -> Option<Type> {
+        // __ptr64, __restrict and __unaligned
+        while self.consume("E") || self.consume("I") || self.consume("F") {}
+
+        if self.consume("6") {
+            let function = self.function_type()?;
+            return Some(wrap(Box::new(Type::Function(Box::new(function)))));
+        }
+        if self.consume("8") {
+            let class = self.scope()?.join("::");
+            let qualifiers = self.storage_qualifiers()?;
+            let mut function = self.function_type()?;
+            function.qualifiers = qualifiers;
+            return Some(Type::MemberPointer {
+                class,
+                member: Box::new(Type::Function(Box::new(function))),
+            });
+        }
+
+        let (is_const, is_volatile, is_member) = match self.next()? {
+            'A' => (false, false, false),
+            'B' => (true, false, false),
+            'C' => (false, true, false),
+            'D' => (true, true, false),
+            'Q' => (false, false, true),
+            'R' => (true, false, true),
+            'S' => (false, true, true),
+            'T' => (true, true, true),
+            _ => return None,
+        };
+        let qualifiers = Qualifiers {
+            is_const,
+            is_volatile,
+        };
+        if is_member {
+            let class = self.scope()?.join("::");
+            let member = self.data_type()?.qualified(qualifiers);
+            return Some(Type::MemberPointer {
+                class,
+                member: Box::new(member),
+            });
+        }
+        Some(wrap(Box::new(self.data_type()?.qualified(qualifiers))))
+    }
+
+    /// Decodes the calling convention, return type, parameters and exception specification of
+    /// a function.
+    fn function_type(&mut self) -> Option<FunctionType> {
+        let calling_convention = calling_convention(self.next()?)?;
+        let return_type = if self.consume("@") {
+            None
+        } else {
+            Some(self.data_type()?)
+        };
+
+        let mut parameters = Vec::new();
+        let mut variadic = false;
+        if !self.consume("X") {
+            loop {
+                if self.consume("@") {
+                    break;
+                }
+                if self.consume("Z") {
+                    variadic = true;
+                    break;
+                }
+                parameters.push(self.remembered_type()?);
+            }
+        }
+        // The exception specification, which is always empty
+        if !self.consume("Z") && !self.consume("_E") {
+            return None;
+        }
+
+        Some(FunctionType {
+            calling_convention,
+            return_type,
+            parameters,
+            variadic,
+            qualifiers: Qualifiers::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn undecorated(symbol: &str) -> String {
+        demangle(symbol).unwrap().to_string()
+    }
+
+    #[test]
+    fn demangles_functions() {
+        assert_eq!(
+            undecorated("?Run@CWinApp@@UAEHXZ"),
+            "public: virtual int __thiscall CWinApp::Run(void)"
+        );
+        assert_eq!(
+            undecorated("??0Foo@@QAE@XZ"),
+            "public: __thiscall Foo::Foo(void)"
+        );
+        assert_eq!(
+            undecorated("?h@N@@YGHPAUS@@ABVC@1@@Z"),
+            "int __stdcall N::h(struct S *, class N::C const &)"
+        );
+        assert_eq!(
+            undecorated("??$max@H@std@@YAABHABH0@Z"),
+            "int const & __cdecl std::max<int>(int const &, int const &)"
+        );
+        assert_eq!(
+            undecorated("?f@@YAXP6AXH@ZPAY02H@Z"),
+            "void __cdecl f(void (__cdecl *)(int), int (*)[3])"
+        );
+        assert_eq!(
+            undecorated("?f@@YAXV?$vector@HV?$allocator@H@std@@@std@@@Z"),
+            "void __cdecl f(class std::vector<int, class std::allocator<int>>)"
+        );
+        assert_eq!(
+            undecorated("?f@@YAXP8C@@AEHXZ@Z"),
+            "void __cdecl f(int (__thiscall C::*)(void))"
+        );
+        assert_eq!(
+            undecorated("??BC@@QAEHXZ"),
+            "public: int __thiscall C::operator int(void)"
+        );
+        assert_eq!(undecorated("?v@@YAXHZZ"), "void __cdecl v(int, ...)");
+    }
+
+    #[test]
+    fn demangles_member_qualifiers() {
+        let demangled = demangle("?m@C@@QBEHXZ").unwrap();
+        assert_eq!(
+            demangled.to_string(),
+            "public: int __thiscall C::m(void) const"
+        );
+        assert_eq!(demangled.class().as_deref(), Some("C"));
+        match demangled.kind {
+            DemangledKind::Function { signature, .. } => {
+                assert!(signature.qualifiers.is_const);
+                assert_eq!(
+                    signature.calling_convention,
+                    Some(CallingConvention::Thiscall)
+                );
+            }
+            _ => panic!("not a function"),
+        }
+    }
+
+    #[test]
+    fn demangles_data() {
+        assert_eq!(undecorated("?x@@3QAHA"), "int *const x");
+        assert_eq!(undecorated("?a@@3PAY02HA"), "int (*a)[3]");
+        assert_eq!(undecorated("??_7Foo@@6B@"), "Foo::`vftable'");
+        assert_eq!(
+            undecorated("??_7Foo@@6BBar@@@"),
+            "Foo::`vftable'{for `Bar'}"
+        );
+        assert_eq!(
+            undecorated("??_R0?AVFoo@@@8"),
+            "class Foo `RTTI Type Descriptor'"
+        );
+        assert_eq!(
+            undecorated("??_R1A@?0A@EA@Foo@@8"),
+            "Foo::`RTTI Base Class Descriptor at (0, -1, 0, 64)'"
+        );
+        assert_eq!(
+            demangle_msvc_type(".?AV?$CArray@PAVCWnd@@PAV1@@@")
+                .unwrap()
+                .to_string(),
+            "class CArray<class CWnd *, class CWnd *>"
+        );
+        assert_eq!(demangle_msvc_type(".PAD").unwrap().to_string(), "char *");
+    }
+
+    #[test]
+    fn rejects_deeply_nested_types() {
+        let nested = |depth| format!("?x@@3{}HA", "PA".repeat(depth));
+        assert_eq!(undecorated(&nested(3)), "int ***x");
+        assert_eq!(demangle(&nested(1000)), None);
+    }
+}
@@ -0,0 +1,611 @@
+use crate::analysis::{
+    code_pointer_targets, Architecture, CodeAddress, ConventionInference, DataClassification,
+    ExceptionFrame, FrameSetup, JumpTable, LoadedImage, SegmentedAddress,
+};
+
+use petgraph::graphmap::DiGraphMap;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::Write;
+use zydis::{
+    DecodedInstruction, DecodedOperand, Decoder, Formatter, FormatterStyle, InstructionCategory,
+    Mnemonic, OperandType, OutputBuffer, Register,
+};
+
+/// The kind of control flow from one instruction to another.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FlowKind {
+    /// Execution continues with the next instruction.
+    Fallthrough,
+    Jump,
+    ConditionalJump,
+    Call,
+    /// An indirect jump to one of the cases of a jump table.
+    Switch,
+    /// From the installation of an exception handler to code the handler calls or continues
+    /// at, such as filters, `__except` blocks and catch funclets.
+    Exception,
+}
+
+pub struct Instruction {
+    pub address: CodeAddress,
+    pub decoded: DecodedInstruction,
+}
+
+impl Instruction {
+    pub fn end(&self) -> u64 {
+        self.address.address + self.decoded.length as u64
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Function {
+    pub entry: u64,
+    pub name: Option<String>,
+    /// The addresses of all instructions reachable from the entry without following calls.
+    pub instructions: BTreeSet<u64>,
+    pub calling_convention: Option<ConventionInference>,
+}
+
+/// The kind of data found in the middle of code.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DataKind {
+    /// The case addresses of a switch.
+    JumpTable,
+    /// The bytes mapping switch values to the entries of a jump table.
+    JumpIndexTable,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DataItem {
+    pub length: u64,
+    pub kind: DataKind,
+}
+
+#[derive(Default)]
+pub struct Disassembly {
+    pub instructions: BTreeMap<u64, Instruction>,
+    /// Control flow between instructions, keyed by linear address.
+    pub flow: DiGraphMap<u64, FlowKind>,
+    pub functions: BTreeMap<u64, Function>,
+    /// Jump tables, keyed by the address of their indirect jump.
+    pub jump_tables: BTreeMap<u64, JumpTable>,
+    /// Data known not to be code, keyed by start address.
+    pub data: BTreeMap<u64, DataItem>,
+    /// Structured exception handling frames, keyed by the instruction installing them.
+    pub exception_frames: BTreeMap<u64, ExceptionFrame>,
+    /// The words of the data sections, classified once all code is disassembled.
+    pub data_classification: DataClassification,
+}
+
+impl Disassembly {
+    /// Formats all instructions of the disassembly, one per line, in address order.
+    pub fn listing(&self, image: &LoadedImage) -> zydis::Result<String> {
+        let formatter = Formatter::new(FormatterStyle::INTEL)?;
+        let mut buffer = [0u8; 256];
+        let mut buffer = OutputBuffer::new(&mut buffer[..]);
+
+        let mut listing = String::new();
+        for instruction in self.instructions.values() {
+            if self.functions.contains_key(&instruction.address.address) {
+                writeln!(listing).unwrap();
+            }
+
+            formatter.format_instruction(
+                &instruction.decoded,
+                &mut buffer,
+                Some(image.instruction_pointer(instruction.address)),
+                None,
+            )?;
+            writeln!(
+                listing,
+                "{}  {}",
+                image.format_address(instruction.address),
+                buffer
+            )
+            .unwrap();
+        }
+
+        Ok(listing)
+    }
+
+    /// Returns the data item containing the given address, together with its start address.
+    pub fn data_at(&self, address: u64) -> Option<(u64, &DataItem)> {
+        self.data
+            .range(..=address)
+            .next_back()
+            .filter(|(&start, item)| address < start + item.length)
+            .map(|(&start, item)| (start, item))
+    }
+
+    /// Returns the instruction that execution falls through from to the instruction at
+    /// `address`, if there is one.
+    pub fn fallthrough_predecessor(&self, address: u64) -> Option<&Instruction> {
+        let (&previous, instruction) = self.instructions.range(..address).next_back()?;
+        match self.flow.edge_weight(previous, address) {
+            Some(FlowKind::Fallthrough) => Some(instruction),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the address of a memory operand that has neither a base nor an index register, such
+/// as an import address table slot in `call dword ptr [slot]`.
+pub(crate) fn absolute_memory_address(operand: &DecodedOperand) -> Option<u64> {
+    if operand.ty == OperandType::MEMORY
+        && operand.mem.base == Register::NONE
+        && operand.mem.index == Register::NONE
+    {
+        Some(operand.mem.disp.displacement as u64 & 0xFFFF_FFFF)
+    } else {
+        None
+    }
+}
+
+/// A recursive-descent disassembler, following control flow from a set of entry points.
+pub struct Disassembler<'a> {
+    image: &'a LoadedImage,
+    decoder: Decoder,
+    function_entries: BTreeSet<CodeAddress>,
+    queue: VecDeque<CodeAddress>,
+    /// Exception frames whose tables are read once the code around them is disassembled.
+    frame_setups: Vec<FrameSetup>,
+    disassembly: Disassembly,
+}
+
+impl<'a> Disassembler<'a> {
+    pub fn new(image: &'a LoadedImage) -> zydis::Result<Self> {
+        let mut disassembler = Self {
+            image,
+            decoder: image.architecture.decoder()?,
+            function_entries: BTreeSet::new(),
+            queue: VecDeque::new(),
+            frame_setups: Vec::new(),
+            disassembly: Disassembly::default(),
+        };
+        for &entry_point in &image.entry_points {
+            disassembler.add_function(entry_point);
+        }
+        for function in image.unwind_functions.values() {
+            for &routine in std::iter::once(&function.entry).chain(&function.handler) {
+                disassembler.add_function(CodeAddress::flat(routine));
+            }
+        }
+
+        Ok(disassembler)
+    }
+
+    /// Marks the given address as a function entry, and queues it for disassembly.
+    pub fn add_function(&mut self, address: CodeAddress) {
+        if self.function_entries.insert(address) {
+            self.queue.push_back(address);
+        }
+    }
+
+    pub fn run(mut self) -> Disassembly {
+        loop {
+            while let Some(address) = self.queue.pop_front() {
+                self.disassemble_from(address);
+            }
+            if !self.recover_exception_frames() && !self.follow_code_pointers() {
+                break;
+            }
+        }
+        self.disassembly.data_classification =
+            DataClassification::classify(self.image, &self.disassembly);
+
+        let function_entries = std::mem::take(&mut self.function_entries);
+        let entries: BTreeSet<u64> = function_entries.iter().map(|entry| entry.address).collect();
+        for entry in function_entries {
+            if self.disassembly.instructions.contains_key(&entry.address) {
+                let function = self.collect_function(entry.address, &entries);
+                self.disassembly.functions.insert(entry.address, function);
+            }
+        }
+
+        self.disassembly
+    }
+
+    fn disassemble_from(&mut self, start: CodeAddress) {
+        let mut address = start;
+        while !self.disassembly.instructions.contains_key(&address.address)
+            && self.disassembly.data_at(address.address).is_none()
+        {
+            let decoded = match self
+                .image
+                .bytes_at(address.address)
+                .and_then(|bytes| self.decoder.decode(bytes).ok().flatten())
+            {
+                Some(decoded) => decoded,
+                None => return,
+            };
+
+            let instruction = Instruction { address, decoded };
+            let next = self.fallthrough_address(&instruction);
+            let target = self.branch_target(&instruction);
+            self.disassembly.flow.add_node(address.address);
+            self.disassembly
+                .instructions
+                .insert(address.address, instruction);
+
+            let continues = match decoded.meta.category {
+                InstructionCategory::RET => false,
+                InstructionCategory::UNCOND_BR => {
+                    match target {
+                        Some(target) => self.add_edge(address, target, FlowKind::Jump),
+                        None => self.recover_jump_table(address),
+                    }
+                    false
+                }
+                InstructionCategory::COND_BR => {
+                    if let Some(target) = target {
+                        self.add_edge(address, target, FlowKind::ConditionalJump);
+                    }
+                    true
+                }
+                InstructionCategory::CALL => {
+                    if let Some(target) = target {
+                        self.disassembly.flow.add_edge(
+                            address.address,
+                            target.address,
+                            FlowKind::Call,
+                        );
+                        self.add_function(target);
+                    }
+                    true
+                }
+                _ => !matches!(
+                    decoded.mnemonic,
+                    Mnemonic::HLT | Mnemonic::UD2 | Mnemonic::IRET | Mnemonic::IRETD
+                ),
+            };
+            if decoded.mnemonic == Mnemonic::MOV
+                || decoded.meta.category == InstructionCategory::CALL
+            {
+                self.find_frame_setup(address);
+            }
+
+            if !continues {
+                return;
+            }
+
+            self.disassembly
+                .flow
+                .add_edge(address.address, next.address, FlowKind::Fallthrough);
+            address = next;
+        }
+    }
+
+    fn add_edge(&mut self, from: CodeAddress, to: CodeAddress, kind: FlowKind) {
+        self.disassembly
+            .flow
+            .add_edge(from.address, to.address, kind);
+        self.queue.push_back(to);
+    }
+
+    /// Follows the cases of a switch ending in the given indirect jump, and marks its tables as
+    /// data.
+    fn recover_jump_table(&mut self, jump: CodeAddress) {
+        if self.image.architecture != Architecture::X86_32 {
+            return;
+        }
+        let table = match JumpTable::recover(self.image, &self.disassembly, jump.address) {
+            Some(table) => table,
+            None => return,
+        };
+
+        let targets: BTreeSet<u64> = table.targets.iter().copied().collect();
+        for target in targets {
+            let target = CodeAddress {
+                address: target,
+                segment: jump.segment,
+            };
+            self.add_edge(jump, target, FlowKind::Switch);
+        }
+        for (start, length, kind) in table.data() {
+            self.disassembly
+                .data
+                .insert(start, DataItem { length, kind });
+        }
+        self.disassembly.jump_tables.insert(jump.address, table);
+    }
+
+    /// Remembers the exception frame installed by the given instruction, if it installs one.
+    fn find_frame_setup(&mut self, install: CodeAddress) {
+        if self.image.architecture != Architecture::X86_32 {
+            return;
+        }
+        if let Some(setup) = FrameSetup::find(self.image, &self.disassembly, install.address) {
+            self.frame_setups.push(setup);
+        }
+    }
+
+    /// Reads the tables of the exception frames found so far, and follows their handler
+    /// routines and the code they call or continue at. Returns whether there is more code to
+    /// disassemble.
+    fn recover_exception_frames(&mut self) -> bool {
+        for setup in std::mem::take(&mut self.frame_setups) {
+            let frame = match ExceptionFrame::recover(self.image, &self.disassembly, &setup) {
+                Some(frame) => frame,
+                None => continue,
+            };
+
+            let install = self.disassembly.instructions[&setup.install].address;
+            for &routine in setup.handler.iter().chain(&setup.stub) {
+                self.add_function(CodeAddress {
+                    address: routine,
+                    segment: install.segment,
+                });
+            }
+            for (funclet, kind) in frame.funclets() {
+                let funclet = CodeAddress {
+                    address: funclet,
+                    segment: install.segment,
+                };
+                if kind.is_called() {
+                    self.add_function(funclet);
+                }
+                self.add_edge(install, funclet, FlowKind::Exception);
+            }
+            self.disassembly
+                .exception_frames
+                .insert(setup.install, frame);
+        }
+        !self.queue.is_empty()
+    }
+
+    /// Follows the code pointers in the data sections, to the callbacks and virtual functions
+    /// that are never called directly. Returns whether there is more code to disassemble.
+    fn follow_code_pointers(&mut self) -> bool {
+        for target in code_pointer_targets(self.image, &self.disassembly) {
+            let is_known = match self.disassembly.instructions.range(..=target).next_back() {
+                Some((_, instruction)) => instruction.end() > target,
+                None => false,
+            };
+            if !is_known {
+                self.add_function(CodeAddress::flat(target));
+            }
+        }
+        !self.queue.is_empty()
+    }
+
+    /// The address execution continues at after the given instruction. In segmented code, the
+    /// instruction pointer wraps around at the end of the segment like branch targets do.
+    fn fallthrough_address(&self, instruction: &Instruction) -> CodeAddress {
+        let address = instruction.address;
+        if !self.image.architecture.is_segmented() {
+            return CodeAddress {
+                address: instruction.end(),
+                segment: address.segment,
+            };
+        }
+
+        let ip = self.image.instruction_pointer(address) + instruction.decoded.length as u64;
+        CodeAddress {
+            address: self.image.segment_base(address.segment).unwrap_or(0) + (ip & 0xFFFF),
+            segment: address.segment,
+        }
+    }
+
+    /// Resolves the target of a direct branch or call.
+    fn branch_target(&self, instruction: &Instruction) -> Option<CodeAddress> {
+        let decoded = &instruction.decoded;
+        let operand = decoded.operands[..decoded.operand_count as usize].first()?;
+
+        match operand.ty {
+            OperandType::IMMEDIATE if operand.imm.is_relative => {
+                let ip = self.image.instruction_pointer(instruction.address);
+                let mut target = decoded.calc_absolute_address(ip, operand).ok()?;
+                if self.image.architecture.is_segmented() {
+                    target &= 0xFFFF;
+                }
+
+                Some(CodeAddress {
+                    address: self.image.segment_base(instruction.address.segment)? + target,
+                    segment: instruction.address.segment,
+                })
+            }
+            OperandType::POINTER => self.image.resolve_far(SegmentedAddress {
+                segment: operand.ptr.segment,
+                offset: operand.ptr.offset as u16,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Collects the instructions reachable from the entry of a function, without following
+    /// calls and without entering funclets that are functions of their own. Functions with
+    /// unwind info do not extend beyond the ranges it gives.
+    fn collect_function(&self, entry: u64, entries: &BTreeSet<u64>) -> Function {
+        let unwind_function = self.image.unwind_functions.get(&entry);
+        let mut instructions = BTreeSet::new();
+        let mut pending = vec![entry];
+        while let Some(address) = pending.pop() {
+            if !self.disassembly.instructions.contains_key(&address)
+                || matches!(unwind_function, Some(function) if !function.contains(address))
+                || !instructions.insert(address)
+            {
+                continue;
+            }
+
+            pending.extend(
+                self.disassembly
+                    .flow
+                    .edges(address)
+                    .filter(|(_, to, kind)| match kind {
+                        FlowKind::Call => false,
+                        FlowKind::Exception => !entries.contains(to),
+                        _ => true,
+                    })
+                    .map(|(_, to, _)| to),
+            );
+        }
+
+        Function {
+            entry,
+            name: None,
+            instructions,
+            calling_convention: None,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::analysis::{
+        load_pe_file, Addressing, DataType, MemoryMap, MemorySection, MemorySlice,
+    };
+    use crate::parsers::pe32::tests::{TestImage, TestSection};
+    use crate::parsers::pe32::{KnownDataDirectoryType, SectionCharacteristics};
+    use crate::parsers::SharedBytes;
+
+    /// Loads the test executable with the given functions in its `.text` section, and
+    /// disassembles them starting with the first. Calls through 0x402040 call `ExitProcess`.
+    pub(crate) fn disassemble_functions(functions: &[(u64, &[u8])]) -> (LoadedImage, Disassembly) {
+        let entries: Vec<u64> = functions.iter().map(|&(address, _)| address).collect();
+        disassemble_code(functions, Vec::new(), &entries)
+    }
+
+    /// Loads the test executable with the given pieces of code in its `.text` section and the
+    /// given data in an `.rdata` section at 0x403000, and disassembles the given functions
+    /// starting with the first.
+    pub(crate) fn disassemble_code(
+        pieces: &[(u64, &[u8])],
+        data: Vec<u8>,
+        entries: &[u64],
+    ) -> (LoadedImage, Disassembly) {
+        let mut code = Vec::new();
+        for (address, piece) in pieces {
+            let start = (address - 0x0040_1000) as usize;
+            if code.len() < start + piece.len() {
+                code.resize(start + piece.len(), 0xCC);
+            }
+            code[start..start + piece.len()].copy_from_slice(piece);
+        }
+        let mut test_image = TestImage::executable();
+        test_image.entry_point = (entries[0] - 0x0040_0000) as u32;
+        test_image.sections[0].virtual_size = code.len() as u32;
+        test_image.sections[0].data = code;
+        // The base relocation would point into the middle of the functions
+        test_image.sections.truncate(2);
+        test_image
+            .directories
+            .retain(|directory| directory.0 != KnownDataDirectoryType::Basereloc as usize);
+        if !data.is_empty() {
+            let characteristics =
+                SectionCharacteristics::CNT_INITIALIZED_DATA | SectionCharacteristics::MEM_READ;
+            test_image
+                .sections
+                .push(TestSection::new(".rdata", 0x3000, data, characteristics));
+        }
+        let image = load_pe_file(&test_image.build()).unwrap();
+
+        let mut disassembler = Disassembler::new(&image).unwrap();
+        for &entry in &entries[1..] {
+            disassembler.add_function(CodeAddress::flat(entry));
+        }
+        let disassembly = disassembler.run();
+        (image, disassembly)
+    }
+
+    /// A real mode image with the given code at the start of segment 0x1000, and a `hlt` at
+    /// 2000:0000.
+    fn real_mode_image(code: &[u8]) -> LoadedImage {
+        let mut memory = vec![0xF4; 0x10010];
+        memory[..code.len()].copy_from_slice(code);
+        let mut memory_map = MemoryMap::new();
+        memory_map.insert(
+            MemorySlice {
+                rva: 0,
+                length: memory.len() as u64,
+            },
+            MemorySection::Code(SharedBytes::from(memory)),
+        );
+        LoadedImage {
+            architecture: Architecture::X86Real16,
+            addressing: Addressing::RealMode,
+            base_address: 0x10000,
+            memory_map,
+            entry_points: vec![CodeAddress {
+                address: 0x10000,
+                segment: Some(0x1000),
+            }],
+            symbols: Vec::new(),
+            unwind_functions: BTreeMap::new(),
+            relocations: BTreeSet::new(),
+        }
+    }
+
+    #[test]
+    fn follows_real_mode_branches() {
+        // call 0008; jmp far 2000:0000; ret
+        let image = real_mode_image(&[0xE8, 0x05, 0x00, 0xEA, 0x00, 0x00, 0x00, 0x20, 0xC3]);
+        let disassembly = Disassembler::new(&image).unwrap().run();
+
+        assert_eq!(
+            disassembly.instructions.keys().copied().collect::<Vec<_>>(),
+            vec![0x10000, 0x10003, 0x10008, 0x20000]
+        );
+        assert_eq!(
+            disassembly.functions.keys().copied().collect::<Vec<_>>(),
+            vec![0x10000, 0x10008]
+        );
+        assert_eq!(
+            disassembly.flow.edge_weight(0x10003, 0x20000),
+            Some(&FlowKind::Jump)
+        );
+        assert_eq!(
+            disassembly.instructions[&0x20000].address.segment,
+            Some(0x2000)
+        );
+        assert_eq!(
+            image.format_address(disassembly.instructions[&0x10008].address),
+            "1000:0008"
+        );
+    }
+
+    #[test]
+    fn wraps_instruction_pointer_at_segment_end() {
+        // jmp short FFFE; ...; nop; nop at 1000:FFFE, wrapping around to the jump
+        let mut code = vec![0xEB, 0xFC];
+        code.resize(0xFFFE, 0xF4);
+        code.extend_from_slice(&[0x90, 0x90]);
+        let image = real_mode_image(&code);
+        let disassembly = Disassembler::new(&image).unwrap().run();
+
+        assert!(disassembly.instructions.contains_key(&0x1FFFE));
+        assert_eq!(
+            disassembly.flow.edge_weight(0x1FFFF, 0x10000),
+            Some(&FlowKind::Fallthrough)
+        );
+        assert!(!disassembly.instructions.contains_key(&0x20000));
+    }
+
+    #[test]
+    fn follows_code_pointers() {
+        let code: &[(u64, &[u8])] = &[
+            (0x0040_1000, &[0xA1, 0x00, 0x30, 0x40, 0x00, 0xC3]), // mov eax, [0x403000]; ret
+            (0x0040_1010, &[0x55, 0x8B, 0xEC, 0x5D, 0xC3]),
+            (0x0040_1020, &[0x33, 0xC0, 0xC3]),
+            (0x0040_1030, &[0xC3]),
+            (0x0040_1040, &[0xC3]),
+        ];
+        // A table of callbacks read by the entry point, and a lone word pointing to code
+        let mut data = Vec::new();
+        for &word in &[0x0040_1010u32, 0x0040_1020, 0x0040_1030, 0, 0x0040_1040] {
+            data.extend_from_slice(&word.to_le_bytes());
+        }
+        let (_, disassembly) = disassemble_code(code, data, &[0x0040_1000]);
+
+        assert_eq!(
+            disassembly.functions.keys().collect::<Vec<_>>(),
+            vec![&0x0040_1000, &0x0040_1010, &0x0040_1020, &0x0040_1030]
+        );
+        let classification = &disassembly.data_classification;
+        assert!(classification.pointer_arrays[&0x0040_3000].is_function_table);
+        assert_eq!(
+            classification.words.get(&0x0040_3010),
+            Some(&DataType::Integer)
+        );
+    }
+}
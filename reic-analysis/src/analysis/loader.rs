@@ -0,0 +1,244 @@
+mod elf;
+pub use elf::*;
+
+mod le;
+pub use le::*;
+
+mod mz;
+pub use mz::*;
+
+mod ne;
+pub use ne::*;
+
+mod pe32;
+pub use pe32::*;
+
+mod raw;
+pub use raw::*;
+
+use crate::analysis::{MemoryMap, MemorySection, MemorySlice, UnwindFunction};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use zydis::{AddressWidth, Decoder, MachineMode};
+
+/// Turns a parsed executable into the memory model used by the analysis.
+pub trait Loadable {
+    fn load(&self) -> LoadedImage;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Architecture {
+    /// 16-bit x86 in real mode (DOS)
+    X86Real16,
+    /// 16-bit x86 in protected mode (Win16, OS/2)
+    X86Protected16,
+    /// 32-bit x86
+    X86_32,
+    /// x86-64
+    X86_64,
+}
+
+impl Architecture {
+    pub fn decoder(self) -> zydis::Result<Decoder> {
+        match self {
+            Architecture::X86Real16 => Decoder::new(MachineMode::REAL_16, AddressWidth::_16),
+            Architecture::X86Protected16 => Decoder::new(MachineMode::LEGACY_16, AddressWidth::_16),
+            Architecture::X86_32 => Decoder::new(MachineMode::LEGACY_32, AddressWidth::_32),
+            Architecture::X86_64 => Decoder::new(MachineMode::LONG_64, AddressWidth::_64),
+        }
+    }
+
+    /// Whether instruction pointers wrap around at 64K, relative to the code segment.
+    pub fn is_segmented(self) -> bool {
+        match self {
+            Architecture::X86Real16 | Architecture::X86Protected16 => true,
+            Architecture::X86_32 | Architecture::X86_64 => false,
+        }
+    }
+}
+
+/// How segment values (as found in far pointers, or in CS) map to linear addresses.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Addressing {
+    /// There are no segments, every address is linear.
+    Flat,
+    /// Real mode: the segment base is the segment value multiplied by 16.
+    RealMode,
+    /// Protected mode: each selector refers to a segment loaded at the given linear address.
+    Selectors(BTreeMap<u16, u64>),
+}
+
+/// A linear address, together with the code segment that is active at that address
+/// (for segmented architectures).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CodeAddress {
+    pub address: u64,
+    pub segment: Option<u16>,
+}
+
+impl CodeAddress {
+    pub fn flat(address: u64) -> Self {
+        Self {
+            address,
+            segment: None,
+        }
+    }
+}
+
+/// A `segment:offset` address.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SegmentedAddress {
+    pub segment: u16,
+    pub offset: u16,
+}
+
+impl SegmentedAddress {
+    /// The linear address of this address in real mode.
+    pub fn real_mode_linear(self) -> u64 {
+        self.segment as u64 * 16 + self.offset as u64
+    }
+}
+
+impl fmt::Display for SegmentedAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04X}:{:04X}", self.segment, self.offset)
+    }
+}
+
+/// A named address of a loaded image.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    pub address: u64,
+    pub name: String,
+    pub kind: SymbolKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// An entry point exported by the image.
+    Export,
+    /// The location the loader binds to an entry imported from another module.
+    Import { module: String },
+    /// A function known from debug or symbol table information.
+    Function,
+    /// A data object known from debug or symbol table information.
+    Data,
+}
+
+/// An executable image as it looks after being loaded into memory.
+pub struct LoadedImage {
+    pub architecture: Architecture,
+    pub addressing: Addressing,
+    /// The linear address the image is loaded at. All memory map entries are relative to it.
+    pub base_address: u64,
+    pub memory_map: MemoryMap,
+    pub entry_points: Vec<CodeAddress>,
+    pub symbols: Vec<Symbol>,
+    /// The functions described by the exception directory of x64 images, keyed by entry.
+    pub unwind_functions: BTreeMap<u64, UnwindFunction>,
+    /// The addresses of the pointers the loader rebases or binds. Empty if the image has no
+    /// relocation information.
+    pub relocations: BTreeSet<u64>,
+}
+
+impl LoadedImage {
+    /// Finds the memory slice that contains the given linear address.
+    pub fn slice_at(&self, address: u64) -> Option<(&MemorySlice, &MemorySection)> {
+        let rva = address.checked_sub(self.base_address)?;
+        self.memory_map
+            .range(
+                ..=MemorySlice {
+                    rva,
+                    length: u64::MAX,
+                },
+            )
+            .next_back()
+            .filter(|(slice, _)| slice.contains(rva))
+    }
+
+    /// Returns the backing bytes from the given linear address to the end of its memory slice.
+    pub fn bytes_at(&self, address: u64) -> Option<&[u8]> {
+        let (slice, section) = self.slice_at(address)?;
+        section
+            .data()?
+            .get((address - self.base_address - slice.rva) as usize..)
+    }
+
+    /// The linear base address of the given segment, or zero for flat addressing.
+    pub fn segment_base(&self, segment: Option<u16>) -> Option<u64> {
+        match (&self.addressing, segment) {
+            (Addressing::Flat, _) | (_, None) => Some(0),
+            (Addressing::RealMode, Some(segment)) => Some(segment as u64 * 16),
+            (Addressing::Selectors(selectors), Some(selector)) => selectors.get(&selector).copied(),
+        }
+    }
+
+    /// The value of the instruction pointer when executing at the given address.
+    pub fn instruction_pointer(&self, address: CodeAddress) -> u64 {
+        address.address - self.segment_base(address.segment).unwrap_or(0)
+    }
+
+    /// Converts a `segment:offset` pair to a linear address.
+    pub fn resolve_far(&self, address: SegmentedAddress) -> Option<CodeAddress> {
+        self.segment_base(Some(address.segment))
+            .map(|base| CodeAddress {
+                address: base + address.offset as u64,
+                segment: Some(address.segment),
+            })
+    }
+
+    /// Returns the `segment:offset` form of the given address, if the image is segmented.
+    pub fn segmented_address(&self, address: CodeAddress) -> Option<SegmentedAddress> {
+        let segment = address.segment?;
+        Some(SegmentedAddress {
+            segment,
+            offset: self.instruction_pointer(address) as u16,
+        })
+    }
+
+    pub fn format_address(&self, address: CodeAddress) -> String {
+        match self.segmented_address(address) {
+            Some(segmented) => segmented.to_string(),
+            None => format!("{:08X}", address.address),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::parsers::SharedBytes;
+
+    /// A 32-bit image based at 0x400000, with the given code padded with `int3` to 0x1000 bytes
+    /// at 0x401000, and the given initialized data at 0x402000.
+    pub(crate) fn test_image(code: &[u8], data: Vec<u8>) -> LoadedImage {
+        let mut code = code.to_vec();
+        code.resize(0x1000, 0xCC);
+        let mut memory_map = MemoryMap::new();
+        memory_map.insert(
+            MemorySlice {
+                rva: 0x1000,
+                length: code.len() as u64,
+            },
+            MemorySection::Code(SharedBytes::from(code)),
+        );
+        memory_map.insert(
+            MemorySlice {
+                rva: 0x2000,
+                length: data.len() as u64,
+            },
+            MemorySection::InitializedData(SharedBytes::from(data)),
+        );
+        LoadedImage {
+            architecture: Architecture::X86_32,
+            addressing: Addressing::Flat,
+            base_address: 0x0040_0000,
+            memory_map,
+            entry_points: Vec::new(),
+            symbols: Vec::new(),
+            unwind_functions: BTreeMap::new(),
+            relocations: BTreeSet::new(),
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use crate::analysis::{
+    Addressing, Architecture, CodeAddress, Loadable, LoadedImage, MemoryMap, MemorySection,
+    MemorySlice, Symbol, SymbolKind,
+};
+use crate::parsers::elf::{
+    ElfClass, ElfImage, ElfMachine, ElfRelocation, ElfSymbol, ElfSymbolType, SegmentFlags,
+    SymbolBinding, R_X86_ABSOLUTE, R_X86_GLOB_DAT, R_X86_JUMP_SLOT, R_X86_RELATIVE,
+};
+use crate::parsers::SharedBytes;
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::convert::TryInto;
+
+impl Loadable for ElfImage {
+    /// Loads every loadable segment at its virtual address, and applies the dynamic
+    /// relocations that can be resolved within the image.
+    ///
+    /// Position independent images are loaded at their link-time addresses (usually starting
+    /// at zero). GOT slots bound to undefined symbols carry `Import` symbols and are left as-is.
+    /// As ELF does not tie imported symbols to a specific library, their module is empty.
+    fn load(&self) -> LoadedImage {
+        let base_address = self
+            .load_segments()
+            .map(|segment| segment.header.virtual_address)
+            .min()
+            .unwrap_or(0);
+
+        let mut memory_map = MemoryMap::new();
+        for segment in self.load_segments() {
+            let header = &segment.header;
+            let mut data = segment.data.clone();
+            data.truncate(header.memory_size as usize);
+            for relocation in self.relocations.iter().chain(&self.plt_relocations) {
+                if header.contains_address(relocation.offset) {
+                    self.apply_relocation(
+                        &mut data,
+                        (relocation.offset - header.virtual_address) as usize,
+                        relocation,
+                    );
+                }
+            }
+
+            let rva = header.virtual_address - base_address;
+            let length = data.len() as u64;
+            let section = if header.flags.contains(SegmentFlags::EXECUTE) {
+                MemorySection::Code(SharedBytes::from(data))
+            } else {
+                MemorySection::InitializedData(SharedBytes::from(data))
+            };
+            if length > 0 {
+                memory_map.insert(MemorySlice { rva, length }, section);
+            }
+            if header.memory_size > length {
+                memory_map.insert(
+                    MemorySlice {
+                        rva: rva + length,
+                        length: header.memory_size - length,
+                    },
+                    MemorySection::UninitializedData,
+                );
+            }
+        }
+
+        let is_code = |address: u64| {
+            self.load_segments().any(|segment| {
+                segment.header.contains_address(address)
+                    && segment.header.flags.contains(SegmentFlags::EXECUTE)
+            })
+        };
+
+        let mut entry_points = Vec::new();
+        if self.header.entry != 0 {
+            entry_points.push(CodeAddress::flat(self.header.entry));
+        }
+
+        // The static symbol table usually repeats the dynamic symbols, so skip duplicates.
+        let mut symbols = Vec::new();
+        let mut seen = HashSet::new();
+        let defined = self
+            .dynamic_symbols
+            .iter()
+            .map(|symbol| (symbol, true))
+            .chain(self.symbols.iter().map(|symbol| (symbol, false)));
+        for (symbol, dynamic) in defined {
+            if !symbol.is_defined() || symbol.name.is_empty() {
+                continue;
+            }
+            let kind = match symbol.symbol_type {
+                _ if dynamic && symbol.binding != SymbolBinding::Local => SymbolKind::Export,
+                ElfSymbolType::Function => SymbolKind::Function,
+                ElfSymbolType::Object => SymbolKind::Data,
+                _ => continue,
+            };
+            if !seen.insert((symbol.value, symbol.name.as_str())) {
+                continue;
+            }
+
+            if symbol.symbol_type == ElfSymbolType::Function && is_code(symbol.value) {
+                entry_points.push(CodeAddress::flat(symbol.value));
+            }
+            symbols.push(Symbol {
+                address: symbol.value,
+                name: symbol.name.clone(),
+                kind,
+            });
+        }
+
+        for (&got_address, &index) in &self.got_slots() {
+            if let Some(symbol) = self.imported_symbol(index) {
+                symbols.push(Symbol {
+                    address: got_address,
+                    name: symbol.name.clone(),
+                    kind: SymbolKind::Import {
+                        module: String::new(),
+                    },
+                });
+            }
+        }
+
+        for entry in self.plt_entries() {
+            if let Some(symbol) = self.dynamic_symbols.get(entry.symbol as usize) {
+                symbols.push(Symbol {
+                    address: entry.address,
+                    name: format!("{}@plt", symbol.name),
+                    kind: SymbolKind::Function,
+                });
+            }
+        }
+
+        LoadedImage {
+            architecture: match self.header.machine {
+                ElfMachine::X86_64 => Architecture::X86_64,
+                _ => Architecture::X86_32,
+            },
+            addressing: Addressing::Flat,
+            base_address,
+            memory_map,
+            entry_points,
+            symbols,
+            unwind_functions: BTreeMap::new(),
+            relocations: self
+                .relocations
+                .iter()
+                .chain(&self.plt_relocations)
+                .filter(|relocation| {
+                    matches!(
+                        relocation.relocation_type,
+                        R_X86_RELATIVE | R_X86_ABSOLUTE | R_X86_GLOB_DAT | R_X86_JUMP_SLOT
+                    )
+                })
+                .map(|relocation| relocation.offset)
+                .collect(),
+        }
+    }
+}
+
+impl ElfImage {
+    /// Returns the dynamic symbol with the given index, if it is not defined by this image.
+    fn imported_symbol(&self, index: u32) -> Option<&ElfSymbol> {
+        self.dynamic_symbols
+            .get(index as usize)
+            .filter(|symbol| !symbol.is_defined() && !symbol.name.is_empty())
+    }
+
+    /// Patches the word at `position` with the resolved relocation value.
+    ///
+    /// Relocations against undefined symbols are skipped, as their target is not known.
+    fn apply_relocation(&self, data: &mut [u8], position: usize, relocation: &ElfRelocation) {
+        let word_size = self.header.class.word_size();
+        let location = match data.get_mut(position..position + word_size) {
+            Some(location) => location,
+            None => return,
+        };
+        let implicit_addend = match self.header.class {
+            ElfClass::Elf32 => u32::from_le_bytes(location.try_into().unwrap()) as u64,
+            ElfClass::Elf64 => u64::from_le_bytes(location.try_into().unwrap()),
+        };
+        let addend = relocation
+            .addend
+            .map_or(implicit_addend, |addend| addend as u64);
+
+        let symbol_value = || {
+            self.dynamic_symbols
+                .get(relocation.symbol as usize)
+                .filter(|symbol| symbol.is_defined())
+                .map(|symbol| symbol.value)
+        };
+        let value = match relocation.relocation_type {
+            R_X86_RELATIVE => addend,
+            R_X86_ABSOLUTE => match symbol_value() {
+                Some(value) => value.wrapping_add(addend),
+                None => return,
+            },
+            // On x86, GOT entries use the symbol value without the implicit addend.
+            R_X86_GLOB_DAT | R_X86_JUMP_SLOT => match symbol_value() {
+                Some(value) => value.wrapping_add(relocation.addend.unwrap_or(0) as u64),
+                None => return,
+            },
+            _ => return,
+        };
+
+        match self.header.class {
+            ElfClass::Elf32 => location.copy_from_slice(&(value as u32).to_le_bytes()),
+            ElfClass::Elf64 => location.copy_from_slice(&value.to_le_bytes()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::elf::tests::elf_file;
+    use crate::parsers::BinParsable;
+    use nom::error::VerboseError;
+
+    #[test]
+    fn loads_segments_and_binds_dynamic_symbols() {
+        let file = elf_file();
+        let (_, image) = ElfImage::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+        let loaded = image.load();
+
+        assert_eq!(loaded.architecture, Architecture::X86_32);
+        assert_eq!(loaded.base_address, 0x0804_8000);
+        let slices: Vec<MemorySlice> = loaded.memory_map.keys().copied().collect();
+        assert_eq!(
+            slices,
+            vec![
+                MemorySlice {
+                    rva: 0,
+                    length: 0x200
+                },
+                MemorySlice {
+                    rva: 0x1200,
+                    length: 0x100
+                },
+                MemorySlice {
+                    rva: 0x1300,
+                    length: 0x80
+                },
+            ]
+        );
+        assert_eq!(
+            loaded.entry_points,
+            vec![
+                CodeAddress::flat(0x0804_8120),
+                CodeAddress::flat(0x0804_8100)
+            ]
+        );
+        assert_eq!(
+            loaded.symbols,
+            vec![
+                Symbol {
+                    address: 0x0804_8100,
+                    name: "main".to_owned(),
+                    kind: SymbolKind::Export,
+                },
+                Symbol {
+                    address: 0x0804_9250,
+                    name: "puts".to_owned(),
+                    kind: SymbolKind::Import {
+                        module: String::new()
+                    },
+                },
+                Symbol {
+                    address: 0x0804_8140,
+                    name: "puts@plt".to_owned(),
+                    kind: SymbolKind::Function,
+                },
+            ]
+        );
+        let relocations: Vec<u64> = loaded.relocations.iter().copied().collect();
+        assert_eq!(relocations, vec![0x0804_9250, 0x0804_9254, 0x0804_9258]);
+
+        // The import slot keeps its lazy binding, the others are resolved within the image
+        let got = loaded.bytes_at(0x0804_9250).unwrap();
+        assert_eq!(&got[0..4], &0x0804_8146u32.to_le_bytes());
+        assert_eq!(&got[4..8], &0x0804_8100u32.to_le_bytes());
+        assert_eq!(&got[8..12], &0x0804_8120u32.to_le_bytes());
+    }
+}
@@ -0,0 +1,388 @@
+use crate::analysis::{
+    Addressing, Architecture, CodeAddress, Loadable, LoadedImage, MemoryMap, MemorySection,
+    MemorySlice, Symbol, SymbolKind,
+};
+use crate::parsers::le::{
+    FixupRecord, FixupSourceType, FixupTarget, LEEntryLocation, LEImage, ObjectFlags,
+};
+use crate::parsers::ne::EntryFlags;
+use crate::parsers::SharedBytes;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Range;
+
+/// The size of an import stub in the synthetic import area.
+const LE_IMPORT_STUB_SIZE: u32 = 4;
+
+/// The alignment of the synthetic import area following the last object, and of synthetic
+/// object base addresses.
+const LE_IMPORT_AREA_ALIGNMENT: u64 = 0x1000;
+
+/// The lowest synthetic base address given to objects without a usable one.
+const LE_SYNTHETIC_BASE_ADDRESS: u64 = 0x10000;
+
+impl LEImage {
+    /// Returns the linear address every object is loaded at.
+    ///
+    /// Objects are loaded at their preferred base address, unless it is zero or overlaps an
+    /// object placed before, as in VxDs. Those objects are placed one after the other, after
+    /// all objects loaded at their preferred base address.
+    pub fn object_bases(&self) -> Vec<u64> {
+        let mut bases = vec![None; self.objects.len()];
+        let mut placed: Vec<Range<u64>> = Vec::new();
+        for (base, object) in bases.iter_mut().zip(&self.objects) {
+            let start = object.header.relocation_base_address as u64;
+            let range = start..start + object.header.virtual_size as u64;
+            if start != 0
+                && !placed
+                    .iter()
+                    .any(|other| other.start < range.end && range.start < other.end)
+            {
+                *base = Some(start);
+                placed.push(range);
+            }
+        }
+
+        let mut next = placed
+            .iter()
+            .map(|range| range.end)
+            .max()
+            .unwrap_or(0)
+            .max(LE_SYNTHETIC_BASE_ADDRESS);
+        bases
+            .into_iter()
+            .zip(&self.objects)
+            .map(|(base, object)| {
+                base.unwrap_or_else(|| {
+                    let base = align_up(next, LE_IMPORT_AREA_ALIGNMENT);
+                    next = base + object.header.virtual_size as u64;
+                    base
+                })
+            })
+            .collect()
+    }
+}
+
+fn align_up(address: u64, alignment: u64) -> u64 {
+    (address + alignment - 1) & !(alignment - 1)
+}
+
+impl Loadable for LEImage {
+    /// Loads every object at the address given by `object_bases`, and applies all fixups.
+    ///
+    /// Imported entries are bound to stubs in a synthetic area following the last
+    /// object of the image, which carry `Import` symbols.
+    fn load(&self) -> LoadedImage {
+        let bases = self.object_bases();
+        let object_base =
+            |number: u16| -> Option<u64> { bases.get((number as usize).checked_sub(1)?).copied() };
+
+        let base_address = bases.iter().copied().min().unwrap_or(0);
+        let objects_end = bases
+            .iter()
+            .zip(&self.objects)
+            .map(|(base, object)| base + object.header.virtual_size as u64)
+            .max()
+            .unwrap_or(base_address);
+        let import_area = align_up(objects_end, LE_IMPORT_AREA_ALIGNMENT);
+
+        // Assign a stub in the import area to every imported entry.
+        let mut symbols = Vec::new();
+        let mut imports = HashMap::new();
+        let fixups = self
+            .objects
+            .iter()
+            .flat_map(|object| &object.pages)
+            .flat_map(|page| &page.fixups);
+        for fixup in fixups {
+            let (module, name) = match fixup.target {
+                FixupTarget::ImportOrdinal { module, ordinal } => (module, format!("#{}", ordinal)),
+                FixupTarget::ImportName {
+                    module,
+                    name_offset,
+                } => match self.imported_names.get(&name_offset) {
+                    Some(name) => (module, name.clone()),
+                    None => continue,
+                },
+                _ => continue,
+            };
+
+            if imports.contains_key(&fixup.target) {
+                continue;
+            }
+            let address = import_area + imports.len() as u64 * LE_IMPORT_STUB_SIZE as u64;
+            symbols.push(Symbol {
+                address,
+                name,
+                kind: SymbolKind::Import {
+                    module: self.import_module(module).unwrap_or_default().to_owned(),
+                },
+            });
+            imports.insert(fixup.target, address);
+        }
+
+        let resolve = |fixup: &FixupRecord| -> Option<(u16, u64)> {
+            let (object, address) = match fixup.target {
+                FixupTarget::Internal { object, offset } => {
+                    (object, object_base(object)? + offset as u64)
+                }
+                FixupTarget::InternalEntry { ordinal } => {
+                    match self.entries.get(&ordinal)?.location {
+                        LEEntryLocation::Object { object, offset } => {
+                            (object, object_base(object)? + offset as u64)
+                        }
+                        LEEntryLocation::Forwarder { .. } => return None,
+                    }
+                }
+                FixupTarget::ImportOrdinal { .. } | FixupTarget::ImportName { .. } => {
+                    (0, *imports.get(&fixup.target)?)
+                }
+            };
+            Some((object, address + fixup.additive.unwrap_or(0) as u64))
+        };
+
+        let page_size = self.le_header.page_size;
+        let mut memory_map = MemoryMap::new();
+        let mut relocations = BTreeSet::new();
+        for (&object_address, object) in bases.iter().zip(&self.objects) {
+            let mut data = object.data(page_size);
+            for (page_index, page) in object.pages.iter().enumerate() {
+                let page_start = page_index as i64 * page_size as i64;
+                for fixup in &page.fixups {
+                    let (selector, target) = match resolve(fixup) {
+                        Some(resolved) => resolved,
+                        None => continue,
+                    };
+                    for &source_offset in &fixup.source_offsets {
+                        let position = page_start + source_offset as i64;
+                        if position >= 0 {
+                            if fixup.source_type == FixupSourceType::Offset32 {
+                                relocations.insert(object_address + position as u64);
+                            }
+                            apply_fixup(
+                                &mut data,
+                                position as usize,
+                                object_address + position as u64,
+                                fixup.source_type,
+                                selector,
+                                target,
+                            );
+                        }
+                    }
+                }
+            }
+
+            let rva = object_address - base_address;
+            let length = data.len() as u64;
+            if length > 0 {
+                let section = if object.header.flags.contains(ObjectFlags::EXECUTABLE) {
+                    MemorySection::Code(SharedBytes::from(data))
+                } else {
+                    MemorySection::InitializedData(SharedBytes::from(data))
+                };
+                memory_map.insert(MemorySlice { rva, length }, section);
+            }
+            let virtual_size = object.header.virtual_size as u64;
+            if virtual_size > length {
+                memory_map.insert(
+                    MemorySlice {
+                        rva: rva + length,
+                        length: virtual_size - length,
+                    },
+                    MemorySection::UninitializedData,
+                );
+            }
+        }
+        if !imports.is_empty() {
+            memory_map.insert(
+                MemorySlice {
+                    rva: import_area - base_address,
+                    length: imports.len() as u64 * LE_IMPORT_STUB_SIZE as u64,
+                },
+                MemorySection::UninitializedData,
+            );
+        }
+
+        let mut entry_points = Vec::new();
+        if let Some(base) = object_base(self.le_header.eip_object as u16) {
+            entry_points.push(CodeAddress::flat(base + self.le_header.eip as u64));
+        }
+
+        for (&ordinal, entry) in &self.entries {
+            let (object, offset) = match entry.location {
+                LEEntryLocation::Object { object, offset } => (object, offset),
+                LEEntryLocation::Forwarder { .. } => continue,
+            };
+            let address = match object_base(object) {
+                Some(base) => base + offset as u64,
+                None => continue,
+            };
+
+            // The initial EIP is commonly exported too
+            let is_code = self.object(object as u32).map_or(false, |object| {
+                object.header.flags.contains(ObjectFlags::EXECUTABLE)
+            });
+            if is_code && !entry_points.contains(&CodeAddress::flat(address)) {
+                entry_points.push(CodeAddress::flat(address));
+            }
+            if entry.flags.contains(EntryFlags::EXPORTED) {
+                symbols.push(Symbol {
+                    address,
+                    name: self
+                        .entry_name(ordinal)
+                        .map_or_else(|| format!("#{}", ordinal), str::to_owned),
+                    kind: SymbolKind::Export,
+                });
+            }
+        }
+
+        LoadedImage {
+            architecture: Architecture::X86_32,
+            addressing: Addressing::Flat,
+            base_address,
+            memory_map,
+            entry_points,
+            symbols,
+            unwind_functions: BTreeMap::new(),
+            relocations,
+        }
+    }
+}
+
+/// Patches a single fixup location with the resolved target.
+///
+/// Selector fixups are patched with the 1-based number of the target object,
+/// as there are no real selectors in the flat memory model.
+fn apply_fixup(
+    data: &mut [u8],
+    position: usize,
+    address: u64,
+    source_type: FixupSourceType,
+    selector: u16,
+    target: u64,
+) {
+    let mut write = |delta: usize, bytes: &[u8]| {
+        if let Some(location) = data.get_mut(position + delta..position + delta + bytes.len()) {
+            location.copy_from_slice(bytes);
+        }
+    };
+
+    match source_type {
+        FixupSourceType::Byte => write(0, &[target as u8]),
+        FixupSourceType::Selector16 => write(0, &selector.to_le_bytes()),
+        FixupSourceType::FarPointer16 => {
+            write(0, &(target as u16).to_le_bytes());
+            write(2, &selector.to_le_bytes());
+        }
+        FixupSourceType::Offset16 => write(0, &(target as u16).to_le_bytes()),
+        FixupSourceType::FarPointer32 => {
+            write(0, &(target as u32).to_le_bytes());
+            write(4, &selector.to_le_bytes());
+        }
+        FixupSourceType::Offset32 => write(0, &(target as u32).to_le_bytes()),
+        FixupSourceType::SelfRelative32 => {
+            let relative = target.wrapping_sub(address + 4) as u32;
+            write(0, &relative.to_le_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::le::tests::le_file;
+    use crate::parsers::BinParsable;
+    use nom::error::VerboseError;
+
+    #[test]
+    fn loads_objects_at_synthetic_bases() {
+        let file = le_file();
+        let (_, image) = LEImage::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+        assert_eq!(image.object_bases(), vec![0x10000, 0x11000]);
+
+        let loaded = image.load();
+        assert_eq!(loaded.base_address, 0x10000);
+        assert_eq!(loaded.entry_points, vec![CodeAddress::flat(0x10000)]);
+        assert_eq!(
+            loaded.symbols,
+            vec![
+                Symbol {
+                    address: 0x12000,
+                    name: "Beep".to_owned(),
+                    kind: SymbolKind::Import {
+                        module: "KERNEL".to_owned()
+                    },
+                },
+                Symbol {
+                    address: 0x10000,
+                    name: "Entry".to_owned(),
+                    kind: SymbolKind::Export,
+                },
+            ]
+        );
+        assert_eq!(
+            loaded.relocations.iter().copied().collect::<Vec<_>>(),
+            vec![0x10001, 0x10007]
+        );
+
+        let code = loaded.bytes_at(0x10000).unwrap();
+        assert_eq!(&code[1..5], &0x11004u32.to_le_bytes());
+        assert_eq!(&code[7..11], &0x12000u32.to_le_bytes());
+        assert_eq!(loaded.bytes_at(0x11000).map(<[u8]>::len), Some(0x20));
+        assert!(loaded.slice_at(0x12000).is_some());
+    }
+
+    #[test]
+    fn maps_objects_beyond_their_pages_as_uninitialized() {
+        let mut file = le_file();
+        file[0x108..0x10C].copy_from_slice(&0x7FFF_0000u32.to_le_bytes());
+        let (_, image) = LEImage::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+
+        let loaded = image.load();
+        assert_eq!(loaded.bytes_at(0x11000).map(<[u8]>::len), Some(0x100));
+        let (slice, section) = loaded.slice_at(0x11100).unwrap();
+        assert_eq!((slice.rva, slice.length), (0x1100, 0x7FFE_FF00));
+        assert!(section.data().is_none());
+    }
+
+    #[test]
+    fn moves_colliding_objects() {
+        let file = le_file();
+        let (_, mut image) = LEImage::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+        for object in &mut image.objects {
+            object.header.relocation_base_address = 0x0040_0000;
+        }
+        assert_eq!(image.object_bases(), vec![0x0040_0000, 0x0040_1000]);
+
+        image.objects[1].header.relocation_base_address = 0x0050_0000;
+        assert_eq!(image.object_bases(), vec![0x0040_0000, 0x0050_0000]);
+    }
+
+    #[test]
+    fn applies_fixups_by_source_type() {
+        let mut data = [0u8; 8];
+        apply_fixup(
+            &mut data,
+            0,
+            0x1000,
+            FixupSourceType::FarPointer32,
+            2,
+            0x2000,
+        );
+        assert_eq!(data, [0x00, 0x20, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00]);
+
+        apply_fixup(
+            &mut data,
+            4,
+            0x1004,
+            FixupSourceType::SelfRelative32,
+            2,
+            0x1000,
+        );
+        assert_eq!(&data[4..], &(-8i32).to_le_bytes());
+
+        // Locations past the end of the object are left alone
+        apply_fixup(&mut data, 6, 0x1006, FixupSourceType::Offset32, 2, 0x2000);
+        assert_eq!(&data[4..], &(-8i32).to_le_bytes());
+    }
+}
@@ -0,0 +1,110 @@
+use crate::analysis::{
+    Addressing, Architecture, CodeAddress, Loadable, LoadedImage, MemoryMap, MemorySection,
+    MemorySlice,
+};
+use crate::parsers::mz::MZImage;
+use crate::parsers::SharedBytes;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The segment standalone DOS executables are loaded at, if not specified otherwise.
+///
+/// The PSP is located in the 256 bytes right before it.
+pub const DEFAULT_DOS_LOAD_SEGMENT: u16 = 0x1000;
+
+impl MZImage {
+    /// Loads the image like the DOS loader would, with the load module starting
+    /// at `load_segment:0000`.
+    pub fn load_at(&self, load_segment: u16) -> LoadedImage {
+        let module = self.relocated_load_module(load_segment);
+        let module_length = module.len() as u64;
+
+        let mut memory_map = MemoryMap::new();
+        memory_map.insert(
+            MemorySlice {
+                rva: 0,
+                length: module_length,
+            },
+            MemorySection::Code(SharedBytes::from(module)),
+        );
+        if self.min_extra_size() > 0 {
+            memory_map.insert(
+                MemorySlice {
+                    rva: module_length,
+                    length: self.min_extra_size() as u64,
+                },
+                MemorySection::UninitializedData,
+            );
+        }
+
+        let entry_segment = load_segment.wrapping_add(self.header.e_cs);
+        let entry_point = CodeAddress {
+            address: entry_segment as u64 * 16 + self.header.e_ip as u64,
+            segment: Some(entry_segment),
+        };
+
+        LoadedImage {
+            architecture: Architecture::X86Real16,
+            addressing: Addressing::RealMode,
+            base_address: load_segment as u64 * 16,
+            memory_map,
+            entry_points: vec![entry_point],
+            symbols: Vec::new(),
+            unwind_functions: BTreeMap::new(),
+            relocations: BTreeSet::new(),
+        }
+    }
+}
+
+impl Loadable for MZImage {
+    fn load(&self) -> LoadedImage {
+        self.load_at(DEFAULT_DOS_LOAD_SEGMENT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::BinParsable;
+    use nom::error::VerboseError;
+
+    #[test]
+    fn loads_at_segment() {
+        let mut file = vec![0u8; 0x40];
+        file[0..2].copy_from_slice(b"MZ");
+        file[0x02..0x04].copy_from_slice(&0x50u16.to_le_bytes()); // e_cblp
+        file[0x04..0x06].copy_from_slice(&1u16.to_le_bytes()); // e_cp
+        file[0x06..0x08].copy_from_slice(&1u16.to_le_bytes()); // e_crlc
+        file[0x08..0x0A].copy_from_slice(&4u16.to_le_bytes()); // e_cparhdr
+        file[0x0A..0x0C].copy_from_slice(&0x10u16.to_le_bytes()); // e_minalloc
+        file[0x14..0x16].copy_from_slice(&0x0004u16.to_le_bytes()); // e_ip
+        file[0x16..0x18].copy_from_slice(&0x0001u16.to_le_bytes()); // e_cs
+        file[0x18..0x1A].copy_from_slice(&0x1Cu16.to_le_bytes()); // e_lfarlc
+        file[0x1C..0x20].copy_from_slice(&[0x01, 0x00, 0x00, 0x00]); // 0000:0001
+                                                                     // mov ax, seg 0x0001; nops
+        file.extend_from_slice(&[0xB8, 0x01, 0x00]);
+        file.resize(0x50, 0x90);
+        let (_, image) = MZImage::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+
+        let loaded = image.load_at(0x2000);
+        assert_eq!(loaded.architecture, Architecture::X86Real16);
+        assert_eq!(loaded.addressing, Addressing::RealMode);
+        assert_eq!(loaded.base_address, 0x20000);
+        assert_eq!(
+            loaded.entry_points,
+            vec![CodeAddress {
+                address: 0x20014,
+                segment: Some(0x2001),
+            }]
+        );
+        assert_eq!(&loaded.bytes_at(0x20000).unwrap()[..3], &[0xB8, 0x01, 0x20]);
+        assert_eq!(loaded.bytes_at(0x20000).map(<[u8]>::len), Some(0x10));
+        assert!(loaded.slice_at(0x20010 + 0xFF).is_some());
+        assert!(loaded.slice_at(0x20010 + 0x100).is_none());
+
+        assert_eq!(
+            image.load().base_address,
+            DEFAULT_DOS_LOAD_SEGMENT as u64 * 16
+        );
+    }
+}
@@ -0,0 +1,348 @@
+use crate::analysis::{
+    Addressing, Architecture, CodeAddress, Loadable, LoadedImage, MemoryMap, MemorySection,
+    MemorySlice, Symbol, SymbolKind,
+};
+use crate::parsers::ne::{
+    EntryFlags, EntryLocation, NEImage, RelocationSourceType, RelocationTarget, SegmentRelocation,
+};
+use crate::parsers::SharedBytes;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// The linear address the first segment of a NE image is loaded at.
+pub const NE_BASE_ADDRESS: u64 = 0x10000;
+
+/// Every segment is loaded into its own 64K slot of linear memory.
+const NE_SEGMENT_SLOT_SIZE: u64 = 0x10000;
+
+/// The size of an import stub in the synthetic import segment.
+const NE_IMPORT_STUB_SIZE: u16 = 4;
+
+/// Returns the (synthetic) selector used for the segment with the given 1-based number.
+///
+/// Selectors mimic the LDT selectors Windows assigns at run time (TI bit set, RPL 3).
+pub fn ne_selector(segment_number: u16) -> u16 {
+    (segment_number << 3) | 0x7
+}
+
+/// Returns the linear address of the slot of the segment with the given 1-based number.
+fn ne_segment_base(segment_number: u16) -> Option<u64> {
+    let index = segment_number.checked_sub(1)?;
+    Some(NE_BASE_ADDRESS + index as u64 * NE_SEGMENT_SLOT_SIZE)
+}
+
+impl Loadable for NEImage {
+    /// Loads every segment into its own 64K slot, and applies all relocations.
+    ///
+    /// Imported entries are bound to stubs in a synthetic segment following the last
+    /// segment of the image, which carry `Import` symbols. If the image uses up all segment
+    /// numbers, imported entries are left unbound.
+    fn load(&self) -> LoadedImage {
+        let segment_count = self.segments.len() as u16;
+        let import_segment = segment_count
+            .checked_add(1)
+            .and_then(|number| Some((number, ne_segment_base(number)?)));
+
+        let mut selectors: BTreeMap<u16, u64> = (1..=segment_count)
+            .filter_map(|number| Some((ne_selector(number), ne_segment_base(number)?)))
+            .collect();
+        let (imports, mut symbols) = match import_segment {
+            Some((_, base)) => self.bind_imports(base),
+            None => (HashMap::new(), Vec::new()),
+        };
+
+        let resolve = |target: &RelocationTarget| -> Option<(u16, u16)> {
+            match *target {
+                RelocationTarget::InternalFixed { segment, offset } => {
+                    Some((ne_selector(segment as u16), offset))
+                }
+                RelocationTarget::InternalMovable { ordinal } => {
+                    match self.entries.get(&ordinal)?.location {
+                        EntryLocation::Segment {
+                            segment, offset, ..
+                        } => Some((ne_selector(segment as u16), offset)),
+                        EntryLocation::Constant(_) => None,
+                    }
+                }
+                RelocationTarget::ImportOrdinal { .. } | RelocationTarget::ImportName { .. } => {
+                    Some((ne_selector(import_segment?.0), *imports.get(target)?))
+                }
+                RelocationTarget::OsFixup { .. } => None,
+            }
+        };
+
+        let mut memory_map = MemoryMap::new();
+        for (index, segment) in self.segments.iter().enumerate() {
+            let mut data = segment.data.clone();
+            // Expanded iterated data can be larger than the segment data in the file
+            data.resize(segment.header.memory_size().max(data.len()), 0);
+            for relocation in &segment.relocations {
+                if let Some(value) = resolve(&relocation.target) {
+                    apply_relocation(&mut data, relocation, value);
+                }
+            }
+
+            let slice = MemorySlice {
+                rva: index as u64 * NE_SEGMENT_SLOT_SIZE,
+                length: data.len() as u64,
+            };
+            let section = if segment.header.is_code() {
+                MemorySection::Code(SharedBytes::from(data))
+            } else {
+                MemorySection::InitializedData(SharedBytes::from(data))
+            };
+            memory_map.insert(slice, section);
+        }
+        if let (Some((number, base)), false) = (import_segment, imports.is_empty()) {
+            selectors.insert(ne_selector(number), base);
+            memory_map.insert(
+                MemorySlice {
+                    rva: base - NE_BASE_ADDRESS,
+                    length: imports.len() as u64 * NE_IMPORT_STUB_SIZE as u64,
+                },
+                MemorySection::UninitializedData,
+            );
+        }
+
+        let mut entry_points = Vec::new();
+        if let Some(base) = ne_segment_base(self.ne_header.initial_cs) {
+            entry_points.push(CodeAddress {
+                address: base + self.ne_header.initial_ip as u64,
+                segment: Some(ne_selector(self.ne_header.initial_cs)),
+            });
+        }
+
+        for (&ordinal, entry) in &self.entries {
+            let (segment, offset) = match entry.location {
+                EntryLocation::Segment {
+                    segment, offset, ..
+                } => (segment as u16, offset),
+                EntryLocation::Constant(_) => continue,
+            };
+            // Movable entries name their segment explicitly, which may be invalid
+            let base = match ne_segment_base(segment) {
+                Some(base) => base,
+                None => continue,
+            };
+            let address = CodeAddress {
+                address: base + offset as u64,
+                segment: Some(ne_selector(segment)),
+            };
+
+            // The initial CS:IP is commonly exported too
+            let is_code = self
+                .segment(segment)
+                .map_or(false, |segment| segment.header.is_code());
+            if is_code && !entry_points.contains(&address) {
+                entry_points.push(address);
+            }
+            if entry.flags.contains(EntryFlags::EXPORTED) {
+                symbols.push(Symbol {
+                    address: address.address,
+                    name: self
+                        .entry_name(ordinal)
+                        .map_or_else(|| format!("#{}", ordinal), str::to_owned),
+                    kind: SymbolKind::Export,
+                });
+            }
+        }
+
+        LoadedImage {
+            architecture: Architecture::X86Protected16,
+            addressing: Addressing::Selectors(selectors),
+            base_address: NE_BASE_ADDRESS,
+            memory_map,
+            entry_points,
+            symbols,
+            unwind_functions: BTreeMap::new(),
+            relocations: BTreeSet::new(),
+        }
+    }
+}
+
+impl NEImage {
+    /// Assigns a stub in the import segment at the given address to every imported entry.
+    /// Returns the offsets of the stubs, keyed by relocation target, and their symbols.
+    fn bind_imports(&self, base: u64) -> (HashMap<RelocationTarget, u16>, Vec<Symbol>) {
+        let mut imports = HashMap::new();
+        let mut symbols = Vec::new();
+        for relocation in self.segments.iter().flat_map(|s| &s.relocations) {
+            let (module, name) = match relocation.target {
+                RelocationTarget::ImportOrdinal { module, ordinal } => {
+                    (module, format!("#{}", ordinal))
+                }
+                RelocationTarget::ImportName {
+                    module,
+                    name_offset,
+                } => match self.imported_names.get(&name_offset) {
+                    Some(name) => (module, name.clone()),
+                    None => continue,
+                },
+                _ => continue,
+            };
+
+            if imports.contains_key(&relocation.target) {
+                continue;
+            }
+            let offset = imports.len() as u16 * NE_IMPORT_STUB_SIZE;
+            symbols.push(Symbol {
+                address: base + offset as u64,
+                name,
+                kind: SymbolKind::Import {
+                    module: self.module_reference(module).unwrap_or_default().to_owned(),
+                },
+            });
+            imports.insert(relocation.target, offset);
+        }
+        (imports, symbols)
+    }
+}
+
+/// Patches the location(s) of a relocation with the given `selector:offset` target.
+fn apply_relocation(
+    data: &mut [u8],
+    relocation: &SegmentRelocation,
+    (selector, offset): (u16, u16),
+) {
+    let mut location = relocation.offset;
+    // Non-additive relocations form a chain through the patched locations.
+    // Limit the number of links to guard against cycles.
+    for _ in 0..=(data.len() / 2) {
+        let position = location as usize;
+        let original = match data.get(position..position + 2) {
+            Some(word) => u16::from_le_bytes([word[0], word[1]]),
+            None => return,
+        };
+        let add = |value: u16| {
+            if relocation.additive {
+                value.wrapping_add(original)
+            } else {
+                value
+            }
+        };
+
+        let patched: &[(usize, u16)] = match relocation.source_type {
+            RelocationSourceType::LowByte => {
+                if let Some(byte) = data.get_mut(position) {
+                    *byte = if relocation.additive {
+                        byte.wrapping_add(offset as u8)
+                    } else {
+                        offset as u8
+                    };
+                }
+                &[]
+            }
+            RelocationSourceType::Segment => &[(0, add(selector))],
+            RelocationSourceType::Offset => &[(0, add(offset))],
+            RelocationSourceType::FarPointer => &[(0, add(offset)), (2, selector)],
+            RelocationSourceType::FarPointer48 => &[(0, add(offset)), (2, 0), (4, selector)],
+            RelocationSourceType::Offset32 => &[(0, add(offset)), (2, 0)],
+        };
+        for &(delta, value) in patched {
+            if let Some(word) = data.get_mut(position + delta..position + delta + 2) {
+                word.copy_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        if relocation.additive || original == 0xFFFF {
+            return;
+        }
+        location = original;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::ne::tests::ne_file;
+    use crate::parsers::BinParsable;
+    use nom::error::VerboseError;
+
+    fn relocation(source_type: RelocationSourceType, additive: bool) -> SegmentRelocation {
+        SegmentRelocation {
+            source_type,
+            additive,
+            offset: 0,
+            target: RelocationTarget::InternalFixed {
+                segment: 1,
+                offset: 0,
+            },
+        }
+    }
+
+    #[test]
+    fn applies_relocation_chains() {
+        // Links at 0 -> 4 -> 8, the last one ending the chain
+        let mut data = [
+            0x04, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0x00,
+        ];
+        let far_pointer = relocation(RelocationSourceType::FarPointer, false);
+        apply_relocation(&mut data, &far_pointer, (0x0F, 0x1234));
+        assert_eq!(
+            data,
+            [0x34, 0x12, 0x0F, 0x00, 0x34, 0x12, 0x0F, 0x00, 0x34, 0x12, 0x0F, 0x00]
+        );
+
+        let mut data = [0x10, 0x00, 0xFF, 0xFF];
+        let offset = relocation(RelocationSourceType::Offset, true);
+        apply_relocation(&mut data, &offset, (0x0F, 0x1234));
+        assert_eq!(data, [0x44, 0x12, 0xFF, 0xFF]);
+
+        // A chain linking back to its start ends once every word was visited
+        let mut data = [0x02, 0x00, 0x00, 0x00];
+        let selector = relocation(RelocationSourceType::Segment, false);
+        apply_relocation(&mut data, &selector, (0x0F, 0x1234));
+        assert_eq!(data, [0x0F, 0x00, 0x0F, 0x00]);
+    }
+
+    #[test]
+    fn loads_segments_and_binds_imports() {
+        let file = ne_file();
+        let (_, image) = NEImage::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+        let loaded = image.load();
+
+        let selectors: BTreeMap<u16, u64> = vec![(0x0F, 0x10000), (0x17, 0x20000), (0x1F, 0x30000)]
+            .into_iter()
+            .collect();
+        assert_eq!(loaded.addressing, Addressing::Selectors(selectors));
+        // The movable entry in segment 0 is skipped, and the exported initial CS:IP is only
+        // listed once
+        assert_eq!(
+            loaded.entry_points,
+            vec![CodeAddress {
+                address: 0x10000,
+                segment: Some(0x0F),
+            }]
+        );
+        assert_eq!(
+            loaded.symbols,
+            vec![
+                Symbol {
+                    address: 0x30000,
+                    name: "Beep".to_owned(),
+                    kind: SymbolKind::Import {
+                        module: "KERNEL".to_owned()
+                    },
+                },
+                Symbol {
+                    address: 0x10000,
+                    name: "Main".to_owned(),
+                    kind: SymbolKind::Export,
+                },
+            ]
+        );
+
+        let code = loaded.bytes_at(0x10000).unwrap();
+        assert_eq!(&code[1..5], &[0x00, 0x00, 0x1F, 0x00]);
+        assert_eq!(&code[8..12], &[0x00, 0x00, 0x1F, 0x00]);
+        assert_eq!(&code[0x0E..0x10], &[0x17, 0x00]);
+
+        let data = loaded.bytes_at(0x20000).unwrap();
+        assert_eq!(data.len(), 0x20);
+        assert_eq!(
+            &data[..8],
+            &[0xAB, 0xCD, 0xAB, 0xCD, 0xAB, 0xCD, 0x00, 0x00]
+        );
+        assert!(loaded.slice_at(0x30000).is_some());
+    }
+}
@@ -0,0 +1,253 @@
+use crate::analysis::{
+    Addressing, Architecture, CodeAddress, Loadable, LoadedImage, MemoryMap, MemorySection,
+    MemorySlice, Symbol, SymbolKind, UnwindFrame, UnwindFunction,
+};
+use crate::parsers::pe32::{
+    BaseRelocationBlock, ExportTarget, PE32Image, ParseOptions, RuntimeFunction,
+    SectionCharacteristics, UnwindTrailer,
+};
+use crate::parsers::{BinParsable, ParseErrorReport, SharedBytes};
+
+use nom::error::VerboseError;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Parses a PE image file and loads it. If the file cannot be parsed, the report describes
+/// where and why.
+pub fn load_pe_file(file: &[u8]) -> Result<LoadedImage, ParseErrorReport> {
+    PE32Image::try_parse::<VerboseError<&[u8]>>(file)
+        .map(|(_, image)| image.load())
+        .map_err(|error| ParseErrorReport::from_nom_error(file, &error))
+}
+
+/// Parses a PE image from a shared buffer, such as a memory-mapped file, and loads it. The
+/// memory map shares the buffer instead of copying the section data.
+pub fn load_pe_buffer(
+    buffer: &SharedBytes,
+    options: ParseOptions,
+) -> Result<LoadedImage, ParseErrorReport> {
+    PE32Image::try_parse_shared::<VerboseError<&[u8]>>(buffer, options)
+        .map(|(_, image)| image.load())
+        .map_err(|error| ParseErrorReport::from_nom_error(buffer, &error))
+}
+
+impl Loadable for PE32Image {
+    /// Maps every section at its preferred image base. Import address table slots carry
+    /// `Import` symbols. The function table of PE32+ images gives the x64 functions.
+    /// Relocations are only known if the image has a base relocation table.
+    fn load(&self) -> LoadedImage {
+        let base_address = self.pe32_optional_header.windows_specific.image_base;
+
+        let mut memory_map = MemoryMap::new();
+        for section in &self.sections {
+            let header = &section.header;
+            // Some linkers leave the virtual size empty, in which case the raw size is used.
+            let virtual_size = if header.virtual_size == 0 {
+                header.size_of_raw_data
+            } else {
+                header.virtual_size
+            } as u64;
+            let data = section
+                .data
+                .slice(0..section.data.len().min(virtual_size as usize))
+                .unwrap_or_default();
+            let length = data.len() as u64;
+
+            let rva = header.virtual_address as u64;
+            if length > 0 {
+                let section = if header.characteristics.intersects(
+                    SectionCharacteristics::CNT_CODE | SectionCharacteristics::MEM_EXECUTE,
+                ) {
+                    MemorySection::Code(data)
+                } else {
+                    MemorySection::InitializedData(data)
+                };
+                memory_map.insert(MemorySlice { rva, length }, section);
+            }
+            if virtual_size > length {
+                memory_map.insert(
+                    MemorySlice {
+                        rva: rva + length,
+                        length: virtual_size - length,
+                    },
+                    MemorySection::UninitializedData,
+                );
+            }
+        }
+
+        let is_code = |rva: u32| {
+            self.sections.iter().any(|section| {
+                rva >= section.header.virtual_address
+                    && section
+                        .header
+                        .virtual_address
+                        .checked_add(section.header.virtual_size)
+                        .map(|end| rva < end)
+                        .unwrap_or(true)
+                    && section
+                        .header
+                        .characteristics
+                        .contains(SectionCharacteristics::MEM_EXECUTE)
+            })
+        };
+
+        let mut entry_points = Vec::new();
+        let entry_rva = self.coff_optional_header.address_of_entry_point;
+        if entry_rva != 0 {
+            entry_points.push(CodeAddress::flat(base_address + entry_rva as u64));
+        }
+
+        let mut symbols = Vec::new();
+        for module in &self.imports {
+            for import in &module.imports {
+                symbols.push(Symbol {
+                    address: base_address + import.iat_rva as u64,
+                    name: import.name.to_display_name(),
+                    kind: SymbolKind::Import {
+                        module: module.name.clone(),
+                    },
+                });
+            }
+        }
+
+        let exports = self.exports.iter().flat_map(|exports| &exports.exports);
+        for export in exports {
+            let rva = match export.target {
+                ExportTarget::Rva(rva) => rva,
+                ExportTarget::Forwarder(_) => continue,
+            };
+
+            if is_code(rva) {
+                entry_points.push(CodeAddress::flat(base_address + rva as u64));
+            }
+            symbols.push(Symbol {
+                address: base_address + rva as u64,
+                name: export
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| format!("#{}", export.ordinal)),
+                kind: SymbolKind::Export,
+            });
+        }
+
+        LoadedImage {
+            architecture: if self.pe32_optional_header.is_pe32_plus() {
+                Architecture::X86_64
+            } else {
+                Architecture::X86_32
+            },
+            addressing: Addressing::Flat,
+            base_address,
+            memory_map,
+            entry_points,
+            symbols,
+            unwind_functions: unwind_functions(base_address, &self.runtime_functions),
+            relocations: self
+                .base_relocations
+                .iter()
+                .flat_map(BaseRelocationBlock::relocated_addresses)
+                .map(|rva| base_address + u64::from(rva))
+                .collect(),
+        }
+    }
+}
+
+/// Groups the function table entries by the primary function their unwind info is chained to.
+fn unwind_functions(
+    base_address: u64,
+    runtime_functions: &[RuntimeFunction],
+) -> BTreeMap<u64, UnwindFunction> {
+    let mut functions = BTreeMap::new();
+    for runtime_function in runtime_functions {
+        let primary = runtime_function.primary();
+        let entry = base_address + u64::from(primary.entry.begin_address);
+        let (handler, handler_data) = match primary.unwind.trailer {
+            UnwindTrailer::Handler { handler, data } => (
+                Some(base_address + u64::from(handler)),
+                Some(base_address + u64::from(data)),
+            ),
+            _ => (None, None),
+        };
+        let function = functions.entry(entry).or_insert_with(|| UnwindFunction {
+            entry,
+            ranges: Vec::new(),
+            frame: UnwindFrame::from_info(&primary.unwind),
+            handler,
+            handler_data,
+        });
+        // The primary entry itself may be missing from the table
+        for fragment in &[&runtime_function.entry, &primary.entry] {
+            let range = base_address + u64::from(fragment.begin_address)
+                ..base_address + u64::from(fragment.end_address);
+            if !function.ranges.contains(&range) {
+                function.ranges.push(range);
+            }
+        }
+    }
+    for function in functions.values_mut() {
+        function.ranges.sort_by_key(|range| range.start);
+    }
+    functions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::pe32::tests::{
+        chained_function_table, TestImage, TEST_IMAGE_BASE, TEST_IMAGE_BASE_64,
+    };
+    use crate::parsers::pe32::ImageLayout;
+    use crate::parsers::ParseMode;
+
+    #[test]
+    fn loads_pe_files_or_reports_errors() {
+        let mut file = TestImage::executable().build();
+        let image = load_pe_file(&file).unwrap();
+        assert_eq!(
+            image.entry_points,
+            vec![CodeAddress::flat(TEST_IMAGE_BASE + 0x1000)]
+        );
+
+        // The PE signature follows the MZ header
+        file[0x41] = b'X';
+        let report = load_pe_file(&file).err().unwrap();
+        assert_eq!(report.context_chain(), "PE32Image");
+        assert_eq!(report.offset, Some(0x40));
+        assert_eq!(report.expected, "matching bytes for PE32Image");
+        assert_eq!(report.actual, b"PX\0\0\x4C\x01\x03\0");
+    }
+
+    #[test]
+    fn loads_shared_buffers_leniently() {
+        let mut file = TestImage::executable().build();
+        // A file alignment below 512
+        file[0x7C..0x80].copy_from_slice(&0x100u32.to_le_bytes());
+        let buffer = SharedBytes::from(file);
+        assert!(load_pe_buffer(&buffer, ParseOptions::default()).is_err());
+
+        let options = ParseOptions {
+            layout: ImageLayout::File,
+            mode: ParseMode::Lenient,
+        };
+        let image = load_pe_buffer(&buffer, options).unwrap();
+        let code = image.bytes_at(TEST_IMAGE_BASE + 0x1000).unwrap();
+        assert_eq!(code.as_ptr(), buffer[0x200..].as_ptr());
+    }
+
+    #[test]
+    fn groups_chained_unwind_functions() {
+        let image = load_pe_file(&chained_function_table().build()).unwrap();
+        let base = TEST_IMAGE_BASE_64;
+        assert_eq!(
+            image.unwind_functions.keys().collect::<Vec<_>>(),
+            vec![&(base + 0x1000)]
+        );
+        let function = &image.unwind_functions[&(base + 0x1000)];
+        assert_eq!(
+            function.ranges,
+            vec![base + 0x1000..base + 0x1040, base + 0x1080..base + 0x10A0]
+        );
+        assert_eq!(function.frame.allocation, 0x28);
+        assert_eq!(function.handler, None);
+        assert!(function.contains(base + 0x1090));
+    }
+}
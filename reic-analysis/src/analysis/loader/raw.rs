@@ -0,0 +1,256 @@
+use crate::analysis::{
+    Addressing, Architecture, CodeAddress, Loadable, LoadedImage, MemoryMap, MemorySection,
+    MemorySlice,
+};
+use crate::parsers::SharedBytes;
+
+use bitflags::bitflags;
+use nameof::name_of;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
+
+/// A file without headers (ROM image, firmware blob, memory dump), together with the layout
+/// it is loaded with.
+pub struct RawImage {
+    pub data: Vec<u8>,
+    pub architecture: Architecture,
+    /// The address the whole file is loaded at, if no segments are given.
+    pub base_address: u64,
+    /// The parts of the file to map. If empty, the whole file is mapped as a single executable
+    /// segment at `base_address`. Segments do not overlap in memory, see `with_segments`.
+    segments: Vec<RawSegment>,
+    pub entry_points: Vec<CodeAddress>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RawSegment {
+    /// The offset of the segment data in the file.
+    pub file_offset: u64,
+    /// The size of the segment in memory. Anything beyond the end of the file is zero-filled.
+    pub size: u64,
+    /// The linear address the segment is mapped at.
+    pub virtual_address: u64,
+    pub permissions: SegmentPermissions,
+}
+
+/// A segment layout that cannot be mapped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RawLayoutError {
+    /// The segments with the given indices overlap in memory.
+    OverlappingSegments(usize, usize),
+    /// The segment with the given index extends beyond the end of the address space.
+    SegmentTooLarge(usize),
+}
+
+impl fmt::Display for RawLayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RawLayoutError::OverlappingSegments(first, second) => {
+                write!(f, "segments {} and {} overlap in memory", first, second)
+            }
+            RawLayoutError::SegmentTooLarge(index) => {
+                write!(f, "segment {} extends beyond the address space", index)
+            }
+        }
+    }
+}
+
+impl Error for RawLayoutError {}
+
+bitflags! {
+    #[derive(Default)]
+    pub struct SegmentPermissions: u8 {
+        const READ = 0x1;
+        const WRITE = 0x2;
+        const EXECUTE = 0x4;
+    }
+}
+
+impl RawImage {
+    /// Creates an image that maps the whole file at `base_address`.
+    pub fn new(data: Vec<u8>, architecture: Architecture, base_address: u64) -> Self {
+        Self {
+            data,
+            architecture,
+            base_address,
+            segments: Vec::new(),
+            entry_points: Vec::new(),
+        }
+    }
+
+    /// Maps the given segments instead of the whole file. Fails if any two of them overlap in
+    /// memory.
+    pub fn with_segments(mut self, segments: Vec<RawSegment>) -> Result<Self, RawLayoutError> {
+        let mut ranges = Vec::with_capacity(segments.len());
+        for (index, segment) in segments.iter().enumerate() {
+            let end = segment
+                .virtual_address
+                .checked_add(segment.size)
+                .ok_or(RawLayoutError::SegmentTooLarge(index))?;
+            if segment.size != 0 {
+                ranges.push((segment.virtual_address..end, index));
+            }
+        }
+        ranges.sort_by_key(|(range, _)| range.start);
+        for pair in ranges.windows(2) {
+            let ((first, first_index), (second, second_index)) = (&pair[0], &pair[1]);
+            if first.end > second.start {
+                return Err(RawLayoutError::OverlappingSegments(
+                    *first_index.min(second_index),
+                    *first_index.max(second_index),
+                ));
+            }
+        }
+
+        self.segments = segments;
+        Ok(self)
+    }
+
+    /// The segments to map, falling back to the whole file if none are given.
+    pub fn effective_segments(&self) -> Vec<RawSegment> {
+        if !self.segments.is_empty() {
+            return self.segments.clone();
+        }
+
+        vec![RawSegment {
+            file_offset: 0,
+            size: self.data.len() as u64,
+            virtual_address: self.base_address,
+            permissions: SegmentPermissions::all(),
+        }]
+    }
+}
+
+impl fmt::Debug for RawImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct(name_of!(type RawImage))
+            .field(
+                name_of!(data in RawImage),
+                &format!("Vec<u8>, len: {:X}", self.data.len()),
+            )
+            .field(name_of!(architecture in RawImage), &self.architecture)
+            .field(name_of!(base_address in RawImage), &self.base_address)
+            .field(name_of!(segments in RawImage), &self.segments)
+            .field(name_of!(entry_points in RawImage), &self.entry_points)
+            .finish()
+    }
+}
+
+impl Loadable for RawImage {
+    /// Maps every segment at its virtual address. The image base is the lowest mapped address.
+    fn load(&self) -> LoadedImage {
+        let segments = self.effective_segments();
+        let base_address = segments
+            .iter()
+            .map(|segment| segment.virtual_address)
+            .min()
+            .unwrap_or(self.base_address);
+
+        let mut memory_map = MemoryMap::new();
+        for segment in segments {
+            let rva = segment.virtual_address - base_address;
+            let start = (segment.file_offset as usize).min(self.data.len());
+            let end =
+                (segment.file_offset.saturating_add(segment.size) as usize).min(self.data.len());
+            let data = SharedBytes::copy_from_slice(&self.data[start..end]);
+            let length = data.len() as u64;
+
+            if length > 0 {
+                let section = if segment.permissions.contains(SegmentPermissions::EXECUTE) {
+                    MemorySection::Code(data)
+                } else {
+                    MemorySection::InitializedData(data)
+                };
+                memory_map.insert(MemorySlice { rva, length }, section);
+            }
+            if segment.size > length {
+                memory_map.insert(
+                    MemorySlice {
+                        rva: rva + length,
+                        length: segment.size - length,
+                    },
+                    MemorySection::UninitializedData,
+                );
+            }
+        }
+
+        LoadedImage {
+            architecture: self.architecture,
+            addressing: match self.architecture {
+                Architecture::X86Real16 => Addressing::RealMode,
+                _ => Addressing::Flat,
+            },
+            base_address,
+            memory_map,
+            entry_points: self.entry_points.clone(),
+            symbols: Vec::new(),
+            unwind_functions: BTreeMap::new(),
+            relocations: BTreeSet::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_segments_and_zero_fills() {
+        let raw = RawImage::new(vec![0x90; 0x30], Architecture::X86_32, 0x1000)
+            .with_segments(vec![
+                RawSegment {
+                    file_offset: 0x10,
+                    size: 0x20,
+                    virtual_address: 0x8000,
+                    permissions: SegmentPermissions::READ | SegmentPermissions::EXECUTE,
+                },
+                RawSegment {
+                    file_offset: 0x20,
+                    size: 0x40,
+                    virtual_address: 0x9000,
+                    permissions: SegmentPermissions::READ | SegmentPermissions::WRITE,
+                },
+            ])
+            .unwrap();
+
+        let image = raw.load();
+        assert_eq!(image.base_address, 0x8000);
+        assert_eq!(image.bytes_at(0x8000).map(<[u8]>::len), Some(0x20));
+        assert_eq!(image.bytes_at(0x9000).map(<[u8]>::len), Some(0x10));
+        assert!(image.slice_at(0x9030).is_some());
+        assert!(image.bytes_at(0x9030).is_none());
+        assert!(image.slice_at(0x9040).is_none());
+    }
+
+    #[test]
+    fn rejects_overlapping_segments() {
+        let segment = |virtual_address, size| RawSegment {
+            file_offset: 0,
+            size,
+            virtual_address,
+            permissions: SegmentPermissions::all(),
+        };
+        let raw = || RawImage::new(vec![0x90; 0x30], Architecture::X86_32, 0x1000);
+
+        assert_eq!(
+            raw()
+                .with_segments(vec![
+                    segment(0x9000, 0x10),
+                    segment(0x8000, 0x100),
+                    segment(0x80F0, 0x20),
+                ])
+                .err(),
+            Some(RawLayoutError::OverlappingSegments(1, 2))
+        );
+        assert_eq!(
+            raw()
+                .with_segments(vec![segment(u64::MAX - 0x10, 0x20)])
+                .err(),
+            Some(RawLayoutError::SegmentTooLarge(0))
+        );
+        assert!(raw()
+            .with_segments(vec![segment(0x8000, 0x100), segment(0x8100, 0x100)])
+            .is_ok());
+    }
+}
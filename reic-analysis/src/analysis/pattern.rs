@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A byte pattern with wildcards, written as hex bytes separated by spaces, with `??` for
+/// bytes that may have any value: `60 BE ?? ?? ?? ?? 8D BE`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BytePattern {
+    bytes: Vec<Option<u8>>,
+}
+
+impl BytePattern {
+    /// Creates a pattern from bytes, with `None` for wildcards.
+    pub fn new(bytes: Vec<Option<u8>>) -> Self {
+        Self { bytes }
+    }
+
+    /// Parses a pattern, returning `None` if a token is neither a hex byte nor `??`.
+    pub fn parse(pattern: &str) -> Option<Self> {
+        pattern
+            .split_whitespace()
+            .map(|token| match token {
+                "??" => Some(None),
+                _ if token.len() == 2 => u8::from_str_radix(token, 16).ok().map(Some),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(|bytes| Self { bytes })
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// The number of bytes that are not wildcards.
+    pub fn fixed_bytes(&self) -> usize {
+        self.bytes.iter().filter(|byte| byte.is_some()).count()
+    }
+
+    /// Whether `data` starts with the pattern.
+    pub fn matches(&self, data: &[u8]) -> bool {
+        data.len() >= self.bytes.len()
+            && self
+                .bytes
+                .iter()
+                .zip(data)
+                .all(|(expected, actual)| match expected {
+                    Some(expected) => expected == actual,
+                    None => true,
+                })
+    }
+
+    /// The offset of the first match in `data`.
+    pub fn find(&self, data: &[u8]) -> Option<usize> {
+        if self.bytes.len() > data.len() {
+            return None;
+        }
+
+        (0..=data.len() - self.bytes.len()).find(|&offset| self.matches(&data[offset..]))
+    }
+}
+
+impl fmt::Display for BytePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, byte) in self.bytes.iter().enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+            match byte {
+                Some(byte) => write!(f, "{:02X}", byte)?,
+                None => write!(f, "??")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `data` contains `needle`.
+pub(crate) fn contains_bytes(data: &[u8], needle: &[u8]) -> bool {
+    data.windows(needle.len()).any(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_with_wildcards() {
+        let pattern = BytePattern::parse("60 BE ?? ?? ?? ?? 8D BE").unwrap();
+        assert_eq!(pattern.to_string(), "60 BE ?? ?? ?? ?? 8D BE");
+        assert!(pattern.matches(&[0x60, 0xBE, 0, 0x10, 0x40, 0, 0x8D, 0xBE, 0xCC]));
+        assert!(!pattern.matches(&[0x60, 0xBE, 0, 0x10, 0x40, 0, 0x8D]));
+        assert_eq!(
+            pattern.find(&[0x90, 0x60, 0xBE, 1, 2, 3, 4, 0x8D, 0xBE]),
+            Some(1)
+        );
+        assert_eq!(BytePattern::parse("60 B"), None);
+    }
+}
@@ -0,0 +1,432 @@
+use crate::analysis::{contains_bytes, BytePattern};
+use crate::parsers::pe32::{ImportName, PE32Image, SectionCharacteristics};
+
+use std::fmt;
+
+/// Sections of code with a higher entropy than this are most likely compressed or encrypted.
+/// Compiled x86 code stays well below 7.
+const HIGH_ENTROPY: f64 = 7.2;
+/// Executable sections without raw data that span at least this many bytes in memory are
+/// flagged.
+const LARGE_VIRTUAL_SIZE: u32 = 0x10000;
+/// Images that import at most this many functions, including the functions to resolve
+/// imports at run time, are flagged.
+const FEW_IMPORTS: usize = 16;
+
+/// A packer or copy protection.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Protection {
+    Upx,
+    AsPack,
+    SafeDisc,
+    SecuRom,
+}
+
+impl fmt::Display for Protection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Protection::Upx => "UPX",
+            Protection::AsPack => "ASPack",
+            Protection::SafeDisc => "SafeDisc",
+            Protection::SecuRom => "SecuROM",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+struct ProtectionSignature {
+    protection: Protection,
+    /// The names of the sections the protection adds.
+    section_names: &'static [&'static str],
+    /// Byte patterns of the unpacking stub at the entry point.
+    entry_point: &'static [&'static str],
+    /// Byte strings the protection leaves in the header area or in the section data.
+    markers: &'static [&'static [u8]],
+}
+
+const SIGNATURES: &[ProtectionSignature] = &[
+    ProtectionSignature {
+        protection: Protection::Upx,
+        section_names: &["UPX0", "UPX1", "UPX2"],
+        // pushad; mov esi, packed; lea edi, [esi - unpacked]; push edi
+        entry_point: &["60 BE ?? ?? ?? ?? 8D BE ?? ?? ?? ?? 57"],
+        markers: &[b"UPX!"],
+    },
+    ProtectionSignature {
+        protection: Protection::AsPack,
+        section_names: &[".aspack", ".adata"],
+        entry_point: &[
+            // 2.1x: pushad; call $+8; jmp ...
+            "60 E8 03 00 00 00 E9 EB 04 5D 45 55 C3 E8 01",
+            // 1.x, 2.0x: pushad; call $+5; pop ebp; sub ebp, ...; mov eax, ...; add eax, ebp
+            "60 E8 00 00 00 00 5D 81 ED ?? ?? ?? ?? B8 ?? ?? ?? ?? 03 C5",
+        ],
+        markers: &[],
+    },
+    ProtectionSignature {
+        protection: Protection::SafeDisc,
+        section_names: &["stxt371", "stxt774"],
+        entry_point: &[],
+        markers: &[b"BoG_ *90.0&!!  Yy>"],
+    },
+    ProtectionSignature {
+        protection: Protection::SecuRom,
+        section_names: &[".securom", ".cms_t", ".cms_d"],
+        entry_point: &[],
+        markers: &[],
+    },
+];
+
+/// The Shannon entropy of the data, in bits per byte: 0 for constant data, 8 for uniformly
+/// distributed bytes.
+pub fn shannon_entropy(data: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+
+    let length = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let probability = count as f64 / length;
+            -probability * probability.log2()
+        })
+        .sum()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SectionEntropy {
+    pub section: String,
+    /// The entropy of the raw data of the section, see `shannon_entropy`.
+    pub entropy: f64,
+}
+
+/// A property of the image that is typical for packed executables.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PackerIndicator {
+    /// A section is named like the sections a known packer adds, such as `UPX0`.
+    PackerSectionName {
+        section: String,
+        protection: Protection,
+    },
+    /// An executable section has no raw data but a large virtual size, to be filled by an
+    /// unpacking stub.
+    EmptyRawData { section: String, virtual_size: u32 },
+    /// The entry point lies in the last of several sections, where packers append their stub.
+    EntryPointInLastSection { section: String },
+    /// The image imports only a handful of functions, including `GetProcAddress` and
+    /// `LoadLibrary`, and resolves everything else at run time.
+    FewImports { count: usize },
+    /// A code section looks compressed or encrypted.
+    HighEntropy { section: String, entropy: f64 },
+}
+
+/// What identified a protection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProtectionEvidence {
+    SectionName(String),
+    /// The entry point matches the given byte pattern.
+    EntryPoint(String),
+    /// The marker was found in the header area or in the data of the given section.
+    Marker {
+        marker: String,
+        section: Option<String>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DetectedProtection {
+    pub protection: Protection,
+    pub evidence: Vec<ProtectionEvidence>,
+}
+
+/// Whether an image is packed or protected, which determines if it can be analyzed as is.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProtectionReport {
+    /// The entropy of every section, in section table order.
+    pub sections: Vec<SectionEntropy>,
+    pub indicators: Vec<PackerIndicator>,
+    /// The protections identified by their signatures.
+    pub protections: Vec<DetectedProtection>,
+}
+
+impl ProtectionReport {
+    pub fn from_image(image: &PE32Image) -> Self {
+        let sections = image
+            .sections
+            .iter()
+            .map(|section| SectionEntropy {
+                section: section.header.name.clone(),
+                entropy: shannon_entropy(&section.data),
+            })
+            .collect();
+
+        Self {
+            sections,
+            indicators: packer_indicators(image),
+            protections: SIGNATURES
+                .iter()
+                .filter_map(|signature| detect(image, signature))
+                .collect(),
+        }
+    }
+
+    /// Whether a protection was identified, or the image shows at least two packer
+    /// indicators.
+    pub fn is_protected(&self) -> bool {
+        !self.protections.is_empty() || self.indicators.len() >= 2
+    }
+}
+
+fn detect(image: &PE32Image, signature: &ProtectionSignature) -> Option<DetectedProtection> {
+    let mut evidence = Vec::new();
+    for section in &image.sections {
+        if signature
+            .section_names
+            .contains(&section.header.name.as_str())
+        {
+            evidence.push(ProtectionEvidence::SectionName(section.header.name.clone()));
+        }
+    }
+
+    if let Some(data) = image.entry_point_data() {
+        for pattern in signature.entry_point {
+            let pattern = BytePattern::parse(pattern).unwrap();
+            if pattern.matches(data) {
+                evidence.push(ProtectionEvidence::EntryPoint(pattern.to_string()));
+            }
+        }
+    }
+
+    for marker in signature.markers {
+        let section = if contains_bytes(&image.header_padding, marker) {
+            Some(None)
+        } else {
+            image
+                .sections
+                .iter()
+                .find(|section| contains_bytes(&section.data, marker))
+                .map(|section| Some(section.header.name.clone()))
+        };
+        if let Some(section) = section {
+            evidence.push(ProtectionEvidence::Marker {
+                marker: String::from_utf8_lossy(marker).into_owned(),
+                section,
+            });
+        }
+    }
+
+    if evidence.is_empty() {
+        None
+    } else {
+        Some(DetectedProtection {
+            protection: signature.protection,
+            evidence,
+        })
+    }
+}
+
+fn packer_indicators(image: &PE32Image) -> Vec<PackerIndicator> {
+    let mut indicators = Vec::new();
+    for section in &image.sections {
+        let header = &section.header;
+        let signature = SIGNATURES
+            .iter()
+            .find(|signature| signature.section_names.contains(&header.name.as_str()));
+        if let Some(signature) = signature {
+            indicators.push(PackerIndicator::PackerSectionName {
+                section: header.name.clone(),
+                protection: signature.protection,
+            });
+        }
+
+        let is_code = header
+            .characteristics
+            .intersects(SectionCharacteristics::CNT_CODE | SectionCharacteristics::MEM_EXECUTE);
+        if is_code && header.size_of_raw_data == 0 && header.virtual_size >= LARGE_VIRTUAL_SIZE {
+            indicators.push(PackerIndicator::EmptyRawData {
+                section: header.name.clone(),
+                virtual_size: header.virtual_size,
+            });
+        }
+
+        let entropy = shannon_entropy(&section.data);
+        if is_code && entropy > HIGH_ENTROPY {
+            indicators.push(PackerIndicator::HighEntropy {
+                section: header.name.clone(),
+                entropy,
+            });
+        }
+    }
+
+    let entry_point = image.coff_optional_header.address_of_entry_point;
+    let last_section = image
+        .sections
+        .iter()
+        .max_by_key(|section| section.header.virtual_address);
+    if let (Some(entry_section), Some(last_section)) =
+        (image.section_containing(entry_point), last_section)
+    {
+        if image.sections.len() > 1 && std::ptr::eq(entry_section, last_section) {
+            indicators.push(PackerIndicator::EntryPointInLastSection {
+                section: entry_section.header.name.clone(),
+            });
+        }
+    }
+
+    let imports: Vec<&str> = image
+        .imports
+        .iter()
+        .flat_map(|module| &module.imports)
+        .filter_map(|import| match &import.name {
+            ImportName::Name { name, .. } => Some(name.as_str()),
+            ImportName::Ordinal(_) => None,
+        })
+        .collect();
+    let count = image
+        .imports
+        .iter()
+        .map(|module| module.imports.len())
+        .sum();
+    if count <= FEW_IMPORTS
+        && imports.contains(&"GetProcAddress")
+        && imports.iter().any(|name| name.starts_with("LoadLibrary"))
+    {
+        indicators.push(PackerIndicator::FewImports { count });
+    }
+
+    indicators
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::pe32::tests::{TestImage, TestSection};
+    use crate::parsers::pe32::KnownDataDirectoryType;
+    use crate::parsers::BinParsable;
+    use nom::error::VerboseError;
+
+    fn parse(test_image: &TestImage) -> PE32Image {
+        PE32Image::try_parse::<VerboseError<&[u8]>>(&test_image.build())
+            .unwrap()
+            .1
+    }
+
+    /// An image laid out like UPX packs it: `UPX0` reserves the memory for the unpacked image,
+    /// and `UPX1` holds the unpacking stub at the entry point, the `UPX!` marker and a minimal
+    /// import directory.
+    fn upx_image() -> TestImage {
+        let mut upx1 = vec![0u8; 0x200];
+        // pushad; mov esi, 0x411000; lea edi, [esi - 0x10000]; push edi
+        upx1[..13].copy_from_slice(&[
+            0x60, 0xBE, 0x00, 0x10, 0x41, 0x00, 0x8D, 0xBE, 0x00, 0x00, 0xFF, 0xFF, 0x57,
+        ]);
+        let mut put = |offset: usize, words: &[u32]| {
+            for (index, word) in words.iter().enumerate() {
+                let offset = offset + index * 4;
+                upx1[offset..offset + 4].copy_from_slice(&word.to_le_bytes());
+            }
+        };
+        put(0x100, &[0x1_1140, 0, 0, 0x1_11C0, 0x1_1150]);
+        put(0x140, &[0x1_1160, 0x1_1180, 0x1_11A0, 0]);
+        put(0x150, &[0x1_1160, 0x1_1180, 0x1_11A0, 0]);
+        upx1[0x162..0x16F].copy_from_slice(b"LoadLibraryA\0");
+        upx1[0x182..0x191].copy_from_slice(b"GetProcAddress\0");
+        upx1[0x1A2..0x1AE].copy_from_slice(b"ExitProcess\0");
+        upx1[0x1C0..0x1CD].copy_from_slice(b"KERNEL32.DLL\0");
+        upx1[0x1E0..0x1E4].copy_from_slice(b"UPX!");
+
+        let characteristics = SectionCharacteristics::CNT_UNINITIALIZED_DATA
+            | SectionCharacteristics::MEM_EXECUTE
+            | SectionCharacteristics::MEM_READ
+            | SectionCharacteristics::MEM_WRITE;
+        let mut upx0 = TestSection::new("UPX0", 0x1000, Vec::new(), characteristics);
+        upx0.virtual_size = 0x1_0000;
+        TestImage {
+            entry_point: 0x1_1000,
+            directories: vec![(KnownDataDirectoryType::Import as usize, 0x1_1100, 0x28)],
+            sections: vec![
+                upx0,
+                TestSection::new("UPX1", 0x1_1000, upx1, characteristics),
+            ],
+            ..TestImage::executable()
+        }
+    }
+
+    #[test]
+    fn entropy_of_constant_and_uniform_data() {
+        assert_eq!(shannon_entropy(&[]), 0.0);
+        assert_eq!(shannon_entropy(&[0x90; 64]), 0.0);
+        let uniform: Vec<u8> = (0..=255).collect();
+        assert!((shannon_entropy(&uniform) - 8.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn signature_patterns_are_valid() {
+        for signature in SIGNATURES {
+            for pattern in signature.entry_point {
+                assert!(BytePattern::parse(pattern).is_some(), "{}", pattern);
+            }
+        }
+    }
+
+    #[test]
+    fn detects_upx_packed_image() {
+        let report = ProtectionReport::from_image(&parse(&upx_image()));
+        assert_eq!(
+            report.indicators,
+            vec![
+                PackerIndicator::PackerSectionName {
+                    section: "UPX0".to_owned(),
+                    protection: Protection::Upx,
+                },
+                PackerIndicator::EmptyRawData {
+                    section: "UPX0".to_owned(),
+                    virtual_size: 0x1_0000,
+                },
+                PackerIndicator::PackerSectionName {
+                    section: "UPX1".to_owned(),
+                    protection: Protection::Upx,
+                },
+                PackerIndicator::EntryPointInLastSection {
+                    section: "UPX1".to_owned(),
+                },
+                PackerIndicator::FewImports { count: 3 },
+            ]
+        );
+        assert_eq!(
+            report.protections,
+            vec![DetectedProtection {
+                protection: Protection::Upx,
+                evidence: vec![
+                    ProtectionEvidence::SectionName("UPX0".to_owned()),
+                    ProtectionEvidence::SectionName("UPX1".to_owned()),
+                    ProtectionEvidence::EntryPoint(
+                        "60 BE ?? ?? ?? ?? 8D BE ?? ?? ?? ?? 57".to_owned()
+                    ),
+                    ProtectionEvidence::Marker {
+                        marker: "UPX!".to_owned(),
+                        section: Some("UPX1".to_owned()),
+                    },
+                ],
+            }]
+        );
+        assert!(report.is_protected());
+    }
+
+    #[test]
+    fn unpacked_image_is_not_protected() {
+        let report = ProtectionReport::from_image(&parse(&TestImage::executable()));
+        let sections: Vec<&str> = report
+            .sections
+            .iter()
+            .map(|section| section.section.as_str())
+            .collect();
+        assert_eq!(sections, vec![".text", ".idata", ".reloc"]);
+        assert!(report.indicators.is_empty());
+        assert!(report.protections.is_empty());
+        assert!(!report.is_protected());
+    }
+}
@@ -0,0 +1,541 @@
+mod parser;
+pub use parser::*;
+
+use crate::analysis::{
+    absolute_memory_address, demangle, Disassembly, FlowKind, LoadedImage, SymbolKind,
+};
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use zydis::{Mnemonic, OperandAction, OperandType, Register};
+
+/// The prototypes bundled with reic, in the text format read by `TypeLibrary::parse`.
+const BUNDLED_LIBRARIES: [(&str, &str); 4] = [
+    ("win32", include_str!("prototypes/win32.api")),
+    ("directx", include_str!("prototypes/directx.api")),
+    ("winmm", include_str!("prototypes/winmm.api")),
+    ("dsound", include_str!("prototypes/dsound.api")),
+];
+
+/// How many instructions are searched backwards for the load of a register called through.
+const MAX_REGISTER_LOAD_DISTANCE: usize = 64;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CallingConvention {
+    /// Arguments are pushed right to left, the caller removes them.
+    Cdecl,
+    /// Arguments are pushed right to left, the callee removes them.
+    Stdcall,
+    /// Like `Stdcall`, but the first two arguments are passed in ECX and EDX.
+    Fastcall,
+    /// Like `Stdcall`, but the `this` pointer is passed in ECX.
+    Thiscall,
+}
+
+impl CallingConvention {
+    /// Parses a calling convention keyword, including the macros of the Windows headers.
+    pub fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "__cdecl" | "cdecl" | "WINAPIV" | "CDECL" => Some(CallingConvention::Cdecl),
+            "__stdcall" | "stdcall" | "WINAPI" | "APIENTRY" | "CALLBACK" | "PASCAL"
+            | "STDMETHODCALLTYPE" => Some(CallingConvention::Stdcall),
+            "__fastcall" | "fastcall" => Some(CallingConvention::Fastcall),
+            "__thiscall" | "thiscall" => Some(CallingConvention::Thiscall),
+            _ => None,
+        }
+    }
+
+    /// The number of leading parameters passed in registers, first in ECX, then in EDX.
+    pub fn register_parameters(self) -> usize {
+        match self {
+            CallingConvention::Cdecl | CallingConvention::Stdcall => 0,
+            CallingConvention::Fastcall => 2,
+            CallingConvention::Thiscall => 1,
+        }
+    }
+
+    /// Whether the called function removes the stack arguments when it returns.
+    pub fn callee_cleans_stack(self) -> bool {
+        self != CallingConvention::Cdecl
+    }
+}
+
+impl fmt::Display for CallingConvention {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CallingConvention::Cdecl => "__cdecl",
+            CallingConvention::Stdcall => "__stdcall",
+            CallingConvention::Fastcall => "__fastcall",
+            CallingConvention::Thiscall => "__thiscall",
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Parameter {
+    pub name: String,
+    pub type_name: String,
+    /// The name of the constant group that constant arguments are shown with.
+    pub constants: Option<String>,
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.type_name, self.name)
+    }
+}
+
+/// The declaration of a function exported by a module.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Prototype {
+    /// The name of the exporting module, such as `KERNEL32.dll`.
+    pub module: String,
+    pub name: String,
+    /// The ordinal of the export, for functions that are commonly imported by ordinal.
+    pub ordinal: Option<u16>,
+    pub return_type: String,
+    pub calling_convention: CallingConvention,
+    pub parameters: Vec<Parameter>,
+    /// Whether further arguments follow the parameters, as for `wsprintfA`.
+    pub variadic: bool,
+}
+
+impl fmt::Display for Prototype {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}(",
+            self.return_type, self.calling_convention, self.name
+        )?;
+        for (index, parameter) in self.parameters.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", parameter)?;
+        }
+        match (self.variadic, self.parameters.is_empty()) {
+            (true, true) => f.write_str("...)"),
+            (true, false) => f.write_str(", ...)"),
+            (false, true) => f.write_str("void)"),
+            (false, false) => f.write_str(")"),
+        }
+    }
+}
+
+/// Named values of a parameter, such as the `CREATE_*` and `OPEN_*` dispositions of
+/// `CreateFileA`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConstantGroup {
+    pub name: String,
+    /// Whether values are combinations of the constants, rather than exactly one of them.
+    pub is_flags: bool,
+    pub values: Vec<(String, u32)>,
+}
+
+impl ConstantGroup {
+    /// Formats a value with the names of the group, like `OPEN_EXISTING` or
+    /// `MB_YESNO | MB_ICONQUESTION`. Flags that cover more bits are preferred, and bits no
+    /// flag covers are shown as a number.
+    pub fn format(&self, value: u32) -> String {
+        if !self.is_flags || value == 0 {
+            return match self.values.iter().find(|(_, constant)| *constant == value) {
+                Some((name, _)) => name.clone(),
+                None => format_number(value),
+            };
+        }
+
+        let mut flags: Vec<&(String, u32)> =
+            self.values.iter().filter(|(_, flag)| *flag != 0).collect();
+        flags.sort_by_key(|(_, flag)| std::cmp::Reverse(flag.count_ones()));
+
+        let mut names = Vec::new();
+        let mut remaining = value;
+        for (name, flag) in flags {
+            if value & flag == *flag && remaining & flag != 0 {
+                names.push(name.clone());
+                remaining &= !flag;
+            }
+        }
+        if remaining != 0 {
+            names.push(format_number(remaining));
+        }
+        names.join(" | ")
+    }
+}
+
+/// Formats small numbers in decimal and others in hex. Values close to the top of the range
+/// are shown as negative numbers, as they usually are in source.
+fn format_number(value: u32) -> String {
+    if value < 10 {
+        value.to_string()
+    } else if value >= 0xFFFF_FF00 {
+        (value as i32).to_string()
+    } else {
+        format!("0x{:X}", value)
+    }
+}
+
+/// A database of function prototypes and the constants their parameters take.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TypeLibrary {
+    pub prototypes: Vec<Prototype>,
+    pub constants: Vec<ConstantGroup>,
+}
+
+/// A call to a function with a known prototype.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallAnnotation {
+    /// The address of the call instruction.
+    pub address: u64,
+    pub prototype: Prototype,
+    pub arguments: Vec<ArgumentAnnotation>,
+}
+
+/// An instruction that passes an argument to an annotated call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArgumentAnnotation {
+    pub address: u64,
+    /// The index of the parameter in the prototype.
+    pub parameter: usize,
+    /// The value, if the argument is a constant.
+    pub value: Option<u32>,
+    /// The annotation shown next to the instruction, such as
+    /// `dwCreationDisposition = OPEN_EXISTING`.
+    pub text: String,
+}
+
+/// Compares module names case-insensitively, with or without the `.dll` extension.
+fn same_module(a: &str, b: &str) -> bool {
+    fn stem(module: &str) -> &str {
+        match module.len().checked_sub(4) {
+            Some(dot)
+                if module.is_char_boundary(dot) && module[dot..].eq_ignore_ascii_case(".dll") =>
+            {
+                &module[..dot]
+            }
+            _ => module,
+        }
+    }
+    stem(a).eq_ignore_ascii_case(stem(b))
+}
+
+impl TypeLibrary {
+    /// The prototypes of the Win32, DirectX, WinMM and DirectSound functions bundled with reic.
+    pub fn bundled() -> Self {
+        let mut library = TypeLibrary::default();
+        for (name, source) in BUNDLED_LIBRARIES.iter() {
+            match TypeLibrary::parse(source) {
+                Ok(bundled) => library.extend(bundled),
+                Err(error) => panic!("bundled type library {} is invalid: {}", name, error),
+            }
+        }
+        library
+    }
+
+    /// Adds the prototypes and constants of another library. Entries of the other library
+    /// replace entries with the same name.
+    pub fn extend(&mut self, other: TypeLibrary) {
+        for prototype in other.prototypes {
+            self.prototypes.retain(|existing| {
+                !(existing.name == prototype.name
+                    && same_module(&existing.module, &prototype.module))
+            });
+            self.prototypes.push(prototype);
+        }
+        for group in other.constants {
+            self.constants
+                .retain(|existing| existing.name != group.name);
+            self.constants.push(group);
+        }
+    }
+
+    /// Looks up the prototype of an import, given by name or as `#ordinal`.
+    pub fn prototype(&self, module: &str, import: &str) -> Option<&Prototype> {
+        let ordinal = import
+            .strip_prefix('#')
+            .and_then(|ordinal| ordinal.parse::<u16>().ok());
+        self.prototypes.iter().find(|prototype| {
+            same_module(&prototype.module, module)
+                && match ordinal {
+                    Some(ordinal) => prototype.ordinal == Some(ordinal),
+                    None => prototype.name == import,
+                }
+        })
+    }
+
+    pub fn constant_group(&self, name: &str) -> Option<&ConstantGroup> {
+        self.constants.iter().find(|group| group.name == name)
+    }
+
+    /// Formats an argument value for a parameter, like `dwCreationDisposition = OPEN_EXISTING`.
+    pub fn format_argument(&self, parameter: &Parameter, value: u32) -> String {
+        let value = match parameter
+            .constants
+            .as_ref()
+            .and_then(|group| self.constant_group(group))
+        {
+            Some(group) => group.format(value),
+            None => format_number(value),
+        };
+        format!("{} = {}", parameter.name, value)
+    }
+
+    /// Maps the import address table slots of the image to the prototypes of their imports.
+    pub fn import_prototypes(&self, image: &LoadedImage) -> HashMap<u64, &Prototype> {
+        image
+            .symbols
+            .iter()
+            .filter_map(|symbol| match &symbol.kind {
+                SymbolKind::Import { module } => self
+                    .prototype(module, &symbol.name)
+                    .map(|prototype| (symbol.address, prototype)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Adds prototypes for the imports of the image with mangled C++ names, such as the
+    /// members of `MFC42.DLL` or `msvcp60.dll`, that the library has none for yet. Returns the
+    /// number of prototypes added.
+    pub fn add_demangled_imports(&mut self, image: &LoadedImage) -> usize {
+        let mut added = 0;
+        for symbol in &image.symbols {
+            let module = match &symbol.kind {
+                SymbolKind::Import { module } => module,
+                _ => continue,
+            };
+            if self.prototype(module, &symbol.name).is_some() {
+                continue;
+            }
+            if let Some(prototype) =
+                demangle(&symbol.name).and_then(|name| name.prototype(module, &symbol.name))
+            {
+                self.prototypes.push(prototype);
+                added += 1;
+            }
+        }
+        added
+    }
+
+    /// Finds the calls to imported functions with a known prototype, and annotates the
+    /// instructions that push their arguments.
+    ///
+    /// Calls through the import address table, through a `jmp` thunk and through a register
+    /// loaded from the import address table are recognized. Arguments are the `push`
+    /// instructions preceding the call in the same basic block.
+    pub fn annotate_calls(
+        &self,
+        image: &LoadedImage,
+        disassembly: &Disassembly,
+    ) -> Vec<CallAnnotation> {
+        let imports = self.import_prototypes(image);
+        disassembly
+            .instructions
+            .values()
+            .filter(|instruction| instruction.decoded.mnemonic == Mnemonic::CALL)
+            .filter_map(|instruction| {
+                let address = instruction.address.address;
+                let slot = import_slot(disassembly, address)?;
+                let prototype = *imports.get(&slot)?;
+                Some(CallAnnotation {
+                    address,
+                    prototype: prototype.clone(),
+                    arguments: self.annotate_arguments(disassembly, address, prototype),
+                })
+            })
+            .collect()
+    }
+
+    fn annotate_arguments(
+        &self,
+        disassembly: &Disassembly,
+        call: u64,
+        prototype: &Prototype,
+    ) -> Vec<ArgumentAnnotation> {
+        let stack_parameters = prototype
+            .parameters
+            .iter()
+            .enumerate()
+            .skip(prototype.calling_convention.register_parameters());
+
+        let mut arguments = Vec::new();
+        let mut address = call;
+        for (index, parameter) in stack_parameters {
+            let push = loop {
+                let instruction = match disassembly.fallthrough_predecessor(address) {
+                    Some(instruction) => instruction,
+                    None => return arguments,
+                };
+                address = instruction.address.address;
+                match instruction.decoded.mnemonic {
+                    Mnemonic::PUSH => break instruction,
+                    // The arguments of an earlier call have been consumed by it
+                    Mnemonic::CALL => return arguments,
+                    _ => {}
+                }
+            };
+
+            let operand = &push.decoded.operands[0];
+            let value = if operand.ty == OperandType::IMMEDIATE {
+                Some(operand.imm.value as u32)
+            } else {
+                None
+            };
+            arguments.push(ArgumentAnnotation {
+                address,
+                parameter: index,
+                value,
+                text: match value {
+                    Some(value) => self.format_argument(parameter, value),
+                    None => parameter.name.clone(),
+                },
+            });
+        }
+        arguments
+    }
+}
+
+/// Returns the import address table slot a call goes through, if any.
+pub(crate) fn import_slot(disassembly: &Disassembly, call: u64) -> Option<u64> {
+    let decoded = &disassembly.instructions.get(&call)?.decoded;
+    let operand = &decoded.operands[0];
+    match operand.ty {
+        // call dword ptr [slot]
+        OperandType::MEMORY => absolute_memory_address(operand),
+        // call thunk, with the thunk being jmp dword ptr [slot]
+        OperandType::IMMEDIATE => {
+            let (_, target, _) = disassembly
+                .flow
+                .edges(call)
+                .find(|(_, _, kind)| **kind == FlowKind::Call)?;
+            let thunk = &disassembly.instructions.get(&target)?.decoded;
+            if thunk.mnemonic == Mnemonic::JMP {
+                absolute_memory_address(&thunk.operands[0])
+            } else {
+                None
+            }
+        }
+        // mov esi, dword ptr [slot] ... call esi
+        OperandType::REGISTER => register_load(disassembly, call, operand.reg),
+        _ => None,
+    }
+}
+
+/// Searches backwards from an instruction for the load of a register from an absolute address.
+fn register_load(disassembly: &Disassembly, from: u64, register: Register) -> Option<u64> {
+    let mut address = from;
+    for _ in 0..MAX_REGISTER_LOAD_DISTANCE {
+        let instruction = disassembly.fallthrough_predecessor(address)?;
+        address = instruction.address.address;
+
+        let decoded = &instruction.decoded;
+        let operands = &decoded.operands[..decoded.operand_count as usize];
+        let writes_register = operands.iter().any(|operand| {
+            operand.ty == OperandType::REGISTER
+                && operand.reg == register
+                && operand.action.intersects(OperandAction::MASK_WRITE)
+        });
+        if !writes_register {
+            continue;
+        }
+
+        return if decoded.mnemonic == Mnemonic::MOV {
+            absolute_memory_address(&operands[1])
+        } else {
+            None
+        };
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{load_pe_file, Disassembler};
+    use crate::parsers::pe32::tests::TestImage;
+
+    #[test]
+    fn bundled_library_is_consistent() {
+        let library = TypeLibrary::bundled();
+        for prototype in &library.prototypes {
+            for parameter in &prototype.parameters {
+                if let Some(group) = &parameter.constants {
+                    assert!(
+                        library.constant_group(group).is_some(),
+                        "{} refers to unknown constants {}",
+                        prototype.name,
+                        group
+                    );
+                }
+            }
+        }
+
+        let create_file = library.prototype("kernel32", "CreateFileA").unwrap();
+        assert_eq!(create_file.calling_convention, CallingConvention::Stdcall);
+        assert_eq!(
+            library.format_argument(&create_file.parameters[4], 3),
+            "dwCreationDisposition = OPEN_EXISTING"
+        );
+        assert_eq!(
+            library.prototype("DSOUND.DLL", "#1").unwrap().name,
+            "DirectSoundCreate"
+        );
+    }
+
+    #[test]
+    fn formats_flags() {
+        let library = TypeLibrary::bundled();
+        let message_box = library.constant_group("MESSAGE_BOX_STYLE").unwrap();
+        assert_eq!(message_box.format(0), "MB_OK");
+        assert_eq!(message_box.format(0x24), "MB_YESNO | MB_ICONQUESTION");
+        assert_eq!(message_box.format(0x30), "MB_ICONEXCLAMATION");
+
+        let access = library.constant_group("GENERIC_ACCESS").unwrap();
+        assert_eq!(
+            access.format(0xC000_0001),
+            "GENERIC_READ | GENERIC_WRITE | 1"
+        );
+    }
+
+    #[test]
+    fn annotates_call_arguments() {
+        // push 0; push 0x80; push 3; push 0; push 1; push 0x80000000; push esi;
+        // call [CreateFileA]; ret
+        let code = vec![
+            0x6A, 0x00, 0x68, 0x80, 0x00, 0x00, 0x00, 0x6A, 0x03, 0x6A, 0x00, 0x6A, 0x01, 0x68,
+            0x00, 0x00, 0x00, 0x80, 0x56, 0xFF, 0x15, 0x40, 0x20, 0x40, 0x00, 0xC3,
+        ];
+        let mut test_image = TestImage::executable();
+        test_image.sections[0].virtual_size = code.len() as u32;
+        test_image.sections[0].data = code;
+        test_image.sections[1].data[0x82..0x8D].copy_from_slice(b"CreateFileA");
+        test_image.sections[2].data[8..10].copy_from_slice(&0x3015u16.to_le_bytes());
+        let image = load_pe_file(&test_image.build()).unwrap();
+        let disassembly = Disassembler::new(&image).unwrap().run();
+
+        let calls = TypeLibrary::bundled().annotate_calls(&image, &disassembly);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].address, 0x0040_1013);
+        assert_eq!(calls[0].prototype.name, "CreateFileA");
+        let arguments: Vec<(u64, &str)> = calls[0]
+            .arguments
+            .iter()
+            .map(|argument| (argument.address, argument.text.as_str()))
+            .collect();
+        assert_eq!(
+            arguments,
+            vec![
+                (0x0040_1012, "lpFileName"),
+                (0x0040_100D, "dwDesiredAccess = GENERIC_READ"),
+                (0x0040_100B, "dwShareMode = FILE_SHARE_READ"),
+                (0x0040_1009, "lpSecurityAttributes = 0"),
+                (0x0040_1007, "dwCreationDisposition = OPEN_EXISTING"),
+                (0x0040_1002, "dwFlagsAndAttributes = FILE_ATTRIBUTE_NORMAL"),
+                (0x0040_1000, "hTemplateFile = 0"),
+            ]
+        );
+        assert_eq!(calls[0].arguments[4].value, Some(3));
+        assert_eq!(calls[0].arguments[0].value, None);
+    }
+}
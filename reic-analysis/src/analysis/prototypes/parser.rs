@@ -0,0 +1,278 @@
+use crate::analysis::{CallingConvention, ConstantGroup, Parameter, Prototype, TypeLibrary};
+
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeLibraryError {
+    /// The one-based line the invalid declaration starts on.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TypeLibraryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for TypeLibraryError {}
+
+/// Splits the text at the first occurrence of `separator`.
+fn split_first(text: &str, separator: char) -> Option<(&str, &str)> {
+    let at = text.find(separator)?;
+    Some((&text[..at], &text[at + separator.len_utf8()..]))
+}
+
+/// Splits the text at the last occurrence of `separator`.
+fn split_last(text: &str, separator: char) -> Option<(&str, &str)> {
+    let at = text.rfind(separator)?;
+    Some((&text[..at], &text[at + separator.len_utf8()..]))
+}
+
+fn parse_number(text: &str) -> Option<u32> {
+    let (negative, number) = match text.strip_prefix('-') {
+        Some(number) => (true, number),
+        None => (false, text),
+    };
+    let value = match number.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => number.parse().ok()?,
+    };
+    Some(if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    })
+}
+
+/// Splits a declaration like `const char* name` into its type and name.
+fn parse_declaration(declaration: &str) -> Option<(String, String)> {
+    let declaration = declaration.trim();
+    let at = declaration.rfind(char::is_whitespace)?;
+    let (type_name, name) = (&declaration[..at], declaration[at..].trim_start());
+    let stars = name.len() - name.trim_start_matches('*').len();
+    let type_name = type_name.split_whitespace().collect::<Vec<_>>().join(" ");
+    Some((type_name + &name[..stars], name[stars..].to_owned()))
+}
+
+fn parse_parameter(parameter: &str) -> Result<Parameter, String> {
+    let (declaration, constants) = match split_first(parameter, ':') {
+        Some((declaration, constants)) => (declaration, Some(constants.trim().to_owned())),
+        None => (parameter, None),
+    };
+    let (type_name, name) = parse_declaration(declaration)
+        .ok_or_else(|| format!("expected type and name of parameter `{}`", parameter.trim()))?;
+    Ok(Parameter {
+        name,
+        type_name,
+        constants,
+    })
+}
+
+/// Parses `RETURN_TYPE CONVENTION Name(PARAMETERS) @ordinal`, with the convention and the
+/// ordinal being optional.
+fn parse_prototype(declaration: &str, module: &str) -> Result<Prototype, String> {
+    let (head, rest) = split_first(declaration, '(').ok_or("expected parameter list")?;
+    let (parameters, tail) = split_last(rest, ')').ok_or("expected `)`")?;
+
+    let mut head: Vec<&str> = head.split_whitespace().collect();
+    let name = head.pop().ok_or("expected function name")?.to_owned();
+    let calling_convention = match head
+        .last()
+        .and_then(|&keyword| CallingConvention::from_keyword(keyword))
+    {
+        Some(convention) => {
+            head.pop();
+            convention
+        }
+        None => CallingConvention::Stdcall,
+    };
+    if head.is_empty() {
+        return Err(format!("expected return type of {}", name));
+    }
+
+    let tail = tail.trim();
+    let ordinal = if tail.is_empty() {
+        None
+    } else {
+        let ordinal = tail
+            .strip_prefix('@')
+            .and_then(|ordinal| ordinal.trim().parse().ok())
+            .ok_or_else(|| format!("expected `@ordinal` after {}, found `{}`", name, tail))?;
+        Some(ordinal)
+    };
+
+    let mut variadic = false;
+    let mut parsed = Vec::new();
+    let parameters = parameters.trim();
+    if !parameters.is_empty() && parameters != "void" {
+        for parameter in parameters.split(',') {
+            if variadic {
+                return Err(format!("parameters of {} follow `...`", name));
+            }
+            if parameter.trim() == "..." {
+                variadic = true;
+            } else {
+                parsed.push(parse_parameter(parameter)?);
+            }
+        }
+    }
+
+    Ok(Prototype {
+        module: module.to_owned(),
+        name,
+        ordinal,
+        return_type: head.join(" "),
+        calling_convention,
+        parameters: parsed,
+        variadic,
+    })
+}
+
+/// Parses `NAME { CONSTANT = VALUE, ... }`.
+fn parse_constant_group(declaration: &str, is_flags: bool) -> Result<ConstantGroup, String> {
+    let (name, body) = split_first(declaration, '{').ok_or("expected `{`")?;
+    let body = body.strip_suffix('}').ok_or("expected `}`")?;
+    let name = name.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(format!("invalid constant group name `{}`", name));
+    }
+
+    let mut values = Vec::new();
+    for constant in body.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+        let (constant, value) =
+            split_first(constant, '=').ok_or_else(|| format!("expected value of {}", constant))?;
+        let value = parse_number(value.trim())
+            .ok_or_else(|| format!("invalid value of {}: `{}`", constant.trim(), value.trim()))?;
+        values.push((constant.trim().to_owned(), value));
+    }
+
+    Ok(ConstantGroup {
+        name: name.to_owned(),
+        is_flags,
+        values,
+    })
+}
+
+impl TypeLibrary {
+    /// Parses a type library from its text form:
+    ///
+    /// ```text
+    /// # Comments run to the end of the line
+    /// module KERNEL32.dll
+    /// HANDLE WINAPI CreateFileA(LPCSTR lpFileName, ..., DWORD dwCreationDisposition: CREATION_DISPOSITION, ...);
+    /// enum CREATION_DISPOSITION { CREATE_NEW = 1, CREATE_ALWAYS = 2, OPEN_EXISTING = 3 }
+    /// flags FILE_SHARE { FILE_SHARE_READ = 0x1, FILE_SHARE_WRITE = 0x2 }
+    /// ```
+    ///
+    /// Prototypes belong to the module named by the preceding `module` line, and end with `;`.
+    /// A parameter can name the constant group its values are shown with, and a prototype can
+    /// end with `@ordinal` for functions that are imported by ordinal. Without a calling
+    /// convention, `__stdcall` is assumed.
+    pub fn parse(source: &str) -> Result<Self, TypeLibraryError> {
+        // Blank out comments, keeping lines where they are for error messages
+        let text: String = source
+            .lines()
+            .map(|line| line.split('#').next().unwrap())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let mut library = TypeLibrary::default();
+        let mut module: Option<String> = None;
+        let mut position = 0;
+        loop {
+            let rest = &text[position..];
+            let start = match rest.find(|c: char| !c.is_whitespace()) {
+                Some(start) => position + start,
+                None => return Ok(library),
+            };
+            let rest = &text[start..];
+            let line = text[..start].matches('\n').count() + 1;
+            let error = |message: String| TypeLibraryError { line, message };
+
+            let keyword = rest
+                .split(|c: char| c.is_whitespace() || c == '{')
+                .next()
+                .unwrap();
+            let terminator = match keyword {
+                "module" => '\n',
+                "enum" | "flags" => '}',
+                _ => ';',
+            };
+            let length = match rest.find(terminator) {
+                Some(end) => end + 1,
+                None if terminator == '\n' => rest.len(),
+                None => return Err(error(format!("expected `{}`", terminator))),
+            };
+            let declaration = &rest[..length];
+            position = start + length;
+
+            match keyword {
+                "module" => module = Some(declaration[keyword.len()..].trim().to_owned()),
+                "enum" | "flags" => library.constants.push(
+                    parse_constant_group(&declaration[keyword.len()..], keyword == "flags")
+                        .map_err(error)?,
+                ),
+                _ => {
+                    let module = module
+                        .as_deref()
+                        .ok_or_else(|| error("prototype outside of a module".to_owned()))?;
+                    let declaration = declaration.trim_end_matches(';');
+                    library
+                        .prototypes
+                        .push(parse_prototype(declaration, module).map_err(error)?);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_declarations() {
+        let library = TypeLibrary::parse(
+            "# test\n\
+             module USER32.dll\n\
+             int __cdecl wsprintfA(LPSTR lpOut, LPCSTR lpFmt, ...);\n\
+             enum SHOW { SW_HIDE = 0, SW_SHOWNORMAL = 1, }\n\
+             module DSOUND.dll\n\
+             HRESULT WINAPI DirectSoundCreate(const GUID *pcGuidDevice,\n\
+             \x20   LPDIRECTSOUND* ppDS, LPUNKNOWN pUnkOuter: SHOW) @1;\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            library.prototypes[0].to_string(),
+            "int __cdecl wsprintfA(LPSTR lpOut, LPCSTR lpFmt, ...)"
+        );
+        assert!(library.prototypes[0].variadic);
+        let create = &library.prototypes[1];
+        assert_eq!(create.module, "DSOUND.dll");
+        assert_eq!(create.ordinal, Some(1));
+        assert_eq!(create.parameters[0].type_name, "const GUID*");
+        assert_eq!(create.parameters[0].name, "pcGuidDevice");
+        assert_eq!(create.parameters[2].constants.as_deref(), Some("SHOW"));
+        assert_eq!(
+            library.constants,
+            vec![ConstantGroup {
+                name: "SHOW".to_owned(),
+                is_flags: false,
+                values: vec![("SW_HIDE".to_owned(), 0), ("SW_SHOWNORMAL".to_owned(), 1)],
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_line_of_error() {
+        let error = TypeLibrary::parse("module A.dll\n\nint Broken;\n").unwrap_err();
+        assert_eq!(error.line, 3);
+        assert_eq!(
+            TypeLibrary::parse("int f(void);").unwrap_err().message,
+            "prototype outside of a module"
+        );
+    }
+}
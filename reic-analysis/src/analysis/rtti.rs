@@ -0,0 +1,506 @@
+use crate::analysis::{demangle_msvc_type, Disassembly, LoadedImage, MemorySection, Type};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::TryInto;
+
+/// The longest mangled type name read from a type descriptor.
+const MAX_TYPE_NAME_LENGTH: usize = 4096;
+/// The most base classes a class hierarchy descriptor is believed to have.
+const MAX_BASE_CLASSES: u32 = 1024;
+
+/// `RTTIClassHierarchyDescriptor::attributes`: the class has more than one base.
+const CHD_MULTIPLE_INHERITANCE: u32 = 1;
+/// `RTTIClassHierarchyDescriptor::attributes`: the class has a virtual base.
+const CHD_VIRTUAL_INHERITANCE: u32 = 2;
+
+/// A base of a class, as described by an `RTTIBaseClassDescriptor`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BaseClass {
+    /// The address of the base's type descriptor, identifying the base class.
+    pub type_descriptor: u64,
+    pub name: String,
+    /// How many of the base classes following this one in the hierarchy are bases of this one.
+    pub contained_bases: u32,
+    /// The offset of the base within the class, `PMD::mdisp`.
+    pub member_displacement: i32,
+    /// The offset of the virtual base table pointer, `PMD::pdisp`, or -1 for non-virtual bases.
+    pub vbtable_displacement: i32,
+    /// The offset of the base's entry in the virtual base table, `PMD::vdisp`.
+    pub vbtable_offset: i32,
+    pub attributes: u32,
+}
+
+impl BaseClass {
+    pub fn is_virtual(&self) -> bool {
+        self.vbtable_displacement >= 0
+    }
+}
+
+/// A virtual function table, found through the complete object locator preceding it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VirtualTable {
+    pub address: u64,
+    /// The address of the `RTTICompleteObjectLocator`.
+    pub locator: u64,
+    /// The offset of the table's pointer within the complete object. Classes with multiple
+    /// bases have a table for each base with virtual functions.
+    pub offset: u32,
+    pub functions: Vec<u64>,
+}
+
+/// A class known from the MSVC run-time type information.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RttiClass {
+    /// The address of the `TypeDescriptor`, identifying the class.
+    pub type_descriptor: u64,
+    /// The decorated name in the type descriptor, such as `.?AVCWnd@@`.
+    pub mangled_name: String,
+    pub name: String,
+    pub multiple_inheritance: bool,
+    pub virtual_inheritance: bool,
+    /// All direct and indirect bases in the order of the base class array, in which each base
+    /// is followed by its own bases.
+    pub base_classes: Vec<BaseClass>,
+    pub vtables: Vec<VirtualTable>,
+}
+
+impl RttiClass {
+    /// The bases the class directly derives from.
+    pub fn direct_bases(&self) -> Vec<&BaseClass> {
+        let mut bases = Vec::new();
+        let mut index = 0;
+        while let Some(base) = self.base_classes.get(index) {
+            bases.push(base);
+            index += 1 + base.contained_bases as usize;
+        }
+        bases
+    }
+
+    /// The table at the start of the object, which holds the virtual functions introduced by
+    /// the class itself.
+    pub fn primary_vtable(&self) -> Option<&VirtualTable> {
+        self.vtables.iter().find(|vtable| vtable.offset == 0)
+    }
+}
+
+/// The classes of an image, recovered from its MSVC run-time type information.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClassHierarchy {
+    /// The classes, keyed by the address of their type descriptor.
+    pub classes: BTreeMap<u64, RttiClass>,
+}
+
+impl ClassHierarchy {
+    /// Scans the image for the RTTI structures of 32-bit MSVC: the complete object locators
+    /// preceding each virtual function table, the type descriptors, class hierarchy descriptors
+    /// and base class arrays they refer to.
+    pub fn scan(image: &LoadedImage) -> Self {
+        let scanner = Scanner::new(image);
+        let mut hierarchy = Self::default();
+
+        let mut locators = BTreeMap::new();
+        for address in scanner.aligned_addresses() {
+            if let Some(locator) = scanner.complete_object_locator(address) {
+                locators.insert(address, locator);
+            }
+        }
+
+        for locator in locators.values() {
+            let class_descriptor = &locator.class_descriptor;
+            let bases = &class_descriptor.bases;
+            // Classes without virtual functions only appear as bases
+            for (index, base) in bases.iter().enumerate() {
+                if hierarchy.classes.contains_key(&base.type_descriptor) {
+                    continue;
+                }
+                let contained = &bases[index + 1..=index + base.contained_bases as usize];
+                let mut class = RttiClass {
+                    type_descriptor: base.type_descriptor,
+                    mangled_name: scanner.type_names[&base.type_descriptor].clone(),
+                    name: base.name.clone(),
+                    multiple_inheritance: false,
+                    virtual_inheritance: contained.iter().any(BaseClass::is_virtual),
+                    base_classes: contained
+                        .iter()
+                        .map(|contained| relative_to(contained, base))
+                        .collect(),
+                    vtables: Vec::new(),
+                };
+                class.multiple_inheritance = class.direct_bases().len() > 1;
+                hierarchy.classes.insert(base.type_descriptor, class);
+            }
+
+            // The hierarchy of a class itself beats what its derived classes tell about it
+            let class = hierarchy.classes.get_mut(&locator.type_descriptor).unwrap();
+            class.multiple_inheritance =
+                class_descriptor.attributes & CHD_MULTIPLE_INHERITANCE != 0;
+            class.virtual_inheritance = class_descriptor.attributes & CHD_VIRTUAL_INHERITANCE != 0;
+            class.base_classes = bases[1..].to_vec();
+        }
+
+        // Each virtual function table is preceded by a pointer to its locator
+        for address in scanner.aligned_addresses() {
+            let locator_address = match scanner.read_u32(address) {
+                Some(locator) => locator as u64,
+                None => continue,
+            };
+            let locator = match locators.get(&locator_address) {
+                Some(locator) => locator,
+                None => continue,
+            };
+            let vtable = address + 4;
+            let functions = scanner.virtual_functions(vtable, &locators);
+            if functions.is_empty() {
+                continue;
+            }
+
+            let class = hierarchy.classes.get_mut(&locator.type_descriptor).unwrap();
+            class.vtables.push(VirtualTable {
+                address: vtable,
+                locator: locator_address,
+                offset: locator.offset,
+                functions,
+            });
+        }
+
+        hierarchy
+    }
+
+    pub fn class_named(&self, name: &str) -> Option<&RttiClass> {
+        self.classes.values().find(|class| class.name == name)
+    }
+
+    /// The classes directly deriving from the class with the given type descriptor.
+    pub fn derived_classes(&self, type_descriptor: u64) -> Vec<&RttiClass> {
+        self.classes
+            .values()
+            .filter(|class| {
+                class
+                    .direct_bases()
+                    .iter()
+                    .any(|base| base.type_descriptor == type_descriptor)
+            })
+            .collect()
+    }
+
+    /// Names the functions of all virtual function tables as `Class::vfunc_N`, by their index
+    /// in the table. A function inherited by derived classes is named after the class with the
+    /// fewest bases using it, which is the class introducing it unless it was overridden.
+    pub fn virtual_function_names(&self) -> BTreeMap<u64, String> {
+        let mut names: HashMap<u64, (usize, String)> = HashMap::new();
+        for class in self.classes.values() {
+            for vtable in &class.vtables {
+                for (index, &function) in vtable.functions.iter().enumerate() {
+                    let rank = class.base_classes.len();
+                    let better = match names.get(&function) {
+                        Some((named_rank, _)) => rank < *named_rank,
+                        None => true,
+                    };
+                    if better {
+                        let name = format!("{}::vfunc_{}", class.name, index);
+                        names.insert(function, (rank, name));
+                    }
+                }
+            }
+        }
+
+        names
+            .into_iter()
+            .map(|(function, (_, name))| (function, name))
+            .collect()
+    }
+
+    /// Names the disassembled virtual functions that have no name yet, and returns how many
+    /// were named.
+    pub fn name_virtual_functions(&self, disassembly: &mut Disassembly) -> usize {
+        let mut named = 0;
+        for (address, name) in self.virtual_function_names() {
+            if let Some(function) = disassembly.functions.get_mut(&address) {
+                if function.name.is_none() {
+                    function.name = Some(name);
+                    named += 1;
+                }
+            }
+        }
+        named
+    }
+}
+
+/// Makes the displacements of a base relative to another base containing it, rather than to
+/// the class whose base class array they come from.
+fn relative_to(base: &BaseClass, container: &BaseClass) -> BaseClass {
+    let mut base = base.clone();
+    if !base.is_virtual() && !container.is_virtual() {
+        base.member_displacement -= container.member_displacement;
+    }
+    base
+}
+
+/// Turns the decorated name of a type descriptor into a qualified class name, such as
+/// `.?AVCFrame@Gui@@` into `Gui::CFrame` or `.?AV?$CArray@HH@@` into `CArray<int, int>`.
+/// Names that cannot be decoded are returned without the type prefix.
+pub(crate) fn type_descriptor_name(mangled: &str) -> String {
+    match demangle_msvc_type(mangled) {
+        Some(Type::Named { name, .. }) => name,
+        Some(data_type) => data_type.to_string(),
+        None => mangled
+            .trim_start_matches(".?AV")
+            .trim_start_matches(".?AU")
+            .to_owned(),
+    }
+}
+
+struct ClassHierarchyDescriptor {
+    attributes: u32,
+    /// The base class array, starting with the class itself.
+    bases: Vec<BaseClass>,
+}
+
+struct CompleteObjectLocator {
+    offset: u32,
+    type_descriptor: u64,
+    class_descriptor: ClassHierarchyDescriptor,
+}
+
+struct Scanner<'a> {
+    image: &'a LoadedImage,
+    /// The mangled names of all type descriptors, keyed by their address.
+    type_names: HashMap<u64, String>,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(image: &'a LoadedImage) -> Self {
+        let mut scanner = Self {
+            image,
+            type_names: HashMap::new(),
+        };
+        for address in scanner.aligned_addresses() {
+            if let Some(name) = scanner.type_descriptor(address) {
+                scanner.type_names.insert(address, name);
+            }
+        }
+        scanner
+    }
+
+    /// All 4-byte aligned addresses of the image that have backing data.
+    fn aligned_addresses(&self) -> impl Iterator<Item = u64> + 'a {
+        let base_address = self.image.base_address;
+        self.image
+            .memory_map
+            .iter()
+            .filter(|(_, section)| section.data().is_some())
+            .flat_map(move |(slice, _)| {
+                let start = base_address + slice.rva;
+                (start..start + slice.length.saturating_sub(3)).step_by(4)
+            })
+    }
+
+    fn read_u32(&self, address: u64) -> Option<u32> {
+        let bytes = self.image.bytes_at(address)?.get(..4)?;
+        Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// Reads a `TypeDescriptor`: the address of the `type_info` vtable, a zero reserved for the
+    /// undecorated name, and the decorated name of a class, struct or enum.
+    fn type_descriptor(&self, address: u64) -> Option<String> {
+        let bytes = self.image.bytes_at(address)?;
+        if bytes.len() < 12 || bytes[8..12] != *b".?AV" && bytes[8..12] != *b".?AU" {
+            return None;
+        }
+        if self.read_u32(address + 4)? != 0 || !self.is_mapped(self.read_u32(address)?) {
+            return None;
+        }
+
+        let name = &bytes[8..bytes.len().min(8 + MAX_TYPE_NAME_LENGTH)];
+        let length = name.iter().position(|&byte| byte == 0)?;
+        let name = &name[..length];
+        if name.iter().all(|byte| byte.is_ascii_graphic()) {
+            Some(String::from_utf8_lossy(name).into_owned())
+        } else {
+            None
+        }
+    }
+
+    fn is_mapped(&self, address: u32) -> bool {
+        self.image.slice_at(address as u64).is_some()
+    }
+
+    fn is_code(&self, address: u32) -> bool {
+        matches!(
+            self.image.slice_at(address as u64),
+            Some((_, MemorySection::Code(_)))
+        )
+    }
+
+    /// Reads an `RTTICompleteObjectLocator` with signature 0, as used by 32-bit images.
+    fn complete_object_locator(&self, address: u64) -> Option<CompleteObjectLocator> {
+        if self.read_u32(address)? != 0 {
+            return None;
+        }
+        let offset = self.read_u32(address + 4)?;
+        let type_descriptor = self.read_u32(address + 12)? as u64;
+        if !self.type_names.contains_key(&type_descriptor) {
+            return None;
+        }
+        let class_descriptor = self.class_hierarchy_descriptor(self.read_u32(address + 16)?)?;
+        if class_descriptor.bases[0].type_descriptor != type_descriptor {
+            return None;
+        }
+
+        Some(CompleteObjectLocator {
+            offset,
+            type_descriptor,
+            class_descriptor,
+        })
+    }
+
+    fn class_hierarchy_descriptor(&self, address: u32) -> Option<ClassHierarchyDescriptor> {
+        let address = address as u64;
+        if self.read_u32(address)? != 0 {
+            return None;
+        }
+        let attributes = self.read_u32(address + 4)?;
+        let count = self.read_u32(address + 8)?;
+        if count == 0 || count > MAX_BASE_CLASSES {
+            return None;
+        }
+
+        let array = self.read_u32(address + 12)? as u64;
+        let bases = (0..count as u64)
+            .map(|index| {
+                let descriptor = self.read_u32(array + index * 4)?;
+                self.base_class_descriptor(descriptor as u64)
+            })
+            .collect::<Option<Vec<_>>>()?;
+        // Each base is followed by its own bases, which must fit into the array
+        let consistent = bases
+            .iter()
+            .enumerate()
+            .all(|(index, base)| index + (base.contained_bases as usize) < bases.len());
+        if bases[0].contained_bases as usize != bases.len() - 1 || !consistent {
+            return None;
+        }
+
+        Some(ClassHierarchyDescriptor { attributes, bases })
+    }
+
+    fn base_class_descriptor(&self, address: u64) -> Option<BaseClass> {
+        let type_descriptor = self.read_u32(address)? as u64;
+        let name = type_descriptor_name(self.type_names.get(&type_descriptor)?);
+        Some(BaseClass {
+            type_descriptor,
+            name,
+            contained_bases: self.read_u32(address + 4)?,
+            member_displacement: self.read_u32(address + 8)? as i32,
+            vbtable_displacement: self.read_u32(address + 12)? as i32,
+            vbtable_offset: self.read_u32(address + 16)? as i32,
+            attributes: self.read_u32(address + 20)?,
+        })
+    }
+
+    /// Reads the code addresses of a virtual function table, which ends before the first entry
+    /// not pointing to code, or at the locator of the next table.
+    fn virtual_functions(
+        &self,
+        vtable: u64,
+        locators: &BTreeMap<u64, CompleteObjectLocator>,
+    ) -> Vec<u64> {
+        let mut functions = Vec::new();
+        let mut address = vtable;
+        while let Some(function) = self.read_u32(address) {
+            if !self.is_code(function) {
+                break;
+            }
+            functions.push(function as u64);
+            address += 4;
+            match self.read_u32(address) {
+                Some(next) if locators.contains_key(&(next as u64)) => break,
+                _ => {}
+            }
+        }
+        functions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::loader::tests::test_image;
+
+    fn put(data: &mut [u8], offset: usize, values: &[u32]) {
+        for (index, value) in values.iter().enumerate() {
+            data[offset + index * 4..][..4].copy_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    /// `Game::Derived` deriving from `Base`, overriding its first virtual function and adding
+    /// a third one.
+    fn image() -> LoadedImage {
+        let mut data = vec![0; 0x100];
+        put(&mut data, 0x00, &[0x0040_2000, 0]);
+        data[0x08..0x12].copy_from_slice(b".?AVBase@@");
+        put(&mut data, 0x20, &[0x0040_2000, 0]);
+        data[0x28..0x3A].copy_from_slice(b".?AVDerived@Game@@");
+        // Base class descriptors, arrays and hierarchy descriptors
+        put(&mut data, 0x40, &[0x0040_2000, 0, 0, -1i32 as u32, 0, 0]);
+        put(&mut data, 0x60, &[0x0040_2020, 1, 0, -1i32 as u32, 0, 0]);
+        put(&mut data, 0x80, &[0x0040_2040]);
+        put(&mut data, 0x88, &[0x0040_2060, 0x0040_2040]);
+        put(&mut data, 0x90, &[0, 0, 1, 0x0040_2080]);
+        put(&mut data, 0xA0, &[0, 0, 2, 0x0040_2088]);
+        // Complete object locators, each followed by the virtual function table using it
+        put(&mut data, 0xB0, &[0, 0, 0, 0x0040_2000, 0x0040_2090]);
+        put(&mut data, 0xC4, &[0, 0, 0, 0x0040_2020, 0x0040_20A0]);
+        put(&mut data, 0xD8, &[0x0040_20B0, 0x0040_1000, 0x0040_1010]);
+        put(
+            &mut data,
+            0xE4,
+            &[0x0040_20C4, 0x0040_1020, 0x0040_1010, 0x0040_1030],
+        );
+
+        test_image(&[], data)
+    }
+
+    #[test]
+    fn recovers_hierarchy_and_vtables() {
+        let hierarchy = ClassHierarchy::scan(&image());
+        assert_eq!(hierarchy.classes.len(), 2);
+
+        let derived = hierarchy.class_named("Game::Derived").unwrap();
+        assert_eq!(derived.mangled_name, ".?AVDerived@Game@@");
+        assert!(!derived.multiple_inheritance);
+        let bases: Vec<&str> = derived
+            .direct_bases()
+            .iter()
+            .map(|base| base.name.as_str())
+            .collect();
+        assert_eq!(bases, vec!["Base"]);
+        assert_eq!(
+            derived.primary_vtable().unwrap().functions,
+            vec![0x0040_1020, 0x0040_1010, 0x0040_1030]
+        );
+
+        let derived_from_base: Vec<&str> = hierarchy
+            .derived_classes(0x0040_2000)
+            .iter()
+            .map(|class| class.name.as_str())
+            .collect();
+        assert_eq!(derived_from_base, vec!["Game::Derived"]);
+
+        let names = hierarchy.virtual_function_names();
+        assert_eq!(names[&0x0040_1000], "Base::vfunc_0");
+        assert_eq!(names[&0x0040_1010], "Base::vfunc_1");
+        assert_eq!(names[&0x0040_1020], "Game::Derived::vfunc_0");
+        assert_eq!(names[&0x0040_1030], "Game::Derived::vfunc_2");
+    }
+
+    #[test]
+    fn names_type_descriptors() {
+        assert_eq!(type_descriptor_name(".?AVCWnd@@"), "CWnd");
+        assert_eq!(type_descriptor_name(".?AUPoint@Math@@"), "Math::Point");
+        assert_eq!(
+            type_descriptor_name(".?AV?$CArray@HH@@"),
+            "CArray<int, int>"
+        );
+    }
+}
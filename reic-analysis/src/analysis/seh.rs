@@ -0,0 +1,949 @@
+use crate::analysis::{is_code, type_descriptor_name, Disassembly, FlowKind, LoadedImage};
+
+use std::collections::BTreeSet;
+use std::convert::TryInto;
+use zydis::{
+    DecodedInstruction, DecodedOperand, InstructionCategory, Mnemonic, OperandType, Register,
+};
+
+/// How many bytes before the installation of a frame are searched for the load of the previous
+/// head of the chain. Functions built with `/GS` save registers and the cookie in between.
+const MAX_SETUP_LENGTH: u64 = 64;
+/// How many instructions of a C++ handler stub are searched for its `FuncInfo`.
+const MAX_STUB_LENGTH: usize = 16;
+/// How many instructions of a prolog helper are searched for the installation of the frame.
+/// `__SEH_prolog4` saves registers and sets up the security cookie first.
+const MAX_HELPER_LENGTH: usize = 32;
+/// Limits on the counts of a `FuncInfo`, beyond which it is taken to be something else.
+const MAX_STATES: u32 = 4096;
+const MAX_TRY_BLOCKS: u32 = 1024;
+const MAX_CATCHES: u32 = 64;
+
+/// The magic numbers of `FuncInfo` structures, from Visual C++ 6 to 2005 and later.
+const FUNC_INFO_MAGIC: [u32; 3] = [0x1993_0520, 0x1993_0521, 0x1993_0522];
+/// The enclosing level of outermost `__try` blocks in the scope tables of `__except_handler3`,
+/// and the state of C++ functions before constructing any object.
+const TOP_LEVEL_3: u32 = 0xFFFF_FFFF;
+/// The enclosing level of outermost `__try` blocks in the scope tables of `__except_handler4`.
+const TOP_LEVEL_4: u32 = 0xFFFF_FFFE;
+
+/// An exception handler installed by a function in its frame, linked into the SEH chain at
+/// `fs:[0]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExceptionFrame {
+    /// The instruction linking the frame into the chain: a `mov fs:[0], esp`, or a call to a
+    /// prolog helper such as `__SEH_prolog4` or `__EH_prolog`.
+    pub install: u64,
+    /// The handler routine of the registration record, such as `__except_handler3` or the
+    /// `__ehhandler$` stub of a C++ function. Prolog helpers install it themselves, so it is
+    /// only known for some C++ frames there.
+    pub handler: Option<u64>,
+    pub table: ExceptionTable,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExceptionTable {
+    /// The `__try` blocks of a function handled by `__except_handler3` or `__except_handler4`.
+    Scope(ScopeTable),
+    /// The try blocks and destructors of a C++ function handled by `__CxxFrameHandler`.
+    Cxx(FuncInfo),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ScopeTableVersion {
+    /// The table of `__except_handler3`, up to Visual C++ 2003.
+    V3,
+    /// The table of `__except_handler4`, which starts with the frame offsets of the security
+    /// cookies checked before calling handlers. A cookie offset of -2 means there is none.
+    V4 {
+        gs_cookie_offset: i32,
+        gs_cookie_xor_offset: i32,
+        eh_cookie_offset: i32,
+        eh_cookie_xor_offset: i32,
+    },
+}
+
+/// The table describing the `__try` blocks of a function, indexed by the try level the
+/// function stores at `[ebp-4]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScopeTable {
+    pub address: u64,
+    pub version: ScopeTableVersion,
+    pub entries: Vec<ScopeEntry>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ScopeEntry {
+    /// The index of the entry of the enclosing `__try` block, if there is one.
+    pub enclosing: Option<u32>,
+    /// The filter expression of an `__except` block, or `None` for a `__finally` block.
+    pub filter: Option<u64>,
+    /// The `__except` block, or the `__finally` block.
+    pub handler: u64,
+}
+
+impl ScopeEntry {
+    pub fn is_finally(&self) -> bool {
+        self.filter.is_none()
+    }
+}
+
+/// The exception handling data of a C++ function, which tracks the objects to destroy and the
+/// try block it is in as a state number stored at `[ebp-4]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FuncInfo {
+    pub address: u64,
+    pub magic: u32,
+    /// The destructor calls unwinding each state, indexed by state.
+    pub unwind_map: Vec<UnwindEntry>,
+    pub try_blocks: Vec<TryBlock>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UnwindEntry {
+    /// The state after unwinding, if any object remains to be destroyed.
+    pub to_state: Option<u32>,
+    /// The funclet destroying the object of the state, if there is one.
+    pub action: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TryBlock {
+    pub try_low: u32,
+    pub try_high: u32,
+    /// The highest state of the catch blocks.
+    pub catch_high: u32,
+    pub catches: Vec<CatchHandler>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CatchHandler {
+    /// The `HT_*` flags of the catch, such as `const` and reference.
+    pub adjectives: u32,
+    /// The type descriptor of the caught type, or `None` for `catch (...)`.
+    pub type_descriptor: Option<u64>,
+    /// The name of the caught type, if the type descriptor could be read.
+    pub type_name: Option<String>,
+    /// The frame offset the exception object is copied to, if it is named.
+    pub catch_object_offset: i32,
+    /// The funclet of the catch block, which returns the address to continue at.
+    pub handler: u64,
+}
+
+/// The role of a piece of code called by an exception handler.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FuncletKind {
+    /// The filter expression of an `__except` block, called during the search for a handler.
+    Filter,
+    /// An `__except` block, continued at with the frame of its function after unwinding.
+    Except,
+    /// A `__finally` block, called while unwinding.
+    Finally,
+    /// A C++ catch block, called with the frame of its function.
+    Catch,
+    /// A destructor call run while unwinding the state of a C++ function.
+    Unwind,
+}
+
+impl FuncletKind {
+    /// Whether the funclet is called and returns, rather than being part of the body of its
+    /// function.
+    pub fn is_called(self) -> bool {
+        self != FuncletKind::Except
+    }
+}
+
+/// How a frame is set up, as found before the instruction installing it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct FrameSetup {
+    pub install: u64,
+    /// The handler routine pushed for the registration record.
+    pub handler: Option<u64>,
+    /// The scope table pushed for `__except_handler3` or `__except_handler4`.
+    pub scope_table: Option<u64>,
+    /// The `__ehhandler$` stub of a C++ function, which loads its `FuncInfo`.
+    pub stub: Option<u64>,
+}
+
+impl FrameSetup {
+    /// Recognizes the 32-bit MSVC frame setups ending in the given instruction:
+    ///
+    /// ```text
+    /// push -1                         push -1                     push 10h
+    /// push offset scope_table         push offset __ehhandler     push offset scope_table
+    /// push offset __except_handler3   mov eax, fs:[0]             call __SEH_prolog4
+    /// mov eax, fs:[0]                 push eax
+    /// push eax                        mov fs:[0], esp             mov eax, offset __ehhandler
+    /// mov fs:[0], esp                                             call __EH_prolog
+    /// ```
+    pub fn find(image: &LoadedImage, disassembly: &Disassembly, install: u64) -> Option<Self> {
+        let decoded = &disassembly.instructions.get(&install)?.decoded;
+        let mut setup = Self {
+            install,
+            handler: None,
+            scope_table: None,
+            stub: None,
+        };
+
+        if decoded.meta.category == InstructionCategory::CALL {
+            let helper = disassembly
+                .flow
+                .edges(install)
+                .find(|(_, _, kind)| **kind == FlowKind::Call)
+                .map(|(_, helper, _)| helper)?;
+            if !installs_frame(image, helper) {
+                return None;
+            }
+            let previous = disassembly.fallthrough_predecessor(install)?;
+            if let Some((Register::EAX, stub)) = immediate_load(&previous.decoded) {
+                if is_code(image, stub) {
+                    setup.stub = Some(stub);
+                    return Some(setup);
+                }
+            }
+            let table = pushed_immediate(&previous.decoded)?;
+            let size = disassembly.fallthrough_predecessor(previous.address.address)?;
+            pushed_immediate(&size.decoded)?;
+            if !is_data(image, table) {
+                return None;
+            }
+            setup.scope_table = Some(table);
+            return Some(setup);
+        }
+
+        let operands = &decoded.operands[..2];
+        if decoded.mnemonic != Mnemonic::MOV
+            || !is_chain_head(&operands[0])
+            || operands[1].ty != OperandType::REGISTER
+        {
+            return None;
+        }
+        // The previous head of the chain is loaded right after pushing the handler
+        let mut address = install;
+        loop {
+            let instruction = disassembly.fallthrough_predecessor(address)?;
+            address = instruction.address.address;
+            let decoded = &instruction.decoded;
+            if decoded.mnemonic == Mnemonic::MOV && is_chain_head(&decoded.operands[1]) {
+                break;
+            }
+            if install - address > MAX_SETUP_LENGTH {
+                return None;
+            }
+        }
+        let mut pushed = Vec::new();
+        while pushed.len() < 2 {
+            let instruction = disassembly.fallthrough_predecessor(address)?;
+            address = instruction.address.address;
+            pushed.push(pushed_immediate(&instruction.decoded)?);
+        }
+
+        // Pushed last, the handler comes first, followed by the scope table or the initial
+        // state of a C++ function
+        let handler = pushed[0];
+        if !is_code(image, handler) {
+            return None;
+        }
+        setup.handler = Some(handler);
+        if is_data(image, pushed[1]) {
+            setup.scope_table = Some(pushed[1]);
+        } else {
+            setup.stub = Some(handler);
+        }
+        Some(setup)
+    }
+}
+
+fn is_data(image: &LoadedImage, address: u64) -> bool {
+    image.slice_at(address).is_some() && !is_code(image, address)
+}
+
+/// The value of a `push imm32`.
+fn pushed_immediate(decoded: &DecodedInstruction) -> Option<u64> {
+    let operand = &decoded.operands[0];
+    if decoded.mnemonic == Mnemonic::PUSH && operand.ty == OperandType::IMMEDIATE {
+        Some(operand.imm.value & 0xFFFF_FFFF)
+    } else {
+        None
+    }
+}
+
+/// Splits `mov reg, imm32` into the register and the value.
+fn immediate_load(decoded: &DecodedInstruction) -> Option<(Register, u64)> {
+    let operands = &decoded.operands[..2];
+    match (decoded.mnemonic, operands[0].ty, operands[1].ty) {
+        (Mnemonic::MOV, OperandType::REGISTER, OperandType::IMMEDIATE) => {
+            Some((operands[0].reg, operands[1].imm.value & 0xFFFF_FFFF))
+        }
+        _ => None,
+    }
+}
+
+/// Whether the code at the given address links a frame into the chain with `mov fs:[0], reg`
+/// before returning, as the prolog helpers do.
+fn installs_frame(image: &LoadedImage, helper: u64) -> bool {
+    straight_line_code(image, helper, MAX_HELPER_LENGTH)
+        .iter()
+        .any(|decoded| decoded.mnemonic == Mnemonic::MOV && is_chain_head(&decoded.operands[0]))
+}
+
+/// Decodes up to `limit` instructions at the given address, stopping after the first jump or
+/// return. Handler stubs and prolog helpers are decoded this way before they are known to be
+/// code worth disassembling.
+fn straight_line_code(image: &LoadedImage, address: u64, limit: usize) -> Vec<DecodedInstruction> {
+    let decoder = match image.architecture.decoder() {
+        Ok(decoder) => decoder,
+        Err(_) => return Vec::new(),
+    };
+    let mut code = Vec::new();
+    let mut address = address;
+    while code.len() < limit {
+        let decoded = match image
+            .bytes_at(address)
+            .and_then(|bytes| decoder.decode(bytes).ok().flatten())
+        {
+            Some(decoded) => decoded,
+            None => break,
+        };
+        address += decoded.length as u64;
+        code.push(decoded);
+        if matches!(
+            decoded.meta.category,
+            InstructionCategory::UNCOND_BR | InstructionCategory::RET
+        ) {
+            break;
+        }
+    }
+    code
+}
+
+/// Whether the operand is `fs:[0]`, the head of the chain of exception registration records.
+fn is_chain_head(operand: &DecodedOperand) -> bool {
+    let memory = &operand.mem;
+    operand.ty == OperandType::MEMORY
+        && memory.segment == Register::FS
+        && memory.base == Register::NONE
+        && memory.index == Register::NONE
+        && memory.disp.displacement == 0
+}
+
+impl ExceptionFrame {
+    /// Reads the exception handling data of a frame setup. The length of a scope table is not
+    /// stored anywhere, so it is taken from the highest try level the function enters.
+    pub(crate) fn recover(
+        image: &LoadedImage,
+        disassembly: &Disassembly,
+        setup: &FrameSetup,
+    ) -> Option<Self> {
+        let table = match (setup.scope_table, setup.stub) {
+            (Some(address), _) => {
+                let levels = highest_try_level(disassembly, setup.install)? + 1;
+                ExceptionTable::Scope(ScopeTable::read(image, address, levels)?)
+            }
+            (None, Some(stub)) => {
+                let address = stub_func_info(image, stub)?;
+                ExceptionTable::Cxx(FuncInfo::read(image, address)?)
+            }
+            (None, None) => return None,
+        };
+        Some(Self {
+            install: setup.install,
+            handler: setup.handler,
+            table,
+        })
+    }
+
+    /// The code the handler calls or continues at, in table order and without duplicates.
+    pub fn funclets(&self) -> Vec<(u64, FuncletKind)> {
+        let mut funclets = Vec::new();
+        match &self.table {
+            ExceptionTable::Scope(table) => {
+                for entry in &table.entries {
+                    match entry.filter {
+                        Some(filter) => {
+                            funclets.push((filter, FuncletKind::Filter));
+                            funclets.push((entry.handler, FuncletKind::Except));
+                        }
+                        None => funclets.push((entry.handler, FuncletKind::Finally)),
+                    }
+                }
+            }
+            ExceptionTable::Cxx(func_info) => {
+                for try_block in &func_info.try_blocks {
+                    for catch in &try_block.catches {
+                        funclets.push((catch.handler, FuncletKind::Catch));
+                    }
+                }
+                for entry in &func_info.unwind_map {
+                    if let Some(action) = entry.action {
+                        funclets.push((action, FuncletKind::Unwind));
+                    }
+                }
+            }
+        }
+
+        let mut seen = BTreeSet::new();
+        funclets.retain(|(address, _)| seen.insert(*address));
+        funclets
+    }
+}
+
+/// Finds the highest try level the function installing a frame stores at `[ebp-4]`, with
+/// `mov dword ptr [ebp-4], level` or `and dword ptr [ebp-4], 0` for the first.
+fn highest_try_level(disassembly: &Disassembly, install: u64) -> Option<u32> {
+    let mut highest = None;
+    let mut visited = BTreeSet::new();
+    let mut pending = vec![install];
+    while let Some(address) = pending.pop() {
+        let instruction = match disassembly.instructions.get(&address) {
+            Some(instruction) if visited.insert(address) => instruction,
+            _ => continue,
+        };
+        pending.extend(
+            disassembly
+                .flow
+                .edges(address)
+                .filter(|(_, _, kind)| **kind != FlowKind::Call)
+                .map(|(_, to, _)| to),
+        );
+
+        let decoded = &instruction.decoded;
+        let (target, value) = (&decoded.operands[0], &decoded.operands[1]);
+        let memory = &target.mem;
+        if target.ty != OperandType::MEMORY
+            || memory.base != Register::EBP
+            || memory.index != Register::NONE
+            || memory.disp.displacement != -4
+            || value.ty != OperandType::IMMEDIATE
+        {
+            continue;
+        }
+        let level = match (decoded.mnemonic, value.imm.value as i32) {
+            (Mnemonic::MOV, level) if level >= 0 => level as u32,
+            (Mnemonic::AND, 0) => 0,
+            _ => continue,
+        };
+        highest = highest.max(Some(level));
+    }
+    highest
+}
+
+/// Finds the `mov eax, offset FuncInfo` of an `__ehhandler$` stub, which then jumps to
+/// `__CxxFrameHandler`.
+fn stub_func_info(image: &LoadedImage, stub: u64) -> Option<u64> {
+    straight_line_code(image, stub, MAX_STUB_LENGTH)
+        .iter()
+        .filter_map(immediate_load)
+        .find(|&(register, value)| {
+            register == Register::EAX && FuncInfo::read(image, value).is_some()
+        })
+        .map(|(_, value)| value)
+}
+
+fn read_u32(image: &LoadedImage, address: u64) -> Option<u32> {
+    let bytes = image.bytes_at(address)?.get(..4)?;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Reads a code address, where zero stands for none.
+fn read_code(image: &LoadedImage, address: u64) -> Option<Option<u64>> {
+    match read_u32(image, address)? as u64 {
+        0 => Some(None),
+        target if is_code(image, target) => Some(Some(target)),
+        _ => None,
+    }
+}
+
+/// Reads a state or try level, where the top level values stand for none.
+fn read_level(image: &LoadedImage, address: u64, top_level: u32) -> Option<Option<u32>> {
+    match read_u32(image, address)? {
+        level if level == top_level => Some(None),
+        level => Some(Some(level)),
+    }
+}
+
+impl ScopeTable {
+    /// Reads a scope table with the given number of entries, telling the versions apart by the
+    /// top level marker of the first entry. Every entry must enclose only entries before it.
+    fn read(image: &LoadedImage, address: u64, length: u32) -> Option<Self> {
+        let (version, entries_address, top_level) = if read_u32(image, address)? == TOP_LEVEL_3 {
+            (ScopeTableVersion::V3, address, TOP_LEVEL_3)
+        } else if read_u32(image, address + 16)? == TOP_LEVEL_4 {
+            let version = ScopeTableVersion::V4 {
+                gs_cookie_offset: read_u32(image, address)? as i32,
+                gs_cookie_xor_offset: read_u32(image, address + 4)? as i32,
+                eh_cookie_offset: read_u32(image, address + 8)? as i32,
+                eh_cookie_xor_offset: read_u32(image, address + 12)? as i32,
+            };
+            (version, address + 16, TOP_LEVEL_4)
+        } else {
+            return None;
+        };
+
+        let mut entries = Vec::new();
+        for index in 0..length {
+            let entry = entries_address + index as u64 * 12;
+            let enclosing = read_level(image, entry, top_level)?;
+            if matches!(enclosing, Some(enclosing) if enclosing >= index) {
+                return None;
+            }
+            entries.push(ScopeEntry {
+                enclosing,
+                filter: read_code(image, entry + 4)?,
+                handler: read_code(image, entry + 8)??,
+            });
+        }
+        Some(Self {
+            address,
+            version,
+            entries,
+        })
+    }
+}
+
+impl FuncInfo {
+    /// Reads and validates a `FuncInfo`: the magic number, the number of states, the unwind map,
+    /// the number of try blocks and the try block map.
+    fn read(image: &LoadedImage, address: u64) -> Option<Self> {
+        // The top bits of the magic number are flags of later compilers
+        let magic = read_u32(image, address)? & 0x1FFF_FFFF;
+        if !FUNC_INFO_MAGIC.contains(&magic) {
+            return None;
+        }
+        let states = read_u32(image, address + 4)?;
+        let unwind_map_address = read_u32(image, address + 8)? as u64;
+        let try_block_count = read_u32(image, address + 12)?;
+        let try_block_map_address = read_u32(image, address + 16)? as u64;
+        if states > MAX_STATES || try_block_count > MAX_TRY_BLOCKS {
+            return None;
+        }
+
+        let mut unwind_map = Vec::new();
+        for state in 0..states {
+            let entry = unwind_map_address + state as u64 * 8;
+            let to_state = read_level(image, entry, TOP_LEVEL_3)?;
+            if matches!(to_state, Some(to_state) if to_state >= state) {
+                return None;
+            }
+            unwind_map.push(UnwindEntry {
+                to_state,
+                action: read_code(image, entry + 4)?,
+            });
+        }
+
+        let mut try_blocks = Vec::new();
+        for index in 0..try_block_count {
+            let entry = try_block_map_address + index as u64 * 20;
+            let try_block = TryBlock {
+                try_low: read_u32(image, entry)?,
+                try_high: read_u32(image, entry + 4)?,
+                catch_high: read_u32(image, entry + 8)?,
+                catches: Vec::new(),
+            };
+            if try_block.try_low > try_block.try_high
+                || try_block.try_high >= try_block.catch_high
+                || try_block.catch_high >= states
+            {
+                return None;
+            }
+            let catch_count = read_u32(image, entry + 12)?;
+            let handler_array = read_u32(image, entry + 16)? as u64;
+            if catch_count > MAX_CATCHES {
+                return None;
+            }
+            let catches = (0..catch_count)
+                .map(|catch| CatchHandler::read(image, handler_array + catch as u64 * 16))
+                .collect::<Option<Vec<_>>>()?;
+            try_blocks.push(TryBlock {
+                catches,
+                ..try_block
+            });
+        }
+
+        Some(Self {
+            address,
+            magic,
+            unwind_map,
+            try_blocks,
+        })
+    }
+}
+
+impl CatchHandler {
+    fn read(image: &LoadedImage, address: u64) -> Option<Self> {
+        let type_descriptor = match read_u32(image, address + 4)? as u64 {
+            0 => None,
+            type_descriptor => Some(type_descriptor),
+        };
+        Some(Self {
+            adjectives: read_u32(image, address)?,
+            type_descriptor,
+            type_name: type_descriptor.and_then(|address| caught_type_name(image, address)),
+            catch_object_offset: read_u32(image, address + 8)? as i32,
+            handler: read_code(image, address + 12)??,
+        })
+    }
+}
+
+/// Reads the decorated name of a type descriptor, which follows the `type_info` vtable and a
+/// reserved field, and decodes it.
+fn caught_type_name(image: &LoadedImage, type_descriptor: u64) -> Option<String> {
+    let bytes = image.bytes_at(type_descriptor + 8)?;
+    let length = bytes.iter().position(|&byte| byte == 0)?;
+    let name = &bytes[..length];
+    if !name.starts_with(b".") || !name.iter().all(|byte| byte.is_ascii_graphic()) {
+        return None;
+    }
+    Some(type_descriptor_name(&String::from_utf8_lossy(name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::disassembly::tests::disassemble_code;
+    use crate::analysis::loader::tests::test_image;
+
+    fn image(data: &[u32]) -> LoadedImage {
+        let bytes = data
+            .iter()
+            .flat_map(|value| value.to_le_bytes().to_vec())
+            .collect();
+        test_image(&[], bytes)
+    }
+
+    #[test]
+    fn reads_scope_tables() {
+        // A __try/__except enclosing a __try/__finally
+        let image = image(&[0xFFFF_FFFF, 0x0040_1100, 0x0040_1110, 0, 0, 0x0040_1200]);
+        let table = ScopeTable::read(&image, 0x0040_2000, 2).unwrap();
+        assert_eq!(table.version, ScopeTableVersion::V3);
+        assert_eq!(
+            table.entries,
+            vec![
+                ScopeEntry {
+                    enclosing: None,
+                    filter: Some(0x0040_1100),
+                    handler: 0x0040_1110,
+                },
+                ScopeEntry {
+                    enclosing: Some(0),
+                    filter: None,
+                    handler: 0x0040_1200,
+                },
+            ]
+        );
+        // An entry cannot enclose itself
+        assert_eq!(ScopeTable::read(&image, 0x0040_200C, 1), None);
+
+        let image = self::image(&[
+            0xFFFF_FFFE,
+            0,
+            0xFFFF_FFD4,
+            0,
+            0xFFFF_FFFE,
+            0x0040_1100,
+            0x0040_1110,
+        ]);
+        let table = ScopeTable::read(&image, 0x0040_2000, 1).unwrap();
+        assert_eq!(
+            table.version,
+            ScopeTableVersion::V4 {
+                gs_cookie_offset: -2,
+                gs_cookie_xor_offset: 0,
+                eh_cookie_offset: -44,
+                eh_cookie_xor_offset: 0,
+            }
+        );
+        assert_eq!(table.entries[0].handler, 0x0040_1110);
+    }
+
+    #[test]
+    fn reads_func_info() {
+        let image = image(&[
+            // FuncInfo at 0x402000
+            0x1993_0520,
+            3,
+            0x0040_201C,
+            1,
+            0x0040_2034,
+            0,
+            0,
+            // Unwind map at 0x40201C
+            0xFFFF_FFFF,
+            0x0040_1300,
+            0xFFFF_FFFF,
+            0,
+            0xFFFF_FFFF,
+            0,
+            // Try block map at 0x402034
+            1,
+            1,
+            2,
+            2,
+            0x0040_2048,
+            // Handler array at 0x402048
+            8,
+            0x0040_2068,
+            0xFFFF_FFEC,
+            0x0040_1400,
+            0x40,
+            0,
+            0,
+            0x0040_1500,
+            // Type descriptor of `int` at 0x402068
+            0x0040_1000,
+            0,
+            u32::from_le_bytes(*b".H\0\0"),
+        ]);
+        let func_info = FuncInfo::read(&image, 0x0040_2000).unwrap();
+        assert_eq!(func_info.unwind_map.len(), 3);
+        assert_eq!(func_info.try_blocks.len(), 1);
+        let catches = &func_info.try_blocks[0].catches;
+        assert_eq!(catches[0].type_name.as_deref(), Some("int"));
+        assert_eq!(catches[0].catch_object_offset, -20);
+        assert_eq!(catches[1].type_descriptor, None);
+
+        let frame = ExceptionFrame {
+            install: 0x0040_1000,
+            handler: None,
+            table: ExceptionTable::Cxx(func_info),
+        };
+        assert_eq!(
+            frame.funclets(),
+            vec![
+                (0x0040_1400, FuncletKind::Catch),
+                (0x0040_1500, FuncletKind::Catch),
+                (0x0040_1300, FuncletKind::Unwind),
+            ]
+        );
+        assert_eq!(FuncInfo::read(&image, 0x0040_2004), None);
+    }
+
+    /// Functions installing frames in each of the ways `FrameSetup` recognizes, their handlers
+    /// and prolog helpers.
+    const FRAMES: &[(u64, &[u8])] = &[
+        (
+            0x0040_1000,
+            &[
+                0x55, // push ebp
+                0x8B, 0xEC, // mov ebp, esp
+                0x6A, 0xFF, // push -1
+                0x68, 0x00, 0x30, 0x40, 0x00, // push 0x403000
+                0x68, 0x00, 0x11, 0x40, 0x00, // push 0x401100
+                0x64, 0xA1, 0x00, 0x00, 0x00, 0x00, // mov eax, fs:[0]
+                0x50, // push eax
+                0x64, 0x89, 0x25, 0x00, 0x00, 0x00, 0x00, // mov fs:[0], esp
+                0xC7, 0x45, 0xFC, 0x00, 0x00, 0x00, 0x00, // mov dword ptr [ebp-4], 0
+                0xC7, 0x45, 0xFC, 0x01, 0x00, 0x00, 0x00, // mov dword ptr [ebp-4], 1
+                0xC7, 0x45, 0xFC, 0xFF, 0xFF, 0xFF, 0xFF, // mov dword ptr [ebp-4], -1
+                0x8B, 0xE5, // mov esp, ebp
+                0x5D, // pop ebp
+                0xC3, // ret
+            ],
+        ),
+        (0x0040_1040, &[0xB8, 0x01, 0x00, 0x00, 0x00, 0xC3]), // filter
+        (0x0040_1050, &[0x8B, 0xE5, 0x5D, 0xC3]),             // __except
+        (0x0040_1060, &[0xC3]),                               // __finally
+        (0x0040_1100, &[0xC3]),                               // __except_handler3
+        (
+            0x0040_1200,
+            &[
+                0xB8, 0x00, 0x13, 0x40, 0x00, // mov eax, 0x401300
+                0xE8, 0xF6, 0x01, 0x00, 0x00, // call __EH_prolog
+                0xC7, 0x45, 0xFC, 0x00, 0x00, 0x00, 0x00, // mov dword ptr [ebp-4], 0
+                0xC3, // ret
+            ],
+        ),
+        (
+            0x0040_1300,
+            &[
+                0xB8, 0x20, 0x30, 0x40, 0x00, // mov eax, 0x403020
+                0xE9, 0xF6, 0x01, 0x00, 0x00, // jmp __CxxFrameHandler
+            ],
+        ),
+        (0x0040_1310, &[0xC3]), // unwind action
+        (
+            0x0040_1400,
+            &[
+                0x6A, 0xFF, // push -1
+                0x50, // push eax
+                0x64, 0xA1, 0x00, 0x00, 0x00, 0x00, // mov eax, fs:[0]
+                0x50, // push eax
+                0x64, 0x89, 0x25, 0x00, 0x00, 0x00, 0x00, // mov fs:[0], esp
+                0xC3, // ret
+            ],
+        ),
+        (0x0040_1500, &[0xC3]), // __CxxFrameHandler
+        (
+            0x0040_1600,
+            &[
+                0xB8, 0x00, 0x13, 0x40, 0x00, // mov eax, 0x401300
+                0xE8, 0x76, 0x00, 0x00, 0x00, // call 0x401680
+                0xC3, // ret
+            ],
+        ),
+        (0x0040_1680, &[0x33, 0xC0, 0xC3]), // xor eax, eax; ret
+        (
+            0x0040_1700,
+            &[
+                0x6A, 0x10, // push 10h
+                0x68, 0x50, 0x30, 0x40, 0x00, // push 0x403050
+                0xE8, 0xF4, 0x00, 0x00, 0x00, // call __SEH_prolog4
+                0xC7, 0x45, 0xFC, 0x00, 0x00, 0x00, 0x00, // mov dword ptr [ebp-4], 0
+                0xC7, 0x45, 0xFC, 0xFE, 0xFF, 0xFF, 0xFF, // mov dword ptr [ebp-4], -2
+                0xC3, // ret
+            ],
+        ),
+        (0x0040_1740, &[0xB8, 0x01, 0x00, 0x00, 0x00, 0xC3]), // filter
+        (0x0040_1750, &[0xC3]),                               // __except
+        (
+            0x0040_1800,
+            &[
+                0x68, 0x00, 0x11, 0x40, 0x00, // push 0x401100
+                0x64, 0xFF, 0x35, 0x00, 0x00, 0x00, 0x00, // push dword ptr fs:[0]
+                0x8D, 0x45, 0xF0, // lea eax, [ebp-10h]
+                0x64, 0xA3, 0x00, 0x00, 0x00, 0x00, // mov fs:[0], eax
+                0xC3, // ret
+            ],
+        ),
+        (
+            0x0040_1900,
+            &[
+                0x55, // push ebp
+                0x8B, 0xEC, // mov ebp, esp
+                0x6A, 0xFF, // push -1
+                0x68, 0x00, 0x30, 0x40, 0x00, // push 0x403000
+                0x68, 0x00, 0x1A, 0x40, 0x00, // push 0x401A00
+                0x64, 0xA1, 0x00, 0x00, 0x00, 0x00, // mov eax, fs:[0]
+                0x50, // push eax
+                0x64, 0x89, 0x25, 0x00, 0x00, 0x00, 0x00, // mov fs:[0], esp
+                0x8B, 0xE5, // mov esp, ebp
+                0x5D, // pop ebp
+                0xC3, // ret
+            ],
+        ),
+        (0x0040_1A00, &[0xC3]), // never entering a try level, this handler is not followed
+    ];
+
+    fn disassemble_frames() -> (LoadedImage, Disassembly) {
+        let words: &[u32] = &[
+            // Scope table at 0x403000
+            0xFFFF_FFFF,
+            0x0040_1040,
+            0x0040_1050,
+            0,
+            0,
+            0x0040_1060,
+            0,
+            0,
+            // FuncInfo at 0x403020, with its unwind map at 0x40303C
+            0x1993_0520,
+            1,
+            0x0040_303C,
+            0,
+            0,
+            0,
+            0,
+            0xFFFF_FFFF,
+            0x0040_1310,
+            0,
+            0,
+            0,
+            // Scope table of `__except_handler4` at 0x403050
+            0xFFFF_FFFE,
+            0,
+            0xFFFF_FFD4,
+            0,
+            0xFFFF_FFFE,
+            0x0040_1740,
+            0x0040_1750,
+        ];
+        let data = words
+            .iter()
+            .flat_map(|word| word.to_le_bytes().to_vec())
+            .collect();
+        let entries = [
+            0x0040_1000,
+            0x0040_1200,
+            0x0040_1600,
+            0x0040_1700,
+            0x0040_1900,
+        ];
+        disassemble_code(FRAMES, data, &entries)
+    }
+
+    #[test]
+    fn finds_frame_setups() {
+        let (image, disassembly) = disassemble_frames();
+        assert_eq!(
+            FrameSetup::find(&image, &disassembly, 0x0040_1016),
+            Some(FrameSetup {
+                install: 0x0040_1016,
+                handler: Some(0x0040_1100),
+                scope_table: Some(0x0040_3000),
+                stub: None,
+            })
+        );
+        assert_eq!(
+            FrameSetup::find(&image, &disassembly, 0x0040_1205),
+            Some(FrameSetup {
+                install: 0x0040_1205,
+                handler: None,
+                scope_table: None,
+                stub: Some(0x0040_1300),
+            })
+        );
+        assert_eq!(
+            FrameSetup::find(&image, &disassembly, 0x0040_1707),
+            Some(FrameSetup {
+                install: 0x0040_1707,
+                handler: None,
+                scope_table: Some(0x0040_3050),
+                stub: None,
+            })
+        );
+        // The callee does not link a frame into the chain
+        assert_eq!(FrameSetup::find(&image, &disassembly, 0x0040_1605), None);
+    }
+
+    #[test]
+    fn finds_highest_try_level() {
+        let (_, disassembly) = disassemble_frames();
+        assert_eq!(highest_try_level(&disassembly, 0x0040_1016), Some(1));
+        assert_eq!(highest_try_level(&disassembly, 0x0040_1707), Some(0));
+        assert_eq!(highest_try_level(&disassembly, 0x0040_1916), None);
+    }
+
+    #[test]
+    fn recovers_exception_frames() {
+        let (_, disassembly) = disassemble_frames();
+        assert_eq!(
+            disassembly.exception_frames.keys().collect::<Vec<_>>(),
+            vec![&0x0040_1016, &0x0040_1205, &0x0040_1707]
+        );
+        match &disassembly.exception_frames[&0x0040_1205].table {
+            ExceptionTable::Cxx(func_info) => assert_eq!(func_info.address, 0x0040_3020),
+            table => panic!("unexpected table {:?}", table),
+        }
+
+        // Handlers, called funclets and prolog helpers are functions of their own
+        for entry in &[
+            0x0040_1040,
+            0x0040_1060,
+            0x0040_1100,
+            0x0040_1300,
+            0x0040_1310,
+            0x0040_1400,
+            0x0040_1740,
+            0x0040_1800,
+        ] {
+            assert!(disassembly.functions.contains_key(entry), "{:X}", entry);
+        }
+        assert_eq!(
+            disassembly.flow.edge_weight(0x0040_1016, 0x0040_1050),
+            Some(&FlowKind::Exception)
+        );
+        // The frame at 0x401916 has no scope table length, so its handler is not followed
+        assert!(!disassembly.instructions.contains_key(&0x0040_1A00));
+    }
+}
@@ -0,0 +1,340 @@
+mod generator;
+pub use generator::*;
+
+use crate::analysis::{BytePattern, Disassembly, LoadedImage};
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+/// The number of leading bytes of a function that are matched as a pattern.
+pub const PATTERN_LENGTH: usize = 32;
+/// The maximum number of bytes after the pattern that are covered by the CRC.
+pub const MAX_CRC_LENGTH: usize = 255;
+
+/// Recognizes a statically linked library function by its code.
+///
+/// The leading bytes are compared with a pattern, in which the bytes the linker relocates are
+/// wildcards. The bytes following the pattern, up to the first relocated byte, are compared by
+/// their CRC. Functions with identical code are told apart by the names of the functions and
+/// variables they refer to.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionSignature {
+    /// The decorated name of the function, such as `_strlen` or `?Create@CWnd@@...`.
+    pub name: String,
+    /// The length of the function in bytes.
+    pub length: u32,
+    pub pattern: BytePattern,
+    pub crc_length: u8,
+    pub crc: u16,
+    pub references: Vec<SignatureReference>,
+}
+
+/// A relocated location in a function that refers to a named symbol.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignatureReference {
+    /// The offset of the relocated dword in the function.
+    pub offset: u32,
+    /// The decorated name of the symbol.
+    pub name: String,
+    pub kind: ReferenceKind,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReferenceKind {
+    /// The dword holds the address of the symbol.
+    Absolute,
+    /// The dword holds the displacement to the symbol from the end of the dword, as in direct
+    /// calls and jumps.
+    Relative,
+}
+
+/// The CRC-16 FLIRT signatures use (polynomial 0x8408, reflected, initial value and final XOR
+/// 0xFFFF).
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for &byte in data {
+        crc ^= u16::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x8408
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Strips the decorations that differ between a reference in an object file and the symbol
+/// it resolves to in an image: the `__imp_` prefix of import address table slots, the leading
+/// underscore of C names and the argument size suffix of `__stdcall` and `__fastcall` names.
+/// C++ names are left as they are.
+fn undecorated(name: &str) -> &str {
+    let name = name.strip_prefix("__imp_").unwrap_or(name);
+    if name.starts_with('?') {
+        return name;
+    }
+
+    let name = name
+        .strip_prefix('_')
+        .or_else(|| name.strip_prefix('@'))
+        .unwrap_or(name);
+    match name.rfind('@') {
+        Some(at) if at > 0 && name[at + 1..].chars().all(|c| c.is_ascii_digit()) => &name[..at],
+        _ => name,
+    }
+}
+
+/// The result of checking a reference of a signature against the image.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ReferenceCheck {
+    /// The referenced address has the expected name.
+    Confirmed,
+    /// The referenced address has a different name.
+    Contradicted,
+    /// The referenced address has no name yet.
+    Unknown,
+}
+
+impl FunctionSignature {
+    /// Whether the code matches the pattern and the CRC. References are not checked.
+    pub fn matches(&self, code: &[u8]) -> bool {
+        if code.len() < self.length as usize || !self.pattern.matches(code) {
+            return false;
+        }
+
+        let start = self.pattern.len();
+        match code.get(start..start + self.crc_length as usize) {
+            Some(tail) => crc16(tail) == self.crc,
+            None => false,
+        }
+    }
+
+    fn check_references(
+        &self,
+        address: u64,
+        code: &[u8],
+        names: &HashMap<u64, String>,
+    ) -> Vec<ReferenceCheck> {
+        self.references
+            .iter()
+            .map(|reference| {
+                let offset = reference.offset as usize;
+                let value = match code.get(offset..offset + 4) {
+                    Some(value) => u32::from_le_bytes(value.try_into().unwrap()),
+                    None => return ReferenceCheck::Contradicted,
+                };
+                let target = match reference.kind {
+                    ReferenceKind::Absolute => u64::from(value),
+                    ReferenceKind::Relative => {
+                        (address + offset as u64 + 4).wrapping_add(value as i32 as i64 as u64)
+                            & 0xFFFF_FFFF
+                    }
+                };
+
+                match names.get(&target) {
+                    Some(name) if undecorated(name) == undecorated(&reference.name) => {
+                        ReferenceCheck::Confirmed
+                    }
+                    Some(_) => ReferenceCheck::Contradicted,
+                    None => ReferenceCheck::Unknown,
+                }
+            })
+            .collect()
+    }
+}
+
+/// A function identified by a signature.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignatureMatch {
+    pub address: u64,
+    pub name: String,
+}
+
+/// A set of function signatures, such as for the C runtime library of a compiler version.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignatureLibrary {
+    pub name: String,
+    pub signatures: Vec<FunctionSignature>,
+}
+
+impl SignatureLibrary {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            signatures: Vec::new(),
+        }
+    }
+
+    /// Reads a signature file written by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, bincode::Error> {
+        bincode::deserialize(bytes)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, bincode::Error> {
+        bincode::serialize(self)
+    }
+
+    /// Names the unnamed functions of the disassembly that match a signature.
+    ///
+    /// If several signatures with different names match, the one whose references agree with
+    /// the names of the referenced symbols and functions wins. As naming a function can
+    /// resolve the references of another, matching repeats until no more functions are named.
+    pub fn apply(&self, image: &LoadedImage, disassembly: &mut Disassembly) -> Vec<SignatureMatch> {
+        let mut names: HashMap<u64, String> = image
+            .symbols
+            .iter()
+            .map(|symbol| (symbol.address, symbol.name.clone()))
+            .collect();
+        names.extend(disassembly.functions.values().filter_map(|function| {
+            function
+                .name
+                .as_ref()
+                .map(|name| (function.entry, name.clone()))
+        }));
+
+        let mut matches = Vec::new();
+        loop {
+            let unnamed: Vec<u64> = disassembly
+                .functions
+                .values()
+                .filter(|function| function.name.is_none())
+                .map(|function| function.entry)
+                .collect();
+
+            let mut named_any = false;
+            for entry in unnamed {
+                let name = match image
+                    .bytes_at(entry)
+                    .and_then(|code| self.identify(entry, code, &names))
+                {
+                    Some(name) => name,
+                    None => continue,
+                };
+
+                names.insert(entry, name.clone());
+                disassembly.functions.get_mut(&entry).unwrap().name = Some(name.clone());
+                matches.push(SignatureMatch {
+                    address: entry,
+                    name,
+                });
+                named_any = true;
+            }
+
+            if !named_any {
+                return matches;
+            }
+        }
+    }
+
+    /// Returns the name of the function at `address`, if the signatures that match its code and
+    /// agree best with the known names all have the same name.
+    fn identify(&self, address: u64, code: &[u8], names: &HashMap<u64, String>) -> Option<String> {
+        let mut best: Vec<&FunctionSignature> = Vec::new();
+        let mut best_confirmed = 0;
+        for signature in &self.signatures {
+            if !signature.matches(code) {
+                continue;
+            }
+
+            let checks = signature.check_references(address, code, names);
+            if checks.contains(&ReferenceCheck::Contradicted) {
+                continue;
+            }
+            let confirmed = checks
+                .iter()
+                .filter(|&&check| check == ReferenceCheck::Confirmed)
+                .count();
+            if best.is_empty() || confirmed > best_confirmed {
+                best = vec![signature];
+                best_confirmed = confirmed;
+            } else if confirmed == best_confirmed {
+                best.push(signature);
+            }
+        }
+
+        let name = &best.first()?.name;
+        if best.iter().all(|signature| signature.name == *name) {
+            Some(name.clone())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::loader::tests::test_image;
+    use crate::analysis::{Function, Symbol, SymbolKind};
+
+    /// `jmp dword ptr [target]`, as in a thunk to an imported function.
+    fn thunk_signature(name: &str, target: &str) -> FunctionSignature {
+        FunctionSignature {
+            name: name.to_owned(),
+            length: 6,
+            pattern: BytePattern::parse("FF 25 ?? ?? ?? ??").unwrap(),
+            crc_length: 0,
+            crc: crc16(&[]),
+            references: vec![SignatureReference {
+                offset: 2,
+                name: target.to_owned(),
+                kind: ReferenceKind::Absolute,
+            }],
+        }
+    }
+
+    #[test]
+    fn references_tell_identical_code_apart() {
+        let mut code = vec![0xFF, 0x25];
+        code.extend_from_slice(&0x0040_2004u32.to_le_bytes());
+        let mut image = test_image(&code, Vec::new());
+        image.symbols = vec![Symbol {
+            address: 0x0040_2004,
+            name: "GetVersion".to_owned(),
+            kind: SymbolKind::Import {
+                module: "KERNEL32.dll".to_owned(),
+            },
+        }];
+        let mut disassembly = Disassembly::default();
+        disassembly.functions.insert(
+            0x0040_1000,
+            Function {
+                entry: 0x0040_1000,
+                ..Function::default()
+            },
+        );
+
+        let mut library = SignatureLibrary::new("thunks");
+        library.signatures = vec![
+            thunk_signature("_GetTickCount@0", "__imp__GetTickCount@0"),
+            thunk_signature("_GetVersion@0", "__imp__GetVersion@0"),
+        ];
+        let library = SignatureLibrary::from_bytes(&library.to_bytes().unwrap()).unwrap();
+        assert_eq!(
+            library.apply(&image, &mut disassembly),
+            vec![SignatureMatch {
+                address: 0x0040_1000,
+                name: "_GetVersion@0".to_owned(),
+            }]
+        );
+        assert_eq!(
+            disassembly.functions[&0x0040_1000].name.as_deref(),
+            Some("_GetVersion@0")
+        );
+    }
+
+    #[test]
+    fn crc16_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x906E);
+    }
+
+    #[test]
+    fn strips_decorations() {
+        assert_eq!(undecorated("__imp__GetVersionExA@4"), "GetVersionExA");
+        assert_eq!(undecorated("_strlen"), "strlen");
+        assert_eq!(undecorated("@fastcall@8"), "fastcall");
+        assert_eq!(undecorated("?Run@CWinApp@@UAEHXZ"), "?Run@CWinApp@@UAEHXZ");
+    }
+}
@@ -0,0 +1,253 @@
+use crate::analysis::{
+    crc16, BytePattern, FunctionSignature, ReferenceKind, SignatureLibrary, SignatureReference,
+    MAX_CRC_LENGTH, PATTERN_LENGTH,
+};
+use crate::parsers::coff::{
+    archive_members, COFFObject, ObjectSection, ARCHIVE_SIGNATURE, RELOCATION_I386_DIR32,
+    RELOCATION_I386_REL32, STORAGE_CLASS_EXTERNAL,
+};
+use crate::parsers::pe32::SectionCharacteristics;
+use crate::parsers::{BinParsable, ParseErrorReport};
+
+use nom::error::VerboseError;
+
+/// Functions with fewer bytes that are not relocated match too much unrelated code.
+const MIN_FIXED_BYTES: usize = 8;
+
+impl SignatureLibrary {
+    /// Adds the signatures of a COFF object file, or of all object files in a `.lib` archive.
+    /// Import library records are skipped.
+    pub fn add_file(&mut self, file: &[u8]) -> Result<(), ParseErrorReport> {
+        if !file.starts_with(ARCHIVE_SIGNATURE) {
+            return self.add_object_file(file);
+        }
+
+        let (_, members) = archive_members::<VerboseError<&[u8]>>(file)
+            .map_err(|error| ParseErrorReport::from_nom_error(file, &error))?;
+        for member in members.iter().filter(|member| !member.is_short_import()) {
+            self.add_object_file(member.data)?;
+        }
+        Ok(())
+    }
+
+    fn add_object_file(&mut self, file: &[u8]) -> Result<(), ParseErrorReport> {
+        let (_, object) = COFFObject::try_parse::<VerboseError<&[u8]>>(file)
+            .map_err(|error| ParseErrorReport::from_nom_error(file, &error))?;
+        self.add_object(&object);
+        Ok(())
+    }
+
+    /// Adds a signature for every public function in the code sections of the object.
+    pub fn add_object(&mut self, object: &COFFObject) {
+        for (index, section) in object.sections.iter().enumerate() {
+            if !section
+                .header
+                .characteristics
+                .contains(SectionCharacteristics::CNT_CODE)
+            {
+                continue;
+            }
+
+            for signature in section_signatures(object, index as i16 + 1, section) {
+                if !self.signatures.contains(&signature) {
+                    self.signatures.push(signature);
+                }
+            }
+        }
+    }
+}
+
+fn section_signatures(
+    object: &COFFObject,
+    section_number: i16,
+    section: &ObjectSection,
+) -> Vec<FunctionSignature> {
+    let defined = object
+        .symbols
+        .iter()
+        .filter(|symbol| symbol.section_number == section_number);
+    // Every function, public or not, ends where the next one starts
+    let mut boundaries: Vec<u32> = defined
+        .clone()
+        .filter(|symbol| symbol.is_function() || symbol.storage_class == STORAGE_CLASS_EXTERNAL)
+        .map(|symbol| symbol.value)
+        .collect();
+    boundaries.push(section.data.len() as u32);
+    boundaries.sort_unstable();
+
+    defined
+        .filter(|symbol| symbol.storage_class == STORAGE_CLASS_EXTERNAL)
+        .filter_map(|symbol| {
+            let start = symbol.value;
+            let end = *boundaries.iter().find(|&&boundary| boundary > start)?;
+            function_signature(object, section, &symbol.name, start, end)
+        })
+        .collect()
+}
+
+fn function_signature(
+    object: &COFFObject,
+    section: &ObjectSection,
+    name: &str,
+    start: u32,
+    end: u32,
+) -> Option<FunctionSignature> {
+    let code = section.data.get(start as usize..end as usize)?;
+    let mut bytes: Vec<Option<u8>> = code.iter().copied().map(Some).collect();
+    let mut references = Vec::new();
+    for relocation in &section.relocations {
+        let offset = match relocation.virtual_address.checked_sub(start) {
+            Some(offset) if offset < end - start => offset,
+            _ => continue,
+        };
+        let relocated = offset as usize..(offset as usize + relocation.size()).min(bytes.len());
+        for byte in &mut bytes[relocated] {
+            *byte = None;
+        }
+
+        let kind = match relocation.relocation_type {
+            RELOCATION_I386_DIR32 => ReferenceKind::Absolute,
+            RELOCATION_I386_REL32 => ReferenceKind::Relative,
+            _ => continue,
+        };
+        // References to section symbols and to local labels name nothing in the image
+        let symbol = match object.symbol(relocation.symbol_table_index) {
+            Some(symbol) if symbol.storage_class == STORAGE_CLASS_EXTERNAL => symbol,
+            _ => continue,
+        };
+        references.push(SignatureReference {
+            offset,
+            name: symbol.name.clone(),
+            kind,
+        });
+    }
+
+    let pattern_length = bytes.len().min(PATTERN_LENGTH);
+    let crc_length = bytes[pattern_length..]
+        .iter()
+        .take(MAX_CRC_LENGTH)
+        .take_while(|byte| byte.is_some())
+        .count();
+    let pattern = BytePattern::new(bytes[..pattern_length].to_vec());
+    if pattern.fixed_bytes() + crc_length < MIN_FIXED_BYTES {
+        return None;
+    }
+
+    Some(FunctionSignature {
+        name: name.to_owned(),
+        length: code.len() as u32,
+        pattern,
+        crc_length: crc_length as u8,
+        crc: crc16(&code[pattern_length..pattern_length + crc_length]),
+        references,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::loader::tests::test_image;
+    use crate::analysis::{Disassembly, Function, LoadedImage, SignatureMatch, Symbol, SymbolKind};
+    use crate::parsers::coff::tests::{object_file, OBJECT_CODE};
+
+    /// The code of the object file linked at 0x401000, with `_g_value` at 0x402000.
+    fn linked_image(g_value_name: &str) -> LoadedImage {
+        let mut code = OBJECT_CODE.to_vec();
+        code[0x0A..0x0E].copy_from_slice(&0x0040_2000u32.to_le_bytes());
+        // The call at 0x40100E returns to 0x401013, 2 bytes before `_helper_function`
+        code[0x0F..0x13].copy_from_slice(&2u32.to_le_bytes());
+
+        let mut image = test_image(&code, Vec::new());
+        image.symbols = vec![Symbol {
+            address: 0x0040_2000,
+            name: g_value_name.to_owned(),
+            kind: SymbolKind::Data,
+        }];
+        image
+    }
+
+    fn unnamed_functions() -> Disassembly {
+        let mut disassembly = Disassembly::default();
+        for &entry in &[0x0040_1000, 0x0040_1015] {
+            disassembly.functions.insert(
+                entry,
+                Function {
+                    entry,
+                    ..Function::default()
+                },
+            );
+        }
+        disassembly
+    }
+
+    #[test]
+    fn generates_signatures_from_object_files() {
+        let mut library = SignatureLibrary::new("test");
+        library.add_file(&object_file()).unwrap();
+
+        assert_eq!(library.signatures.len(), 2);
+        let add_one = &library.signatures[0];
+        assert_eq!(add_one.name, "_add_one");
+        assert_eq!(add_one.length, 0x15);
+        assert_eq!(
+            add_one.pattern.to_string(),
+            "55 8B EC 8B 45 08 83 C0 01 A3 ?? ?? ?? ?? E8 ?? ?? ?? ?? 5D C3"
+        );
+        assert_eq!(add_one.crc_length, 0);
+        assert_eq!(
+            add_one.references,
+            vec![
+                SignatureReference {
+                    offset: 0x0A,
+                    name: "_g_value".to_owned(),
+                    kind: ReferenceKind::Absolute,
+                },
+                SignatureReference {
+                    offset: 0x0F,
+                    name: "_helper_function".to_owned(),
+                    kind: ReferenceKind::Relative,
+                },
+            ]
+        );
+        let helper = &library.signatures[1];
+        assert_eq!(helper.name, "_helper_function");
+        assert_eq!(helper.length, 9);
+        assert!(helper.references.is_empty());
+
+        // Adding the same object again adds no duplicates
+        library.add_file(&object_file()).unwrap();
+        assert_eq!(library.signatures.len(), 2);
+    }
+
+    #[test]
+    fn matches_generated_signatures() {
+        let mut library = SignatureLibrary::new("test");
+        library.add_file(&object_file()).unwrap();
+
+        let mut disassembly = unnamed_functions();
+        assert_eq!(
+            library.apply(&linked_image("g_value"), &mut disassembly),
+            vec![
+                SignatureMatch {
+                    address: 0x0040_1000,
+                    name: "_add_one".to_owned(),
+                },
+                SignatureMatch {
+                    address: 0x0040_1015,
+                    name: "_helper_function".to_owned(),
+                },
+            ]
+        );
+
+        // A reference to a differently named variable rules the signature out
+        let mut disassembly = unnamed_functions();
+        assert_eq!(
+            library.apply(&linked_image("g_other"), &mut disassembly),
+            vec![SignatureMatch {
+                address: 0x0040_1015,
+                name: "_helper_function".to_owned(),
+            }]
+        );
+        assert_eq!(disassembly.functions[&0x0040_1000].name, None);
+    }
+}
@@ -0,0 +1,689 @@
+use crate::analysis::{
+    import_slot, Disassembly, FlowKind, Function, Instruction, LoadedImage, Prototype, TypeLibrary,
+    UnwindFunction,
+};
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use zydis::{Mnemonic, OperandAction, OperandType, OperandVisibility, Register};
+
+/// The names of the runtime functions that grow the stack by EAX bytes, probing every page.
+const STACK_PROBES: [&str; 2] = ["chkstk", "alloca_probe"];
+
+/// A local variable or argument of a function, identified by its offset on the stack.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StackVariable {
+    /// The offset from the stack pointer at the entry of the function, where it points to the
+    /// return address. Arguments start at 4, local variables are at negative offsets.
+    pub offset: i32,
+    /// The size of the largest access in bytes, or zero if the variable is only referred to by
+    /// address.
+    pub size: u32,
+    pub name: String,
+    pub type_name: Option<String>,
+    /// The instructions accessing the variable.
+    pub accesses: BTreeSet<u64>,
+}
+
+impl StackVariable {
+    pub fn new(offset: i32) -> Self {
+        Self {
+            offset,
+            size: 0,
+            name: StackVariable::default_name(offset),
+            type_name: None,
+            accesses: BTreeSet::new(),
+        }
+    }
+
+    /// `arg_0` for the first argument, `var_C` for the local variable 12 bytes below the return
+    /// address.
+    pub fn default_name(offset: i32) -> String {
+        if offset > 0 {
+            format!("arg_{:X}", offset - 4)
+        } else {
+            format!("var_{:X}", -i64::from(offset))
+        }
+    }
+
+    pub fn is_argument(&self) -> bool {
+        self.offset > 0
+    }
+}
+
+/// A name or type given to a stack variable by the user.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StackVariableOverride {
+    /// The entry of the function.
+    pub function: u64,
+    /// The offset of the variable, see `StackVariable::offset`.
+    pub offset: i32,
+    pub name: Option<String>,
+    pub type_name: Option<String>,
+}
+
+/// The stack usage of a 32-bit function. For x64 functions with unwind info, only the prolog
+/// the unwind info describes is known.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StackFrame {
+    /// The entry of the function.
+    pub function: u64,
+    /// The stack pointer before each instruction, relative to its value at the entry. Missing
+    /// for instructions where it is not known, such as after aligning the stack.
+    pub stack_pointer: BTreeMap<u64, i32>,
+    /// Where EBP points to, relative to the stack pointer at the entry, if the function uses
+    /// it as frame pointer. For x64 functions, where the frame register of the unwind info
+    /// points to.
+    pub frame_pointer: Option<i32>,
+    /// The number of bytes reserved for local variables by `sub esp`, `enter` or a stack probe,
+    /// or by the allocations of the unwind info.
+    pub frame_size: u32,
+    /// The variables of the function, keyed by offset.
+    pub variables: BTreeMap<i32, StackVariable>,
+    /// The returns at which the stack pointer differs from its value at the entry.
+    pub unbalanced_returns: Vec<u64>,
+}
+
+impl StackFrame {
+    /// Analyzes the stack frames of all functions of the disassembly.
+    ///
+    /// The bytes calls remove from the stack are taken from the `ret imm16` of called
+    /// functions, and from the prototypes of the library for imported functions.
+    pub fn analyze_all(
+        image: &LoadedImage,
+        disassembly: &Disassembly,
+        library: &TypeLibrary,
+    ) -> BTreeMap<u64, StackFrame> {
+        let analyzer = StackAnalyzer::new(image, disassembly, library);
+        disassembly
+            .functions
+            .values()
+            .map(|function| (function.entry, analyzer.analyze(function)))
+            .collect()
+    }
+
+    /// Whether the stack pointer is restored at every return whose stack pointer is known.
+    pub fn is_balanced(&self) -> bool {
+        self.unbalanced_returns.is_empty()
+    }
+
+    pub fn arguments(&self) -> impl Iterator<Item = &StackVariable> {
+        self.variables
+            .values()
+            .filter(|variable| variable.is_argument())
+    }
+
+    pub fn locals(&self) -> impl Iterator<Item = &StackVariable> {
+        self.variables
+            .values()
+            .filter(|variable| !variable.is_argument())
+    }
+
+    /// Renames and retypes a variable. Variables the analysis did not find are added.
+    pub fn apply_override(&mut self, stack_override: &StackVariableOverride) {
+        let variable = self
+            .variables
+            .entry(stack_override.offset)
+            .or_insert_with(|| StackVariable::new(stack_override.offset));
+        if let Some(name) = &stack_override.name {
+            variable.name = name.clone();
+        }
+        if let Some(type_name) = &stack_override.type_name {
+            variable.type_name = Some(type_name.clone());
+        }
+    }
+}
+
+/// The known values of the stack and frame pointers, relative to the stack pointer at the entry.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Pointers {
+    esp: Option<i32>,
+    ebp: Option<i32>,
+}
+
+struct StackAnalyzer<'a> {
+    disassembly: &'a Disassembly,
+    imports: HashMap<u64, &'a Prototype>,
+    /// The bytes each function removes from the stack with `ret imm16`.
+    cleanup: HashMap<u64, u32>,
+    unwind_functions: &'a BTreeMap<u64, UnwindFunction>,
+}
+
+impl<'a> StackAnalyzer<'a> {
+    fn new(image: &'a LoadedImage, disassembly: &'a Disassembly, library: &'a TypeLibrary) -> Self {
+        let cleanup = disassembly
+            .functions
+            .values()
+            .filter_map(|function| {
+                function
+                    .instructions
+                    .iter()
+                    .filter_map(|address| returned_bytes(&disassembly.instructions[address]))
+                    .next()
+                    .map(|bytes| (function.entry, bytes))
+            })
+            .collect();
+
+        Self {
+            disassembly,
+            imports: library.import_prototypes(image),
+            cleanup,
+            unwind_functions: &image.unwind_functions,
+        }
+    }
+
+    fn analyze(&self, function: &Function) -> StackFrame {
+        let mut frame = StackFrame {
+            function: function.entry,
+            stack_pointer: BTreeMap::new(),
+            frame_pointer: None,
+            frame_size: 0,
+            variables: BTreeMap::new(),
+            unbalanced_returns: Vec::new(),
+        };
+
+        let mut states = HashMap::new();
+        let mut queue = VecDeque::new();
+        states.insert(
+            function.entry,
+            Pointers {
+                esp: Some(0),
+                ebp: None,
+            },
+        );
+        queue.push_back(function.entry);
+        while let Some(address) = queue.pop_front() {
+            let instruction = match self.disassembly.instructions.get(&address) {
+                Some(instruction) => instruction,
+                None => continue,
+            };
+            let state = states[&address];
+            if let Some(esp) = state.esp {
+                frame.stack_pointer.insert(address, esp);
+            }
+            self.collect_variables(instruction, state, &mut frame);
+            if instruction.decoded.mnemonic == Mnemonic::RET {
+                match state.esp {
+                    Some(0) | None => {}
+                    Some(_) => frame.unbalanced_returns.push(address),
+                }
+            }
+
+            let (next, allocated) = self.transfer(instruction, state);
+            frame.frame_size = frame.frame_size.max(allocated);
+            if frame.frame_pointer.is_none() && state.ebp.is_none() {
+                frame.frame_pointer = next.ebp;
+            }
+            // Handlers are entered with a stack pointer restored from the frame, not with the
+            // state at the installation of the exception frame
+            for (_, to, kind) in self.disassembly.flow.edges(address) {
+                if *kind != FlowKind::Call
+                    && *kind != FlowKind::Exception
+                    && function.instructions.contains(&to)
+                    && !states.contains_key(&to)
+                {
+                    states.insert(to, next);
+                    queue.push_back(to);
+                }
+            }
+        }
+
+        // The unwind info is authoritative for the prolog, which uses registers the tracking
+        // above does not follow
+        if let Some(unwind_function) = self.unwind_functions.get(&function.entry) {
+            let unwind_frame = &unwind_function.frame;
+            frame.frame_size = unwind_frame.allocation;
+            frame.frame_pointer = unwind_frame.frame_pointer.map(|(_, offset)| offset);
+            for &(offset, stack_pointer) in &unwind_frame.stack_pointer {
+                frame
+                    .stack_pointer
+                    .insert(function.entry + u64::from(offset), stack_pointer);
+            }
+        }
+
+        frame
+    }
+
+    /// Computes the stack and frame pointers after an instruction, and the bytes it reserves
+    /// for local variables.
+    fn transfer(&self, instruction: &Instruction, state: Pointers) -> (Pointers, u32) {
+        let decoded = &instruction.decoded;
+        let operands = &decoded.operands[..decoded.operand_count as usize];
+        let width = i32::from(decoded.operand_width / 8);
+        let is_register = |index: usize, register: Register| match operands.get(index) {
+            Some(operand) => operand.ty == OperandType::REGISTER && operand.reg == register,
+            None => false,
+        };
+        let immediate = match operands.get(1) {
+            Some(operand) if operand.ty == OperandType::IMMEDIATE => Some(operand.imm.value as i32),
+            _ => None,
+        };
+        let Pointers { esp, ebp } = state;
+        let mut allocated = 0;
+        let mut allocate = |bytes: i32| allocated = bytes.max(0) as u32;
+
+        let pointers = match decoded.mnemonic {
+            Mnemonic::PUSH | Mnemonic::PUSHFD | Mnemonic::PUSHF => Pointers {
+                esp: esp.map(|esp| esp - width),
+                ebp,
+            },
+            Mnemonic::PUSHAD | Mnemonic::PUSHA => Pointers {
+                esp: esp.map(|esp| esp - 8 * width),
+                ebp,
+            },
+            Mnemonic::POPAD | Mnemonic::POPA => Pointers {
+                esp: esp.map(|esp| esp + 8 * width),
+                ebp: None,
+            },
+            Mnemonic::POP | Mnemonic::POPFD | Mnemonic::POPF => Pointers {
+                esp: if is_register(0, Register::ESP) {
+                    None
+                } else {
+                    esp.map(|esp| esp + width)
+                },
+                ebp: if is_register(0, Register::EBP) {
+                    None
+                } else {
+                    ebp
+                },
+            },
+            Mnemonic::SUB | Mnemonic::ADD if is_register(0, Register::ESP) => {
+                let delta = match (decoded.mnemonic, immediate) {
+                    (Mnemonic::SUB, Some(bytes)) => Some(-bytes),
+                    (_, bytes) => bytes,
+                };
+                if let Some(delta) = delta {
+                    allocate(-delta);
+                }
+                Pointers {
+                    esp: esp.and_then(|esp| Some(esp + delta?)),
+                    ebp,
+                }
+            }
+            Mnemonic::MOV | Mnemonic::LEA
+                if is_register(0, Register::ESP) || is_register(0, Register::EBP) =>
+            {
+                let source = &operands[1];
+                let value = match source.ty {
+                    OperandType::REGISTER if decoded.mnemonic == Mnemonic::MOV => {
+                        match source.reg {
+                            Register::ESP => esp,
+                            Register::EBP => ebp,
+                            _ => None,
+                        }
+                    }
+                    OperandType::MEMORY
+                        if decoded.mnemonic == Mnemonic::LEA
+                            && source.mem.index == Register::NONE =>
+                    {
+                        let base = match source.mem.base {
+                            Register::ESP => esp,
+                            Register::EBP => ebp,
+                            _ => None,
+                        };
+                        base.map(|base| base + source.mem.disp.displacement as i32)
+                    }
+                    _ => None,
+                };
+                if is_register(0, Register::ESP) {
+                    if let (Some(esp), Some(value)) = (esp, value) {
+                        allocate(esp - value);
+                    }
+                    Pointers { esp: value, ebp }
+                } else {
+                    Pointers { esp, ebp: value }
+                }
+            }
+            Mnemonic::LEAVE => Pointers {
+                esp: ebp.map(|ebp| ebp + width),
+                ebp: None,
+            },
+            Mnemonic::ENTER => {
+                let bytes = match operands.first() {
+                    Some(operand) if operand.ty == OperandType::IMMEDIATE => {
+                        operand.imm.value as i32
+                    }
+                    _ => 0,
+                };
+                allocate(bytes);
+                let ebp = esp.map(|esp| esp - width);
+                Pointers {
+                    esp: ebp.map(|ebp| ebp - bytes),
+                    ebp,
+                }
+            }
+            Mnemonic::CALL => {
+                let address = instruction.address.address;
+                let delta = match self.stack_probe_size(address) {
+                    Some(bytes) => {
+                        allocate(bytes);
+                        -bytes
+                    }
+                    None => self.callee_cleanup(address) as i32,
+                };
+                Pointers {
+                    esp: esp.map(|esp| esp + delta),
+                    ebp,
+                }
+            }
+            _ => {
+                let writes = |register: Register| {
+                    operands.iter().any(|operand| {
+                        operand.visibility == OperandVisibility::EXPLICIT
+                            && operand.ty == OperandType::REGISTER
+                            && operand.reg == register
+                            && operand.action.intersects(OperandAction::MASK_WRITE)
+                    })
+                };
+                Pointers {
+                    esp: if writes(Register::ESP) { None } else { esp },
+                    ebp: if writes(Register::EBP) { None } else { ebp },
+                }
+            }
+        };
+        (pointers, allocated)
+    }
+
+    /// Records the stack variables the memory operands of an instruction refer to.
+    fn collect_variables(
+        &self,
+        instruction: &Instruction,
+        state: Pointers,
+        frame: &mut StackFrame,
+    ) {
+        let decoded = &instruction.decoded;
+        for operand in &decoded.operands[..decoded.operand_count as usize] {
+            if operand.visibility != OperandVisibility::EXPLICIT
+                || operand.ty != OperandType::MEMORY
+                || operand.mem.index != Register::NONE
+            {
+                continue;
+            }
+            let base = match operand.mem.base {
+                Register::ESP => state.esp,
+                Register::EBP => state.ebp,
+                _ => None,
+            };
+            let offset = match base {
+                Some(base) => base + operand.mem.disp.displacement as i32,
+                None => continue,
+            };
+            // The return address is not a variable
+            if (0..4).contains(&offset) {
+                continue;
+            }
+
+            let variable = frame
+                .variables
+                .entry(offset)
+                .or_insert_with(|| StackVariable::new(offset));
+            variable.accesses.insert(instruction.address.address);
+            if decoded.mnemonic != Mnemonic::LEA {
+                variable.size = variable.size.max(u32::from(operand.size / 8));
+            }
+        }
+    }
+
+    /// The bytes the function called at `call` removes from the stack.
+    fn callee_cleanup(&self, call: u64) -> u32 {
+        if let Some(prototype) =
+            import_slot(self.disassembly, call).and_then(|slot| self.imports.get(&slot))
+        {
+            if !prototype.calling_convention.callee_cleans_stack() {
+                return 0;
+            }
+            let stack_parameters = prototype
+                .parameters
+                .len()
+                .saturating_sub(prototype.calling_convention.register_parameters());
+            return 4 * stack_parameters as u32;
+        }
+
+        match call_target(self.disassembly, call) {
+            Some(target) => self.cleanup.get(&target).copied().unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    /// If `call` calls a stack probe like `_chkstk`, returns the size it allocates, which is
+    /// loaded into EAX before the call.
+    fn stack_probe_size(&self, call: u64) -> Option<i32> {
+        let target = call_target(self.disassembly, call)?;
+        let name = self.disassembly.functions.get(&target)?.name.as_deref()?;
+        if !STACK_PROBES.contains(&name.trim_start_matches('_')) {
+            return None;
+        }
+
+        let load = &self.disassembly.fallthrough_predecessor(call)?.decoded;
+        let (destination, source) = (&load.operands[0], &load.operands[1]);
+        if load.mnemonic == Mnemonic::MOV
+            && destination.ty == OperandType::REGISTER
+            && destination.reg == Register::EAX
+            && source.ty == OperandType::IMMEDIATE
+        {
+            Some(source.imm.value as i32)
+        } else {
+            None
+        }
+    }
+}
+
+/// The target of a direct call.
+fn call_target(disassembly: &Disassembly, call: u64) -> Option<u64> {
+    disassembly
+        .flow
+        .edges(call)
+        .find(|(_, _, kind)| **kind == FlowKind::Call)
+        .map(|(_, target, _)| target)
+}
+
+/// The bytes a `ret imm16` removes from the stack besides the return address.
+fn returned_bytes(instruction: &Instruction) -> Option<u32> {
+    let decoded = &instruction.decoded;
+    if decoded.mnemonic != Mnemonic::RET {
+        return None;
+    }
+    match decoded.operands.first() {
+        Some(operand)
+            if operand.visibility == OperandVisibility::EXPLICIT
+                && operand.ty == OperandType::IMMEDIATE =>
+        {
+            Some(operand.imm.value as u32)
+        }
+        _ => Some(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::disassembly::tests::disassemble_functions;
+
+    /// The functions of `frames`. Calls through 0x402040 call `ExitProcess`.
+    const FUNCTIONS: &[(u64, &[u8])] = &[
+        // push ebp; mov ebp, esp; sub esp, 0x10; mov eax, [ebp + 8]; mov [ebp - 4], eax;
+        // push 2; push 1; call 0x401030; push 0; call [ExitProcess]; mov esp, ebp; pop ebp; ret
+        (
+            0x0040_1000,
+            &[
+                0x55, 0x8B, 0xEC, 0x83, 0xEC, 0x10, 0x8B, 0x45, 0x08, 0x89, 0x45, 0xFC, 0x6A, 0x02,
+                0x6A, 0x01, 0xE8, 0x1B, 0x00, 0x00, 0x00, 0x6A, 0x00, 0xFF, 0x15, 0x40, 0x20, 0x40,
+                0x00, 0x8B, 0xE5, 0x5D, 0xC3,
+            ],
+        ),
+        // enter 8, 0; mov eax, [ebp + 0xC]; leave; ret 8
+        (
+            0x0040_1030,
+            &[
+                0xC8, 0x08, 0x00, 0x00, 0x8B, 0x45, 0x0C, 0xC9, 0xC2, 0x08, 0x00,
+            ],
+        ),
+        // push ebp; mov ebp, esp; mov eax, 0x2000; call __chkstk; lea eax, [ebp - 0x2000];
+        // push eax; call 0x401070; add esp, 4; leave; ret
+        (
+            0x0040_1040,
+            &[
+                0x55, 0x8B, 0xEC, 0xB8, 0x00, 0x20, 0x00, 0x00, 0xE8, 0x13, 0x00, 0x00, 0x00, 0x8D,
+                0x85, 0x00, 0xE0, 0xFF, 0xFF, 0x50, 0xE8, 0x17, 0x00, 0x00, 0x00, 0x83, 0xC4, 0x04,
+                0xC9, 0xC3,
+            ],
+        ),
+        // __chkstk: ret
+        (0x0040_1060, &[0xC3]),
+        // mov eax, [esp + 4]; ret
+        (0x0040_1070, &[0x8B, 0x44, 0x24, 0x04, 0xC3]),
+        // push 0; ret
+        (0x0040_1080, &[0x6A, 0x00, 0xC3]),
+        // push ebp; mov ebp, esp; and esp, -8; mov esp, ebp; pop ebp; ret
+        (
+            0x0040_1090,
+            &[0x55, 0x8B, 0xEC, 0x83, 0xE4, 0xF8, 0x8B, 0xE5, 0x5D, 0xC3],
+        ),
+    ];
+
+    fn frames() -> BTreeMap<u64, StackFrame> {
+        let (image, mut disassembly) = disassemble_functions(FUNCTIONS);
+        disassembly.functions.get_mut(&0x0040_1060).unwrap().name = Some("__chkstk".to_owned());
+        StackFrame::analyze_all(&image, &disassembly, &TypeLibrary::bundled())
+    }
+
+    fn stack_pointers(frame: &StackFrame) -> Vec<(u64, i32)> {
+        frame
+            .stack_pointer
+            .iter()
+            .map(|(&address, &esp)| (address, esp))
+            .collect()
+    }
+
+    fn variables(frame: &StackFrame) -> Vec<(&str, u32)> {
+        frame
+            .variables
+            .values()
+            .map(|variable| (variable.name.as_str(), variable.size))
+            .collect()
+    }
+
+    #[test]
+    fn names_variables_by_offset() {
+        assert_eq!(StackVariable::default_name(4), "arg_0");
+        assert_eq!(StackVariable::default_name(0x10), "arg_C");
+        assert_eq!(StackVariable::default_name(-0x30), "var_30");
+
+        let mut frame = StackFrame {
+            function: 0x401000,
+            stack_pointer: BTreeMap::new(),
+            frame_pointer: Some(-4),
+            frame_size: 0x2C,
+            variables: BTreeMap::new(),
+            unbalanced_returns: Vec::new(),
+        };
+        frame.variables.insert(-0x30, StackVariable::new(-0x30));
+        frame.apply_override(&StackVariableOverride {
+            function: 0x401000,
+            offset: -0x30,
+            name: Some("fileHandle".to_owned()),
+            type_name: Some("HANDLE".to_owned()),
+        });
+        frame.apply_override(&StackVariableOverride {
+            function: 0x401000,
+            offset: 8,
+            name: None,
+            type_name: Some("int".to_owned()),
+        });
+
+        let locals: Vec<_> = frame
+            .locals()
+            .map(|v| (v.name.as_str(), v.type_name.as_deref()))
+            .collect();
+        assert_eq!(locals, vec![("fileHandle", Some("HANDLE"))]);
+        let arguments: Vec<_> = frame
+            .arguments()
+            .map(|v| (v.name.as_str(), v.type_name.as_deref()))
+            .collect();
+        assert_eq!(arguments, vec![("arg_4", Some("int"))]);
+    }
+
+    #[test]
+    fn tracks_frame_pointer_functions() {
+        let frames = frames();
+        assert_eq!(frames.len(), FUNCTIONS.len());
+
+        // The stdcall callee and ExitProcess remove their arguments
+        let frame = &frames[&0x0040_1000];
+        assert_eq!(
+            stack_pointers(frame),
+            vec![
+                (0x0040_1000, 0),
+                (0x0040_1001, -4),
+                (0x0040_1003, -4),
+                (0x0040_1006, -0x14),
+                (0x0040_1009, -0x14),
+                (0x0040_100C, -0x14),
+                (0x0040_100E, -0x18),
+                (0x0040_1010, -0x1C),
+                (0x0040_1015, -0x14),
+                (0x0040_1017, -0x18),
+                (0x0040_101D, -0x14),
+                (0x0040_101F, -4),
+                (0x0040_1020, 0),
+            ]
+        );
+        assert_eq!(frame.frame_pointer, Some(-4));
+        assert_eq!(frame.frame_size, 0x10);
+        assert_eq!(variables(frame), vec![("var_8", 4), ("arg_0", 4)]);
+        assert!(frame.is_balanced());
+
+        let frame = &frames[&0x0040_1030];
+        assert_eq!(
+            stack_pointers(frame),
+            vec![
+                (0x0040_1030, 0),
+                (0x0040_1034, -0xC),
+                (0x0040_1037, -0xC),
+                (0x0040_1038, 0),
+            ]
+        );
+        assert_eq!(frame.frame_pointer, Some(-4));
+        assert_eq!(frame.frame_size, 8);
+        assert_eq!(variables(frame), vec![("arg_4", 4)]);
+    }
+
+    #[test]
+    fn allocates_frames_with_stack_probes() {
+        let frames = frames();
+        let frame = &frames[&0x0040_1040];
+        assert_eq!(frame.frame_size, 0x2000);
+        assert_eq!(frame.stack_pointer[&0x0040_104D], -0x2004);
+        // The cdecl callee leaves its argument to the caller
+        assert_eq!(frame.stack_pointer[&0x0040_1059], -0x2008);
+        assert_eq!(frame.stack_pointer[&0x0040_105C], -0x2004);
+        assert_eq!(frame.stack_pointer[&0x0040_105D], 0);
+        // Only referred to by address
+        assert_eq!(variables(frame), vec![("var_2004", 0)]);
+        assert!(frame.is_balanced());
+
+        let callee = &frames[&0x0040_1070];
+        assert_eq!(callee.frame_pointer, None);
+        assert_eq!(variables(callee), vec![("arg_0", 4)]);
+    }
+
+    #[test]
+    fn reports_unbalanced_returns() {
+        let frames = frames();
+        assert_eq!(frames[&0x0040_1080].unbalanced_returns, vec![0x0040_1082]);
+
+        // Aligning the stack loses track of it until it is restored from EBP
+        let frame = &frames[&0x0040_1090];
+        assert_eq!(
+            stack_pointers(frame),
+            vec![
+                (0x0040_1090, 0),
+                (0x0040_1091, -4),
+                (0x0040_1093, -4),
+                (0x0040_1098, -4),
+                (0x0040_1099, 0),
+            ]
+        );
+        assert!(frame.is_balanced());
+    }
+}
@@ -0,0 +1,453 @@
+use crate::analysis::{DataKind, Disassembly, LoadedImage, MemorySection};
+
+use std::convert::TryInto;
+use zydis::{DecodedOperand, InstructionCategory, Mnemonic, OperandAction, OperandType, Register};
+
+/// How many instructions before an indirect jump are searched for the rest of a switch idiom.
+const MAX_IDIOM_DISTANCE: usize = 16;
+/// How many entries of a jump table are read at most when no bounds check was found.
+const MAX_UNBOUNDED_CASES: u64 = 256;
+
+/// A `switch` compiled to an indirect jump through a table of case addresses.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JumpTable {
+    /// The address of the indirect jump.
+    pub jump: u64,
+    /// The address of the table of case addresses.
+    pub table: u64,
+    /// The case addresses in table order. Cases sharing code appear more than once.
+    pub targets: Vec<u64>,
+    /// The byte table mapping switch values to indices into `targets`, emitted by MSVC for
+    /// sparse switches.
+    pub index_table: Option<IndexTable>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexTable {
+    pub address: u64,
+    pub indices: Vec<u8>,
+}
+
+impl JumpTable {
+    /// The address range of the jump table and, if there is one, of the index table.
+    pub fn data(&self) -> impl Iterator<Item = (u64, u64, DataKind)> + '_ {
+        std::iter::once((
+            self.table,
+            self.targets.len() as u64 * 4,
+            DataKind::JumpTable,
+        ))
+        .chain(self.index_table.iter().map(|index_table| {
+            (
+                index_table.address,
+                index_table.indices.len() as u64,
+                DataKind::JumpIndexTable,
+            )
+        }))
+    }
+
+    /// Recognizes the 32-bit switch idioms ending in the given indirect jump, and reads the
+    /// tables they use:
+    ///
+    /// ```text
+    /// cmp eax, 7                           cmp eax, 20
+    /// ja default                           ja default
+    /// jmp dword ptr [eax*4+table]          movzx eax, byte ptr [eax+index_table]
+    ///                                      jmp dword ptr [eax*4+table]
+    /// ```
+    ///
+    /// The index may also be loaded from a stack variable compared against the bound, the
+    /// index table may be read with `mov al, byte ptr [...]`, and the case address may be loaded
+    /// into a register that is jumped to.
+    pub fn recover(image: &LoadedImage, disassembly: &Disassembly, jump: u64) -> Option<Self> {
+        let idiom = SwitchIdiom::find(disassembly, jump)?;
+        Self::read(image, jump, &idiom)
+    }
+
+    fn read(image: &LoadedImage, jump: u64, idiom: &SwitchIdiom) -> Option<Self> {
+        let (index_table, cases, bounded) = match (idiom.index_table, idiom.cases) {
+            (Some(address), Some(count)) => {
+                let indices = image.bytes_at(address)?.get(..count as usize)?.to_vec();
+                let cases = *indices.iter().max()? as u64 + 1;
+                (Some(IndexTable { address, indices }), cases, true)
+            }
+            // Without a bound there is no telling how long the index table is
+            (Some(_), None) => return None,
+            (None, Some(count)) => (None, count, true),
+            (None, None) => (None, MAX_UNBOUNDED_CASES, false),
+        };
+
+        let entries = image.bytes_at(idiom.table)?;
+        let mut targets = Vec::new();
+        for index in 0..cases {
+            let target = entries
+                .get(index as usize * 4..)
+                .and_then(|bytes| bytes.get(..4))
+                .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()) as u64)
+                .filter(|&target| is_code(image, target));
+            match target {
+                Some(target) => targets.push(target),
+                // A bounded table with a bad entry was not a jump table to begin with
+                None if bounded => return None,
+                None => break,
+            }
+        }
+
+        if targets.is_empty() {
+            return None;
+        }
+        Some(Self {
+            jump,
+            table: idiom.table,
+            targets,
+            index_table,
+        })
+    }
+}
+
+pub(crate) fn is_code(image: &LoadedImage, address: u64) -> bool {
+    matches!(image.slice_at(address), Some((_, MemorySection::Code(_))))
+}
+
+/// The value a switch idiom indexes with, as it is tracked backwards from the jump.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum SwitchIndex {
+    Register(Register),
+    /// A stack variable or global, as `[base + displacement]`
+    Memory(Register, i64),
+}
+
+impl SwitchIndex {
+    fn matches(self, operand: &DecodedOperand) -> bool {
+        match (self, operand.ty) {
+            (SwitchIndex::Register(register), OperandType::REGISTER) => operand.reg == register,
+            (SwitchIndex::Memory(base, displacement), OperandType::MEMORY) => {
+                operand.mem.base == base
+                    && operand.mem.index == Register::NONE
+                    && operand.mem.disp.displacement == displacement
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The parts of a switch idiom found before an indirect jump.
+#[derive(Debug, PartialEq, Eq)]
+struct SwitchIdiom {
+    table: u64,
+    index_table: Option<u64>,
+    /// The number of switch values passing the bounds check, if there is one.
+    cases: Option<u64>,
+}
+
+impl SwitchIdiom {
+    fn find(disassembly: &Disassembly, jump: u64) -> Option<Self> {
+        let decoded = &disassembly.instructions.get(&jump)?.decoded;
+        let mut address = jump;
+        let (table, index) = match decoded.operands[0].ty {
+            // jmp dword ptr [eax*4+table]
+            OperandType::MEMORY => scaled_table(&decoded.operands[0])?,
+            // mov eax, dword ptr [eax*4+table]; jmp eax
+            OperandType::REGISTER => {
+                let register = decoded.operands[0].reg;
+                let mut load = None;
+                for _ in 0..MAX_IDIOM_DISTANCE {
+                    let instruction = disassembly.fallthrough_predecessor(address)?;
+                    address = instruction.address.address;
+                    let decoded = &instruction.decoded;
+                    if writes(
+                        &decoded.operands[..decoded.operand_count as usize],
+                        register,
+                    ) {
+                        if decoded.mnemonic == Mnemonic::MOV {
+                            load = scaled_table(&decoded.operands[1]);
+                        }
+                        break;
+                    }
+                }
+                load?
+            }
+            _ => return None,
+        };
+
+        let mut idiom = Self {
+            table,
+            index_table: None,
+            cases: None,
+        };
+        let mut index = SwitchIndex::Register(index);
+        // The conditional jump leaving the switch for values out of range, `ja` or `jae`
+        let mut bounds_check = None;
+        for _ in 0..MAX_IDIOM_DISTANCE {
+            let instruction = match disassembly.fallthrough_predecessor(address) {
+                Some(instruction) => instruction,
+                None => break,
+            };
+            address = instruction.address.address;
+            let decoded = &instruction.decoded;
+            let operands = &decoded.operands[..decoded.operand_count as usize];
+
+            if decoded.meta.category == InstructionCategory::COND_BR {
+                if bounds_check.is_none() {
+                    bounds_check = Some(decoded.mnemonic);
+                }
+                continue;
+            }
+            if decoded.mnemonic == Mnemonic::CMP
+                && index.matches(&operands[0])
+                && operands[1].ty == OperandType::IMMEDIATE
+            {
+                let bound = operands[1].imm.value & 0xFFFF_FFFF;
+                idiom.cases = match bounds_check {
+                    Some(Mnemonic::JNBE) => Some(bound + 1),
+                    Some(Mnemonic::JNB) => Some(bound),
+                    _ => None,
+                };
+                break;
+            }
+
+            let register = match index {
+                SwitchIndex::Register(register) => register,
+                SwitchIndex::Memory(..) => continue,
+            };
+            let loads_byte =
+                writes(operands, low_byte(register)) && decoded.mnemonic == Mnemonic::MOV;
+            if !writes(operands, register) && !loads_byte {
+                continue;
+            }
+            match (decoded.mnemonic, operands[1].ty) {
+                // movzx eax, byte ptr [eax+index_table]
+                (Mnemonic::MOVZX, OperandType::MEMORY) | (Mnemonic::MOV, OperandType::MEMORY)
+                    if operands[1].size == 8 && idiom.index_table.is_none() =>
+                {
+                    let (index_table, byte_index) = byte_table(&operands[1])?;
+                    idiom.index_table = Some(index_table);
+                    index = SwitchIndex::Register(byte_index);
+                }
+                // mov eax, dword ptr [ebp-8]
+                (Mnemonic::MOV, OperandType::MEMORY) if !loads_byte => {
+                    let memory = &operands[1].mem;
+                    if memory.index != Register::NONE || memory.base == Register::NONE {
+                        break;
+                    }
+                    index = SwitchIndex::Memory(memory.base, memory.disp.displacement);
+                }
+                _ => break,
+            }
+        }
+
+        Some(idiom)
+    }
+}
+
+/// Splits `[index*4+table]` into the table address and the index register.
+fn scaled_table(operand: &DecodedOperand) -> Option<(u64, Register)> {
+    let memory = &operand.mem;
+    if operand.ty == OperandType::MEMORY
+        && memory.base == Register::NONE
+        && memory.index != Register::NONE
+        && memory.scale == 4
+        && memory.disp.displacement != 0
+    {
+        Some((memory.disp.displacement as u64 & 0xFFFF_FFFF, memory.index))
+    } else {
+        None
+    }
+}
+
+/// Splits `[index+table]` into the table address and the index register.
+fn byte_table(operand: &DecodedOperand) -> Option<(u64, Register)> {
+    let memory = &operand.mem;
+    let index = match (memory.base, memory.index) {
+        (base, Register::NONE) => base,
+        (Register::NONE, index) if memory.scale <= 1 => index,
+        _ => return None,
+    };
+    if index == Register::NONE || memory.disp.displacement == 0 {
+        return None;
+    }
+    Some((memory.disp.displacement as u64 & 0xFFFF_FFFF, index))
+}
+
+fn writes(operands: &[DecodedOperand], register: Register) -> bool {
+    register != Register::NONE
+        && operands.iter().any(|operand| {
+            operand.ty == OperandType::REGISTER
+                && operand.reg == register
+                && operand.action.intersects(OperandAction::MASK_WRITE)
+        })
+}
+
+/// The lowest byte of a 32-bit general purpose register, if it has one.
+fn low_byte(register: Register) -> Register {
+    match register {
+        Register::EAX => Register::AL,
+        Register::ECX => Register::CL,
+        Register::EDX => Register::DL,
+        Register::EBX => Register::BL,
+        _ => Register::NONE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::disassembly::tests::disassemble_functions;
+    use crate::analysis::loader::tests::test_image;
+
+    /// The three switch idioms, each followed by its tables and cases.
+    const SWITCHES: &[(u64, &[u8])] = &[
+        (
+            0x0040_1000,
+            &[
+                0x8B, 0x44, 0x24, 0x04, // mov eax, dword ptr [esp+4]
+                0x83, 0xF8, 0x02, // cmp eax, 2
+                0x77, 0x1A, // ja 0x401023
+                0xFF, 0x24, 0x85, 0x10, 0x10, 0x40, 0x00, // jmp dword ptr [eax*4+0x401010]
+                0x20, 0x10, 0x40, 0x00, // 0x401020
+                0x21, 0x10, 0x40, 0x00, // 0x401021
+                0x22, 0x10, 0x40, 0x00, // 0x401022
+                0xCC, 0xCC, 0xCC, 0xCC, //
+                0xC3, 0xC3, 0xC3, // cases
+                0x33, 0xC0, 0xC3, // xor eax, eax; ret
+            ],
+        ),
+        (
+            0x0040_1040,
+            &[
+                0x8B, 0x44, 0x24, 0x04, // mov eax, dword ptr [esp+4]
+                0x83, 0xF8, 0x14, // cmp eax, 20
+                0x77, 0x4A, // ja 0x401093
+                0x0F, 0xB6, 0x80, 0x6C, 0x10, 0x40, 0x00, // movzx eax, byte [eax+0x40106C]
+                0xFF, 0x24, 0x85, 0x60, 0x10, 0x40, 0x00, // jmp dword ptr [eax*4+0x401060]
+                0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC, //
+                0x90, 0x10, 0x40, 0x00, // 0x401090
+                0x91, 0x10, 0x40, 0x00, // 0x401091
+                0x92, 0x10, 0x40, 0x00, // 0x401092
+                0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, // index table
+                0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC, //
+                0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC, //
+                0xC3, 0xC3, 0xC3, // cases
+                0x33, 0xC0, 0xC3, // xor eax, eax; ret
+            ],
+        ),
+        (
+            0x0040_10C0,
+            &[
+                0x55, // push ebp
+                0x8B, 0xEC, // mov ebp, esp
+                0x83, 0x7D, 0x08, 0x03, // cmp dword ptr [ebp+8], 3
+                0x73, 0x1D, // jae 0x4010E6
+                0x8B, 0x4D, 0x08, // mov ecx, dword ptr [ebp+8]
+                0xFF, 0x24, 0x8D, 0xD4, 0x10, 0x40, 0x00, // jmp dword ptr [ecx*4+0x4010D4]
+                0xCC, //
+                0xE0, 0x10, 0x40, 0x00, // 0x4010E0
+                0xE2, 0x10, 0x40, 0x00, // 0x4010E2
+                0xE4, 0x10, 0x40, 0x00, // 0x4010E4
+                0x5D, 0xC3, 0x5D, 0xC3, 0x5D, 0xC3, // cases
+                0x33, 0xC0, 0x5D, 0xC3, // xor eax, eax; pop ebp; ret
+            ],
+        ),
+    ];
+
+    #[test]
+    fn finds_bounded_tables() {
+        let (_, disassembly) = disassemble_functions(SWITCHES);
+        assert_eq!(
+            SwitchIdiom::find(&disassembly, 0x0040_1009),
+            Some(SwitchIdiom {
+                table: 0x0040_1010,
+                index_table: None,
+                cases: Some(3),
+            })
+        );
+        assert_eq!(
+            disassembly.jump_tables[&0x0040_1009].targets,
+            vec![0x0040_1020, 0x0040_1021, 0x0040_1022]
+        );
+    }
+
+    #[test]
+    fn finds_two_level_tables() {
+        let (_, disassembly) = disassemble_functions(SWITCHES);
+        assert_eq!(
+            SwitchIdiom::find(&disassembly, 0x0040_1050),
+            Some(SwitchIdiom {
+                table: 0x0040_1060,
+                index_table: Some(0x0040_106C),
+                cases: Some(21),
+            })
+        );
+        let table = &disassembly.jump_tables[&0x0040_1050];
+        assert_eq!(table.targets, vec![0x0040_1090, 0x0040_1091, 0x0040_1092]);
+        assert_eq!(table.index_table.as_ref().unwrap().indices.len(), 21);
+    }
+
+    #[test]
+    fn finds_stack_variable_indices() {
+        let (_, disassembly) = disassemble_functions(SWITCHES);
+        assert_eq!(
+            SwitchIdiom::find(&disassembly, 0x0040_10CC),
+            Some(SwitchIdiom {
+                table: 0x0040_10D4,
+                index_table: None,
+                cases: Some(3),
+            })
+        );
+        assert_eq!(
+            disassembly.jump_tables[&0x0040_10CC].targets,
+            vec![0x0040_10E0, 0x0040_10E2, 0x0040_10E4]
+        );
+    }
+
+    #[test]
+    fn reads_two_level_tables() {
+        let mut data = Vec::new();
+        for &target in &[0x0040_1100u32, 0x0040_1200, 0x0040_1300] {
+            data.extend_from_slice(&target.to_le_bytes());
+        }
+        data.extend_from_slice(&[0, 2, 2, 1, 2]);
+        let image = test_image(&[], data);
+
+        let idiom = SwitchIdiom {
+            table: 0x0040_2000,
+            index_table: Some(0x0040_200C),
+            cases: Some(5),
+        };
+        let table = JumpTable::read(&image, 0x0040_1000, &idiom).unwrap();
+        assert_eq!(table.targets, vec![0x0040_1100, 0x0040_1200, 0x0040_1300]);
+        assert_eq!(
+            table.data().collect::<Vec<_>>(),
+            vec![
+                (0x0040_2000, 12, DataKind::JumpTable),
+                (0x0040_200C, 5, DataKind::JumpIndexTable),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_tables_not_pointing_to_code() {
+        let mut data = Vec::new();
+        for &target in &[0x0040_1100u32, 0x0040_2000, 0] {
+            data.extend_from_slice(&target.to_le_bytes());
+        }
+        let image = test_image(&[], data);
+
+        let bounded = SwitchIdiom {
+            table: 0x0040_2000,
+            index_table: None,
+            cases: Some(3),
+        };
+        assert_eq!(JumpTable::read(&image, 0x0040_1000, &bounded), None);
+        // Without a bounds check, the table ends at the first entry not pointing to code
+        let unbounded = SwitchIdiom {
+            cases: None,
+            ..bounded
+        };
+        assert_eq!(
+            JumpTable::read(&image, 0x0040_1000, &unbounded)
+                .unwrap()
+                .targets,
+            vec![0x0040_1100]
+        );
+    }
+}
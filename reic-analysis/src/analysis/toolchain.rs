@@ -0,0 +1,551 @@
+use crate::analysis::{contains_bytes, BytePattern};
+use crate::parsers::pe32::{PE32Image, RichEntry, RichHeader};
+
+use std::fmt;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CompilerFamily {
+    Msvc,
+    BorlandCpp,
+    Delphi,
+    Watcom,
+    /// GCC, as packaged by MinGW or Cygwin.
+    Gcc,
+    /// A .NET assembly, which only contains a stub calling into `mscoree.dll`.
+    DotNet,
+}
+
+impl fmt::Display for CompilerFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CompilerFamily::Msvc => "MSVC",
+            CompilerFamily::BorlandCpp => "Borland C++",
+            CompilerFamily::Delphi => "Delphi",
+            CompilerFamily::Watcom => "Watcom",
+            CompilerFamily::Gcc => "GCC",
+            CompilerFamily::DotNet => ".NET",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Compiler {
+    pub family: CompilerFamily,
+    /// The product version, such as `6.0 SP3` for Visual C++.
+    pub version: Option<String>,
+}
+
+impl fmt::Display for Compiler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.version {
+            Some(version) => write!(f, "{} {}", self.family, version),
+            None => write!(f, "{}", self.family),
+        }
+    }
+}
+
+/// How a library is linked into the image.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Linkage {
+    Static,
+    /// Imported from the given DLL.
+    Dynamic(String),
+}
+
+/// A class library the image is built on, such as MFC.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Library {
+    pub name: String,
+    pub version: Option<String>,
+    pub linkage: Linkage,
+}
+
+impl fmt::Display for Library {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.linkage == Linkage::Static {
+            write!(f, "static ")?;
+        }
+        match &self.version {
+            Some(version) => write!(f, "{} {}", self.name, version),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// The tools and runtime libraries an image was most likely built with, which determine the
+/// signature library and the calling convention defaults to analyze it with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ToolchainReport {
+    /// The `major_linker_version` and `minor_linker_version` of the optional header.
+    pub linker_version: (u8, u8),
+    pub rich_header: Option<RichHeader>,
+    pub compiler: Option<Compiler>,
+    pub libraries: Vec<Library>,
+    /// How the C runtime library is linked, if the compiler is known.
+    pub runtime: Option<Linkage>,
+}
+
+impl fmt::Display for ToolchainReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.compiler {
+            Some(compiler) => write!(f, "{}", compiler)?,
+            None => write!(
+                f,
+                "unknown compiler, linker {}.{:02}",
+                self.linker_version.0, self.linker_version.1
+            )?,
+        }
+        for library in &self.libraries {
+            write!(f, ", {}", library)?;
+        }
+        match &self.runtime {
+            Some(Linkage::Static) => write!(f, ", static CRT"),
+            Some(Linkage::Dynamic(dll)) => write!(f, ", dynamic CRT ({})", dll),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Entry point byte patterns of the startup code of each compiler's runtime.
+const ENTRY_POINT_SIGNATURES: &[(CompilerFamily, &str)] = &[
+    // jmp short +0x10, "fb:C++HOOK"
+    (
+        CompilerFamily::BorlandCpp,
+        "EB 10 66 62 3A 43 2B 2B 48 4F 4F 4B",
+    ),
+    // push ebp; mov ebp, esp; add esp, -x; mov eax, InitTable; call @InitExe
+    (
+        CompilerFamily::Delphi,
+        "55 8B EC 83 C4 ?? B8 ?? ?? ?? ?? E8",
+    ),
+    // The SEH prologue of mainCRTStartup and WinMainCRTStartup of Visual C++ 5 and 6
+    (
+        CompilerFamily::Msvc,
+        "55 8B EC 6A FF 68 ?? ?? ?? ?? 68 ?? ?? ?? ?? 64 A1 00 00 00 00 50 64 89 25 00 00 00 00",
+    ),
+];
+
+/// Section names only a single toolchain uses.
+const SECTION_NAMES: &[(CompilerFamily, &str)] = &[
+    (CompilerFamily::Delphi, "CODE"),
+    (CompilerFamily::Delphi, "DATA"),
+    (CompilerFamily::Watcom, "AUTO"),
+    (CompilerFamily::Watcom, "DGROUP"),
+    (CompilerFamily::Gcc, ".CRT"),
+    (CompilerFamily::Gcc, ".eh_fram"),
+];
+
+/// Strings the runtime libraries leave in the section data.
+const MARKERS: &[(CompilerFamily, &[u8])] = &[
+    (CompilerFamily::Watcom, b"C/C++32 Run-Time system"),
+    (CompilerFamily::Delphi, b"SOFTWARE\\Borland\\Delphi\\RTL"),
+    (CompilerFamily::Gcc, b"Mingw runtime failure"),
+];
+
+/// The Visual C++ version of the compiler with the given Rich header product id.
+fn msvc_compiler_version(entry: &RichEntry) -> Option<String> {
+    let version = match entry.product_id {
+        0x000A | 0x000B | 0x0015 | 0x0016 => {
+            let service_pack = match entry.build {
+                8168 => "",
+                8447 => " SP3",
+                // SP6 did not update the compiler, so SP5 and SP6 cannot be told apart
+                8804 => " SP5/SP6",
+                _ => "",
+            };
+            return Some(format!("6.0{}", service_pack));
+        }
+        0x001C | 0x001D => "7.0",
+        0x005F | 0x0060 => "7.1",
+        0x006D | 0x006E => "8.0",
+        0x0083 | 0x0084 => "9.0",
+        0x00AA | 0x00AB => "10.0",
+        0x0104 | 0x0105 => "14.x",
+        _ => return None,
+    };
+    Some(version.to_owned())
+}
+
+/// The Visual C++ version that shipped the linker with the given version, for images
+/// without a Rich header.
+fn msvc_linker_version(major: u8, minor: u8) -> Option<String> {
+    let version = match (major, minor) {
+        (3, _) | (4, _) => "4.x",
+        (5, _) => "5.0",
+        (6, _) => "6.0",
+        (7, 10) => "7.1",
+        (7, _) => "7.0",
+        (14, _) => "14.x",
+        (major, _) if major >= 8 => return Some(format!("{}.0", major)),
+        _ => return None,
+    };
+    Some(version.to_owned())
+}
+
+/// The Borland C++ version that shipped the linker with the given version. ILINK32 of
+/// Borland C++ 5.5 and C++Builder writes 5.0, while TLINK32 writes the 2.25 of the Delphi
+/// linker.
+fn borland_linker_version(major: u8, _minor: u8) -> Option<String> {
+    match major {
+        5 => Some("5".to_owned()),
+        _ => None,
+    }
+}
+
+/// The version of an MFC DLL such as `MFC42.DLL` or `MFC42UD.DLL`.
+fn mfc_version(dll: &str) -> Option<String> {
+    let dll = dll.to_ascii_uppercase();
+    let digits: String = dll
+        .strip_prefix("MFC")?
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    match digits.len() {
+        2 | 3 => {
+            let (major, minor) = digits.split_at(digits.len() - 1);
+            Some(format!("{}.{}", major, minor))
+        }
+        _ => None,
+    }
+}
+
+/// Whether the DLL is a C runtime library of the given toolchain.
+fn is_runtime_dll(family: CompilerFamily, dll: &str) -> bool {
+    let dll = dll.to_ascii_lowercase();
+    match family {
+        CompilerFamily::Msvc => {
+            dll.starts_with("msvcr")
+                || dll.starts_with("vcruntime")
+                || dll.starts_with("api-ms-win-crt-")
+        }
+        CompilerFamily::BorlandCpp => dll.starts_with("cc32") || dll.starts_with("cw32"),
+        CompilerFamily::Delphi => dll.starts_with("rtl") && dll.ends_with(".bpl"),
+        CompilerFamily::Watcom => dll.starts_with("clib") || dll.starts_with("plib"),
+        CompilerFamily::Gcc => dll == "msvcrt.dll" || dll.starts_with("cygwin"),
+        CompilerFamily::DotNet => false,
+    }
+}
+
+impl ToolchainReport {
+    pub fn from_image(image: &PE32Image) -> Self {
+        let header = &image.coff_optional_header;
+        let linker_version = (header.major_linker_version, header.minor_linker_version);
+        let rich_header = image.rich_header();
+        let dlls: Vec<&str> = image
+            .imports
+            .iter()
+            .map(|module| module.name.as_str())
+            .collect();
+
+        let compiler = identify_compiler(image, &rich_header, &dlls);
+        let runtime = compiler.as_ref().and_then(|compiler| {
+            if compiler.family == CompilerFamily::DotNet {
+                return None;
+            }
+
+            let dll = dlls.iter().find(|dll| is_runtime_dll(compiler.family, dll));
+            Some(match dll {
+                Some(dll) => Linkage::Dynamic((*dll).to_owned()),
+                None => Linkage::Static,
+            })
+        });
+
+        let mut libraries = Vec::new();
+        let mfc = dlls
+            .iter()
+            .find_map(|dll| mfc_version(dll).map(|version| (dll, version)));
+        if let Some((dll, version)) = mfc {
+            libraries.push(Library {
+                name: "MFC".to_owned(),
+                version: Some(version),
+                linkage: Linkage::Dynamic((*dll).to_owned()),
+            });
+        } else if image
+            .sections
+            .iter()
+            .any(|section| contains_bytes(&section.data, b"CWinApp"))
+        {
+            libraries.push(Library {
+                name: "MFC".to_owned(),
+                version: None,
+                linkage: Linkage::Static,
+            });
+        }
+
+        let vcl = dlls
+            .iter()
+            .find(|dll| dll.to_ascii_lowercase().starts_with("vcl"));
+        if let Some(dll) = vcl {
+            libraries.push(Library {
+                name: "VCL".to_owned(),
+                version: None,
+                linkage: Linkage::Dynamic((*dll).to_owned()),
+            });
+        } else if image
+            .sections
+            .iter()
+            // The name of the resource VCL applications carry their license information in
+            .any(|section| contains_bytes(&section.data, b"D\0V\0C\0L\0A\0L\0"))
+        {
+            libraries.push(Library {
+                name: "VCL".to_owned(),
+                version: None,
+                linkage: Linkage::Static,
+            });
+        }
+
+        Self {
+            linker_version,
+            rich_header,
+            compiler,
+            libraries,
+            runtime,
+        }
+    }
+}
+
+fn identify_compiler(
+    image: &PE32Image,
+    rich_header: &Option<RichHeader>,
+    dlls: &[&str],
+) -> Option<Compiler> {
+    if dlls
+        .iter()
+        .any(|dll| dll.eq_ignore_ascii_case("mscoree.dll"))
+    {
+        return Some(Compiler {
+            family: CompilerFamily::DotNet,
+            version: None,
+        });
+    }
+
+    // Only Microsoft linkers write a Rich header. The tool that compiled the most object
+    // files is the compiler of the image, rather than of some static library.
+    if let Some(rich_header) = rich_header {
+        let compiler = rich_header
+            .entries
+            .iter()
+            .filter_map(|entry| msvc_compiler_version(entry).map(|version| (entry.count, version)))
+            .max_by_key(|(count, _)| *count);
+        if let Some((_, version)) = compiler {
+            return Some(Compiler {
+                family: CompilerFamily::Msvc,
+                version: Some(version),
+            });
+        }
+    }
+
+    let entry_point_data = image.entry_point_data().unwrap_or_default();
+    let family = ENTRY_POINT_SIGNATURES
+        .iter()
+        .find(|(_, pattern)| {
+            BytePattern::parse(pattern)
+                .unwrap()
+                .matches(entry_point_data)
+        })
+        .map(|(family, _)| *family)
+        .or_else(|| {
+            SECTION_NAMES
+                .iter()
+                .find(|(_, name)| {
+                    image
+                        .sections
+                        .iter()
+                        .any(|section| section.header.name == *name)
+                })
+                .map(|(family, _)| *family)
+        })
+        .or_else(|| {
+            dlls.iter().find_map(|dll| {
+                [CompilerFamily::BorlandCpp, CompilerFamily::Delphi]
+                    .iter()
+                    .find(|&&family| is_runtime_dll(family, dll))
+                    .copied()
+            })
+        })
+        .or_else(|| {
+            MARKERS
+                .iter()
+                .find(|(_, marker)| {
+                    image
+                        .sections
+                        .iter()
+                        .any(|section| contains_bytes(&section.data, marker))
+                })
+                .map(|(family, _)| *family)
+        });
+
+    let (major, minor) = (
+        image.coff_optional_header.major_linker_version,
+        image.coff_optional_header.minor_linker_version,
+    );
+    match family {
+        Some(CompilerFamily::Msvc) | None => {
+            msvc_linker_version(major, minor).map(|version| Compiler {
+                family: CompilerFamily::Msvc,
+                version: Some(version),
+            })
+        }
+        Some(CompilerFamily::BorlandCpp) => Some(Compiler {
+            family: CompilerFamily::BorlandCpp,
+            version: borland_linker_version(major, minor),
+        }),
+        Some(family) => Some(Compiler {
+            family,
+            version: None,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::pe32::tests::TestImage;
+    use crate::parsers::pe32::{DANS_MAGIC, RICH_MAGIC};
+    use crate::parsers::BinParsable;
+    use nom::error::VerboseError;
+
+    /// The SEH prologue of `WinMainCRTStartup` of Visual C++ 6.0.
+    const MSVC_ENTRY_POINT: &[u8] = &[
+        0x55, 0x8B, 0xEC, 0x6A, 0xFF, 0x68, 0x00, 0x20, 0x40, 0x00, 0x68, 0x00, 0x21, 0x40, 0x00,
+        0x64, 0xA1, 0x00, 0x00, 0x00, 0x00, 0x50, 0x64, 0x89, 0x25, 0x00, 0x00, 0x00, 0x00,
+    ];
+    /// The `jmp short` over the `fb:C++HOOK` marker of the Borland C++ startup code.
+    const BORLAND_ENTRY_POINT: &[u8] = b"\xEB\x10fb:C++HOOK\x90\x90\x90\x90\x90\x90";
+    /// `push ebp; mov ebp, esp; add esp, -0x10; mov eax, InitTable; call @InitExe`
+    const DELPHI_ENTRY_POINT: &[u8] = &[
+        0x55, 0x8B, 0xEC, 0x83, 0xC4, 0xF0, 0xB8, 0x00, 0x20, 0x40, 0x00, 0xE8, 0x00, 0x00, 0x00,
+        0x00,
+    ];
+
+    /// The test executable with the given code at the entry point, and the given DLLs in place
+    /// of KERNEL32.dll and COMCTL32.dll.
+    fn image(entry_point: &[u8], dlls: [&str; 2], rich_entries: &[(u32, u32)]) -> PE32Image {
+        let mut test_image = TestImage::executable();
+        test_image.sections[0].data = entry_point.to_vec();
+        if !rich_entries.is_empty() {
+            let key = 0x1234_5678;
+            let mut dwords = vec![DANS_MAGIC ^ key, key, key, key];
+            for (id, count) in rich_entries {
+                dwords.push(id ^ key);
+                dwords.push(count ^ key);
+            }
+            dwords.push(RICH_MAGIC);
+            dwords.push(key);
+            for dword in dwords {
+                test_image.dos_stub.extend_from_slice(&dword.to_le_bytes());
+            }
+        }
+
+        let file = test_image.build();
+        let (_, mut image) = PE32Image::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+        for (module, dll) in image.imports.iter_mut().zip(dlls.iter()) {
+            module.name = (*dll).to_owned();
+        }
+        image
+    }
+
+    const DEFAULT_DLLS: [&str; 2] = ["KERNEL32.dll", "COMCTL32.dll"];
+
+    fn compiler(image: &PE32Image) -> Option<String> {
+        ToolchainReport::from_image(image)
+            .compiler
+            .map(|compiler| compiler.to_string())
+    }
+
+    #[test]
+    fn prefers_the_rich_header() {
+        // The Delphi entry point is ignored, and the compiler of most object files wins over
+        // the Visual C++ 7.1 compiler of a static library. The linker entry is no compiler.
+        let msvc = image(
+            DELPHI_ENTRY_POINT,
+            DEFAULT_DLLS,
+            &[(0x000B_2264, 12), (0x005F_0C05, 2), (0x0004_2264, 30)],
+        );
+        assert_eq!(compiler(&msvc), Some("MSVC 6.0 SP5/SP6".to_owned()));
+
+        // Without a known compiler in the Rich header, the entry point decides
+        let delphi = image(DELPHI_ENTRY_POINT, DEFAULT_DLLS, &[(0x0004_2264, 30)]);
+        assert_eq!(compiler(&delphi), Some("Delphi".to_owned()));
+    }
+
+    #[test]
+    fn identifies_entry_point_signatures() {
+        let image_with = |entry_point| image(entry_point, DEFAULT_DLLS, &[]);
+        assert_eq!(
+            compiler(&image_with(MSVC_ENTRY_POINT)),
+            Some("MSVC 6.0".to_owned())
+        );
+        assert_eq!(
+            compiler(&image_with(DELPHI_ENTRY_POINT)),
+            Some("Delphi".to_owned())
+        );
+
+        let mut borland = image_with(BORLAND_ENTRY_POINT);
+        assert_eq!(compiler(&borland), Some("Borland C++".to_owned()));
+        borland.coff_optional_header.major_linker_version = 5;
+        assert_eq!(compiler(&borland), Some("Borland C++ 5".to_owned()));
+
+        // Unknown startup code falls back to the linker version
+        assert_eq!(compiler(&image_with(&[0xC3])), Some("MSVC 6.0".to_owned()));
+    }
+
+    #[test]
+    fn identifies_section_names() {
+        let mut renamed = image(&[0xC3], DEFAULT_DLLS, &[]);
+        renamed.sections[0].header.name = "CODE".to_owned();
+        assert_eq!(compiler(&renamed), Some("Delphi".to_owned()));
+        renamed.sections[0].header.name = ".CRT".to_owned();
+        assert_eq!(compiler(&renamed), Some("GCC".to_owned()));
+    }
+
+    #[test]
+    fn identifies_runtime_linkage() {
+        let report = ToolchainReport::from_image(&image(MSVC_ENTRY_POINT, DEFAULT_DLLS, &[]));
+        assert_eq!(report.runtime, Some(Linkage::Static));
+        assert_eq!(report.to_string(), "MSVC 6.0, static CRT");
+
+        let report =
+            ToolchainReport::from_image(&image(MSVC_ENTRY_POINT, ["MSVCRT.dll", "MFC42.DLL"], &[]));
+        assert_eq!(
+            report.runtime,
+            Some(Linkage::Dynamic("MSVCRT.dll".to_owned()))
+        );
+        assert_eq!(
+            report.to_string(),
+            "MSVC 6.0, MFC 4.2, dynamic CRT (MSVCRT.dll)"
+        );
+
+        let mut borland = image(BORLAND_ENTRY_POINT, ["CC3250MT.DLL", "VCL50.BPL"], &[]);
+        borland.coff_optional_header.major_linker_version = 5;
+        assert_eq!(
+            ToolchainReport::from_image(&borland).to_string(),
+            "Borland C++ 5, VCL, dynamic CRT (CC3250MT.DLL)"
+        );
+    }
+
+    #[test]
+    fn formats_the_report() {
+        let report = ToolchainReport {
+            linker_version: (6, 0),
+            rich_header: None,
+            compiler: Some(Compiler {
+                family: CompilerFamily::Msvc,
+                version: msvc_compiler_version(&RichEntry {
+                    product_id: 0x000B,
+                    build: 8804,
+                    count: 12,
+                }),
+            }),
+            libraries: vec![Library {
+                name: "MFC".to_owned(),
+                version: mfc_version("MFC42.DLL"),
+                linkage: Linkage::Dynamic("MFC42.DLL".to_owned()),
+            }],
+            runtime: Some(Linkage::Static),
+        };
+        assert_eq!(report.to_string(), "MSVC 6.0 SP5/SP6, MFC 4.2, static CRT");
+        assert_eq!(mfc_version("mfc100u.dll"), Some("10.0".to_owned()));
+    }
+}
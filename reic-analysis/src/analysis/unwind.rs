@@ -0,0 +1,199 @@
+use crate::parsers::pe32::{UnwindInfo, UnwindOperation, UnwindRegister};
+
+use std::ops::Range;
+use zydis::Register;
+
+/// An x64 function described by the exception directory, whose boundaries and prolog are known
+/// from its unwind info.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnwindFunction {
+    /// The linear address of the entry of the function.
+    pub entry: u64,
+    /// The address ranges of the function: its primary range, and the fragments whose unwind
+    /// info is chained to it, in address order.
+    pub ranges: Vec<Range<u64>>,
+    pub frame: UnwindFrame,
+    /// The language specific handler, such as `__C_specific_handler` or `__CxxFrameHandler3`.
+    pub handler: Option<u64>,
+    /// The linear address of the data passed to the handler.
+    pub handler_data: Option<u64>,
+}
+
+impl UnwindFunction {
+    pub fn contains(&self, address: u64) -> bool {
+        self.ranges.iter().any(|range| range.contains(&address))
+    }
+}
+
+/// The stack frame the prolog of an x64 function builds. Offsets are relative to the stack
+/// pointer at the entry, where it points to the return address, like `StackVariable::offset`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnwindFrame {
+    /// The size of the prolog in bytes.
+    pub prolog_size: u8,
+    /// The nonvolatile registers pushed by the prolog, in order of execution.
+    pub pushed_registers: Vec<Register>,
+    /// The number of bytes reserved for local variables and outgoing arguments.
+    pub allocation: u32,
+    /// The nonvolatile registers saved with `mov` or `movaps`, and where to.
+    pub saved_registers: Vec<(Register, i32)>,
+    /// The frame register and where it points to, if the function establishes one.
+    pub frame_pointer: Option<(Register, i32)>,
+    /// Whether the function is entered with a machine frame, and whether it has an error code.
+    /// Such functions are interrupt or exception handlers.
+    pub machine_frame: Option<bool>,
+    /// The stack pointer after each instruction of the prolog, keyed by the offset of the
+    /// instruction that follows it.
+    pub stack_pointer: Vec<(u8, i32)>,
+}
+
+impl UnwindFrame {
+    /// Replays the unwind codes in the order the prolog executes them.
+    pub fn from_info(info: &UnwindInfo) -> Self {
+        let mut frame = UnwindFrame {
+            prolog_size: info.size_of_prolog,
+            ..UnwindFrame::default()
+        };
+
+        let mut stack_pointer = 0i32;
+        // Save operations refer to the stack pointer at the end of the prolog, so they are
+        // resolved after the allocations are known
+        let mut saves = Vec::new();
+        for code in info.codes.iter().rev() {
+            match code.operation {
+                UnwindOperation::PushNonvolatile(register) => {
+                    frame.pushed_registers.push(general_register(register));
+                    stack_pointer -= 8;
+                }
+                UnwindOperation::AllocLarge(size) | UnwindOperation::AllocSmall(size) => {
+                    frame.allocation += size;
+                    stack_pointer -= size as i32;
+                }
+                UnwindOperation::SetFramePointer => {
+                    frame.frame_pointer = Some((
+                        general_register(info.frame_register),
+                        stack_pointer + i32::from(info.frame_offset) * 16,
+                    ));
+                }
+                UnwindOperation::SaveNonvolatile { register, offset } => {
+                    saves.push((general_register(register), offset));
+                }
+                UnwindOperation::SaveXmm128 { register, offset } => {
+                    saves.push((xmm_register(register), offset));
+                }
+                UnwindOperation::PushMachineFrame { error_code } => {
+                    frame.machine_frame = Some(error_code);
+                }
+                UnwindOperation::Epilog { .. } => continue,
+            }
+            frame.stack_pointer.push((code.code_offset, stack_pointer));
+        }
+        frame.saved_registers = saves
+            .into_iter()
+            .map(|(register, offset)| (register, stack_pointer + offset as i32))
+            .collect();
+        frame
+    }
+
+    /// The stack pointer after the prolog, relative to the entry.
+    pub fn final_stack_pointer(&self) -> i32 {
+        -(self.pushed_registers.len() as i32 * 8 + self.allocation as i32)
+    }
+}
+
+fn general_register(register: UnwindRegister) -> Register {
+    const REGISTERS: [Register; 16] = [
+        Register::RAX,
+        Register::RCX,
+        Register::RDX,
+        Register::RBX,
+        Register::RSP,
+        Register::RBP,
+        Register::RSI,
+        Register::RDI,
+        Register::R8,
+        Register::R9,
+        Register::R10,
+        Register::R11,
+        Register::R12,
+        Register::R13,
+        Register::R14,
+        Register::R15,
+    ];
+    REGISTERS[register as usize & 0x0F]
+}
+
+fn xmm_register(register: UnwindRegister) -> Register {
+    const REGISTERS: [Register; 16] = [
+        Register::XMM0,
+        Register::XMM1,
+        Register::XMM2,
+        Register::XMM3,
+        Register::XMM4,
+        Register::XMM5,
+        Register::XMM6,
+        Register::XMM7,
+        Register::XMM8,
+        Register::XMM9,
+        Register::XMM10,
+        Register::XMM11,
+        Register::XMM12,
+        Register::XMM13,
+        Register::XMM14,
+        Register::XMM15,
+    ];
+    REGISTERS[register as usize & 0x0F]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::pe32::{UnwindCode, UnwindFlags, UnwindTrailer};
+
+    #[test]
+    fn replays_prolog() {
+        // push rbp; push rsi; sub rsp, 0x90; lea rbp, [rsp+0x30]; movaps [rsp+0x40], xmm6
+        let code = |code_offset, operation| UnwindCode {
+            code_offset,
+            operation,
+        };
+        let info = UnwindInfo {
+            version: 1,
+            flags: UnwindFlags::empty(),
+            size_of_prolog: 0x13,
+            frame_register: 5,
+            frame_offset: 3,
+            codes: vec![
+                code(
+                    0x13,
+                    UnwindOperation::SaveXmm128 {
+                        register: 6,
+                        offset: 0x40,
+                    },
+                ),
+                code(0x0E, UnwindOperation::SetFramePointer),
+                code(0x09, UnwindOperation::AllocLarge(0x90)),
+                code(0x02, UnwindOperation::PushNonvolatile(6)),
+                code(0x01, UnwindOperation::PushNonvolatile(5)),
+            ],
+            trailer: UnwindTrailer::None,
+        };
+
+        let frame = UnwindFrame::from_info(&info);
+        assert_eq!(frame.pushed_registers, vec![Register::RBP, Register::RSI]);
+        assert_eq!(frame.allocation, 0x90);
+        assert_eq!(frame.frame_pointer, Some((Register::RBP, -0xA0 + 0x30)));
+        assert_eq!(frame.saved_registers, vec![(Register::XMM6, -0xA0 + 0x40)]);
+        assert_eq!(frame.final_stack_pointer(), -0xA0);
+        assert_eq!(
+            frame.stack_pointer,
+            vec![
+                (0x01, -8),
+                (0x02, -0x10),
+                (0x09, -0xA0),
+                (0x0E, -0xA0),
+                (0x13, -0xA0)
+            ]
+        );
+    }
+}
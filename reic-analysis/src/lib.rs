@@ -2,6 +2,17 @@
 
 mod analysis;
 mod parsers;
+pub mod patching;
+
+pub use analysis::{
+    demangle, load_pe_buffer, load_pe_file, Architecture, CallingConvention,
+    CallingConventionOverride, ClassHierarchy, CodeAddress, Demangled, DemangledKind, Disassembler,
+    Disassembly, FunctionSignature, Loadable, LoadedImage, RawImage, RawLayoutError, RawSegment,
+    RttiClass, SegmentPermissions, SignatureLibrary, SignatureMatch, StackVariableOverride,
+    TypeLibrary,
+};
+pub use parsers::pe32::{ImageLayout, PE32Image, ParseOptions};
+pub use parsers::{ParseErrorReport, ParseMode, ParseWarning, SharedBytes};
 
 #[cfg(test)]
 mod tests {
@@ -9,11 +20,8 @@ mod tests {
     use super::*;
     use crate::parsers::pe32::PE32Image;
     use crate::parsers::BinParsable;
-    use nom::error::{context, VerboseError, VerboseErrorKind};
-    use nom::Err::{Error, Failure};
-    use nom::Offset;
-    use nom::{HexDisplay, IResult};
-    use std::cmp::min;
+    use nom::error::{context, VerboseError};
+    use nom::IResult;
 
     use zydis::{AddressWidth, Decoder, Formatter, FormatterStyle, MachineMode, OutputBuffer};
 
@@ -52,7 +60,7 @@ mod tests {
                     .instruction_iterator(
                         &code_section.data
                             [(entry_rva - code_section.header.virtual_address) as usize..],
-                        (base_addr + entry_rva) as u64,
+                        base_addr + u64::from(entry_rva),
                     )
                     .take(10)
                 {
@@ -70,27 +78,8 @@ mod tests {
     }
 
     fn print_err(result: IResult<&[u8], PE32Image, VerboseError<&[u8]>>) {
-        match result {
-            Err(Error(e)) | Err(Failure(e)) => {
-                for (pos, error_kind) in &e.errors {
-                    match error_kind {
-                        VerboseErrorKind::Context(c) => {
-                            println!("context at 0x{:X}: {}", EXE.offset(pos), c)
-                        }
-                        VerboseErrorKind::Char(c) => {
-                            println!("char expected at 0x{:X}: {}", EXE.offset(pos), c)
-                        }
-                        VerboseErrorKind::Nom(n) => {
-                            println!("nom error at 0x{:X}: {:?}", EXE.offset(pos), n)
-                        }
-                    }
-                }
-                if let Some((pos, _)) = e.errors.first() {
-                    println!("data at error:\n{}", pos[0..min(128, pos.len())].to_hex(16));
-                }
-                panic!();
-            }
-            _ => (),
+        if let Err(e) = result {
+            panic!("{}", ParseErrorReport::from_nom_error(EXE, &e));
         }
     }
 }
@@ -1,7 +1,19 @@
 use nom::{error::ParseError, IResult};
 
+mod diagnostics;
+pub use diagnostics::*;
+
+mod error;
+pub use error::*;
+
+mod shared_bytes;
+pub use shared_bytes::*;
+
 pub mod coff;
+pub mod elf;
+pub mod le;
 pub mod mz;
+pub mod ne;
 pub mod pe32;
 
 pub trait BinParsable {
@@ -9,3 +21,8 @@ pub trait BinParsable {
     where
         Self: Sized;
 }
+
+/// The counterpart of `BinParsable`, serializing a structure in its on-disk format.
+pub trait BinWritable {
+    fn write_to(&self, out: &mut Vec<u8>);
+}
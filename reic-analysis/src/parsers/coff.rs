@@ -3,3 +3,9 @@ pub use coff_header::*;
 
 mod coff_image_optional_header;
 pub use coff_image_optional_header::*;
+
+mod archive;
+pub use archive::*;
+
+mod object;
+pub use object::*;
@@ -0,0 +1,146 @@
+use nom::{
+    bytes::complete::{tag, take},
+    combinator::map_res,
+    error::context,
+    error::{make_error, ErrorKind, ParseError},
+    sequence::tuple,
+    Err, IResult,
+};
+use std::str;
+
+pub const ARCHIVE_SIGNATURE: &[u8] = b"!<arch>\n";
+
+/// A member of a `.lib` archive: an object file, or a short import record of an import
+/// library.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ArchiveMember<'a> {
+    /// The member name, with long names resolved from the longnames member.
+    pub name: String,
+    pub data: &'a [u8],
+}
+
+impl ArchiveMember<'_> {
+    /// Whether the member is an import library record rather than an object file.
+    pub fn is_short_import(&self) -> bool {
+        self.data.starts_with(&[0, 0, 0xFF, 0xFF])
+    }
+}
+
+/// Parses the 60-byte member header, returning the name field and the size of the member.
+fn member_header<'a, E: ParseError<&'a [u8]>>(
+    i: &'a [u8],
+) -> IResult<&'a [u8], (&'a str, usize), E> {
+    let field = |length: usize| map_res(take(length), str::from_utf8);
+    let (i, (name, _date, _user_id, _group_id, _mode, size, _)) = context(
+        "Archive member header",
+        tuple((
+            field(16),
+            field(12),
+            field(6),
+            field(6),
+            field(8),
+            map_res(field(10), |size: &str| size.trim_end().parse::<usize>()),
+            tag(b"`\n"),
+        )),
+    )(i)?;
+    Ok((i, (name.trim_end(), size)))
+}
+
+/// Parses a `.lib` archive, skipping the linker members and the longnames member.
+pub fn archive_members<'a, E: ParseError<&'a [u8]>>(
+    archive: &'a [u8],
+) -> IResult<&'a [u8], Vec<ArchiveMember<'a>>, E> {
+    context("Archive", |archive: &'a [u8]| {
+        let (mut i, _) = tag(ARCHIVE_SIGNATURE)(archive)?;
+
+        let mut long_names: &[u8] = &[];
+        let mut members = Vec::new();
+        while !i.is_empty() {
+            let (rest, (name, size)) = member_header(i)?;
+            let (rest, data) = take(size)(rest)?;
+            // Members are aligned to two bytes
+            i = if size % 2 == 1 && !rest.is_empty() {
+                &rest[1..]
+            } else {
+                rest
+            };
+
+            let name = match name {
+                "/" => continue,
+                "//" => {
+                    long_names = data;
+                    continue;
+                }
+                _ => match name.strip_prefix('/') {
+                    Some(offset) => {
+                        let offset: usize = offset
+                            .parse()
+                            .map_err(|_| Err::Error(make_error(rest, ErrorKind::Digit)))?;
+                        let name = long_names
+                            .get(offset..)
+                            .ok_or_else(|| Err::Error(make_error(rest, ErrorKind::Eof)))?;
+                        let length = name
+                            .iter()
+                            .position(|&b| b == 0 || b == b'\n')
+                            .unwrap_or(name.len());
+                        String::from_utf8_lossy(&name[..length])
+                            .trim_end_matches('/')
+                            .to_owned()
+                    }
+                    None => name.trim_end_matches('/').to_owned(),
+                },
+            };
+            members.push(ArchiveMember { name, data });
+        }
+
+        Ok((i, members))
+    })(archive)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nom::error::VerboseError;
+
+    fn member(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut member = format!(
+            "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+            name,
+            0,
+            0,
+            0,
+            644,
+            data.len()
+        )
+        .into_bytes();
+        member.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            member.push(b'\n');
+        }
+        member
+    }
+
+    #[test]
+    fn resolves_long_names() {
+        let mut archive = ARCHIVE_SIGNATURE.to_vec();
+        archive.extend(member("/", &[0; 4]));
+        archive.extend(member("//", b"a_very_long_name.obj\0"));
+        archive.extend(member("/0", b"abc"));
+        archive.extend(member("short.obj/", b"de"));
+
+        let (_, members) = archive_members::<VerboseError<&[u8]>>(&archive).unwrap();
+        assert_eq!(
+            members,
+            vec![
+                ArchiveMember {
+                    name: "a_very_long_name.obj".to_owned(),
+                    data: b"abc",
+                },
+                ArchiveMember {
+                    name: "short.obj".to_owned(),
+                    data: b"de",
+                },
+            ]
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::parsers::BinParsable;
+use crate::parsers::{BinParsable, BinWritable};
 
 use nameof::name_of;
 
@@ -68,8 +68,20 @@ impl BinParsable for COFFHeader {
     }
 }
 
+impl BinWritable for COFFHeader {
+    fn write_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.machine_type as u16).to_le_bytes());
+        out.extend_from_slice(&self.number_of_sections.to_le_bytes());
+        out.extend_from_slice(&self.time_date_stamp.to_le_bytes());
+        out.extend_from_slice(&self.pointer_to_symbol_table.to_le_bytes());
+        out.extend_from_slice(&self.number_of_symbols.to_le_bytes());
+        out.extend_from_slice(&self.size_of_optional_header.to_le_bytes());
+        out.extend_from_slice(&self.characteristics.bits().to_le_bytes());
+    }
+}
+
 #[repr(u16)]
-#[derive(Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromPrimitive, ToPrimitive)]
 pub enum MachineType {
     /// The contents of this field are assumed to be applicable to any machine type
     Unknown = 0x0,
@@ -1,4 +1,4 @@
-use crate::parsers::BinParsable;
+use crate::parsers::{BinParsable, BinWritable};
 
 use nameof::name_of;
 use nom::combinator::{map, map_opt};
@@ -9,7 +9,7 @@ use nom::{error::ParseError, number::complete::le_u16, IResult};
 use num_derive::{FromPrimitive, ToPrimitive};
 use num_traits::FromPrimitive;
 
-#[derive(Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromPrimitive, ToPrimitive)]
 #[repr(u16)]
 pub enum COFFImageOptionalHeaderType {
     /// 0x107: ROM image
@@ -89,3 +89,16 @@ impl BinParsable for COFFImageStandardOptionalHeader {
         )(i)
     }
 }
+
+impl BinWritable for COFFImageStandardOptionalHeader {
+    fn write_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.magic as u16).to_le_bytes());
+        out.push(self.major_linker_version);
+        out.push(self.minor_linker_version);
+        out.extend_from_slice(&self.size_of_code.to_le_bytes());
+        out.extend_from_slice(&self.size_of_initialized_data.to_le_bytes());
+        out.extend_from_slice(&self.size_of_uninitialized_data.to_le_bytes());
+        out.extend_from_slice(&self.address_of_entry_point.to_le_bytes());
+        out.extend_from_slice(&self.base_of_code.to_le_bytes());
+    }
+}
@@ -0,0 +1,447 @@
+use crate::parsers::coff::{COFFHeader, MachineType};
+use crate::parsers::pe32::{SectionCharacteristics, SectionHeader};
+use crate::parsers::BinParsable;
+
+use nameof::name_of;
+use nom::{
+    bytes::complete::{take, take_till},
+    combinator::{map, verify},
+    error::context,
+    error::{make_error, ErrorKind, ParseError},
+    multi::count,
+    number::complete::{le_i16, le_u16, le_u32, le_u8},
+    sequence::tuple,
+    Err, IResult,
+};
+
+const SYMBOL_SIZE: usize = 18;
+const RELOCATION_SIZE: usize = 10;
+
+/// A COFF object file, as produced by Microsoft compilers and contained in `.lib` archives.
+#[derive(Debug, PartialEq, Eq)]
+pub struct COFFObject {
+    pub header: COFFHeader,
+    pub sections: Vec<ObjectSection>,
+    /// The symbol table. Auxiliary records are not included, so the position in this list
+    /// differs from the symbol table index relocations refer to, see `symbol`.
+    pub symbols: Vec<ObjectSymbol>,
+}
+
+impl COFFObject {
+    /// Returns the symbol with the given symbol table index.
+    pub fn symbol(&self, index: u32) -> Option<&ObjectSymbol> {
+        self.symbols
+            .binary_search_by_key(&index, |symbol| symbol.index)
+            .ok()
+            .map(|position| &self.symbols[position])
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ObjectSection {
+    /// The section header, with long names resolved from the string table.
+    pub header: SectionHeader,
+    pub data: Vec<u8>,
+    pub relocations: Vec<Relocation>,
+}
+
+/// A location in the section data the linker fills in with the address of a symbol.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Relocation {
+    /// The offset of the relocated bytes in the section data.
+    pub virtual_address: u32,
+    pub symbol_table_index: u32,
+    pub relocation_type: u16,
+}
+
+/// `IMAGE_REL_I386_DIR32`: the 32-bit virtual address of the symbol.
+pub const RELOCATION_I386_DIR32: u16 = 0x0006;
+/// `IMAGE_REL_I386_DIR32NB`: the 32-bit RVA of the symbol.
+pub const RELOCATION_I386_DIR32NB: u16 = 0x0007;
+/// `IMAGE_REL_I386_SECTION`: the 16-bit index of the section containing the symbol.
+pub const RELOCATION_I386_SECTION: u16 = 0x000A;
+/// `IMAGE_REL_I386_SECREL`: the 32-bit offset of the symbol within its section.
+pub const RELOCATION_I386_SECREL: u16 = 0x000B;
+/// `IMAGE_REL_I386_REL32`: the 32-bit displacement to the symbol, relative to the end of the
+/// relocated bytes.
+pub const RELOCATION_I386_REL32: u16 = 0x0014;
+
+impl Relocation {
+    /// The number of bytes the relocation overwrites.
+    pub fn size(&self) -> usize {
+        match self.relocation_type {
+            0 => 0,
+            RELOCATION_I386_SECTION => 2,
+            _ => 4,
+        }
+    }
+}
+
+impl BinParsable for Relocation {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(
+            name_of!(type Relocation),
+            map(tuple((le_u32, le_u32, le_u16)), |p| Self {
+                virtual_address: p.0,
+                symbol_table_index: p.1,
+                relocation_type: p.2,
+            }),
+        )(i)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectSymbol {
+    /// The index of the symbol in the symbol table, counting auxiliary records.
+    pub index: u32,
+    pub name: String,
+    /// For symbols defined in a section, the offset in the section.
+    pub value: u32,
+    /// The one-based index of the section the symbol is defined in. Zero for undefined
+    /// symbols, negative for absolute and debugging symbols.
+    pub section_number: i16,
+    /// `0x20` for functions.
+    pub symbol_type: u16,
+    pub storage_class: u8,
+    pub number_of_aux_symbols: u8,
+}
+
+/// `IMAGE_SYM_CLASS_EXTERNAL`: a public symbol, or an undefined one if the section number is
+/// zero.
+pub const STORAGE_CLASS_EXTERNAL: u8 = 2;
+/// `IMAGE_SYM_CLASS_STATIC`: a symbol private to the object, or a section name.
+pub const STORAGE_CLASS_STATIC: u8 = 3;
+/// `IMAGE_SYM_DTYPE_FUNCTION << 4`
+pub const SYMBOL_TYPE_FUNCTION: u16 = 0x20;
+
+impl ObjectSymbol {
+    pub fn is_function(&self) -> bool {
+        self.symbol_type & 0x30 == SYMBOL_TYPE_FUNCTION
+    }
+
+    /// Whether the symbol is defined in a section of this object.
+    pub fn is_defined(&self) -> bool {
+        self.section_number > 0
+    }
+}
+
+/// The raw name field of a symbol: either the name itself, or an offset into the string
+/// table.
+enum RawName<'a> {
+    Short(&'a [u8]),
+    Long(u32),
+}
+
+fn raw_name<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], RawName<'a>, E> {
+    let (rest, name) = take(8usize)(i)?;
+    if name[..4] == [0, 0, 0, 0] {
+        let (_, offset) = le_u32(&name[4..])?;
+        Ok((rest, RawName::Long(offset)))
+    } else {
+        Ok((rest, RawName::Short(name)))
+    }
+}
+
+fn symbol_record<'a, E: ParseError<&'a [u8]>>(
+    i: &'a [u8],
+) -> IResult<&'a [u8], (RawName<'a>, ObjectSymbol), E> {
+    context(
+        name_of!(type ObjectSymbol),
+        map(
+            tuple((raw_name, le_u32, le_i16, le_u16, le_u8, le_u8)),
+            |p| {
+                (
+                    p.0,
+                    ObjectSymbol {
+                        index: 0,
+                        name: String::new(),
+                        value: p.1,
+                        section_number: p.2,
+                        symbol_type: p.3,
+                        storage_class: p.4,
+                        number_of_aux_symbols: p.5,
+                    },
+                )
+            },
+        ),
+    )(i)
+}
+
+/// Reads a name from the string table, which starts with its own size.
+fn string_at<'a, E: ParseError<&'a [u8]>>(
+    strings: &'a [u8],
+    offset: u32,
+) -> Result<String, Err<E>> {
+    let (i, _) = take(offset as usize)(strings)?;
+    let (_, name) = take_till(|b| b == 0)(i)?;
+    Ok(String::from_utf8_lossy(name).into_owned())
+}
+
+fn short_name(name: &[u8]) -> String {
+    let length = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+    String::from_utf8_lossy(&name[..length]).into_owned()
+}
+
+impl BinParsable for COFFObject {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(object: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(name_of!(type COFFObject), |file: &'a [u8]| {
+            let eof = || Err::Error(make_error(file, ErrorKind::Eof));
+
+            let (i, header) = context(
+                "Check for x86 machine",
+                verify(COFFHeader::try_parse, |header| {
+                    header.machine_type == MachineType::I386
+                }),
+            )(file)?;
+            let (i, _) = take(header.size_of_optional_header)(i)?;
+            let (_, section_headers) = context(
+                "Section table",
+                count(SectionHeader::try_parse, header.number_of_sections as usize),
+            )(i)?;
+
+            let symbol_table_size = header.number_of_symbols as usize * SYMBOL_SIZE;
+            let symbol_table = file
+                .get(header.pointer_to_symbol_table as usize..)
+                .filter(|table| table.len() >= symbol_table_size)
+                .ok_or_else(eof)?;
+            let (strings, symbol_table) = take(symbol_table_size)(symbol_table)?;
+
+            let mut symbols = Vec::new();
+            let mut index = 0;
+            while index < header.number_of_symbols {
+                let record = &symbol_table[index as usize * SYMBOL_SIZE..];
+                let (_, (name, mut symbol)) = symbol_record(record)?;
+                symbol.index = index;
+                symbol.name = match name {
+                    RawName::Short(name) => short_name(name),
+                    RawName::Long(offset) => string_at(strings, offset)?,
+                };
+                index += 1 + symbol.number_of_aux_symbols as u32;
+                symbols.push(symbol);
+            }
+
+            let mut sections = Vec::new();
+            for mut section_header in section_headers {
+                // Long section names are written as `/<offset into the string table>`
+                let long_name = section_header
+                    .name
+                    .strip_prefix('/')
+                    .and_then(|offset| offset.parse().ok());
+                if let Some(offset) = long_name {
+                    section_header.name = string_at(strings, offset)?;
+                }
+
+                let data = if section_header
+                    .characteristics
+                    .contains(SectionCharacteristics::CNT_UNINITIALIZED_DATA)
+                {
+                    Vec::new()
+                } else {
+                    let start = section_header.pointer_to_raw_data as usize;
+                    file.get(start..start + section_header.size_of_raw_data as usize)
+                        .ok_or_else(eof)?
+                        .to_vec()
+                };
+
+                let (relocation_table, _) =
+                    take(section_header.pointer_to_relocations as usize)(file)?;
+                // With more than 0xFFFF relocations, the first one holds the actual count,
+                // including itself
+                let overflow = section_header
+                    .characteristics
+                    .contains(SectionCharacteristics::LNK_NRELOC_OVFL);
+                let relocation_count = if overflow {
+                    let (_, first) =
+                        context("Relocations", Relocation::try_parse)(relocation_table)?;
+                    first.virtual_address as usize
+                } else {
+                    section_header.number_of_relocations as usize
+                };
+                if relocation_table.len() / RELOCATION_SIZE < relocation_count {
+                    return Err(eof());
+                }
+                let (_, relocations) = context(
+                    "Relocations",
+                    count(Relocation::try_parse, relocation_count),
+                )(relocation_table)?;
+                let relocations = relocations
+                    .into_iter()
+                    .skip(if overflow { 1 } else { 0 })
+                    .collect();
+
+                sections.push(ObjectSection {
+                    header: section_header,
+                    data,
+                    relocations,
+                });
+            }
+
+            Ok((
+                &file[file.len()..],
+                Self {
+                    header,
+                    sections,
+                    symbols,
+                },
+            ))
+        })(object)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use nom::error::VerboseError;
+
+    /// The code of `object_file`, with the relocated dwords zeroed:
+    ///
+    /// - `_add_one` at 0: `push ebp; mov ebp, esp; mov eax, [ebp + 8]; add eax, 1;
+    ///   mov [_g_value], eax; call _helper_function; pop ebp; ret`
+    /// - `_helper_function` at 0x15: `push ebp; mov ebp, esp; xor eax, eax; inc eax; inc eax;
+    ///   pop ebp; ret`
+    pub(crate) const OBJECT_CODE: &[u8] = &[
+        0x55, 0x8B, 0xEC, 0x8B, 0x45, 0x08, 0x83, 0xC0, 0x01, 0xA3, 0x00, 0x00, 0x00, 0x00, 0xE8,
+        0x00, 0x00, 0x00, 0x00, 0x5D, 0xC3, 0x55, 0x8B, 0xEC, 0x33, 0xC0, 0x40, 0x40, 0x5D, 0xC3,
+    ];
+
+    /// An object file as Visual C++ writes it, with a long section name, an auxiliary record
+    /// for the section symbol, a long symbol name and an overflowed relocation count. The code
+    /// section holds `OBJECT_CODE`, which writes `_g_value` and calls `_helper_function`.
+    pub(crate) fn object_file() -> Vec<u8> {
+        let mut strings = vec![0u8; 4];
+        strings.extend_from_slice(b".text$mn_long\0_helper_function\0");
+        let strings_size = strings.len() as u32;
+        strings[..4].copy_from_slice(&strings_size.to_le_bytes());
+
+        let code_start = 20 + 40;
+        let relocations_start = code_start + OBJECT_CODE.len();
+        let symbols_start = relocations_start + 3 * RELOCATION_SIZE;
+
+        let mut file = Vec::new();
+        let put_u16 = |file: &mut Vec<u8>, value: u16| file.extend_from_slice(&value.to_le_bytes());
+        let put_u32 = |file: &mut Vec<u8>, value: u32| file.extend_from_slice(&value.to_le_bytes());
+        put_u16(&mut file, 0x014C);
+        put_u16(&mut file, 1); // number_of_sections
+        put_u32(&mut file, 0); // time_date_stamp
+        put_u32(&mut file, symbols_start as u32);
+        put_u32(&mut file, 5); // number_of_symbols, including the auxiliary record
+        put_u16(&mut file, 0); // size_of_optional_header
+        put_u16(&mut file, 0); // characteristics
+
+        file.extend_from_slice(b"/4\0\0\0\0\0\0");
+        put_u32(&mut file, 0); // virtual_size
+        put_u32(&mut file, 0); // virtual_address
+        put_u32(&mut file, OBJECT_CODE.len() as u32);
+        put_u32(&mut file, code_start as u32);
+        put_u32(&mut file, relocations_start as u32);
+        put_u32(&mut file, 0); // pointer_to_line_numbers
+        put_u16(&mut file, 0xFFFF);
+        put_u16(&mut file, 0); // number_of_line_numbers
+        let characteristics = SectionCharacteristics::CNT_CODE
+            | SectionCharacteristics::LNK_NRELOC_OVFL
+            | SectionCharacteristics::MEM_EXECUTE
+            | SectionCharacteristics::MEM_READ;
+        put_u32(&mut file, characteristics.bits());
+
+        file.extend_from_slice(OBJECT_CODE);
+
+        for &(virtual_address, symbol_table_index, relocation_type) in &[
+            (3, 0, 0),
+            (0x0A, 4, RELOCATION_I386_DIR32),
+            (0x0F, 3, RELOCATION_I386_REL32),
+        ] {
+            put_u32(&mut file, virtual_address);
+            put_u32(&mut file, symbol_table_index);
+            put_u16(&mut file, relocation_type);
+        }
+
+        let mut put_symbol = |name: &[u8],
+                              value: u32,
+                              section_number: i16,
+                              symbol_type: u16,
+                              storage_class: u8,
+                              aux: u8| {
+            file.extend_from_slice(name);
+            put_u32(&mut file, value);
+            file.extend_from_slice(&section_number.to_le_bytes());
+            put_u16(&mut file, symbol_type);
+            file.extend_from_slice(&[storage_class, aux]);
+        };
+        put_symbol(b".text$mn", 0, 1, 0, STORAGE_CLASS_STATIC, 1);
+        // The auxiliary section definition: length, relocation and line number counts
+        put_symbol(&[0x1E, 0, 0, 0, 0xFF, 0xFF, 0, 0], 0, 0, 0, 0, 0);
+        put_symbol(
+            b"_add_one",
+            0,
+            1,
+            SYMBOL_TYPE_FUNCTION,
+            STORAGE_CLASS_EXTERNAL,
+            0,
+        );
+        put_symbol(
+            b"\0\0\0\0\x12\0\0\0",
+            0x15,
+            1,
+            SYMBOL_TYPE_FUNCTION,
+            STORAGE_CLASS_EXTERNAL,
+            0,
+        );
+        put_symbol(b"_g_value", 0, 0, 0, STORAGE_CLASS_EXTERNAL, 0);
+
+        file.extend_from_slice(&strings);
+        file
+    }
+
+    #[test]
+    fn parses_object_file() {
+        let file = object_file();
+        let (_, object) = COFFObject::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+
+        assert_eq!(object.sections.len(), 1);
+        let section = &object.sections[0];
+        assert_eq!(section.header.name, ".text$mn_long");
+        assert_eq!(section.data, OBJECT_CODE);
+        assert_eq!(
+            section.relocations,
+            vec![
+                Relocation {
+                    virtual_address: 0x0A,
+                    symbol_table_index: 4,
+                    relocation_type: RELOCATION_I386_DIR32,
+                },
+                Relocation {
+                    virtual_address: 0x0F,
+                    symbol_table_index: 3,
+                    relocation_type: RELOCATION_I386_REL32,
+                },
+            ]
+        );
+
+        let names: Vec<(u32, &str)> = object
+            .symbols
+            .iter()
+            .map(|symbol| (symbol.index, symbol.name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                (0, ".text$mn"),
+                (2, "_add_one"),
+                (3, "_helper_function"),
+                (4, "_g_value"),
+            ]
+        );
+        assert_eq!(object.symbol(3).unwrap().value, 0x15);
+        assert!(object.symbol(1).is_none());
+        assert!(!object.symbol(4).unwrap().is_defined());
+    }
+
+    #[test]
+    fn rejects_overflowed_relocation_count_past_the_end() {
+        let mut file = object_file();
+        // The first relocation claims 0x10000 relocations
+        let relocations_start = 20 + 40 + OBJECT_CODE.len();
+        file[relocations_start..relocations_start + 4].copy_from_slice(&0x1_0000u32.to_le_bytes());
+        assert!(COFFObject::try_parse::<VerboseError<&[u8]>>(&file).is_err());
+    }
+}
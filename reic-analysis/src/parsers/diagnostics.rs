@@ -0,0 +1,214 @@
+use nom::{
+    error::{ErrorKind, ParseError},
+    Err,
+};
+use std::fmt;
+
+/// How anomalies in the parsed data are handled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Any anomaly fails the parse.
+    Strict,
+    /// Anomalies are recorded as warnings, and parsing continues with a best-effort result.
+    Lenient,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The data violates the specification, but can be used as-is.
+    Warning,
+    /// The data is inconsistent, so the parsed result is incomplete.
+    Error,
+}
+
+/// A rule of the file format that the parsed data violates.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ParseRule {
+    /// `file_alignment` is not a power of two between 512 and 64K.
+    FileAlignment { file_alignment: u32 },
+    /// `section_alignment` is less than `file_alignment`.
+    SectionAlignment {
+        section_alignment: u32,
+        file_alignment: u32,
+    },
+    /// The virtual address, raw data size or raw data pointer of a section is not aligned.
+    SectionHeaderAlignment { section: usize },
+    /// A section starts before the end of the previous section.
+    SectionOrder { section: usize },
+    /// The raw data of a section extends beyond the end of the file. Its data was truncated.
+    SectionDataOutOfBounds { section: usize },
+    /// The import directory could not be parsed. No imports were read.
+    ImportDirectory,
+    /// The export directory could not be parsed. No exports were read.
+    ExportDirectory,
+    /// The exception directory could not be parsed. No function table entries were read.
+    ExceptionDirectory,
+    /// The unwind info of an entry of the exception directory could not be parsed. The entry
+    /// was left out.
+    RuntimeFunction { entry: usize },
+    /// The base relocation directory could not be parsed. No relocations were read.
+    BaseRelocationDirectory,
+}
+
+impl fmt::Display for ParseRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ParseRule::FileAlignment { file_alignment } => write!(
+                f,
+                "file alignment {:#X} is not a power of two between 512 and 64K",
+                file_alignment
+            ),
+            ParseRule::SectionAlignment {
+                section_alignment,
+                file_alignment,
+            } => write!(
+                f,
+                "section alignment {:#X} is less than file alignment {:#X}",
+                section_alignment, file_alignment
+            ),
+            ParseRule::SectionHeaderAlignment { section } => {
+                write!(f, "section {} is not aligned", section)
+            }
+            ParseRule::SectionOrder { section } => write!(
+                f,
+                "section {} starts before the end of the previous section",
+                section
+            ),
+            ParseRule::SectionDataOutOfBounds { section } => write!(
+                f,
+                "raw data of section {} extends beyond the end of the file",
+                section
+            ),
+            ParseRule::ImportDirectory => write!(f, "import directory is malformed"),
+            ParseRule::ExportDirectory => write!(f, "export directory is malformed"),
+            ParseRule::ExceptionDirectory => write!(f, "exception directory is malformed"),
+            ParseRule::RuntimeFunction { entry } => {
+                write!(
+                    f,
+                    "unwind info of exception directory entry {} is malformed",
+                    entry
+                )
+            }
+            ParseRule::BaseRelocationDirectory => {
+                write!(f, "base relocation directory is malformed")
+            }
+        }
+    }
+}
+
+/// An anomaly found while parsing.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ParseWarning {
+    /// The file offset of the offending structure or field.
+    pub offset: usize,
+    pub rule: ParseRule,
+    pub severity: Severity,
+}
+
+/// Collects the warnings of a parse, depending on the parse mode.
+#[derive(Debug)]
+pub(crate) struct Diagnostics {
+    mode: ParseMode,
+    warnings: Vec<ParseWarning>,
+}
+
+impl Diagnostics {
+    pub fn new(mode: ParseMode) -> Self {
+        Self {
+            mode,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Checks a rule. In strict mode, a violation fails the parse at `input` with the given
+    /// context, like `nom::combinator::verify` would. In lenient mode, it is recorded instead.
+    pub fn check<'a, E: ParseError<&'a [u8]>>(
+        &mut self,
+        valid: bool,
+        input: &'a [u8],
+        context: &'static str,
+        warning: ParseWarning,
+    ) -> Result<(), Err<E>> {
+        if valid {
+            return Ok(());
+        }
+
+        match self.mode {
+            ParseMode::Strict => Err(Err::Error(E::add_context(
+                input,
+                context,
+                E::from_error_kind(input, ErrorKind::Verify),
+            ))),
+            ParseMode::Lenient => {
+                self.warnings.push(warning);
+                Ok(())
+            }
+        }
+    }
+
+    /// Records a warning for an anomaly the caller has already recovered from.
+    pub fn warn(&mut self, warning: ParseWarning) {
+        self.warnings.push(warning);
+    }
+
+    /// Takes the result of parsing an optional structure, such as a data directory. If parsing
+    /// failed, the warning is recorded in either mode, and the structure is left out.
+    pub fn recover<T, E>(&mut self, result: Result<T, E>, warning: ParseWarning) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(_) => {
+                self.warn(warning);
+                None
+            }
+        }
+    }
+
+    pub fn into_warnings(self) -> Vec<ParseWarning> {
+        self.warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nom::error::VerboseError;
+
+    const WARNING: ParseWarning = ParseWarning {
+        offset: 0x1F8,
+        rule: ParseRule::SectionOrder { section: 1 },
+        severity: Severity::Warning,
+    };
+
+    #[test]
+    fn strict_mode_fails_on_violation() {
+        let mut diagnostics = Diagnostics::new(ParseMode::Strict);
+        let input = &[0u8; 4][..];
+        assert!(diagnostics
+            .check::<VerboseError<&[u8]>>(true, input, "Verify sections", WARNING)
+            .is_ok());
+        assert!(diagnostics
+            .check::<VerboseError<&[u8]>>(false, input, "Verify sections", WARNING)
+            .is_err());
+        assert!(diagnostics.into_warnings().is_empty());
+    }
+
+    #[test]
+    fn both_modes_recover_from_optional_structures() {
+        for &mode in &[ParseMode::Strict, ParseMode::Lenient] {
+            let mut diagnostics = Diagnostics::new(mode);
+            assert_eq!(diagnostics.recover(Ok::<_, ()>(1), WARNING), Some(1));
+            assert_eq!(diagnostics.recover(Err::<u32, _>(()), WARNING), None);
+            assert_eq!(diagnostics.into_warnings(), vec![WARNING]);
+        }
+    }
+
+    #[test]
+    fn lenient_mode_records_violation() {
+        let mut diagnostics = Diagnostics::new(ParseMode::Lenient);
+        let input = &[0u8; 4][..];
+        assert!(diagnostics
+            .check::<VerboseError<&[u8]>>(false, input, "Verify sections", WARNING)
+            .is_ok());
+        assert_eq!(diagnostics.into_warnings(), vec![WARNING]);
+    }
+}
@@ -0,0 +1,595 @@
+mod header;
+pub use header::*;
+
+mod dynamic;
+pub use dynamic::*;
+
+mod plt;
+pub use plt::*;
+
+mod program_header;
+pub use program_header::*;
+
+mod relocation;
+pub use relocation::*;
+
+mod section_header;
+pub use section_header::*;
+
+mod symbol;
+pub use symbol::*;
+
+use crate::parsers::BinParsable;
+
+use nameof::name_of;
+use nom::{
+    bytes::complete::{take, take_till},
+    combinator::verify,
+    error::context,
+    error::{make_error, ErrorKind, ParseError},
+    number::complete::le_u32,
+    sequence::tuple,
+    Err, IResult,
+};
+use std::fmt;
+
+/// An ELF executable or shared object for x86 or x86-64.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ElfImage {
+    pub header: ElfHeader,
+    pub segments: Vec<ElfSegment>,
+    pub sections: Vec<ElfSectionHeader>,
+    /// The entries of the dynamic section, up to (not including) the terminating null entry.
+    pub dynamic: Vec<DynamicEntry>,
+    /// The names of the shared objects this image depends on.
+    pub needed_libraries: Vec<String>,
+    /// The static symbol table (`.symtab`). Empty for stripped images.
+    pub symbols: Vec<ElfSymbol>,
+    /// The dynamic symbol table (`.dynsym`).
+    pub dynamic_symbols: Vec<ElfSymbol>,
+    /// The dynamic relocations (`DT_REL`/`DT_RELA`), referring to `dynamic_symbols`.
+    pub relocations: Vec<ElfRelocation>,
+    /// The PLT relocations (`DT_JMPREL`), referring to `dynamic_symbols`.
+    pub plt_relocations: Vec<ElfRelocation>,
+}
+
+/// A segment described by the program header table, together with its file data.
+#[derive(PartialEq, Eq)]
+pub struct ElfSegment {
+    pub header: ProgramHeader,
+    pub data: Vec<u8>,
+}
+
+impl ElfSegment {
+    pub fn from_file_and_header(file: &[u8], header: ProgramHeader) -> Option<Self> {
+        let start = header.offset as usize;
+        file.get(start..start.checked_add(header.file_size as usize)?)
+            .map(|data| Self {
+                data: data.to_vec(),
+                header,
+            })
+    }
+}
+
+impl fmt::Debug for ElfSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct(name_of!(type ElfSegment))
+            .field(name_of!(header in ElfSegment), &self.header)
+            .field(
+                name_of!(data in ElfSegment),
+                &(
+                    format!("Vec<u8>, len: {:X}", self.data.len()),
+                    if self.data.len() > 16 {
+                        &self.data[..16]
+                    } else {
+                        &self.data[..]
+                    },
+                ),
+            )
+            .finish()
+    }
+}
+
+impl ElfImage {
+    /// The loadable segments of the image.
+    pub fn load_segments(&self) -> impl Iterator<Item = &ElfSegment> {
+        self.segments
+            .iter()
+            .filter(|segment| segment.header.segment_type == SegmentType::Load)
+    }
+
+    /// Returns the value of the first dynamic entry with the given tag.
+    pub fn dynamic_value(&self, tag: DynamicTag) -> Option<u64> {
+        self.dynamic
+            .iter()
+            .find(|entry| entry.tag == tag)
+            .map(|entry| entry.value)
+    }
+
+    /// Returns the section with the given name.
+    pub fn section(&self, name: &str) -> Option<&ElfSectionHeader> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    /// Returns the file data from the given virtual address to the end of its segment.
+    pub fn data_at(&self, address: u64) -> Option<&[u8]> {
+        self.load_segments()
+            .find(|segment| segment.header.contains_address(address))
+            .and_then(|segment| {
+                segment
+                    .data
+                    .get((address - segment.header.virtual_address) as usize..)
+            })
+    }
+}
+
+/// Converts a virtual address to a file offset, using the loadable segments.
+fn file_offset(segments: &[ElfSegment], address: u64) -> Option<u64> {
+    segments
+        .iter()
+        .map(|segment| &segment.header)
+        .filter(|header| header.segment_type == SegmentType::Load)
+        .find(|header| {
+            address >= header.virtual_address && address - header.virtual_address < header.file_size
+        })
+        .and_then(|header| (address - header.virtual_address).checked_add(header.offset))
+}
+
+/// Parses a table of `count` entries of `entry_size` bytes each, starting at `offset`.
+fn table<'a, T, E: ParseError<&'a [u8]>>(
+    file: &'a [u8],
+    offset: u64,
+    entry_size: u64,
+    count: u64,
+    parser: impl Fn(&'a [u8]) -> IResult<&'a [u8], T, E>,
+) -> Result<Vec<T>, Err<E>> {
+    let (table, _) = take(offset as usize)(file)?;
+    let (_, table) = take(entry_size.saturating_mul(count) as usize)(table)?;
+    table
+        .chunks(entry_size as usize)
+        .map(|entry| parser(entry).map(|(_, value)| value))
+        .collect()
+}
+
+/// Reads the null-terminated string at the given offset into the string table at `strings`.
+fn string_at<'a, E: ParseError<&'a [u8]>>(
+    file: &'a [u8],
+    strings: u64,
+    offset: u64,
+) -> Result<String, Err<E>> {
+    let offset = strings
+        .checked_add(offset)
+        .ok_or_else(|| Err::Error(make_error(file, ErrorKind::TooLarge)))?;
+    let (i, _) = take(offset as usize)(file)?;
+    let (_, name) = take_till(|b| b == 0)(i)?;
+    Ok(String::from_utf8_lossy(name).into_owned())
+}
+
+/// Determines the number of dynamic symbols from the symbol hash table, for images without
+/// section headers.
+fn dynamic_symbol_count<'a, E: ParseError<&'a [u8]>>(
+    file: &'a [u8],
+    class: ElfClass,
+    hash: Option<u64>,
+    gnu_hash: Option<u64>,
+) -> Result<u64, Err<E>> {
+    if let Some(hash) = hash {
+        // nbucket, nchain: the chain array has one entry per symbol
+        let (i, _) = take(hash as usize)(file)?;
+        let (_, (_, symbol_count)) = tuple((le_u32, le_u32))(i)?;
+        return Ok(symbol_count as u64);
+    }
+
+    let gnu_hash = match gnu_hash {
+        Some(gnu_hash) => gnu_hash,
+        None => return Ok(0),
+    };
+    let (i, _) = take(gnu_hash as usize)(file)?;
+    let (_, header) = nom::multi::count(le_u32, 4)(i)?;
+    let (bucket_count, symbol_offset, bloom_size) = (header[0], header[1], header[2]);
+
+    // The highest symbol index is the start of the last chain, which ends with an odd value.
+    let (i, _) = take(16 + bloom_size as usize * class.word_size())(i)?;
+    let (chains, buckets) = nom::multi::count(le_u32, bucket_count as usize)(i)?;
+    let mut last = match buckets.into_iter().max() {
+        Some(last) if last >= symbol_offset => last,
+        _ => return Ok(symbol_offset as u64),
+    };
+    loop {
+        let (chain, _) = take((last - symbol_offset) as usize * 4)(chains)?;
+        let (_, value) = le_u32(chain)?;
+        if value & 1 != 0 {
+            return Ok(last as u64 + 1);
+        }
+        last += 1;
+    }
+}
+
+impl BinParsable for ElfImage {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(image: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(name_of!(type ElfImage), |file: &'a [u8]| {
+            let (i, header) = context(
+                "Check for x86 machine",
+                verify(ElfHeader::try_parse, |header| {
+                    header.machine == ElfMachine::I386 || header.machine == ElfMachine::X86_64
+                }),
+            )(file)?;
+            let class = header.class;
+
+            let eof = || Err::Error(make_error(file, ErrorKind::Eof));
+
+            let program_headers = context("Program header table", |file| {
+                table(
+                    file,
+                    header.program_header_offset,
+                    header.program_header_entry_size.max(1) as u64,
+                    header.program_header_count as u64,
+                    ProgramHeader::try_parse(class),
+                )
+                .map(|headers| (file, headers))
+            })(file)?
+            .1;
+
+            let mut sections = if header.section_header_offset == 0 {
+                Vec::new()
+            } else {
+                context("Section header table", |file| {
+                    table(
+                        file,
+                        header.section_header_offset,
+                        header.section_header_entry_size.max(1) as u64,
+                        header.section_header_count as u64,
+                        ElfSectionHeader::try_parse(class),
+                    )
+                    .map(|headers| (file, headers))
+                })(file)?
+                .1
+            };
+            if let Some(names) = sections.get(header.section_name_table_index as usize) {
+                let names_offset = names.offset;
+                for section in &mut sections {
+                    section.name = string_at(file, names_offset, section.name_offset as u64)?;
+                }
+            }
+
+            let symbol_table = |section: &ElfSectionHeader| -> Result<Vec<ElfSymbol>, Err<E>> {
+                let strings = sections.get(section.link as usize).ok_or_else(eof)?.offset;
+                let entry_size = ElfSymbol::entry_size(class) as u64;
+                let mut symbols = table(
+                    file,
+                    section.offset,
+                    entry_size,
+                    section.size / entry_size,
+                    ElfSymbol::try_parse(class),
+                )?;
+                for symbol in &mut symbols {
+                    symbol.name = string_at(file, strings, symbol.name_offset as u64)?;
+                }
+                Ok(symbols)
+            };
+            let symbols = match sections
+                .iter()
+                .find(|section| section.section_type == SectionType::SymbolTable)
+            {
+                Some(section) => {
+                    context("Symbol table", |file| {
+                        symbol_table(section).map(|symbols| (file, symbols))
+                    })(file)?
+                    .1
+                }
+                None => Vec::new(),
+            };
+
+            let segments = program_headers
+                .into_iter()
+                .map(|header| ElfSegment::from_file_and_header(file, header).ok_or_else(eof))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let dynamic = match segments
+                .iter()
+                .find(|segment| segment.header.segment_type == SegmentType::Dynamic)
+            {
+                Some(segment) => {
+                    let entry_size = 2 * class.word_size() as u64;
+                    let (_, mut dynamic) = context("Dynamic section", |file| {
+                        table(
+                            file,
+                            segment.header.offset,
+                            entry_size,
+                            segment.header.file_size / entry_size,
+                            DynamicEntry::try_parse(class),
+                        )
+                        .map(|entries| (file, entries))
+                    })(file)?;
+                    if let Some(end) = dynamic
+                        .iter()
+                        .position(|entry| entry.tag == DynamicTag::Null)
+                    {
+                        dynamic.truncate(end);
+                    }
+                    dynamic
+                }
+                None => Vec::new(),
+            };
+
+            let dynamic_value = |tag| {
+                dynamic
+                    .iter()
+                    .find(|entry| entry.tag == tag)
+                    .map(|entry| entry.value)
+            };
+            let dynamic_offset = |tag| -> Result<Option<u64>, Err<E>> {
+                match dynamic_value(tag) {
+                    Some(address) => file_offset(&segments, address).map(Some).ok_or_else(eof),
+                    None => Ok(None),
+                }
+            };
+
+            let strings = dynamic_offset(DynamicTag::StringTable)?;
+            let needed_libraries = match strings {
+                Some(strings) => {
+                    context("Needed libraries", |file| {
+                        dynamic
+                            .iter()
+                            .filter(|entry| entry.tag == DynamicTag::Needed)
+                            .map(|entry| string_at(file, strings, entry.value))
+                            .collect::<Result<Vec<_>, _>>()
+                            .map(|names| (file, names))
+                    })(file)?
+                    .1
+                }
+                None => Vec::new(),
+            };
+
+            let dynamic_symbols = match (
+                sections
+                    .iter()
+                    .find(|section| section.section_type == SectionType::DynamicSymbolTable),
+                dynamic_offset(DynamicTag::SymbolTable)?,
+                strings,
+            ) {
+                (Some(section), _, _) => {
+                    context("Dynamic symbol table", |file| {
+                        symbol_table(section).map(|symbols| (file, symbols))
+                    })(file)?
+                    .1
+                }
+                (None, Some(symbol_table), Some(strings)) => {
+                    context("Dynamic symbol table", |file| {
+                        let symbol_count = dynamic_symbol_count(
+                            file,
+                            class,
+                            dynamic_offset(DynamicTag::Hash)?,
+                            dynamic_offset(DynamicTag::GnuHash)?,
+                        )?;
+                        let mut symbols = table(
+                            file,
+                            symbol_table,
+                            ElfSymbol::entry_size(class) as u64,
+                            symbol_count,
+                            ElfSymbol::try_parse(class),
+                        )?;
+                        for symbol in &mut symbols {
+                            symbol.name = string_at(file, strings, symbol.name_offset as u64)?;
+                        }
+                        Ok((file, symbols))
+                    })(file)?
+                    .1
+                }
+                _ => Vec::new(),
+            };
+
+            let relocation_table =
+                |address_tag, size_tag, with_addend| -> Result<Vec<ElfRelocation>, Err<E>> {
+                    match (dynamic_offset(address_tag)?, dynamic_value(size_tag)) {
+                        (Some(offset), Some(size)) => {
+                            let entry_size = ElfRelocation::entry_size(class, with_addend) as u64;
+                            table(
+                                file,
+                                offset,
+                                entry_size,
+                                size / entry_size,
+                                ElfRelocation::try_parse(class, with_addend),
+                            )
+                        }
+                        _ => Ok(Vec::new()),
+                    }
+                };
+            let (_, relocations) = context("Dynamic relocations", |file| {
+                let mut relocations =
+                    relocation_table(DynamicTag::Rel, DynamicTag::RelSize, false)?;
+                relocations.extend(relocation_table(
+                    DynamicTag::Rela,
+                    DynamicTag::RelaSize,
+                    true,
+                )?);
+                Ok((file, relocations))
+            })(file)?;
+
+            // DT_PLTREL holds the tag of the relocation type used (DT_REL or DT_RELA)
+            let plt_with_addend = dynamic_value(DynamicTag::PltRelocationType) == Some(7);
+            let (_, plt_relocations) = context("PLT relocations", |file| {
+                relocation_table(
+                    DynamicTag::JumpRelocations,
+                    DynamicTag::PltRelocationsSize,
+                    plt_with_addend,
+                )
+                .map(|relocations| (file, relocations))
+            })(file)?;
+
+            Ok((
+                i,
+                Self {
+                    header,
+                    segments,
+                    sections,
+                    dynamic,
+                    needed_libraries,
+                    symbols,
+                    dynamic_symbols,
+                    relocations,
+                    plt_relocations,
+                },
+            ))
+        })(image)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use nom::error::VerboseError;
+
+    /// An x86 executable without section headers, calling `puts` from `libc.so.6` through its
+    /// PLT stub and exporting `main`. The dynamic symbols are only reachable through the GNU
+    /// hash table.
+    pub(crate) fn elf_file() -> Vec<u8> {
+        let mut file = vec![0u8; 0x300];
+        let mut put =
+            |offset: usize, bytes: &[u8]| file[offset..offset + bytes.len()].copy_from_slice(bytes);
+        let words = |words: &[u32]| {
+            words
+                .iter()
+                .flat_map(|word| word.to_le_bytes().to_vec())
+                .collect::<Vec<u8>>()
+        };
+
+        put(0x00, b"\x7FELF\x01\x01\x01");
+        put(0x10, &[0x02, 0x00, 0x03, 0x00]); // executable, i386
+        put(0x14, &words(&[1, 0x0804_8120, 0x34, 0])); // version, entry, phoff, shoff
+        put(0x28, &[0x34, 0x00, 0x20, 0x00, 0x03, 0x00, 0x28, 0x00]);
+
+        // Program headers: code, data with a zero-filled tail, and the dynamic section
+        put(
+            0x34,
+            &words(&[1, 0x000, 0x0804_8000, 0, 0x200, 0x200, 5, 0x1000]),
+        );
+        put(
+            0x54,
+            &words(&[1, 0x200, 0x0804_9200, 0, 0x100, 0x180, 6, 0x1000]),
+        );
+        put(0x74, &words(&[2, 0x200, 0x0804_9200, 0, 0x50, 0x50, 6, 4]));
+
+        put(0xA0, b"\0libc.so.6\0puts\0main\0"); // dynamic strings
+        put(0xC0, &words(&[0, 0, 0, 0])); // dynamic symbols: null, puts, main
+        put(0xD0, &words(&[11, 0, 0, 0x12]));
+        put(0xE0, &words(&[16, 0x0804_8100, 0x10, 0x0001_0012]));
+
+        // GNU hash: one bucket starting at symbol 1, the chain ending at symbol 2
+        put(
+            0xF0,
+            &words(&[1, 1, 1, 0, 0xFFFF_FFFF, 1, 0x1234_5678, 0x1234_5679]),
+        );
+
+        put(0x120, &[0xE8, 0x1B, 0x00, 0x00, 0x00, 0xC3]); // call puts@plt; ret
+        put(0x140, &[0xFF, 0x25, 0x50, 0x92, 0x04, 0x08]); // jmp [puts@got]
+        put(0x146, &[0x68, 0x00, 0x00, 0x00, 0x00]); // push 0
+
+        put(
+            0x200,
+            &words(&[
+                1,
+                1, // DT_NEEDED
+                5,
+                0x0804_80A0, // DT_STRTAB
+                6,
+                0x0804_80C0, // DT_SYMTAB
+                0x6FFF_FEF5,
+                0x0804_80F0, // DT_GNU_HASH
+                23,
+                0x0804_9260, // DT_JMPREL
+                2,
+                8, // DT_PLTRELSZ
+                20,
+                17, // DT_PLTREL: DT_REL
+                17,
+                0x0804_9268, // DT_REL
+                18,
+                16, // DT_RELSZ
+                0,
+                0, // DT_NULL
+            ]),
+        );
+        put(0x250, &words(&[0x0804_8146, 0, 0x0804_8120])); // GOT
+        put(0x260, &words(&[0x0804_9250, 0x107])); // puts: R_386_JMP_SLOT
+        put(0x268, &words(&[0x0804_9254, 0x206])); // main: R_386_GLOB_DAT
+        put(0x270, &words(&[0x0804_9258, 0x008])); // R_386_RELATIVE
+        file
+    }
+
+    #[test]
+    fn parses_dynamic_image_without_sections() {
+        let file = elf_file();
+        let (_, image) = ElfImage::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+
+        assert!(image.sections.is_empty());
+        assert_eq!(image.load_segments().count(), 2);
+        assert_eq!(image.dynamic.len(), 9);
+        assert_eq!(image.needed_libraries, vec!["libc.so.6".to_owned()]);
+
+        let names: Vec<&str> = image
+            .dynamic_symbols
+            .iter()
+            .map(|symbol| symbol.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["", "puts", "main"]);
+        let main = &image.dynamic_symbols[2];
+        assert_eq!(main.value, 0x0804_8100);
+        assert_eq!(main.binding, SymbolBinding::Global);
+        assert_eq!(main.symbol_type, ElfSymbolType::Function);
+        assert!(main.is_defined());
+        assert!(!image.dynamic_symbols[1].is_defined());
+
+        assert_eq!(image.plt_relocations.len(), 1);
+        assert_eq!(image.plt_relocations[0].relocation_type, R_X86_JUMP_SLOT);
+        assert_eq!(image.plt_relocations[0].symbol, 1);
+        assert_eq!(image.relocations.len(), 2);
+        assert_eq!(image.relocations[1].relocation_type, R_X86_RELATIVE);
+    }
+
+    #[test]
+    fn counts_dynamic_symbols_from_hash_tables() {
+        let mut file = vec![0u8; 0x48];
+        let mut put = |offset: usize, words: &[u32]| {
+            for (index, word) in words.iter().enumerate() {
+                let offset = offset + index * 4;
+                file[offset..offset + 4].copy_from_slice(&word.to_le_bytes());
+            }
+        };
+        // SysV hash: nbucket, nchain
+        put(0x00, &[1, 7]);
+        // GNU hash: two buckets, the last chain starting at symbol 4 and ending at symbol 5
+        put(0x08, &[2, 3, 1, 0, 0, 3, 4, 1, 0, 1]);
+        // GNU hash with every bucket empty
+        put(0x34, &[1, 3, 0, 0, 0]);
+
+        let count = |hash, gnu_hash| {
+            dynamic_symbol_count::<VerboseError<&[u8]>>(&file, ElfClass::Elf32, hash, gnu_hash)
+                .unwrap()
+        };
+        assert_eq!(count(Some(0x00), Some(0x08)), 7);
+        assert_eq!(count(None, Some(0x08)), 6);
+        assert_eq!(count(None, Some(0x34)), 3);
+        assert_eq!(count(None, None), 0);
+    }
+
+    #[test]
+    fn rejects_overflowing_offsets() {
+        let file = elf_file();
+        assert!(string_at::<VerboseError<&[u8]>>(&file, 0xA0, u64::MAX).is_err());
+        assert!(dynamic_symbol_count::<VerboseError<&[u8]>>(
+            &file,
+            ElfClass::Elf32,
+            Some(u64::MAX),
+            None
+        )
+        .is_err());
+
+        // A code segment reaching the end of the address space
+        let (_, mut image) = ElfImage::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+        image.segments[0].header.virtual_address = u64::MAX - 0x10;
+        assert_eq!(file_offset(&image.segments, u64::MAX), Some(0x10));
+        assert_eq!(image.data_at(u64::MAX).map(<[u8]>::len), Some(0x1F0));
+        assert_eq!(image.plt_entries().len(), 1);
+    }
+}
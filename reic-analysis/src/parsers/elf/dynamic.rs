@@ -0,0 +1,116 @@
+use crate::parsers::elf::ElfClass;
+
+use nameof::name_of;
+use nom::{combinator::map, error::context, error::ParseError, sequence::pair, IResult};
+
+/// An entry of the dynamic section.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DynamicEntry {
+    pub tag: DynamicTag,
+    /// An integer value or a virtual address, depending on the tag.
+    pub value: u64,
+}
+
+impl DynamicEntry {
+    pub fn try_parse<'a, E: ParseError<&'a [u8]>>(
+        class: ElfClass,
+    ) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], Self, E> {
+        move |i: &'a [u8]| {
+            context(
+                name_of!(type DynamicEntry),
+                map(pair(class.word(), class.word()), |p| Self {
+                    tag: DynamicTag::from_raw(p.0),
+                    value: p.1,
+                }),
+            )(i)
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DynamicTag {
+    /// Marks the end of the dynamic section
+    Null,
+    /// String table offset of the name of a needed library
+    Needed,
+    /// Size of the PLT relocations in bytes
+    PltRelocationsSize,
+    /// Address of the PLT/GOT
+    PltGot,
+    /// Address of the symbol hash table
+    Hash,
+    /// Address of the string table
+    StringTable,
+    /// Address of the symbol table
+    SymbolTable,
+    /// Address of the relocations with explicit addends
+    Rela,
+    /// Size of the relocations with explicit addends in bytes
+    RelaSize,
+    /// Size of a relocation with explicit addend in bytes
+    RelaEntrySize,
+    /// Size of the string table in bytes
+    StringTableSize,
+    /// Size of a symbol table entry in bytes
+    SymbolEntrySize,
+    /// Address of the initialization function
+    Init,
+    /// Address of the termination function
+    Fini,
+    /// String table offset of the name of this shared object
+    SharedObjectName,
+    /// Address of the relocations without explicit addends
+    Rel,
+    /// Size of the relocations without explicit addends in bytes
+    RelSize,
+    /// Size of a relocation without explicit addend in bytes
+    RelEntrySize,
+    /// Type of the PLT relocations (`Rel` or `Rela`)
+    PltRelocationType,
+    /// Address of the PLT relocations
+    JumpRelocations,
+    /// Address of the array of initialization functions
+    InitArray,
+    /// Address of the array of termination functions
+    FiniArray,
+    /// Size of the array of initialization functions in bytes
+    InitArraySize,
+    /// Size of the array of termination functions in bytes
+    FiniArraySize,
+    /// Address of the GNU-style symbol hash table
+    GnuHash,
+    Other(u64),
+}
+
+impl DynamicTag {
+    fn from_raw(raw: u64) -> Self {
+        match raw {
+            0 => DynamicTag::Null,
+            1 => DynamicTag::Needed,
+            2 => DynamicTag::PltRelocationsSize,
+            3 => DynamicTag::PltGot,
+            4 => DynamicTag::Hash,
+            5 => DynamicTag::StringTable,
+            6 => DynamicTag::SymbolTable,
+            7 => DynamicTag::Rela,
+            8 => DynamicTag::RelaSize,
+            9 => DynamicTag::RelaEntrySize,
+            10 => DynamicTag::StringTableSize,
+            11 => DynamicTag::SymbolEntrySize,
+            12 => DynamicTag::Init,
+            13 => DynamicTag::Fini,
+            14 => DynamicTag::SharedObjectName,
+            17 => DynamicTag::Rel,
+            18 => DynamicTag::RelSize,
+            19 => DynamicTag::RelEntrySize,
+            20 => DynamicTag::PltRelocationType,
+            23 => DynamicTag::JumpRelocations,
+            25 => DynamicTag::InitArray,
+            26 => DynamicTag::FiniArray,
+            27 => DynamicTag::InitArraySize,
+            28 => DynamicTag::FiniArraySize,
+            0x6FFF_FEF5 => DynamicTag::GnuHash,
+            other => DynamicTag::Other(other),
+        }
+    }
+}
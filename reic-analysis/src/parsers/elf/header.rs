@@ -0,0 +1,191 @@
+use crate::parsers::BinParsable;
+
+use nameof::name_of;
+use nom::{
+    bytes::complete::{tag, take},
+    combinator::{map, map_opt},
+    error::context,
+    error::ParseError,
+    number::complete::{le_u16, le_u32, le_u64, le_u8},
+    sequence::tuple,
+    IResult,
+};
+use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::FromPrimitive;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(u8)]
+pub enum ElfClass {
+    /// 32-bit objects
+    Elf32 = 1,
+    /// 64-bit objects
+    Elf64 = 2,
+}
+
+impl BinParsable for ElfClass {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(name_of!(type ElfClass), map_opt(le_u8, Self::from_u8))(i)
+    }
+}
+
+impl ElfClass {
+    /// Parses an address, offset or size field, whose size depends on the class.
+    pub fn word<'a, E: ParseError<&'a [u8]>>(
+        self,
+    ) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], u64, E> {
+        move |i: &'a [u8]| match self {
+            ElfClass::Elf32 => map(le_u32, u64::from)(i),
+            ElfClass::Elf64 => le_u64(i),
+        }
+    }
+
+    /// The size of an address, offset or size field in bytes.
+    pub fn word_size(self) -> usize {
+        match self {
+            ElfClass::Elf32 => 4,
+            ElfClass::Elf64 => 8,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(u16)]
+pub enum ElfType {
+    /// No file type
+    None = 0,
+    /// Relocatable file
+    Relocatable = 1,
+    /// Executable file
+    Executable = 2,
+    /// Shared object file (also used for position independent executables)
+    SharedObject = 3,
+    /// Core file
+    Core = 4,
+}
+
+impl BinParsable for ElfType {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(name_of!(type ElfType), map_opt(le_u16, Self::from_u16))(i)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(u16)]
+pub enum ElfMachine {
+    /// No machine
+    None = 0,
+    /// Intel 80386
+    I386 = 3,
+    /// Motorola 68000
+    M68K = 4,
+    /// MIPS I
+    MIPS = 8,
+    /// PowerPC
+    PPC = 20,
+    /// ARM 32-bit
+    ARM = 40,
+    /// SuperH
+    SH = 42,
+    /// AMD x86-64
+    X86_64 = 62,
+    /// ARM 64-bit
+    AARCH64 = 183,
+}
+
+impl BinParsable for ElfMachine {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(name_of!(type ElfMachine), map_opt(le_u16, Self::from_u16))(i)
+    }
+}
+
+/// The ELF file header. Only little endian files are supported.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ElfHeader {
+    pub class: ElfClass,
+    /// The ELF header version, must be 1.
+    pub ident_version: u8,
+    /// The OS/ABI the object targets (0: System V, 3: Linux, 9: FreeBSD).
+    pub os_abi: u8,
+    /// The version of the OS/ABI.
+    pub abi_version: u8,
+    pub file_type: ElfType,
+    pub machine: ElfMachine,
+    /// The object file version, must be 1.
+    pub version: u32,
+    /// The virtual address of the entry point, or zero if there is none.
+    pub entry: u64,
+    /// The file offset of the program header table.
+    pub program_header_offset: u64,
+    /// The file offset of the section header table.
+    pub section_header_offset: u64,
+    /// Processor-specific flags.
+    pub flags: u32,
+    /// The size of the ELF header in bytes.
+    pub header_size: u16,
+    /// The size of a program header table entry in bytes.
+    pub program_header_entry_size: u16,
+    /// The number of entries in the program header table.
+    pub program_header_count: u16,
+    /// The size of a section header table entry in bytes.
+    pub section_header_entry_size: u16,
+    /// The number of entries in the section header table.
+    pub section_header_count: u16,
+    /// The section header table index of the section name string table.
+    pub section_name_table_index: u16,
+}
+
+/// `ELFDATA2LSB`: two's complement, little endian.
+const ELF_DATA_LITTLE_ENDIAN: u8 = 1;
+
+impl BinParsable for ElfHeader {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(name_of!(type ElfHeader), |i: &'a [u8]| {
+            let (i, (_, class, _, ident_version, os_abi, abi_version, _)) = tuple((
+                tag(b"\x7FELF"),
+                ElfClass::try_parse,
+                context("Little endian", tag(&[ELF_DATA_LITTLE_ENDIAN][..])),
+                le_u8,
+                le_u8,
+                le_u8,
+                take(7usize),
+            ))(i)?;
+
+            map(
+                tuple((
+                    ElfType::try_parse,
+                    ElfMachine::try_parse,
+                    le_u32,
+                    class.word(),
+                    class.word(),
+                    class.word(),
+                    le_u32,
+                    le_u16,
+                    le_u16,
+                    le_u16,
+                    le_u16,
+                    le_u16,
+                    le_u16,
+                )),
+                move |p| Self {
+                    class,
+                    ident_version,
+                    os_abi,
+                    abi_version,
+                    file_type: p.0,
+                    machine: p.1,
+                    version: p.2,
+                    entry: p.3,
+                    program_header_offset: p.4,
+                    section_header_offset: p.5,
+                    flags: p.6,
+                    header_size: p.7,
+                    program_header_entry_size: p.8,
+                    program_header_count: p.9,
+                    section_header_entry_size: p.10,
+                    section_header_count: p.11,
+                    section_name_table_index: p.12,
+                },
+            )(i)
+        })(i)
+    }
+}
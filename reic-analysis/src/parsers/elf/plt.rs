@@ -0,0 +1,136 @@
+use crate::parsers::elf::{
+    DynamicTag, ElfClass, ElfImage, SegmentFlags, R_X86_GLOB_DAT, R_X86_JUMP_SLOT,
+};
+
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+
+/// A PLT stub, jumping through a GOT slot to an imported symbol.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PltEntry {
+    /// The virtual address of the stub.
+    pub address: u64,
+    /// The virtual address of the GOT slot the stub jumps through.
+    pub got_address: u64,
+    /// The index of the target symbol in the dynamic symbol table.
+    pub symbol: u32,
+}
+
+/// `endbr32`/`endbr64`, found at the start of PLT stubs with CET enabled.
+const ENDBR_PREFIX: [u8; 3] = [0xF3, 0x0F, 0x1E];
+/// The `bnd` prefix, found in front of PLT jumps with MPX enabled.
+const BND_PREFIX: u8 = 0xF2;
+
+impl ElfImage {
+    /// Maps every GOT slot that the dynamic linker binds to a symbol to the index of that
+    /// symbol in the dynamic symbol table.
+    pub fn got_slots(&self) -> BTreeMap<u64, u32> {
+        self.plt_relocations
+            .iter()
+            .chain(&self.relocations)
+            .filter(|relocation| {
+                relocation.relocation_type == R_X86_JUMP_SLOT
+                    || relocation.relocation_type == R_X86_GLOB_DAT
+            })
+            .map(|relocation| (relocation.offset, relocation.symbol))
+            .collect()
+    }
+
+    /// Finds the PLT stubs of the image, by scanning the executable segments for indirect
+    /// jumps through GOT slots.
+    ///
+    /// This covers `.plt`, `.plt.sec` and `.plt.got` alike, and does not rely on section
+    /// headers being present.
+    pub fn plt_entries(&self) -> Vec<PltEntry> {
+        let got_slots = self.got_slots();
+        let got_base = self.dynamic_value(DynamicTag::PltGot);
+
+        let mut entries = Vec::new();
+        let code_segments = self
+            .load_segments()
+            .filter(|segment| segment.header.flags.contains(SegmentFlags::EXECUTE));
+        for segment in code_segments {
+            let data = &segment.data;
+            for position in 0..data.len().saturating_sub(5) {
+                // Addresses wrap around the address space, as they do for the processor
+                let displacement =
+                    u32::from_le_bytes(data[position + 2..position + 6].try_into().unwrap()) as i32
+                        as u64;
+                let instruction_address =
+                    segment.header.virtual_address.wrapping_add(position as u64);
+
+                // jmp [disp32] (absolute on x86, RIP-relative on x86-64) and jmp [ebx + disp32]
+                let got_address = match (self.header.class, data[position], data[position + 1]) {
+                    (ElfClass::Elf32, 0xFF, 0x25) => displacement as u32 as u64,
+                    (ElfClass::Elf64, 0xFF, 0x25) => instruction_address
+                        .wrapping_add(6)
+                        .wrapping_add(displacement),
+                    (ElfClass::Elf32, 0xFF, 0xA3) => match got_base {
+                        Some(got_base) => got_base.wrapping_add(displacement) as u32 as u64,
+                        None => continue,
+                    },
+                    _ => continue,
+                };
+                let symbol = match got_slots.get(&got_address) {
+                    Some(&symbol) => symbol,
+                    None => continue,
+                };
+
+                let mut start = position;
+                if start >= 1 && data[start - 1] == BND_PREFIX {
+                    start -= 1;
+                }
+                if start >= 4 && data[start - 4..start - 1] == ENDBR_PREFIX {
+                    start -= 4;
+                }
+
+                entries.push(PltEntry {
+                    address: segment.header.virtual_address.wrapping_add(start as u64),
+                    got_address,
+                    symbol,
+                });
+            }
+        }
+
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::elf::tests::elf_file;
+    use crate::parsers::BinParsable;
+    use nom::error::VerboseError;
+
+    #[test]
+    fn resolves_plt_stubs_through_got_slots() {
+        let file = elf_file();
+        let (_, image) = ElfImage::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+
+        let got_slots: Vec<(u64, u32)> = image.got_slots().into_iter().collect();
+        assert_eq!(got_slots, vec![(0x0804_9250, 1), (0x0804_9254, 2)]);
+        assert_eq!(
+            image.plt_entries(),
+            vec![PltEntry {
+                address: 0x0804_8140,
+                got_address: 0x0804_9250,
+                symbol: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn includes_cet_and_mpx_prefixes() {
+        let mut file = elf_file();
+        // endbr32; bnd jmp [puts@got]
+        file[0x140..0x14B].copy_from_slice(&[
+            0xF3, 0x0F, 0x1E, 0xFB, 0xF2, 0xFF, 0x25, 0x50, 0x92, 0x04, 0x08,
+        ]);
+        let (_, image) = ElfImage::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+
+        let entries = image.plt_entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].address, 0x0804_8140);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::parsers::elf::ElfClass;
+
+use bitflags::bitflags;
+use nameof::name_of;
+use nom::{
+    combinator::map, error::context, error::ParseError, number::complete::le_u32, sequence::tuple,
+    IResult,
+};
+
+/// An entry of the program header table, describing a segment.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ProgramHeader {
+    pub segment_type: SegmentType,
+    pub flags: SegmentFlags,
+    /// The file offset of the segment data.
+    pub offset: u64,
+    /// The virtual address of the segment in memory.
+    pub virtual_address: u64,
+    /// The physical address of the segment, on systems where it is relevant.
+    pub physical_address: u64,
+    /// The size of the segment data in the file.
+    pub file_size: u64,
+    /// The size of the segment in memory. If larger than `file_size`, the rest is zero-filled.
+    pub memory_size: u64,
+    /// The alignment of the segment in memory and in the file.
+    pub alignment: u64,
+}
+
+impl ProgramHeader {
+    pub fn try_parse<'a, E: ParseError<&'a [u8]>>(
+        class: ElfClass,
+    ) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], Self, E> {
+        move |i: &'a [u8]| {
+            context(name_of!(type ProgramHeader), |i: &'a [u8]| match class {
+                ElfClass::Elf32 => map(
+                    tuple((
+                        le_u32,
+                        class.word(),
+                        class.word(),
+                        class.word(),
+                        class.word(),
+                        class.word(),
+                        le_u32,
+                        class.word(),
+                    )),
+                    |p| Self {
+                        segment_type: SegmentType::from_raw(p.0),
+                        offset: p.1,
+                        virtual_address: p.2,
+                        physical_address: p.3,
+                        file_size: p.4,
+                        memory_size: p.5,
+                        flags: SegmentFlags::from_bits_truncate(p.6),
+                        alignment: p.7,
+                    },
+                )(i),
+                ElfClass::Elf64 => map(
+                    tuple((
+                        le_u32,
+                        le_u32,
+                        class.word(),
+                        class.word(),
+                        class.word(),
+                        class.word(),
+                        class.word(),
+                        class.word(),
+                    )),
+                    |p| Self {
+                        segment_type: SegmentType::from_raw(p.0),
+                        flags: SegmentFlags::from_bits_truncate(p.1),
+                        offset: p.2,
+                        virtual_address: p.3,
+                        physical_address: p.4,
+                        file_size: p.5,
+                        memory_size: p.6,
+                        alignment: p.7,
+                    },
+                )(i),
+            })(i)
+        }
+    }
+
+    pub fn contains_address(&self, address: u64) -> bool {
+        address >= self.virtual_address && address - self.virtual_address < self.memory_size
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SegmentType {
+    /// Unused entry
+    Null,
+    /// Loadable segment
+    Load,
+    /// Dynamic linking information
+    Dynamic,
+    /// Path of the program interpreter
+    Interpreter,
+    /// Auxiliary information
+    Note,
+    /// The program header table itself
+    ProgramHeader,
+    /// Thread-local storage template
+    Tls,
+    /// Exception handling frame header (`.eh_frame_hdr`)
+    GnuEhFrame,
+    /// Stack executability
+    GnuStack,
+    /// Read-only after relocation
+    GnuRelro,
+    Other(u32),
+}
+
+impl SegmentType {
+    fn from_raw(raw: u32) -> Self {
+        match raw {
+            0 => SegmentType::Null,
+            1 => SegmentType::Load,
+            2 => SegmentType::Dynamic,
+            3 => SegmentType::Interpreter,
+            4 => SegmentType::Note,
+            6 => SegmentType::ProgramHeader,
+            7 => SegmentType::Tls,
+            0x6474_E550 => SegmentType::GnuEhFrame,
+            0x6474_E551 => SegmentType::GnuStack,
+            0x6474_E552 => SegmentType::GnuRelro,
+            other => SegmentType::Other(other),
+        }
+    }
+}
+
+bitflags! {
+    #[derive(Default)]
+    pub struct SegmentFlags: u32 {
+        /// The segment is executable.
+        const EXECUTE = 0x1;
+        /// The segment is writable.
+        const WRITE = 0x2;
+        /// The segment is readable.
+        const READ = 0x4;
+    }
+}
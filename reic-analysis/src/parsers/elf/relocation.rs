@@ -0,0 +1,99 @@
+use crate::parsers::elf::ElfClass;
+
+use nameof::name_of;
+use nom::{
+    combinator::{cond, map},
+    error::context,
+    error::ParseError,
+    sequence::tuple,
+    IResult,
+};
+
+/// An entry of a relocation table.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ElfRelocation {
+    /// The virtual address of the patched location (for executables and shared objects).
+    pub offset: u64,
+    /// The index of the referenced symbol in the associated symbol table.
+    pub symbol: u32,
+    /// The machine-specific relocation type.
+    pub relocation_type: u32,
+    /// The explicit addend of `Rela` relocations. `Rel` relocations use the value at the
+    /// patched location as implicit addend.
+    pub addend: Option<i64>,
+}
+
+/// `R_386_32` / `R_X86_64_64`: symbol value plus addend.
+pub const R_X86_ABSOLUTE: u32 = 1;
+/// `R_386_GLOB_DAT` / `R_X86_64_GLOB_DAT`: GOT entry, set to the symbol value.
+pub const R_X86_GLOB_DAT: u32 = 6;
+/// `R_386_JMP_SLOT` / `R_X86_64_JUMP_SLOT`: PLT GOT entry, set to the symbol value.
+pub const R_X86_JUMP_SLOT: u32 = 7;
+/// `R_386_RELATIVE` / `R_X86_64_RELATIVE`: load base plus addend.
+pub const R_X86_RELATIVE: u32 = 8;
+
+impl ElfRelocation {
+    pub fn try_parse<'a, E: ParseError<&'a [u8]>>(
+        class: ElfClass,
+        with_addend: bool,
+    ) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], Self, E> {
+        move |i: &'a [u8]| {
+            context(
+                name_of!(type ElfRelocation),
+                map(
+                    tuple((class.word(), class.word(), cond(with_addend, class.word()))),
+                    |(offset, info, addend)| {
+                        let (symbol, relocation_type) = match class {
+                            ElfClass::Elf32 => ((info >> 8) as u32, (info & 0xFF) as u32),
+                            ElfClass::Elf64 => ((info >> 32) as u32, info as u32),
+                        };
+                        Self {
+                            offset,
+                            symbol,
+                            relocation_type,
+                            addend: addend.map(|addend| match class {
+                                ElfClass::Elf32 => addend as u32 as i32 as i64,
+                                ElfClass::Elf64 => addend as i64,
+                            }),
+                        }
+                    },
+                ),
+            )(i)
+        }
+    }
+
+    /// The size of a relocation table entry in bytes.
+    pub fn entry_size(class: ElfClass, with_addend: bool) -> usize {
+        class.word_size() * if with_addend { 3 } else { 2 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nom::error::VerboseError;
+
+    #[test]
+    fn splits_info_by_class() {
+        let rel32 = [0x10, 0x20, 0x00, 0x00, 0x07, 0x05, 0x00, 0x00];
+        let (_, relocation) =
+            ElfRelocation::try_parse::<VerboseError<&[u8]>>(ElfClass::Elf32, false)(&rel32)
+                .unwrap();
+        assert_eq!(relocation.offset, 0x2010);
+        assert_eq!(relocation.symbol, 5);
+        assert_eq!(relocation.relocation_type, R_X86_JUMP_SLOT);
+        assert_eq!(relocation.addend, None);
+
+        let mut rela64 = Vec::new();
+        rela64.extend_from_slice(&0x4018u64.to_le_bytes());
+        rela64.extend_from_slice(&((3u64 << 32) | R_X86_GLOB_DAT as u64).to_le_bytes());
+        rela64.extend_from_slice(&(-8i64).to_le_bytes());
+        let (_, relocation) =
+            ElfRelocation::try_parse::<VerboseError<&[u8]>>(ElfClass::Elf64, true)(&rela64)
+                .unwrap();
+        assert_eq!(relocation.offset, 0x4018);
+        assert_eq!(relocation.symbol, 3);
+        assert_eq!(relocation.relocation_type, R_X86_GLOB_DAT);
+        assert_eq!(relocation.addend, Some(-8));
+    }
+}
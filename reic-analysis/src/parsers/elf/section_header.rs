@@ -0,0 +1,150 @@
+use crate::parsers::elf::ElfClass;
+
+use bitflags::bitflags;
+use nameof::name_of;
+use nom::{
+    combinator::map, error::context, error::ParseError, number::complete::le_u32, sequence::tuple,
+    IResult,
+};
+
+/// An entry of the section header table.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ElfSectionHeader {
+    /// The name of the section, resolved from the section name string table.
+    pub name: String,
+    /// The offset of the name in the section name string table.
+    pub name_offset: u32,
+    pub section_type: SectionType,
+    pub flags: ElfSectionFlags,
+    /// The virtual address of the section in memory, or zero if it is not loaded.
+    pub address: u64,
+    /// The file offset of the section data.
+    pub offset: u64,
+    /// The size of the section in bytes.
+    pub size: u64,
+    /// A section header table index, whose meaning depends on the section type.
+    /// For symbol tables, it refers to the associated string table.
+    /// For relocation tables, it refers to the associated symbol table.
+    pub link: u32,
+    /// Extra information, whose meaning depends on the section type.
+    /// For relocation tables, it refers to the section the relocations apply to.
+    pub info: u32,
+    /// The alignment of the section.
+    pub alignment: u64,
+    /// The size of each entry, for sections containing a table of fixed-size entries.
+    pub entry_size: u64,
+}
+
+impl ElfSectionHeader {
+    pub fn try_parse<'a, E: ParseError<&'a [u8]>>(
+        class: ElfClass,
+    ) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], Self, E> {
+        move |i: &'a [u8]| {
+            context(
+                name_of!(type ElfSectionHeader),
+                map(
+                    tuple((
+                        le_u32,
+                        le_u32,
+                        class.word(),
+                        class.word(),
+                        class.word(),
+                        class.word(),
+                        le_u32,
+                        le_u32,
+                        class.word(),
+                        class.word(),
+                    )),
+                    |p| Self {
+                        name: String::new(),
+                        name_offset: p.0,
+                        section_type: SectionType::from_raw(p.1),
+                        flags: ElfSectionFlags::from_bits_truncate(p.2),
+                        address: p.3,
+                        offset: p.4,
+                        size: p.5,
+                        link: p.6,
+                        info: p.7,
+                        alignment: p.8,
+                        entry_size: p.9,
+                    },
+                ),
+            )(i)
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SectionType {
+    /// Unused entry
+    Null,
+    /// Program-defined contents
+    ProgramBits,
+    /// Symbol table
+    SymbolTable,
+    /// String table
+    StringTable,
+    /// Relocations with explicit addends
+    Rela,
+    /// Symbol hash table
+    Hash,
+    /// Dynamic linking information
+    Dynamic,
+    /// Notes
+    Note,
+    /// Uninitialized data (`.bss`)
+    NoBits,
+    /// Relocations without explicit addends
+    Rel,
+    /// Dynamic linker symbol table
+    DynamicSymbolTable,
+    /// Array of constructors
+    InitArray,
+    /// Array of destructors
+    FiniArray,
+    /// GNU-style symbol hash table
+    GnuHash,
+    Other(u32),
+}
+
+impl SectionType {
+    fn from_raw(raw: u32) -> Self {
+        match raw {
+            0 => SectionType::Null,
+            1 => SectionType::ProgramBits,
+            2 => SectionType::SymbolTable,
+            3 => SectionType::StringTable,
+            4 => SectionType::Rela,
+            5 => SectionType::Hash,
+            6 => SectionType::Dynamic,
+            7 => SectionType::Note,
+            8 => SectionType::NoBits,
+            9 => SectionType::Rel,
+            11 => SectionType::DynamicSymbolTable,
+            14 => SectionType::InitArray,
+            15 => SectionType::FiniArray,
+            0x6FFF_FFF6 => SectionType::GnuHash,
+            other => SectionType::Other(other),
+        }
+    }
+}
+
+bitflags! {
+    #[derive(Default)]
+    pub struct ElfSectionFlags: u64 {
+        /// The section is writable at run time.
+        const WRITE = 0x1;
+        /// The section occupies memory at run time.
+        const ALLOC = 0x2;
+        /// The section contains executable code.
+        const EXECINSTR = 0x4;
+        /// The section may be merged to eliminate duplication.
+        const MERGE = 0x10;
+        /// The section contains null-terminated strings.
+        const STRINGS = 0x20;
+        /// The `info` field contains a section header table index.
+        const INFO_LINK = 0x40;
+        /// The section holds thread-local storage.
+        const TLS = 0x400;
+    }
+}
@@ -0,0 +1,129 @@
+use crate::parsers::elf::ElfClass;
+
+use nameof::name_of;
+use nom::{
+    combinator::map,
+    error::context,
+    error::ParseError,
+    number::complete::{le_u16, le_u32, le_u8},
+    sequence::tuple,
+    IResult,
+};
+
+/// An entry of a symbol table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElfSymbol {
+    /// The name of the symbol, resolved from the associated string table.
+    pub name: String,
+    /// The offset of the name in the associated string table.
+    pub name_offset: u32,
+    /// The value of the symbol, usually its virtual address.
+    pub value: u64,
+    /// The size of the object the symbol refers to, or zero if unknown.
+    pub size: u64,
+    pub binding: SymbolBinding,
+    pub symbol_type: ElfSymbolType,
+    /// The visibility of the symbol (0: default, 1: internal, 2: hidden, 3: protected).
+    pub visibility: u8,
+    /// The index of the section the symbol is defined in, or zero if it is undefined.
+    pub section_index: u16,
+}
+
+impl ElfSymbol {
+    /// Whether the symbol is defined in this object (as opposed to imported from another one).
+    pub fn is_defined(&self) -> bool {
+        self.section_index != 0
+    }
+
+    pub fn try_parse<'a, E: ParseError<&'a [u8]>>(
+        class: ElfClass,
+    ) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], Self, E> {
+        move |i: &'a [u8]| {
+            let symbol = |name_offset, value, size, info: u8, other: u8, section_index| Self {
+                name: String::new(),
+                name_offset,
+                value,
+                size,
+                binding: SymbolBinding::from_raw(info >> 4),
+                symbol_type: ElfSymbolType::from_raw(info & 0xF),
+                visibility: other & 0x3,
+                section_index,
+            };
+
+            context(name_of!(type ElfSymbol), move |i: &'a [u8]| match class {
+                ElfClass::Elf32 => map(
+                    tuple((le_u32, class.word(), class.word(), le_u8, le_u8, le_u16)),
+                    move |p| symbol(p.0, p.1, p.2, p.3, p.4, p.5),
+                )(i),
+                ElfClass::Elf64 => map(
+                    tuple((le_u32, le_u8, le_u8, le_u16, class.word(), class.word())),
+                    move |p| symbol(p.0, p.4, p.5, p.1, p.2, p.3),
+                )(i),
+            })(i)
+        }
+    }
+
+    /// The size of a symbol table entry in bytes.
+    pub fn entry_size(class: ElfClass) -> usize {
+        match class {
+            ElfClass::Elf32 => 16,
+            ElfClass::Elf64 => 24,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SymbolBinding {
+    /// Not visible outside of the object
+    Local,
+    /// Visible to all objects
+    Global,
+    /// Like global, but with lower precedence
+    Weak,
+    Other(u8),
+}
+
+impl SymbolBinding {
+    fn from_raw(raw: u8) -> Self {
+        match raw {
+            0 => SymbolBinding::Local,
+            1 => SymbolBinding::Global,
+            2 => SymbolBinding::Weak,
+            other => SymbolBinding::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ElfSymbolType {
+    /// Not specified
+    NoType,
+    /// A data object (variable, array, ...)
+    Object,
+    /// A function or other executable code
+    Function,
+    /// A section
+    Section,
+    /// The source file of the object
+    File,
+    /// An uninitialized common block
+    Common,
+    /// A thread-local storage entity
+    Tls,
+    Other(u8),
+}
+
+impl ElfSymbolType {
+    fn from_raw(raw: u8) -> Self {
+        match raw {
+            0 => ElfSymbolType::NoType,
+            1 => ElfSymbolType::Object,
+            2 => ElfSymbolType::Function,
+            3 => ElfSymbolType::Section,
+            4 => ElfSymbolType::File,
+            5 => ElfSymbolType::Common,
+            6 => ElfSymbolType::Tls,
+            other => ElfSymbolType::Other(other),
+        }
+    }
+}
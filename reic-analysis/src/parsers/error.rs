@@ -0,0 +1,224 @@
+use nom::error::{ErrorKind, VerboseError, VerboseErrorKind};
+use std::error::Error;
+use std::fmt::{self, Write};
+
+/// The number of bytes shown before and after the error offset in the hex dump excerpt.
+const HEXDUMP_CONTEXT: usize = 32;
+/// The number of bytes shown as the actual value found at the error offset.
+const ACTUAL_VALUE_LENGTH: usize = 8;
+
+/// A human-readable report of a failed parse, built from nom's `VerboseError`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseErrorReport {
+    /// The contexts the error occurred in, outermost first.
+    pub context: Vec<String>,
+    /// The file offset the error occurred at, if it lies within the parsed file.
+    pub offset: Option<usize>,
+    /// What the parser expected to find at the offset.
+    pub expected: String,
+    /// The bytes found at the offset.
+    pub actual: Vec<u8>,
+    /// A hex dump of the data around the offset.
+    pub hexdump: String,
+}
+
+impl ParseErrorReport {
+    /// Creates a report for an error returned by a parser applied to `file`.
+    pub fn from_nom_error(file: &[u8], error: &nom::Err<VerboseError<&[u8]>>) -> Self {
+        match error {
+            nom::Err::Error(error) | nom::Err::Failure(error) => {
+                Self::from_verbose_error(file, error)
+            }
+            nom::Err::Incomplete(needed) => Self {
+                context: Vec::new(),
+                offset: Some(file.len()),
+                expected: format!("more data ({:?})", needed),
+                actual: Vec::new(),
+                hexdump: hexdump(file, file.len()),
+            },
+        }
+    }
+
+    pub fn from_verbose_error(file: &[u8], error: &VerboseError<&[u8]>) -> Self {
+        // The errors are ordered from the innermost to the outermost parser.
+        let context = error
+            .errors
+            .iter()
+            .rev()
+            .filter_map(|(_, kind)| match kind {
+                VerboseErrorKind::Context(context) => Some((*context).to_owned()),
+                _ => None,
+            })
+            .collect();
+
+        // The context directly around the failed parser names the structure it was reading.
+        let enclosing_context = match error.errors.get(1) {
+            Some((_, VerboseErrorKind::Context(context))) => Some(*context),
+            _ => None,
+        };
+        let (input, expected) = match error.errors.first() {
+            Some((input, VerboseErrorKind::Char(c))) => (*input, format!("character {:?}", c)),
+            Some((input, VerboseErrorKind::Nom(kind))) => {
+                let expected = expected_value(kind);
+                match enclosing_context {
+                    Some(context) => (*input, format!("{} for {}", expected, context)),
+                    None => (*input, expected.to_owned()),
+                }
+            }
+            Some((input, VerboseErrorKind::Context(context))) => (*input, (*context).to_owned()),
+            None => (file, String::new()),
+        };
+
+        let offset = offset_in(file, input);
+        Self {
+            context,
+            offset,
+            expected,
+            actual: input[..input.len().min(ACTUAL_VALUE_LENGTH)].to_vec(),
+            hexdump: offset.map_or_else(String::new, |offset| hexdump(file, offset)),
+        }
+    }
+
+    /// The context chain, for example `PE32Image > Sections > Validate header`.
+    pub fn context_chain(&self) -> String {
+        self.context.join(" > ")
+    }
+}
+
+impl fmt::Display for ParseErrorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.context_chain())?;
+        if let Some(offset) = self.offset {
+            write!(f, " at 0x{:X}", offset)?;
+        }
+        write!(f, ": expected {}, found", self.expected)?;
+        if self.actual.is_empty() {
+            write!(f, " end of data")?;
+        }
+        for byte in &self.actual {
+            write!(f, " {:02X}", byte)?;
+        }
+
+        if !self.hexdump.is_empty() {
+            write!(f, "\n{}", self.hexdump)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ParseErrorReport {}
+
+/// Describes what a parser failing with the given error kind expected to find.
+fn expected_value(kind: &ErrorKind) -> &str {
+    match kind {
+        ErrorKind::Tag => "matching bytes",
+        ErrorKind::Verify => "a valid value",
+        ErrorKind::MapOpt | ErrorKind::MapRes => "a known value",
+        // `take` and friends report running out of data as `Eof`
+        ErrorKind::Eof => "more data",
+        ErrorKind::TooLarge => "a smaller value",
+        kind => kind.description(),
+    }
+}
+
+/// Returns the offset of `input` in `file`, if it is a subslice of it.
+fn offset_in(file: &[u8], input: &[u8]) -> Option<usize> {
+    let start = file.as_ptr() as usize;
+    let position = input.as_ptr() as usize;
+    if position >= start && position + input.len() <= start + file.len() {
+        Some(position - start)
+    } else {
+        None
+    }
+}
+
+/// Formats the bytes around `offset` as a hex dump with absolute offsets, marking the byte at
+/// `offset`.
+fn hexdump(file: &[u8], offset: usize) -> String {
+    let start = offset.saturating_sub(HEXDUMP_CONTEXT) & !0xF;
+    let end = (offset + HEXDUMP_CONTEXT).min(file.len());
+
+    let mut dump = String::new();
+    for line_start in (start..end).step_by(16) {
+        let line = &file[line_start..(line_start + 16).min(end)];
+        write!(dump, "{:08X} ", line_start).unwrap();
+        for (index, byte) in line.iter().enumerate() {
+            let marker = if line_start + index == offset {
+                '>'
+            } else {
+                ' '
+            };
+            write!(dump, "{}{:02X}", marker, byte).unwrap();
+        }
+        for _ in line.len()..16 {
+            dump.push_str("   ");
+        }
+        dump.push_str("  ");
+        dump.extend(line.iter().map(|&byte| {
+            if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            }
+        }));
+        dump.push('\n');
+    }
+
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nom::bytes::complete::{tag, take};
+    use nom::combinator::verify;
+    use nom::error::context;
+    use nom::number::complete::be_u8;
+    use nom::sequence::preceded;
+
+    #[test]
+    fn reports_context_chain_and_offset() {
+        let file = b"MZ\x90\x00PE\x00\x01";
+        let result = context(
+            "PE32Image",
+            preceded(
+                tag(&b"MZ\x90\x00"[..]),
+                context("Signature", tag(&b"PE\0\0"[..])),
+            ),
+        )(&file[..]);
+
+        let report = ParseErrorReport::from_nom_error(file, &result.unwrap_err());
+        assert_eq!(report.context_chain(), "PE32Image > Signature");
+        assert_eq!(report.offset, Some(4));
+        assert_eq!(report.expected, "matching bytes for Signature");
+        assert_eq!(report.actual, b"PE\x00\x01");
+        assert!(report.hexdump.starts_with("00000000  4D 5A 90 00>50"));
+    }
+
+    #[test]
+    fn describes_expected_values() {
+        let file = [0x7F, b'E', b'L', b'F', 0x05];
+        let result = context(
+            "ElfImage",
+            preceded(
+                tag(&b"\x7FELF"[..]),
+                context(
+                    "ElfClass",
+                    verify(be_u8, |class| *class == 1 || *class == 2),
+                ),
+            ),
+        )(&file[..]);
+
+        let report = ParseErrorReport::from_nom_error(&file, &result.unwrap_err());
+        assert_eq!(report.expected, "a valid value for ElfClass");
+        assert_eq!(report.offset, Some(4));
+        assert_eq!(
+            report.to_string().lines().next().unwrap(),
+            "ElfImage > ElfClass at 0x4: expected a valid value for ElfClass, found 05"
+        );
+
+        let result = take::<_, _, VerboseError<&[u8]>>(8usize)(&file[..]);
+        let report = ParseErrorReport::from_nom_error(&file, &result.unwrap_err());
+        assert_eq!(report.expected, "more data");
+    }
+}
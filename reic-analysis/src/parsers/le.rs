@@ -0,0 +1,346 @@
+mod header;
+pub use header::*;
+
+mod entry_table;
+pub use entry_table::*;
+
+mod fixup;
+pub use fixup::*;
+
+mod object;
+pub use object::*;
+
+use crate::parsers::mz::MZHeader;
+use crate::parsers::ne::{name_table, pascal_string, NameTableEntry};
+use crate::parsers::BinParsable;
+
+use nameof::name_of;
+use nom::{
+    bytes::complete::take,
+    combinator::{all_consuming, verify},
+    error::context,
+    error::{make_error, ErrorKind, ParseError},
+    multi::{count, many0},
+    number::complete::le_u32,
+    Err, IResult,
+};
+use std::collections::{btree_map, BTreeMap};
+
+/// The largest page size accepted. Linkers use 4 KiB pages, the size of an x86 page.
+const MAX_PAGE_SIZE: u32 = 0x10000;
+
+/// A linear executable (LE or LX), as used by DOS extenders, VxDs and OS/2.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LEImage {
+    pub mz_header: MZHeader,
+    pub le_header: LEHeader,
+    /// The objects of the image. Object numbers are 1-based, so object `n` is at index `n - 1`.
+    pub objects: Vec<Object>,
+    pub entries: BTreeMap<u16, LEEntry>,
+    /// The module name, followed by the exported names of resident entries.
+    pub resident_names: Vec<NameTableEntry>,
+    /// The module description, followed by the exported names of non-resident entries.
+    pub non_resident_names: Vec<NameTableEntry>,
+    /// The names of the modules this image imports from.
+    pub import_modules: Vec<String>,
+    /// The names of procedures imported by name, keyed by their offset in the import procedure
+    /// name table.
+    pub imported_names: BTreeMap<u32, String>,
+}
+
+impl LEImage {
+    /// The module name, as specified by the first entry of the resident name table.
+    pub fn module_name(&self) -> Option<&str> {
+        self.resident_names.first().map(|entry| entry.name.as_str())
+    }
+
+    /// Returns the exported name of the entry with the given ordinal, if it has one.
+    pub fn entry_name(&self, ordinal: u16) -> Option<&str> {
+        self.resident_names
+            .iter()
+            .skip(1)
+            .chain(self.non_resident_names.iter().skip(1))
+            .find(|entry| entry.ordinal == ordinal)
+            .map(|entry| entry.name.as_str())
+    }
+
+    /// Returns the name of the module with the given 1-based import module index.
+    pub fn import_module(&self, module: u16) -> Option<&str> {
+        self.import_modules
+            .get((module as usize).checked_sub(1)?)
+            .map(String::as_str)
+    }
+
+    /// Returns the object with the given 1-based object number.
+    pub fn object(&self, number: u32) -> Option<&Object> {
+        self.objects.get((number as usize).checked_sub(1)?)
+    }
+}
+
+impl BinParsable for LEImage {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(image: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(name_of!(type LEImage), |file: &'a [u8]| {
+            let (_, mz_header) = context(
+                "LE header offset sanity check",
+                verify(MZHeader::try_parse, |mz| file.len() > mz.e_lfanew as usize),
+            )(file)?;
+
+            let (le, _) = take(mz_header.e_lfanew)(file)?;
+            let (i, le_header) = context(
+                "Check byte and word order",
+                verify(LEHeader::try_parse, |header| {
+                    header.byte_order == 0
+                        && header.word_order == 0
+                        && header.page_size != 0
+                        && header.page_size <= MAX_PAGE_SIZE
+                }),
+            )(le)?;
+
+            let table = |offset: u32| take(offset)(le).map(|(table, _)| table);
+
+            let (_, object_headers) = context(
+                "Object table",
+                count(ObjectHeader::try_parse, le_header.object_count as usize),
+            )(table(le_header.object_table_offset)?)?;
+
+            let page_map_entry = |i| match le_header.format {
+                LinearExecutableFormat::LE => ObjectPageMapEntry::try_parse_le(
+                    le_header.page_size,
+                    le_header.page_count,
+                    le_header.last_page_size_or_page_shift,
+                )(i),
+                LinearExecutableFormat::LX => {
+                    ObjectPageMapEntry::try_parse_lx(le_header.last_page_size_or_page_shift)(i)
+                }
+            };
+            let (_, page_map) = context(
+                "Object page map",
+                count(page_map_entry, le_header.page_count as usize),
+            )(table(le_header.object_page_map_offset)?)?;
+
+            let (_, fixup_page_table) = context(
+                "Fixup page table",
+                count(le_u32, le_header.page_count as usize + 1),
+            )(table(le_header.fixup_page_table_offset)?)?;
+            let fixup_records = table(le_header.fixup_record_table_offset)?;
+
+            let (data_pages, _) = take(le_header.data_pages_offset)(file)?;
+
+            let mut objects = Vec::with_capacity(object_headers.len());
+            for header in object_headers {
+                // Objects refer to their first page by its 1-based index in the page map
+                let first_page = match header.page_table_entries {
+                    0 => 0,
+                    _ => header
+                        .page_table_index
+                        .checked_sub(1)
+                        .ok_or_else(|| Err::Error(make_error(le, ErrorKind::Verify)))?
+                        as usize,
+                };
+                let page_indices = first_page
+                    ..first_page
+                        .checked_add(header.page_table_entries as usize)
+                        .filter(|&end| end <= page_map.len())
+                        .ok_or_else(|| Err::Error(make_error(le, ErrorKind::Eof)))?;
+
+                let mut pages = Vec::with_capacity(page_indices.len());
+                for index in page_indices {
+                    let map_entry = page_map[index];
+
+                    let (_, raw) = context("Page data", |i| {
+                        let (i, _) = take(map_entry.data_offset)(i)?;
+                        take(map_entry.data_size)(i)
+                    })(data_pages)?;
+                    let data = match map_entry.page_type {
+                        PageType::Legal => raw.to_vec(),
+                        PageType::Iterated => {
+                            expand_iterated_page(raw, le_header.page_size as usize)
+                        }
+                        _ => Vec::new(),
+                    };
+
+                    let (fixups_start, fixups_end) =
+                        (fixup_page_table[index], fixup_page_table[index + 1]);
+                    let (_, fixups) = context("Fixup records", |i| {
+                        let (i, _) = take(fixups_start)(i)?;
+                        let (_, page_fixups) = take(fixups_end.saturating_sub(fixups_start))(i)?;
+                        all_consuming(many0(FixupRecord::try_parse))(page_fixups)
+                    })(fixup_records)?;
+
+                    pages.push(ObjectPage {
+                        map_entry,
+                        data,
+                        fixups,
+                    });
+                }
+                objects.push(Object { header, pages });
+            }
+
+            let (_, entries) =
+                context("Entry table", le_entry_table)(table(le_header.entry_table_offset)?)?;
+
+            let (_, resident_names) = context("Resident name table", name_table)(table(
+                le_header.resident_name_table_offset,
+            )?)?;
+
+            let non_resident_names = if le_header.non_resident_name_table_size == 0 {
+                Vec::new()
+            } else {
+                let (non_resident, _) = take(le_header.non_resident_name_table_offset)(file)?;
+                context("Non-resident name table", name_table)(non_resident)?.1
+            };
+
+            let (_, import_modules) = context(
+                "Import module name table",
+                count(pascal_string, le_header.import_module_count as usize),
+            )(table(le_header.import_module_table_offset)?)?;
+
+            let import_procedures = table(le_header.import_procedure_table_offset)?;
+            let mut imported_names = BTreeMap::new();
+            let fixups = objects
+                .iter()
+                .flat_map(|object| &object.pages)
+                .flat_map(|page| &page.fixups);
+            for fixup in fixups {
+                if let FixupTarget::ImportName { name_offset, .. } = fixup.target {
+                    if let btree_map::Entry::Vacant(vacant) = imported_names.entry(name_offset) {
+                        let (name, _) = take(name_offset)(import_procedures)?;
+                        let (_, name) = context("Imported name", pascal_string)(name)?;
+                        vacant.insert(name);
+                    }
+                }
+            }
+
+            Ok((
+                i,
+                Self {
+                    mz_header,
+                    le_header,
+                    objects,
+                    entries,
+                    resident_names,
+                    non_resident_names,
+                    import_modules,
+                    imported_names,
+                },
+            ))
+        })(image)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use nom::error::VerboseError;
+
+    /// A VxD-like module with two objects without base addresses, whose pages are stored in
+    /// the opposite order. The code object loads a dword of the data object and calls an
+    /// imported procedure.
+    pub(crate) fn le_file() -> Vec<u8> {
+        let mut file = vec![0u8; 0x310];
+        let mut put =
+            |offset: usize, bytes: &[u8]| file[offset..offset + bytes.len()].copy_from_slice(bytes);
+        let dword = |value: u32| value.to_le_bytes();
+        put(0x00, b"MZ");
+        put(0x3C, &dword(0x40)); // e_lfanew
+
+        put(0x40, b"LE");
+        put(0x48, &[0x02, 0x00, 0x04, 0x00]); // cpu_type: 386, os_type: Windows 386
+        put(0x54, &dword(2)); // page_count
+        put(0x58, &dword(1)); // eip_object
+        put(0x68, &dword(0x100)); // page_size
+        put(0x6C, &dword(0x10)); // last_page_size
+        put(0x80, &dword(0xB0)); // object_table_offset
+        put(0x84, &dword(2)); // object_count
+        put(0x88, &dword(0xE0)); // object_page_map_offset
+        put(0x98, &dword(0xF0)); // resident_name_table_offset
+        put(0x9C, &dword(0x100)); // entry_table_offset
+        put(0xA8, &dword(0x110)); // fixup_page_table_offset
+        put(0xAC, &dword(0x120)); // fixup_record_table_offset
+        put(0xB0, &dword(0x140)); // import_module_table_offset
+        put(0xB4, &dword(1)); // import_module_count
+        put(0xB8, &dword(0x150)); // import_procedure_table_offset
+        put(0xC0, &dword(0x200)); // data_pages_offset
+
+        // Object table: code in the second page map entry, data in the first one
+        put(0xF0, &dword(0x100));
+        put(0xF8, &dword(0x2005)); // readable, executable, 32-bit
+        put(0xFC, &dword(2));
+        put(0x100, &dword(1));
+        put(0x108, &dword(0x20));
+        put(0x110, &dword(0x0003)); // readable, writable
+        put(0x114, &dword(1));
+        put(0x118, &dword(1));
+
+        // Object page map: data pages 2 and 1
+        put(0x120, &[0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x00]);
+
+        // Resident names
+        put(0x130, b"\x04DEMO\x00\x00\x05Entry\x01\x00\x00");
+        // Entry table: an exported 32-bit entry at 1:00000000
+        put(
+            0x140,
+            &[0x01, 0x03, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00],
+        );
+
+        // Fixup page table: all fixups are on the page of the code object
+        put(0x150, &dword(0));
+        put(0x154, &dword(0));
+        put(0x158, &dword(14));
+        // Offset32 at 0x01 to 2:0004, Offset32 at 0x07 to KERNEL.Beep
+        put(0x160, &[0x07, 0x00, 0x01, 0x00, 0x02, 0x04, 0x00]);
+        put(0x167, &[0x07, 0x02, 0x07, 0x00, 0x01, 0x01, 0x00]);
+
+        put(0x180, b"\x06KERNEL"); // import module names
+        put(0x190, b"\x00\x04Beep"); // import procedure names
+
+        // mov eax, [data+4]; call [Beep]; ret
+        let mut code = vec![0xCC; 0x100];
+        code[..12].copy_from_slice(&[
+            0xA1, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x15, 0x00, 0x00, 0x00, 0x00, 0xC3,
+        ]);
+        put(0x200, &code);
+        put(0x300, &(0..0x10).collect::<Vec<u8>>());
+        file
+    }
+
+    #[test]
+    fn parses_objects_by_page_table_index() {
+        let file = le_file();
+        let (_, image) = LEImage::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+
+        assert_eq!(image.module_name(), Some("DEMO"));
+        assert_eq!(image.entry_name(1), Some("Entry"));
+        assert_eq!(image.import_module(1), Some("KERNEL"));
+        assert_eq!(
+            image.imported_names.get(&1).map(String::as_str),
+            Some("Beep")
+        );
+
+        let code = image.object(1).unwrap();
+        assert_eq!(code.pages.len(), 1);
+        assert_eq!(code.pages[0].map_entry.data_offset, 0);
+        assert_eq!(&code.pages[0].data[..2], &[0xA1, 0x00]);
+        assert_eq!(code.pages[0].fixups.len(), 2);
+
+        let data = image.object(2).unwrap();
+        assert_eq!(data.pages[0].map_entry.data_offset, 0x100);
+        assert_eq!(data.pages[0].data, (0..0x10).collect::<Vec<u8>>());
+        assert!(data.pages[0].fixups.is_empty());
+        assert_eq!(data.data(0x100).len(), 0x20);
+    }
+
+    #[test]
+    fn rejects_pages_outside_the_page_map() {
+        let mut file = le_file();
+        file[0xFC..0x100].copy_from_slice(&3u32.to_le_bytes());
+        assert!(LEImage::try_parse::<VerboseError<&[u8]>>(&file).is_err());
+    }
+
+    #[test]
+    fn rejects_page_counts_beyond_the_page_map() {
+        let mut file = le_file();
+        file[0x100..0x104].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(LEImage::try_parse::<VerboseError<&[u8]>>(&file).is_err());
+    }
+}
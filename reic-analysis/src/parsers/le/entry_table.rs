@@ -0,0 +1,186 @@
+use crate::parsers::ne::EntryFlags;
+use crate::parsers::BinParsable;
+
+use nameof::name_of;
+use nom::{
+    error::context,
+    error::ParseError,
+    error::{make_error, ErrorKind},
+    number::complete::{le_u16, le_u32, le_u8},
+    sequence::tuple,
+    Err, IResult,
+};
+use std::collections::BTreeMap;
+
+/// An entry point of the module, as listed in the entry table.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LEEntry {
+    pub flags: EntryFlags,
+    pub location: LEEntryLocation,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LEEntryLocation {
+    /// The entry is located in an object of this module. `object` is 1-based.
+    Object { object: u16, offset: u32 },
+    /// The entry is forwarded to another module. `module` is a 1-based index into the import
+    /// module name table, `target` is either an ordinal or an offset into the import procedure
+    /// name table.
+    Forwarder {
+        module: u16,
+        target: u32,
+        by_ordinal: bool,
+    },
+}
+
+/// Bundle type of unused entries.
+const UNUSED_BUNDLE: u8 = 0x00;
+/// Bundle type of entries with 16-bit offsets.
+const ENTRY_16_BUNDLE: u8 = 0x01;
+/// Bundle type of 286 call gate entries.
+const CALL_GATE_BUNDLE: u8 = 0x02;
+/// Bundle type of entries with 32-bit offsets.
+const ENTRY_32_BUNDLE: u8 = 0x03;
+/// Bundle type of forwarder entries.
+const FORWARDER_BUNDLE: u8 = 0x04;
+/// Set if the bundle contains parameter typing information, which is not used.
+const PARAMETER_TYPING_FLAG: u8 = 0x80;
+/// Set in the entry flags of forwarders imported by ordinal.
+const FORWARDER_BY_ORDINAL: u8 = 0x01;
+
+/// Parses the entry table, which consists of bundles of entries sharing the same type
+/// and object.
+///
+/// Entries are keyed by their ordinal, which starts at 1.
+pub fn le_entry_table<'a, E: ParseError<&'a [u8]>>(
+    table: &'a [u8],
+) -> IResult<&'a [u8], BTreeMap<u16, LEEntry>, E> {
+    context(name_of!(type LEEntry), |mut i: &'a [u8]| {
+        let mut entries = BTreeMap::new();
+        let mut ordinal = 1u16;
+        loop {
+            let (rest, bundle_size) = le_u8(i)?;
+            if bundle_size == 0 {
+                return Ok((rest, entries));
+            }
+            let (rest, bundle_type) = le_u8(rest)?;
+            let bundle_type = bundle_type & !PARAMETER_TYPING_FLAG;
+            let (rest, object) = if bundle_type == UNUSED_BUNDLE {
+                (rest, 0)
+            } else {
+                le_u16(rest)?
+            };
+            i = rest;
+
+            for _ in 0..bundle_size {
+                let entry = match bundle_type {
+                    UNUSED_BUNDLE => None,
+                    ENTRY_16_BUNDLE => {
+                        let (rest, (flags, offset)) = tuple((EntryFlags::try_parse, le_u16))(i)?;
+                        i = rest;
+                        Some(LEEntry {
+                            flags,
+                            location: LEEntryLocation::Object {
+                                object,
+                                offset: offset as u32,
+                            },
+                        })
+                    }
+                    CALL_GATE_BUNDLE => {
+                        let (rest, (flags, offset, _call_gate)) =
+                            tuple((EntryFlags::try_parse, le_u16, le_u16))(i)?;
+                        i = rest;
+                        Some(LEEntry {
+                            flags,
+                            location: LEEntryLocation::Object {
+                                object,
+                                offset: offset as u32,
+                            },
+                        })
+                    }
+                    ENTRY_32_BUNDLE => {
+                        let (rest, (flags, offset)) = tuple((EntryFlags::try_parse, le_u32))(i)?;
+                        i = rest;
+                        Some(LEEntry {
+                            flags,
+                            location: LEEntryLocation::Object { object, offset },
+                        })
+                    }
+                    FORWARDER_BUNDLE => {
+                        let (rest, (raw_flags, module, target)) =
+                            tuple((le_u8, le_u16, le_u32))(i)?;
+                        i = rest;
+                        Some(LEEntry {
+                            flags: EntryFlags::EXPORTED,
+                            location: LEEntryLocation::Forwarder {
+                                module,
+                                target,
+                                by_ordinal: raw_flags & FORWARDER_BY_ORDINAL != 0,
+                            },
+                        })
+                    }
+                    _ => return Err(Err::Error(make_error(i, ErrorKind::Switch))),
+                };
+
+                if let Some(entry) = entry {
+                    entries.insert(ordinal, entry);
+                }
+                ordinal = ordinal
+                    .checked_add(1)
+                    .ok_or_else(|| Err::Error(make_error(i, ErrorKind::TooLarge)))?;
+            }
+        }
+    })(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nom::error::VerboseError;
+
+    #[test]
+    fn parses_bundles() {
+        let table = [
+            0x02, 0x00, // 2 unused entries
+            0x01, 0x01, 0x02, 0x00, 0x01, 0x34, 0x12, // 1 16-bit entry in object 2
+            0x01, 0x82, 0x01, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, // 1 call gate entry
+            0x01, 0x04, 0x00, 0x00, 0x01, 0x03, 0x00, 0x07, 0x00, 0x00, 0x00, // forwarder
+            0x00, 0xCC,
+        ];
+        let (rest, entries) = le_entry_table::<VerboseError<&[u8]>>(&table).unwrap();
+
+        assert_eq!(rest, &[0xCC]);
+        assert_eq!(entries.keys().copied().collect::<Vec<_>>(), vec![3, 4, 5]);
+        assert_eq!(
+            entries[&3],
+            LEEntry {
+                flags: EntryFlags::EXPORTED,
+                location: LEEntryLocation::Object {
+                    object: 2,
+                    offset: 0x1234
+                },
+            }
+        );
+        assert_eq!(
+            entries[&4].location,
+            LEEntryLocation::Object {
+                object: 1,
+                offset: 0x1000
+            }
+        );
+        assert_eq!(
+            entries[&5].location,
+            LEEntryLocation::Forwarder {
+                module: 3,
+                target: 7,
+                by_ordinal: true
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_bundle_types() {
+        let table = [0x01, 0x05, 0x01, 0x00, 0x00];
+        assert!(le_entry_table::<VerboseError<&[u8]>>(&table).is_err());
+    }
+}
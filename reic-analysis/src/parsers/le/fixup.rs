@@ -0,0 +1,251 @@
+use crate::parsers::BinParsable;
+
+use nameof::name_of;
+use nom::{
+    combinator::{cond, map, map_opt},
+    error::context,
+    error::ParseError,
+    multi::count,
+    number::complete::{le_i16, le_u16, le_u32, le_u8},
+    IResult,
+};
+use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::FromPrimitive;
+
+/// A fixup record of the fixup record table.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FixupRecord {
+    /// The kind of value that is patched.
+    pub source_type: FixupSourceType,
+    /// Whether the fixup refers to the 16:16 alias of the target object.
+    pub alias: bool,
+    /// The offsets of the patched locations, relative to the start of the page.
+    ///
+    /// Offsets can be negative or exceed the page size for fixups spanning a page boundary.
+    pub source_offsets: Vec<i16>,
+    pub target: FixupTarget,
+    /// A value to add to the target address.
+    pub additive: Option<u32>,
+}
+
+impl FixupRecord {
+    const SOURCE_TYPE_MASK: u8 = 0x0F;
+    const SOURCE_ALIAS: u8 = 0x10;
+    const SOURCE_LIST: u8 = 0x20;
+
+    const TARGET_TYPE_MASK: u8 = 0x03;
+    const TARGET_ADDITIVE: u8 = 0x04;
+    const TARGET_OFFSET_32: u8 = 0x10;
+    const TARGET_ADDITIVE_32: u8 = 0x20;
+    const TARGET_ORDINAL_16: u8 = 0x40;
+    const TARGET_IMPORT_ORDINAL_8: u8 = 0x80;
+}
+
+impl BinParsable for FixupRecord {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(name_of!(type FixupRecord), |i: &'a [u8]| {
+            let (i, (source, source_type)) = context(
+                name_of!(type FixupSourceType),
+                map_opt(le_u8, |source| {
+                    FixupSourceType::from_u8(source & Self::SOURCE_TYPE_MASK)
+                        .map(|source_type| (source, source_type))
+                }),
+            )(i)?;
+            let (i, flags) = le_u8(i)?;
+
+            let (i, source_list_length) = cond(source & Self::SOURCE_LIST != 0, le_u8)(i)?;
+            let (i, single_source_offset) = cond(source_list_length.is_none(), le_i16)(i)?;
+
+            let has_flag = |flag: u8| flags & flag != 0;
+            let ordinal_16 = has_flag(Self::TARGET_ORDINAL_16);
+            let index = |i: &'a [u8]| -> IResult<&'a [u8], u16, E> {
+                if ordinal_16 {
+                    le_u16(i)
+                } else {
+                    map(le_u8, u16::from)(i)
+                }
+            };
+            let offset = |i: &'a [u8]| -> IResult<&'a [u8], u32, E> {
+                if has_flag(Self::TARGET_OFFSET_32) {
+                    le_u32(i)
+                } else {
+                    map(le_u16, u32::from)(i)
+                }
+            };
+
+            let (i, target) = match flags & Self::TARGET_TYPE_MASK {
+                0 => {
+                    let (i, object) = index(i)?;
+                    // Selector fixups only refer to the object, not an offset in it.
+                    let (i, target_offset) =
+                        cond(source_type != FixupSourceType::Selector16, offset)(i)?;
+                    (
+                        i,
+                        FixupTarget::Internal {
+                            object,
+                            offset: target_offset.unwrap_or(0),
+                        },
+                    )
+                }
+                1 => {
+                    let (i, module) = index(i)?;
+                    let (i, ordinal) = if has_flag(Self::TARGET_IMPORT_ORDINAL_8) {
+                        map(le_u8, u32::from)(i)?
+                    } else {
+                        offset(i)?
+                    };
+                    (i, FixupTarget::ImportOrdinal { module, ordinal })
+                }
+                2 => {
+                    let (i, module) = index(i)?;
+                    let (i, name_offset) = offset(i)?;
+                    (
+                        i,
+                        FixupTarget::ImportName {
+                            module,
+                            name_offset,
+                        },
+                    )
+                }
+                _ => {
+                    let (i, ordinal) = index(i)?;
+                    (i, FixupTarget::InternalEntry { ordinal })
+                }
+            };
+
+            let (i, additive) = cond(has_flag(Self::TARGET_ADDITIVE), |i: &'a [u8]| {
+                if has_flag(Self::TARGET_ADDITIVE_32) {
+                    le_u32(i)
+                } else {
+                    map(le_u16, u32::from)(i)
+                }
+            })(i)?;
+
+            let (i, source_offsets) = match (source_list_length, single_source_offset) {
+                (Some(length), _) => count(le_i16, length as usize)(i)?,
+                (None, Some(offset)) => (i, vec![offset]),
+                (None, None) => unreachable!(),
+            };
+
+            Ok((
+                i,
+                Self {
+                    source_type,
+                    alias: source & Self::SOURCE_ALIAS != 0,
+                    source_offsets,
+                    target,
+                    additive,
+                },
+            ))
+        })(i)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(u8)]
+pub enum FixupSourceType {
+    /// The low byte of the target offset.
+    Byte = 0,
+    /// The 16-bit selector of the target object.
+    Selector16 = 2,
+    /// A 16:16 far pointer.
+    FarPointer16 = 3,
+    /// The 16-bit offset of the target.
+    Offset16 = 5,
+    /// A 16:32 far pointer.
+    FarPointer32 = 6,
+    /// The 32-bit offset of the target.
+    Offset32 = 7,
+    /// The 32-bit offset of the target, relative to the end of the patched location.
+    SelfRelative32 = 8,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FixupTarget {
+    /// An offset in an object of this module. `object` is 1-based.
+    Internal { object: u16, offset: u32 },
+    /// An entry of an imported module, by ordinal. `module` is a 1-based index into the
+    /// import module name table.
+    ImportOrdinal { module: u16, ordinal: u32 },
+    /// An entry of an imported module, by name. `name_offset` is relative to the
+    /// import procedure name table.
+    ImportName { module: u16, name_offset: u32 },
+    /// An entry of this module, referenced by its entry table ordinal.
+    InternalEntry { ordinal: u16 },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nom::error::VerboseError;
+
+    fn parse(record: &[u8]) -> FixupRecord {
+        let (rest, record) = FixupRecord::try_parse::<VerboseError<&[u8]>>(record).unwrap();
+        assert!(rest.is_empty());
+        record
+    }
+
+    #[test]
+    fn parses_targets() {
+        // Selector of object 3, which has no target offset
+        let selector = parse(&[0x02, 0x00, 0x10, 0x00, 0x03]);
+        assert_eq!(selector.source_type, FixupSourceType::Selector16);
+        assert_eq!(
+            selector.target,
+            FixupTarget::Internal {
+                object: 3,
+                offset: 0
+            }
+        );
+
+        // 32-bit target offset in object 0x102, with a 16-bit object number
+        let internal = parse(&[0x07, 0x50, 0xFC, 0xFF, 0x02, 0x01, 0x78, 0x56, 0x34, 0x12]);
+        assert_eq!(internal.source_offsets, vec![-4]);
+        assert_eq!(
+            internal.target,
+            FixupTarget::Internal {
+                object: 0x102,
+                offset: 0x1234_5678
+            }
+        );
+
+        // Import by 8-bit ordinal, with a 32-bit additive
+        let import = parse(&[0x07, 0xA5, 0x20, 0x00, 0x02, 0x2A, 0x10, 0x00, 0x00, 0x00]);
+        assert_eq!(
+            import.target,
+            FixupTarget::ImportOrdinal {
+                module: 2,
+                ordinal: 42
+            }
+        );
+        assert_eq!(import.additive, Some(0x10));
+
+        let entry = parse(&[0x08, 0x03, 0x30, 0x00, 0x05]);
+        assert_eq!(entry.target, FixupTarget::InternalEntry { ordinal: 5 });
+    }
+
+    #[test]
+    fn parses_source_lists() {
+        // Offset32 alias to 1:0100 at three locations
+        let record = parse(&[
+            0x37, 0x00, 0x03, 0x01, 0x00, 0x01, 0x04, 0x00, 0x08, 0x00, 0x10, 0x00,
+        ]);
+        assert!(record.alias);
+        assert_eq!(record.source_type, FixupSourceType::Offset32);
+        assert_eq!(record.source_offsets, vec![4, 8, 0x10]);
+        assert_eq!(
+            record.target,
+            FixupTarget::Internal {
+                object: 1,
+                offset: 0x100
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_source_types() {
+        assert!(
+            FixupRecord::try_parse::<VerboseError<&[u8]>>(&[0x01, 0x00, 0x00, 0x00, 0x01]).is_err()
+        );
+    }
+}
@@ -0,0 +1,320 @@
+use crate::parsers::BinParsable;
+
+use bitflags::bitflags;
+use nameof::name_of;
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    combinator::{map, value},
+    error::context,
+    error::ParseError,
+    number::complete::{le_u16, le_u32, le_u8},
+    sequence::tuple,
+    IResult,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LinearExecutableFormat {
+    /// Linear Executable, used by DOS extenders (DOS/4GW etc.) and Windows VxDs
+    LE,
+    /// Linear eXecutable, used by OS/2 2.x and later
+    LX,
+}
+
+/// The LE/LX header, located at `e_lfanew` of the MZ header.
+///
+/// Unless noted otherwise, table offsets are relative to the start of the LE/LX header.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LEHeader {
+    pub format: LinearExecutableFormat,
+    /// The byte order of the image, zero for little endian.
+    pub byte_order: u8,
+    /// The word order of the image, zero for little endian.
+    pub word_order: u8,
+    /// The version of the executable format, must be zero.
+    pub format_level: u32,
+    /// The minimum CPU required to run the image (1: 286, 2: 386, 3: 486).
+    pub cpu_type: u16,
+    /// The operating system the image targets (1: OS/2, 2: Windows, 3: DOS 4.x, 4: Windows 386).
+    pub os_type: u16,
+    /// The version of the module.
+    pub module_version: u32,
+    /// The flags that indicate the attributes of the module.
+    pub module_flags: LEModuleFlags,
+    /// The number of pages of all objects.
+    pub page_count: u32,
+    /// The 1-based number of the object the initial EIP is relative to.
+    pub eip_object: u32,
+    /// The initial EIP value, relative to `eip_object`.
+    pub eip: u32,
+    /// The 1-based number of the object the initial ESP is relative to.
+    pub esp_object: u32,
+    /// The initial ESP value, relative to `esp_object`.
+    pub esp: u32,
+    /// The size of a page in bytes.
+    pub page_size: u32,
+    /// LE: the number of bytes used on the last page.
+    /// LX: the shift count for page data offsets in the object page map.
+    pub last_page_size_or_page_shift: u32,
+    /// The size of the fixup section (fixup page table and fixup record table) in bytes.
+    pub fixup_section_size: u32,
+    /// The checksum of the fixup section.
+    pub fixup_section_checksum: u32,
+    /// The size of the loader section (everything from the object table up to the fixup section).
+    pub loader_section_size: u32,
+    /// The checksum of the loader section.
+    pub loader_section_checksum: u32,
+    /// The offset of the object table.
+    pub object_table_offset: u32,
+    /// The number of entries in the object table.
+    pub object_count: u32,
+    /// The offset of the object page map.
+    pub object_page_map_offset: u32,
+    /// The offset of the object iterated data map.
+    pub object_iterated_data_map_offset: u32,
+    /// The offset of the resource table.
+    pub resource_table_offset: u32,
+    /// The number of entries in the resource table.
+    pub resource_count: u32,
+    /// The offset of the resident name table.
+    pub resident_name_table_offset: u32,
+    /// The offset of the entry table.
+    pub entry_table_offset: u32,
+    /// The offset of the module format directives table.
+    pub module_directives_offset: u32,
+    /// The number of module format directives.
+    pub module_directives_count: u32,
+    /// The offset of the fixup page table.
+    pub fixup_page_table_offset: u32,
+    /// The offset of the fixup record table.
+    pub fixup_record_table_offset: u32,
+    /// The offset of the import module name table.
+    pub import_module_table_offset: u32,
+    /// The number of entries in the import module name table.
+    pub import_module_count: u32,
+    /// The offset of the import procedure name table.
+    pub import_procedure_table_offset: u32,
+    /// The offset of the per-page checksum table.
+    pub per_page_checksum_offset: u32,
+    /// The offset of the page data, relative to the start of the file.
+    pub data_pages_offset: u32,
+    /// The number of pages that need to be loaded immediately.
+    pub preload_page_count: u32,
+    /// The offset of the non-resident name table, relative to the start of the file.
+    pub non_resident_name_table_offset: u32,
+    /// The size of the non-resident name table in bytes.
+    pub non_resident_name_table_size: u32,
+    /// The checksum of the non-resident name table.
+    pub non_resident_name_table_checksum: u32,
+    /// The 1-based number of the automatic data object.
+    pub auto_data_object: u32,
+    /// The offset of the debug information, relative to the start of the file.
+    pub debug_info_offset: u32,
+    /// The size of the debug information in bytes.
+    pub debug_info_size: u32,
+    /// The number of instance pages in the preload section.
+    pub preload_instance_page_count: u32,
+    /// The number of instance pages in the demand load section.
+    pub demand_instance_page_count: u32,
+    /// The size of the heap (16-bit modules only).
+    pub heap_size: u32,
+}
+
+impl LEHeader {
+    /// The size of the data of the last page in the file, for LE images.
+    pub fn last_page_size(&self) -> Option<u32> {
+        match self.format {
+            LinearExecutableFormat::LE => Some(self.last_page_size_or_page_shift),
+            LinearExecutableFormat::LX => None,
+        }
+    }
+
+    /// The shift count for page data offsets in the object page map, for LX images.
+    pub fn page_shift(&self) -> Option<u32> {
+        match self.format {
+            LinearExecutableFormat::LE => None,
+            LinearExecutableFormat::LX => Some(self.last_page_size_or_page_shift),
+        }
+    }
+}
+
+impl BinParsable for LEHeader {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(
+            name_of!(type LEHeader),
+            map(
+                tuple((
+                    tuple((
+                        alt((
+                            value(LinearExecutableFormat::LE, tag(b"LE")),
+                            value(LinearExecutableFormat::LX, tag(b"LX")),
+                        )), // format
+                        le_u8,                    // byte_order
+                        le_u8,                    // word_order
+                        le_u32,                   // format_level
+                        le_u16,                   // cpu_type
+                        le_u16,                   // os_type
+                        le_u32,                   // module_version
+                        LEModuleFlags::try_parse, // module_flags
+                        le_u32,                   // page_count
+                        le_u32,                   // eip_object
+                        le_u32,                   // eip
+                        le_u32,                   // esp_object
+                        le_u32,                   // esp
+                        le_u32,                   // page_size
+                        le_u32,                   // last_page_size_or_page_shift
+                        le_u32,                   // fixup_section_size
+                        le_u32,                   // fixup_section_checksum
+                        le_u32,                   // loader_section_size
+                        le_u32,                   // loader_section_checksum
+                    )),
+                    tuple((
+                        le_u32, // object_table_offset
+                        le_u32, // object_count
+                        le_u32, // object_page_map_offset
+                        le_u32, // object_iterated_data_map_offset
+                        le_u32, // resource_table_offset
+                        le_u32, // resource_count
+                        le_u32, // resident_name_table_offset
+                        le_u32, // entry_table_offset
+                        le_u32, // module_directives_offset
+                        le_u32, // module_directives_count
+                        le_u32, // fixup_page_table_offset
+                        le_u32, // fixup_record_table_offset
+                        le_u32, // import_module_table_offset
+                        le_u32, // import_module_count
+                        le_u32, // import_procedure_table_offset
+                        le_u32, // per_page_checksum_offset
+                        le_u32, // data_pages_offset
+                        le_u32, // preload_page_count
+                    )),
+                    tuple((
+                        le_u32, // non_resident_name_table_offset
+                        le_u32, // non_resident_name_table_size
+                        le_u32, // non_resident_name_table_checksum
+                        le_u32, // auto_data_object
+                        le_u32, // debug_info_offset
+                        le_u32, // debug_info_size
+                        le_u32, // preload_instance_page_count
+                        le_u32, // demand_instance_page_count
+                        le_u32, // heap_size
+                    )),
+                )),
+                |(p, q, r)| Self {
+                    format: p.0,
+                    byte_order: p.1,
+                    word_order: p.2,
+                    format_level: p.3,
+                    cpu_type: p.4,
+                    os_type: p.5,
+                    module_version: p.6,
+                    module_flags: p.7,
+                    page_count: p.8,
+                    eip_object: p.9,
+                    eip: p.10,
+                    esp_object: p.11,
+                    esp: p.12,
+                    page_size: p.13,
+                    last_page_size_or_page_shift: p.14,
+                    fixup_section_size: p.15,
+                    fixup_section_checksum: p.16,
+                    loader_section_size: p.17,
+                    loader_section_checksum: p.18,
+                    object_table_offset: q.0,
+                    object_count: q.1,
+                    object_page_map_offset: q.2,
+                    object_iterated_data_map_offset: q.3,
+                    resource_table_offset: q.4,
+                    resource_count: q.5,
+                    resident_name_table_offset: q.6,
+                    entry_table_offset: q.7,
+                    module_directives_offset: q.8,
+                    module_directives_count: q.9,
+                    fixup_page_table_offset: q.10,
+                    fixup_record_table_offset: q.11,
+                    import_module_table_offset: q.12,
+                    import_module_count: q.13,
+                    import_procedure_table_offset: q.14,
+                    per_page_checksum_offset: q.15,
+                    data_pages_offset: q.16,
+                    preload_page_count: q.17,
+                    non_resident_name_table_offset: r.0,
+                    non_resident_name_table_size: r.1,
+                    non_resident_name_table_checksum: r.2,
+                    auto_data_object: r.3,
+                    debug_info_offset: r.4,
+                    debug_info_size: r.5,
+                    preload_instance_page_count: r.6,
+                    demand_instance_page_count: r.7,
+                    heap_size: r.8,
+                },
+            ),
+        )(i)
+    }
+}
+
+bitflags! {
+    #[derive(Default)]
+    pub struct LEModuleFlags: u32 {
+        /// The module uses per-process library initialization.
+        const PER_PROCESS_INIT = 0x0000_0004;
+        /// The module has no internal fixups, it must be loaded at its preferred addresses.
+        const NO_INTERNAL_FIXUPS = 0x0000_0010;
+        /// The module has no external fixups.
+        const NO_EXTERNAL_FIXUPS = 0x0000_0020;
+        /// The application is incompatible with the Presentation Manager.
+        const PM_INCOMPATIBLE = 0x0000_0100;
+        /// The application is compatible with the Presentation Manager.
+        const PM_COMPATIBLE = 0x0000_0200;
+        /// The application uses the Presentation Manager API.
+        const USES_PM_API = 0x0000_0300;
+        /// The module is not loadable, e.g. because of link errors.
+        const NOT_LOADABLE = 0x0000_2000;
+        /// The module is a library (DLL).
+        const LIBRARY = 0x0000_8000;
+        /// The module is a protected memory library.
+        const PROTECTED_MEMORY_LIBRARY = 0x0001_8000;
+        /// The module is a physical device driver.
+        const PHYSICAL_DEVICE_DRIVER = 0x0002_0000;
+        /// The module is a virtual device driver.
+        const VIRTUAL_DEVICE_DRIVER = 0x0002_8000;
+        /// The module uses per-process library termination.
+        const PER_PROCESS_TERMINATION = 0x4000_0000;
+    }
+}
+
+impl BinParsable for LEModuleFlags {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(
+            name_of!(type LEModuleFlags),
+            map(le_u32, Self::from_bits_truncate),
+        )(i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::le::tests::le_file;
+    use nom::error::VerboseError;
+
+    #[test]
+    fn parses_le_and_lx_headers() {
+        let mut file = le_file();
+        let (rest, header) = LEHeader::try_parse::<VerboseError<&[u8]>>(&file[0x40..]).unwrap();
+        assert_eq!(file.len() - rest.len(), 0x40 + 0xAC);
+        assert_eq!(header.format, LinearExecutableFormat::LE);
+        assert_eq!((header.cpu_type, header.os_type), (2, 4));
+        assert_eq!((header.page_count, header.page_size), (2, 0x100));
+        assert_eq!(header.last_page_size(), Some(0x10));
+        assert_eq!(header.page_shift(), None);
+        assert_eq!(header.object_table_offset, 0xB0);
+        assert_eq!(header.data_pages_offset, 0x200);
+
+        file[0x41] = b'X';
+        let (_, header) = LEHeader::try_parse::<VerboseError<&[u8]>>(&file[0x40..]).unwrap();
+        assert_eq!(header.format, LinearExecutableFormat::LX);
+        assert_eq!(header.last_page_size(), None);
+        assert_eq!(header.page_shift(), Some(0x10));
+    }
+}
@@ -0,0 +1,312 @@
+use crate::parsers::le::FixupRecord;
+use crate::parsers::BinParsable;
+
+use bitflags::bitflags;
+use nameof::name_of;
+use nom::{
+    combinator::{map, map_opt},
+    error::context,
+    error::ParseError,
+    number::complete::{le_u16, le_u32, le_u8},
+    sequence::tuple,
+    IResult,
+};
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Object {
+    pub header: ObjectHeader,
+    pub pages: Vec<ObjectPage>,
+}
+
+impl Object {
+    /// Returns the contents of the object's pages, concatenated and cut off at the virtual size
+    /// of the object. The rest of the object, up to its virtual size, is zero-filled memory.
+    pub fn data(&self, page_size: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        for page in &self.pages {
+            let page_start = data.len();
+            data.extend_from_slice(&page.data);
+            data.resize(page_start + page_size as usize, 0);
+        }
+        data.truncate(self.header.virtual_size as usize);
+        data
+    }
+}
+
+/// An entry of the object table.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ObjectHeader {
+    /// The size of the object when loaded into memory.
+    pub virtual_size: u32,
+    /// The preferred (linear) address the object is loaded at.
+    pub relocation_base_address: u32,
+    /// The flags that describe the characteristics of the object.
+    pub flags: ObjectFlags,
+    /// The 1-based index of the first page of the object in the object page map.
+    pub page_table_index: u32,
+    /// The number of entries in the object page map that belong to this object.
+    pub page_table_entries: u32,
+    pub reserved: u32,
+}
+
+impl BinParsable for ObjectHeader {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(
+            name_of!(type ObjectHeader),
+            map(
+                tuple((
+                    le_u32,                 // virtual_size
+                    le_u32,                 // relocation_base_address
+                    ObjectFlags::try_parse, // flags
+                    le_u32,                 // page_table_index
+                    le_u32,                 // page_table_entries
+                    le_u32,                 // reserved
+                )),
+                |p| Self {
+                    virtual_size: p.0,
+                    relocation_base_address: p.1,
+                    flags: p.2,
+                    page_table_index: p.3,
+                    page_table_entries: p.4,
+                    reserved: p.5,
+                },
+            ),
+        )(i)
+    }
+}
+
+bitflags! {
+    #[derive(Default)]
+    pub struct ObjectFlags: u32 {
+        /// The object can be read.
+        const READABLE = 0x0000_0001;
+        /// The object can be written to.
+        const WRITABLE = 0x0000_0002;
+        /// The object can be executed.
+        const EXECUTABLE = 0x0000_0004;
+        /// The object contains resources.
+        const RESOURCE = 0x0000_0008;
+        /// The object can be discarded.
+        const DISCARDABLE = 0x0000_0010;
+        /// The object is shared between processes.
+        const SHARED = 0x0000_0020;
+        /// The object has pages that are loaded immediately.
+        const PRELOAD = 0x0000_0040;
+        /// The object has invalid pages.
+        const INVALID = 0x0000_0080;
+        /// The object has zero-filled pages.
+        const ZERO_FILLED = 0x0000_0100;
+        /// The object is resident (valid for VxDs only).
+        const RESIDENT = 0x0000_0200;
+        /// The object is resident and contiguous.
+        const RESIDENT_CONTIGUOUS = 0x0000_0300;
+        /// The object is resident and long-lockable.
+        const RESIDENT_LONG_LOCKABLE = 0x0000_0400;
+        /// The object requires a 16:16 alias.
+        const ALIAS_16_16 = 0x0000_1000;
+        /// The object uses 32-bit code/data ("big" segment).
+        const BIG_DEFAULT = 0x0000_2000;
+        /// The object is a conforming code segment.
+        const CONFORMING = 0x0000_4000;
+        /// The object requires I/O privilege.
+        const IO_PRIVILEGE = 0x0000_8000;
+    }
+}
+
+impl BinParsable for ObjectFlags {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(
+            name_of!(type ObjectFlags),
+            map(le_u32, Self::from_bits_truncate),
+        )(i)
+    }
+}
+
+/// A page of an object, together with the fixups that apply to it.
+#[derive(PartialEq, Eq)]
+pub struct ObjectPage {
+    pub map_entry: ObjectPageMapEntry,
+    /// The (decompressed) page data. This might be shorter than the page size,
+    /// the rest of the page is zero-filled.
+    pub data: Vec<u8>,
+    pub fixups: Vec<FixupRecord>,
+}
+
+impl fmt::Debug for ObjectPage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct(name_of!(type ObjectPage))
+            .field(name_of!(map_entry in ObjectPage), &self.map_entry)
+            .field(
+                name_of!(data in ObjectPage),
+                &format!("Vec<u8>, len: {:X}", self.data.len()),
+            )
+            .field(name_of!(fixups in ObjectPage), &self.fixups)
+            .finish()
+    }
+}
+
+/// An entry of the object page map.
+///
+/// LE and LX use different encodings, this is the common representation of both.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ObjectPageMapEntry {
+    /// The offset of the page data, relative to the data pages offset of the header.
+    pub data_offset: u32,
+    /// The size of the page data in the file.
+    pub data_size: u32,
+    pub page_type: PageType,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PageType {
+    /// The page data is stored as-is.
+    Legal,
+    /// The page data is stored as iterated data records (EXEPACK1).
+    Iterated,
+    /// The page is invalid, accessing it is an error.
+    Invalid,
+    /// The page is not stored in the file, and filled with zeros.
+    ZeroFilled,
+    /// The page data is compressed with the EXEPACK2 algorithm.
+    Compressed,
+    Unknown(u16),
+}
+
+impl ObjectPageMapEntry {
+    /// Parses an LE page map entry.
+    ///
+    /// LE page map entries only store a 1-based page number (big endian, 24 bits).
+    /// All pages have the same size, except the last one. Page numbers whose data offset does
+    /// not fit into 32 bits are rejected.
+    pub fn try_parse_le<'a, E: ParseError<&'a [u8]>>(
+        page_size: u32,
+        page_count: u32,
+        last_page_size: u32,
+    ) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], Self, E> {
+        move |i: &'a [u8]| {
+            context(
+                name_of!(type ObjectPageMapEntry),
+                map_opt(
+                    tuple((le_u8, le_u8, le_u8, le_u8)),
+                    |(high, middle, low, flags)| {
+                        let page_number = (high as u32) << 16 | (middle as u32) << 8 | low as u32;
+                        Some(Self {
+                            data_offset: page_number.saturating_sub(1).checked_mul(page_size)?,
+                            data_size: if page_number == page_count {
+                                last_page_size
+                            } else {
+                                page_size
+                            },
+                            page_type: PageType::from_raw(flags as u16),
+                        })
+                    },
+                ),
+            )(i)
+        }
+    }
+
+    /// Parses an LX page map entry.
+    pub fn try_parse_lx<'a, E: ParseError<&'a [u8]>>(
+        page_shift: u32,
+    ) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], Self, E> {
+        move |i: &'a [u8]| {
+            context(
+                name_of!(type ObjectPageMapEntry),
+                map(tuple((le_u32, le_u16, le_u16)), |p| Self {
+                    data_offset: p.0.checked_shl(page_shift).unwrap_or(0),
+                    data_size: p.1 as u32,
+                    page_type: PageType::from_raw(p.2),
+                }),
+            )(i)
+        }
+    }
+}
+
+impl PageType {
+    fn from_raw(raw: u16) -> Self {
+        match raw {
+            0 => PageType::Legal,
+            1 => PageType::Iterated,
+            2 => PageType::Invalid,
+            3 => PageType::ZeroFilled,
+            5 => PageType::Compressed,
+            other => PageType::Unknown(other),
+        }
+    }
+}
+
+/// Expands iterated data records (EXEPACK1).
+///
+/// Each record consists of a 16-bit repeat count, a 16-bit data length and the data
+/// to repeat. A repeat count of zero ends the page.
+pub fn expand_iterated_page(mut data: &[u8], page_size: usize) -> Vec<u8> {
+    let mut page = Vec::with_capacity(page_size);
+    while data.len() >= 4 && page.len() < page_size {
+        let repeat = u16::from_le_bytes([data[0], data[1]]) as usize;
+        let length = u16::from_le_bytes([data[2], data[3]]) as usize;
+        let pattern = match data.get(4..4 + length) {
+            Some(pattern) if repeat != 0 => pattern,
+            _ => break,
+        };
+        for _ in 0..repeat {
+            page.extend_from_slice(pattern);
+        }
+        data = &data[4 + length..];
+    }
+    page.truncate(page_size);
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nom::error::VerboseError;
+
+    #[test]
+    fn expands_iterated_records() {
+        let records = [
+            0x03, 0x00, 0x02, 0x00, 0xAB, 0xCD, // 3x AB CD
+            0x02, 0x00, 0x01, 0x00, 0x90, // 2x 90
+            0x00, 0x00, // end of page
+        ];
+
+        assert_eq!(
+            expand_iterated_page(&records, 0x1000),
+            vec![0xAB, 0xCD, 0xAB, 0xCD, 0xAB, 0xCD, 0x90, 0x90]
+        );
+        assert_eq!(
+            expand_iterated_page(&records, 4),
+            vec![0xAB, 0xCD, 0xAB, 0xCD]
+        );
+    }
+
+    #[test]
+    fn locates_le_and_lx_pages() {
+        let le = ObjectPageMapEntry::try_parse_le::<VerboseError<&[u8]>>(0x1000, 3, 0x200);
+        let (_, entry) = le(&[0x00, 0x00, 0x02, 0x01]).unwrap();
+        assert_eq!(
+            entry,
+            ObjectPageMapEntry {
+                data_offset: 0x1000,
+                data_size: 0x1000,
+                page_type: PageType::Iterated,
+            }
+        );
+        let (_, last) = le(&[0x00, 0x00, 0x03, 0x00]).unwrap();
+        assert_eq!((last.data_offset, last.data_size), (0x2000, 0x200));
+        // The data offset of page 0x100000 does not fit into 32 bits
+        assert!(le(&[0x10, 0x00, 0x01, 0x00]).is_err());
+
+        let lx = ObjectPageMapEntry::try_parse_lx::<VerboseError<&[u8]>>(4);
+        let (_, entry) = lx(&[0x10, 0x00, 0x00, 0x00, 0x80, 0x00, 0x03, 0x00]).unwrap();
+        assert_eq!(
+            entry,
+            ObjectPageMapEntry {
+                data_offset: 0x100,
+                data_size: 0x80,
+                page_type: PageType::ZeroFilled,
+            }
+        );
+    }
+}
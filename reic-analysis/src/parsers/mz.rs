@@ -1,13 +1,16 @@
+use crate::parsers::{BinParsable, BinWritable};
+
 use nameof::name_of;
 use nom::{
-    bytes::complete::tag,
-    combinator::map,
+    bytes::complete::{tag, take},
+    combinator::{map, verify},
     error::{context, ParseError},
     multi::count,
     number::complete::{le_u16, le_u32},
-    sequence::tuple,
+    sequence::{pair, tuple},
     IResult,
 };
+use std::fmt;
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct MZHeader {
@@ -49,6 +52,26 @@ pub struct MZHeader {
     pub e_lfanew: u32,
 }
 
+impl MZHeader {
+    /// The size of the header area in bytes, as specified by `e_cparhdr`.
+    pub fn header_size(&self) -> usize {
+        self.e_cparhdr as usize * 16
+    }
+
+    /// The size of the file as seen by the DOS loader, in bytes.
+    ///
+    /// `e_cp` counts 512-byte pages, the last of which only uses `e_cblp` bytes
+    /// (unless `e_cblp` is zero, in which case the last page is full). Like DOS, an `e_cblp`
+    /// of 512 or more is taken as is rather than truncated to the page size.
+    pub fn file_size(&self) -> usize {
+        let pages = self.e_cp as usize * 512;
+        match self.e_cblp {
+            0 => pages,
+            last_page_bytes => (pages + last_page_bytes as usize).saturating_sub(512),
+        }
+    }
+}
+
 impl MZHeader {
     pub fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
         context(
@@ -107,3 +130,207 @@ impl MZHeader {
         )(i)
     }
 }
+
+impl BinWritable for MZHeader {
+    fn write_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(b"MZ");
+        for value in [
+            self.e_cblp,
+            self.e_cp,
+            self.e_crlc,
+            self.e_cparhdr,
+            self.e_minalloc,
+            self.e_maxalloc,
+            self.e_ss,
+            self.e_sp,
+            self.e_csum,
+            self.e_ip,
+            self.e_cs,
+            self.e_lfarlc,
+            self.e_ovno,
+        ]
+        .iter()
+        .chain(&self.e_res)
+        .chain(&[self.e_oemid, self.e_oeminfo])
+        .chain(&self.e_res2)
+        {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        out.extend_from_slice(&self.e_lfanew.to_le_bytes());
+    }
+}
+
+/// An entry of the MZ relocation table.
+///
+/// Each entry points to a 16-bit segment value inside the load module,
+/// relative to the start of the load module.
+/// The DOS loader adds the load segment to every such value.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MZRelocation {
+    pub offset: u16,
+    pub segment: u16,
+}
+
+impl MZRelocation {
+    /// The offset of the patched word from the start of the load module.
+    pub fn linear_offset(&self) -> usize {
+        self.segment as usize * 16 + self.offset as usize
+    }
+}
+
+impl BinParsable for MZRelocation {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(
+            name_of!(type MZRelocation),
+            map(pair(le_u16, le_u16), |p| Self {
+                offset: p.0,
+                segment: p.1,
+            }),
+        )(i)
+    }
+}
+
+/// A standalone DOS executable.
+///
+/// The load module is the part of the file after the header area (`e_cparhdr`)
+/// up to the file size specified by `e_cp`/`e_cblp`. Anything after that (overlays,
+/// a new exe header, appended data) is not part of the load module.
+#[derive(PartialEq, Eq)]
+pub struct MZImage {
+    pub header: MZHeader,
+    pub relocations: Vec<MZRelocation>,
+    pub load_module: Vec<u8>,
+}
+
+impl MZImage {
+    /// Returns a copy of the load module as it would look after being loaded at `load_segment`,
+    /// i.e. with `load_segment` added to every relocated segment value.
+    ///
+    /// Relocations pointing outside of the load module are ignored.
+    pub fn relocated_load_module(&self, load_segment: u16) -> Vec<u8> {
+        let mut module = self.load_module.clone();
+        for relocation in &self.relocations {
+            let offset = relocation.linear_offset();
+            if let Some(word) = module.get_mut(offset..offset + 2) {
+                let value = u16::from_le_bytes([word[0], word[1]]).wrapping_add(load_segment);
+                word.copy_from_slice(&value.to_le_bytes());
+            }
+        }
+        module
+    }
+
+    /// The size of the memory the DOS loader allocates beyond the load module, in bytes.
+    pub fn min_extra_size(&self) -> usize {
+        self.header.e_minalloc as usize * 16
+    }
+}
+
+impl BinParsable for MZImage {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(image: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(name_of!(type MZImage), |file: &'a [u8]| {
+            let (_, header) = context(
+                "Load module size sanity check",
+                verify(MZHeader::try_parse, |mz| {
+                    mz.header_size() >= 0x1C
+                        && mz.header_size() <= mz.file_size()
+                        && mz.file_size() <= file.len()
+                }),
+            )(file)?;
+
+            let (_, relocations) = context("Relocation table", |file: &'a [u8]| {
+                let (i, _) = take(header.e_lfarlc)(file)?;
+                count(MZRelocation::try_parse, header.e_crlc as usize)(i)
+            })(file)?;
+
+            let load_module = file[header.header_size()..header.file_size()].to_vec();
+
+            Ok((
+                &file[header.file_size()..],
+                Self {
+                    header,
+                    relocations,
+                    load_module,
+                },
+            ))
+        })(image)
+    }
+}
+
+impl fmt::Debug for MZImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct(name_of!(type MZImage))
+            .field(name_of!(header in MZImage), &self.header)
+            .field(name_of!(relocations in MZImage), &self.relocations)
+            .field(
+                name_of!(load_module in MZImage),
+                &format!("Vec<u8>, len: {:X}", self.load_module.len()),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nom::error::VerboseError;
+
+    fn dos_exe() -> Vec<u8> {
+        let mut file = vec![0u8; 0x40];
+        file[0..2].copy_from_slice(b"MZ");
+        // 0x40 header bytes + 0x10 bytes of load module, one (partial) page
+        file[0x02..0x04].copy_from_slice(&0x50u16.to_le_bytes()); // e_cblp
+        file[0x04..0x06].copy_from_slice(&1u16.to_le_bytes()); // e_cp
+        file[0x06..0x08].copy_from_slice(&1u16.to_le_bytes()); // e_crlc
+        file[0x08..0x0A].copy_from_slice(&4u16.to_le_bytes()); // e_cparhdr
+        file[0x14..0x16].copy_from_slice(&0x0003u16.to_le_bytes()); // e_ip
+        file[0x16..0x18].copy_from_slice(&0x0000u16.to_le_bytes()); // e_cs
+        file[0x18..0x1A].copy_from_slice(&0x1Cu16.to_le_bytes()); // e_lfarlc
+                                                                  // relocation entry 0000:0001 at 0x1C
+        file[0x1C..0x20].copy_from_slice(&[0x01, 0x00, 0x00, 0x00]);
+        // load module: mov ax, seg 0x0001; nops
+        file.extend_from_slice(&[0xB8, 0x01, 0x00, 0x90]);
+        file.extend_from_slice(&[0x90; 12]);
+        // trailing overlay data
+        file.extend_from_slice(b"overlay");
+        file
+    }
+
+    #[test]
+    fn parses_load_module() {
+        let file = dos_exe();
+        let (rest, image) = MZImage::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+
+        assert_eq!(rest, b"overlay");
+        assert_eq!(image.load_module.len(), 0x10);
+        assert_eq!(
+            image.relocations,
+            vec![MZRelocation {
+                offset: 1,
+                segment: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn computes_file_size_from_last_page() {
+        let (_, mut header) = MZHeader::try_parse::<VerboseError<&[u8]>>(&dos_exe()).unwrap();
+        assert_eq!(header.file_size(), 0x50);
+
+        header.e_cp = 2;
+        header.e_cblp = 0;
+        assert_eq!(header.file_size(), 1024);
+        header.e_cblp = 512;
+        assert_eq!(header.file_size(), 1024);
+        header.e_cblp = 0x210;
+        assert_eq!(header.file_size(), 1024 + 0x10);
+    }
+
+    #[test]
+    fn applies_relocations() {
+        let file = dos_exe();
+        let (_, image) = MZImage::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+
+        let module = image.relocated_load_module(0x1000);
+        assert_eq!(&module[0..4], &[0xB8, 0x01, 0x10, 0x90]);
+    }
+}
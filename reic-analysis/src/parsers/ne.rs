@@ -0,0 +1,255 @@
+mod header;
+pub use header::*;
+
+mod entry_table;
+pub use entry_table::*;
+
+mod names;
+pub use names::*;
+
+mod segment;
+pub use segment::*;
+
+use crate::parsers::mz::MZHeader;
+use crate::parsers::BinParsable;
+
+use nameof::name_of;
+use nom::{
+    bytes::complete::take, combinator::map_opt, combinator::verify, error::context,
+    error::ParseError, multi::count, number::complete::le_u16, IResult,
+};
+use std::collections::{btree_map, BTreeMap};
+
+/// A 16-bit "New Executable", as used by Windows 3.x and OS/2 1.x.
+#[derive(Debug, PartialEq, Eq)]
+pub struct NEImage {
+    pub mz_header: MZHeader,
+    pub ne_header: NEHeader,
+    /// The segments of the image. Segment numbers are 1-based, so segment `n` is at index `n - 1`.
+    pub segments: Vec<Segment>,
+    pub entries: BTreeMap<u16, Entry>,
+    /// The module name, followed by the exported names of resident entries.
+    pub resident_names: Vec<NameTableEntry>,
+    /// The module description, followed by the exported names of non-resident entries.
+    pub non_resident_names: Vec<NameTableEntry>,
+    /// The names of the modules this image imports from.
+    pub module_references: Vec<String>,
+    /// The names of entries imported by name, keyed by their offset in the imported names table.
+    pub imported_names: BTreeMap<u16, String>,
+}
+
+impl NEImage {
+    /// The module name, as specified by the first entry of the resident name table.
+    pub fn module_name(&self) -> Option<&str> {
+        self.resident_names.first().map(|entry| entry.name.as_str())
+    }
+
+    /// Returns the exported name of the entry with the given ordinal, if it has one.
+    pub fn entry_name(&self, ordinal: u16) -> Option<&str> {
+        self.resident_names
+            .iter()
+            .skip(1)
+            .chain(self.non_resident_names.iter().skip(1))
+            .find(|entry| entry.ordinal == ordinal)
+            .map(|entry| entry.name.as_str())
+    }
+
+    /// Returns the name of the module with the given 1-based module reference index.
+    pub fn module_reference(&self, module: u16) -> Option<&str> {
+        self.module_references
+            .get((module as usize).checked_sub(1)?)
+            .map(String::as_str)
+    }
+
+    /// Returns the segment with the given 1-based segment number.
+    pub fn segment(&self, number: u16) -> Option<&Segment> {
+        self.segments.get((number as usize).checked_sub(1)?)
+    }
+}
+
+impl BinParsable for NEImage {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(image: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(name_of!(type NEImage), |file: &'a [u8]| {
+            let (_, mz_header) = context(
+                "NE header offset sanity check",
+                verify(MZHeader::try_parse, |mz| file.len() > mz.e_lfanew as usize),
+            )(file)?;
+
+            let (ne, _) = take(mz_header.e_lfanew)(file)?;
+            let (i, ne_header) = NEHeader::try_parse(ne)?;
+
+            let table = |offset: u16| take(offset)(ne).map(|(table, _)| table);
+
+            let (_, segments) = context(
+                "Segment table",
+                count(
+                    map_opt(SegmentHeader::try_parse, |header| {
+                        Segment::from_file_and_header(file, ne_header.alignment_shift, header)
+                    }),
+                    ne_header.segment_count as usize,
+                ),
+            )(table(ne_header.segment_table_offset)?)?;
+
+            let (_, entries) = context(
+                "Entry table",
+                entry_table(ne_header.entry_table_size as usize),
+            )(table(ne_header.entry_table_offset)?)?;
+
+            let (_, resident_names) = context("Resident name table", name_table)(table(
+                ne_header.resident_name_table_offset,
+            )?)?;
+
+            let non_resident_names = if ne_header.non_resident_name_table_size == 0 {
+                Vec::new()
+            } else {
+                let (non_resident, _) = take(ne_header.non_resident_name_table_offset)(file)?;
+                context("Non-resident name table", name_table)(non_resident)?.1
+            };
+
+            let imported_names_table = table(ne_header.imported_names_table_offset)?;
+            let (_, module_references) =
+                context(
+                    "Module reference table",
+                    count(
+                        |i| {
+                            let (i, offset) = le_u16(i)?;
+                            let (name, _) = take(offset)(imported_names_table)?;
+                            let (_, name) = pascal_string(name)?;
+                            Ok((i, name))
+                        },
+                        ne_header.module_reference_count as usize,
+                    ),
+                )(table(ne_header.module_reference_table_offset)?)?;
+
+            let mut imported_names = BTreeMap::new();
+            for relocation in segments.iter().flat_map(|segment| &segment.relocations) {
+                if let RelocationTarget::ImportName { name_offset, .. } = relocation.target {
+                    if let btree_map::Entry::Vacant(vacant) = imported_names.entry(name_offset) {
+                        let (name, _) = take(name_offset)(imported_names_table)?;
+                        let (_, name) = context("Imported name", pascal_string)(name)?;
+                        vacant.insert(name);
+                    }
+                }
+            }
+
+            Ok((
+                i,
+                Self {
+                    mz_header,
+                    ne_header,
+                    segments,
+                    entries,
+                    resident_names,
+                    non_resident_names,
+                    module_references,
+                    imported_names,
+                },
+            ))
+        })(image)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use nom::error::VerboseError;
+
+    /// A Windows module with a code segment calling an imported entry twice and loading the
+    /// selector of its iterated data segment, and an exported entry in the code segment.
+    pub(crate) fn ne_file() -> Vec<u8> {
+        let mut file = vec![0u8; 0x220];
+        let mut put =
+            |offset: usize, bytes: &[u8]| file[offset..offset + bytes.len()].copy_from_slice(bytes);
+        put(0x00, b"MZ");
+        put(0x3C, &0x40u32.to_le_bytes()); // e_lfanew
+
+        put(0x40, b"NE");
+        put(0x44, &0x70u16.to_le_bytes()); // entry_table_offset
+        put(0x46, &14u16.to_le_bytes()); // entry_table_size
+        put(0x4C, &0x0302u16.to_le_bytes()); // flags
+        put(0x54, &0x0000u16.to_le_bytes()); // initial_ip
+        put(0x56, &0x0001u16.to_le_bytes()); // initial_cs
+        put(0x5C, &2u16.to_le_bytes()); // segment_count
+        put(0x5E, &1u16.to_le_bytes()); // module_reference_count
+        put(0x62, &0x40u16.to_le_bytes()); // segment_table_offset
+        put(0x64, &0x50u16.to_le_bytes()); // resource_table_offset
+        put(0x66, &0x50u16.to_le_bytes()); // resident_name_table_offset
+        put(0x68, &0x60u16.to_le_bytes()); // module_reference_table_offset
+        put(0x6A, &0x62u16.to_le_bytes()); // imported_names_table_offset
+        put(0x72, &4u16.to_le_bytes()); // alignment_shift
+        put(0x76, &[0x02]); // target_os: Windows
+
+        // Entry table: a fixed entry at 1:0000, and a movable entry in the invalid segment 0
+        put(0xB0, &[0x01, 0x01, 0x01, 0x00, 0x00]);
+        put(
+            0xB5,
+            &[0x01, 0xFF, 0x01, 0xCD, 0x3F, 0x00, 0x00, 0x00, 0x00],
+        );
+
+        // Segment table: code at 0x100 with relocations, iterated data at 0x200
+        put(0x80, &[0x10, 0x00, 0x10, 0x00, 0x00, 0x01, 0x10, 0x00]);
+        put(0x88, &[0x20, 0x00, 0x06, 0x00, 0x09, 0x00, 0x20, 0x00]);
+
+        put(0x90, b"\x04DEMO\x00\x00\x04Main\x01\x00\x00"); // resident names
+        put(0xA0, &1u16.to_le_bytes()); // module reference to KERNEL
+        put(0xA2, b"\x00\x06KERNEL\x04Beep"); // imported names
+
+        // call far Beep (chained twice); retf; mov ax, seg 2
+        put(
+            0x100,
+            &[
+                0x9A, 0x08, 0x00, 0x00, 0x00, 0x90, 0x90, 0x9A, 0xFF, 0xFF, 0x00, 0x00, 0xCB, 0xB8,
+                0xFF, 0xFF,
+            ],
+        );
+        put(0x110, &2u16.to_le_bytes());
+        put(0x112, &[0x03, 0x02, 0x01, 0x00, 0x01, 0x00, 0x08, 0x00]);
+        put(0x11A, &[0x02, 0x00, 0x0E, 0x00, 0x02, 0x00, 0x00, 0x00]);
+
+        put(0x200, &[0x03, 0x00, 0x02, 0x00, 0xAB, 0xCD]); // 3x AB CD
+        file
+    }
+
+    #[test]
+    fn parses_image() {
+        let file = ne_file();
+        let (_, image) = NEImage::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+
+        assert_eq!(image.ne_header.segment_count, 2);
+        assert_eq!(image.module_name(), Some("DEMO"));
+        assert_eq!(image.entry_name(1), Some("Main"));
+        assert_eq!(image.module_reference(1), Some("KERNEL"));
+        assert_eq!(image.module_reference(0), None);
+        assert_eq!(
+            image.imported_names.get(&8).map(String::as_str),
+            Some("Beep")
+        );
+        assert_eq!(
+            image.entries.keys().copied().collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        let code = image.segment(1).unwrap();
+        assert!(code.header.is_code());
+        assert_eq!(code.data.len(), 0x10);
+        assert_eq!(
+            code.relocations[0].target,
+            RelocationTarget::ImportName {
+                module: 1,
+                name_offset: 8
+            }
+        );
+        assert_eq!(
+            code.relocations[1].target,
+            RelocationTarget::InternalFixed {
+                segment: 2,
+                offset: 0
+            }
+        );
+
+        let data = image.segment(2).unwrap();
+        assert!(!data.header.is_code());
+        assert_eq!(data.data, vec![0xAB, 0xCD, 0xAB, 0xCD, 0xAB, 0xCD]);
+        assert!(image.segment(3).is_none());
+    }
+}
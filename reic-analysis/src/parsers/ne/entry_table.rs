@@ -0,0 +1,166 @@
+use crate::parsers::BinParsable;
+
+use bitflags::bitflags;
+use nameof::name_of;
+use nom::{
+    combinator::map,
+    error::context,
+    error::ParseError,
+    error::{make_error, ErrorKind},
+    number::complete::{le_u16, le_u8},
+    sequence::tuple,
+    Err, IResult,
+};
+use std::collections::BTreeMap;
+
+/// An entry point of the module, as listed in the entry table.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub flags: EntryFlags,
+    pub location: EntryLocation,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum EntryLocation {
+    /// The entry is located in a segment.
+    ///
+    /// Entries in movable segments are referenced via the entry table by relocations,
+    /// as the segment might be moved in memory at run time.
+    Segment {
+        segment: u8,
+        offset: u16,
+        movable: bool,
+    },
+    /// The entry is a constant value that does not refer to a segment.
+    Constant(u16),
+}
+
+bitflags! {
+    #[derive(Default)]
+    pub struct EntryFlags: u8 {
+        /// The entry is exported.
+        const EXPORTED = 0x01;
+        /// The entry uses a global (shared) data segment.
+        const SHARED_DATA = 0x02;
+    }
+}
+
+/// The segment indicator of a bundle of unused entries.
+const UNUSED_BUNDLE: u8 = 0x00;
+/// The segment indicator of a bundle of constant entries.
+const CONSTANT_BUNDLE: u8 = 0xFE;
+/// The segment indicator of a bundle of entries in movable segments.
+const MOVABLE_BUNDLE: u8 = 0xFF;
+
+/// Parses the entry table, which consists of bundles of entries sharing the same segment type.
+///
+/// Entries are keyed by their ordinal, which starts at 1.
+pub fn entry_table<'a, E: ParseError<&'a [u8]>>(
+    size: usize,
+) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], BTreeMap<u16, Entry>, E> {
+    move |table: &'a [u8]| {
+        context(name_of!(type Entry), |table: &'a [u8]| {
+            let mut entries = BTreeMap::new();
+            let mut ordinal = 1u16;
+            let mut i = table;
+            while table.len() - i.len() < size {
+                let (rest, bundle_size) = le_u8(i)?;
+                if bundle_size == 0 {
+                    i = rest;
+                    break;
+                }
+                let (rest, indicator) = le_u8(rest)?;
+                i = rest;
+
+                for _ in 0..bundle_size {
+                    let entry = match indicator {
+                        UNUSED_BUNDLE => None,
+                        MOVABLE_BUNDLE => {
+                            let (rest, (flags, _int_3f, segment, offset)) =
+                                tuple((EntryFlags::try_parse, le_u16, le_u8, le_u16))(i)?;
+                            i = rest;
+                            Some(Entry {
+                                flags,
+                                location: EntryLocation::Segment {
+                                    segment,
+                                    offset,
+                                    movable: true,
+                                },
+                            })
+                        }
+                        CONSTANT_BUNDLE => {
+                            let (rest, (flags, value)) = tuple((EntryFlags::try_parse, le_u16))(i)?;
+                            i = rest;
+                            Some(Entry {
+                                flags,
+                                location: EntryLocation::Constant(value),
+                            })
+                        }
+                        segment => {
+                            let (rest, (flags, offset)) =
+                                tuple((EntryFlags::try_parse, le_u16))(i)?;
+                            i = rest;
+                            Some(Entry {
+                                flags,
+                                location: EntryLocation::Segment {
+                                    segment,
+                                    offset,
+                                    movable: false,
+                                },
+                            })
+                        }
+                    };
+
+                    if let Some(entry) = entry {
+                        entries.insert(ordinal, entry);
+                    }
+                    ordinal = ordinal
+                        .checked_add(1)
+                        .ok_or_else(|| Err::Error(make_error(i, ErrorKind::TooLarge)))?;
+                }
+            }
+
+            Ok((i, entries))
+        })(table)
+    }
+}
+
+impl BinParsable for EntryFlags {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(
+            name_of!(type EntryFlags),
+            map(le_u8, Self::from_bits_truncate),
+        )(i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nom::error::VerboseError;
+
+    #[test]
+    fn parses_bundles() {
+        let table = [
+            0x01, 0x02, 0x01, 0x34, 0x12, // 1 fixed entry in segment 2
+            0x02, 0x00, // 2 unused entries
+            0x01, 0xFF, 0x03, 0xCD, 0x3F, 0x01, 0x78, 0x56, // 1 movable entry in segment 1
+            0x00,
+        ];
+        let (rest, entries) = entry_table::<VerboseError<&[u8]>>(table.len())(&table[..]).unwrap();
+
+        assert!(rest.is_empty());
+        assert_eq!(entries.keys().copied().collect::<Vec<_>>(), vec![1, 4]);
+        assert_eq!(
+            entries[&4],
+            Entry {
+                flags: EntryFlags::EXPORTED | EntryFlags::SHARED_DATA,
+                location: EntryLocation::Segment {
+                    segment: 1,
+                    offset: 0x5678,
+                    movable: true,
+                },
+            }
+        );
+    }
+}
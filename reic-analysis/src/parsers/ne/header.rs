@@ -0,0 +1,281 @@
+use crate::parsers::BinParsable;
+
+use bitflags::bitflags;
+use nameof::name_of;
+use nom::{
+    bytes::complete::tag,
+    combinator::map,
+    error::context,
+    error::ParseError,
+    number::complete::{le_u16, le_u32, le_u8},
+    sequence::tuple,
+    IResult,
+};
+use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::FromPrimitive;
+
+/// The NE header, located at `e_lfanew` of the MZ header.
+///
+/// Unless noted otherwise, table offsets are relative to the start of the NE header.
+#[derive(Debug, PartialEq, Eq)]
+pub struct NEHeader {
+    /// The linker major version number.
+    pub linker_version: u8,
+    /// The linker minor version number.
+    pub linker_revision: u8,
+    /// The offset of the entry table.
+    pub entry_table_offset: u16,
+    /// The size of the entry table in bytes.
+    pub entry_table_size: u16,
+    /// The 32-bit CRC of the entire file, calculated with this field set to zero.
+    pub file_crc: u32,
+    /// The flags that indicate the attributes of the module.
+    pub flags: ModuleFlags,
+    /// The kind of user interface of the application, stored in bits 8 and 9 of the flags.
+    pub application_type: ApplicationType,
+    /// The segment number of the automatic data segment (DGROUP), or zero if there is none.
+    pub auto_data_segment: u16,
+    /// The initial size of the local heap, in bytes.
+    pub heap_size: u16,
+    /// The initial size of the stack, in bytes.
+    pub stack_size: u16,
+    /// The initial IP value.
+    pub initial_ip: u16,
+    /// The segment number of the initial CS value.
+    pub initial_cs: u16,
+    /// The initial SP value.
+    pub initial_sp: u16,
+    /// The segment number of the initial SS value.
+    pub initial_ss: u16,
+    /// The number of entries in the segment table.
+    pub segment_count: u16,
+    /// The number of entries in the module reference table.
+    pub module_reference_count: u16,
+    /// The size of the non-resident name table, in bytes.
+    pub non_resident_name_table_size: u16,
+    /// The offset of the segment table.
+    pub segment_table_offset: u16,
+    /// The offset of the resource table.
+    pub resource_table_offset: u16,
+    /// The offset of the resident name table.
+    pub resident_name_table_offset: u16,
+    /// The offset of the module reference table.
+    pub module_reference_table_offset: u16,
+    /// The offset of the imported names table.
+    pub imported_names_table_offset: u16,
+    /// The offset of the non-resident name table, relative to the start of the file.
+    pub non_resident_name_table_offset: u32,
+    /// The number of movable entries in the entry table.
+    pub movable_entry_count: u16,
+    /// The logical sector alignment shift count. Segment data is located at
+    /// `sector << alignment_shift`. Zero means the default of 9 (512-byte sectors).
+    pub alignment_shift: u16,
+    /// The number of resource segments.
+    pub resource_segment_count: u16,
+    /// The operating system the image targets.
+    pub target_os: u8,
+    /// Additional flags, mostly about Windows 2.x compatibility.
+    pub other_flags: u8,
+    /// The offset of the return thunks, in bytes.
+    pub return_thunks_offset: u16,
+    /// The offset of the segment reference thunks, in bytes.
+    pub segment_reference_thunks_offset: u16,
+    /// The minimum code swap area size.
+    pub min_code_swap_size: u16,
+    /// The expected Windows version (minor version in the low byte).
+    pub expected_windows_version: u16,
+}
+
+impl BinParsable for NEHeader {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(
+            name_of!(type NEHeader),
+            map(
+                tuple((
+                    tuple((
+                        tag(b"NE"),
+                        le_u8,  // linker_version
+                        le_u8,  // linker_revision
+                        le_u16, // entry_table_offset
+                        le_u16, // entry_table_size
+                        le_u32, // file_crc
+                        le_u16, // flags
+                        le_u16, // auto_data_segment
+                        le_u16, // heap_size
+                        le_u16, // stack_size
+                        le_u16, // initial_ip
+                        le_u16, // initial_cs
+                        le_u16, // initial_sp
+                        le_u16, // initial_ss
+                        le_u16, // segment_count
+                        le_u16, // module_reference_count
+                        le_u16, // non_resident_name_table_size
+                    )),
+                    tuple((
+                        le_u16, // segment_table_offset
+                        le_u16, // resource_table_offset
+                        le_u16, // resident_name_table_offset
+                        le_u16, // module_reference_table_offset
+                        le_u16, // imported_names_table_offset
+                        le_u32, // non_resident_name_table_offset
+                        le_u16, // movable_entry_count
+                        le_u16, // alignment_shift
+                        le_u16, // resource_segment_count
+                        le_u8,  // target_os
+                        le_u8,  // other_flags
+                        le_u16, // return_thunks_offset
+                        le_u16, // segment_reference_thunks_offset
+                        le_u16, // min_code_swap_size
+                        le_u16, // expected_windows_version
+                    )),
+                )),
+                |(p, q)| Self {
+                    linker_version: p.1,
+                    linker_revision: p.2,
+                    entry_table_offset: p.3,
+                    entry_table_size: p.4,
+                    file_crc: p.5,
+                    flags: ModuleFlags::from_bits_truncate(p.6),
+                    application_type: ApplicationType::from_flags(p.6),
+                    auto_data_segment: p.7,
+                    heap_size: p.8,
+                    stack_size: p.9,
+                    initial_ip: p.10,
+                    initial_cs: p.11,
+                    initial_sp: p.12,
+                    initial_ss: p.13,
+                    segment_count: p.14,
+                    module_reference_count: p.15,
+                    non_resident_name_table_size: p.16,
+                    segment_table_offset: q.0,
+                    resource_table_offset: q.1,
+                    resident_name_table_offset: q.2,
+                    module_reference_table_offset: q.3,
+                    imported_names_table_offset: q.4,
+                    non_resident_name_table_offset: q.5,
+                    movable_entry_count: q.6,
+                    alignment_shift: if q.7 == 0 { 9 } else { q.7 },
+                    resource_segment_count: q.8,
+                    target_os: q.9,
+                    other_flags: q.10,
+                    return_thunks_offset: q.11,
+                    segment_reference_thunks_offset: q.12,
+                    min_code_swap_size: q.13,
+                    expected_windows_version: q.14,
+                },
+            ),
+        )(i)
+    }
+}
+
+bitflags! {
+    #[derive(Default)]
+    pub struct ModuleFlags: u16 {
+        /// The module has a single, shared automatic data segment (DLLs).
+        const SINGLE_DATA = 0x0001;
+        /// Every instance of the module has its own automatic data segment (applications).
+        const MULTIPLE_DATA = 0x0002;
+        /// The module is a global initialization library.
+        const GLOBAL_INIT = 0x0004;
+        /// The module only runs in protected mode.
+        const PROTECTED_MODE_ONLY = 0x0008;
+        /// The module contains 8086 instructions.
+        const INSTRUCTIONS_8086 = 0x0010;
+        /// The module contains 80286 instructions.
+        const INSTRUCTIONS_80286 = 0x0020;
+        /// The module contains 80386 instructions.
+        const INSTRUCTIONS_80386 = 0x0040;
+        /// The module contains 80x87 instructions.
+        const INSTRUCTIONS_80X87 = 0x0080;
+        /// The module is an OS/2 family application.
+        const FAMILY_APPLICATION = 0x0800;
+        /// Errors were detected at link time, the module will not load.
+        const LINK_ERRORS = 0x2000;
+        /// The module is a library (DLL).
+        const LIBRARY = 0x8000;
+    }
+}
+
+/// The kind of user interface of an application. Unlike the other module flags, this is a
+/// two-bit field, so a Windows API application has both of its bits set.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(u8)]
+pub enum ApplicationType {
+    /// The type is not given, as for libraries.
+    Unspecified = 0,
+    /// The application uses the full screen (not a windowed application).
+    FullScreen = 1,
+    /// The application is compatible with the Windows/PM API.
+    WindowsCompatible = 2,
+    /// The application uses the Windows/PM API.
+    WindowsApi = 3,
+}
+
+impl ApplicationType {
+    /// Reads the application type from bits 8 and 9 of the module flags.
+    pub fn from_flags(flags: u16) -> Self {
+        Self::from_u16((flags >> 8) & 0b11).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nom::error::VerboseError;
+
+    #[test]
+    fn parses_header() {
+        let mut header = vec![0u8; 0x40];
+        header[0..4].copy_from_slice(&[b'N', b'E', 5, 10]);
+        header[0x0C..0x0E].copy_from_slice(&0x8301u16.to_le_bytes()); // flags
+        header[0x14..0x18].copy_from_slice(&[0x34, 0x12, 0x02, 0x00]); // 2:1234
+        header[0x1C..0x1E].copy_from_slice(&3u16.to_le_bytes()); // segment_count
+        header[0x2C..0x30].copy_from_slice(&0x400u32.to_le_bytes()); // non-resident names
+        header[0x36] = 0x02; // target_os
+        header[0x3E..0x40].copy_from_slice(&0x030Au16.to_le_bytes());
+        let (rest, header) = NEHeader::try_parse::<VerboseError<&[u8]>>(&header).unwrap();
+
+        assert!(rest.is_empty());
+        assert_eq!((header.linker_version, header.linker_revision), (5, 10));
+        assert_eq!(
+            header.flags,
+            ModuleFlags::LIBRARY | ModuleFlags::SINGLE_DATA
+        );
+        assert_eq!(header.application_type, ApplicationType::WindowsApi);
+        assert_eq!((header.initial_cs, header.initial_ip), (2, 0x1234));
+        assert_eq!(header.segment_count, 3);
+        assert_eq!(header.non_resident_name_table_offset, 0x400);
+        // An alignment shift of zero means 512-byte sectors
+        assert_eq!(header.alignment_shift, 9);
+        assert_eq!(header.target_os, 2);
+        assert_eq!(header.expected_windows_version, 0x030A);
+    }
+
+    #[test]
+    fn parses_application_type_apart_from_flags() {
+        for &(flags, application_type) in &[
+            (0x0000, ApplicationType::Unspecified),
+            (0x0100, ApplicationType::FullScreen),
+            (0x0200, ApplicationType::WindowsCompatible),
+            (0x0302, ApplicationType::WindowsApi),
+        ] {
+            let mut header = vec![0u8; 0x40];
+            header[0..2].copy_from_slice(b"NE");
+            header[0x0C..0x0E].copy_from_slice(&u16::to_le_bytes(flags));
+            let (_, header) = NEHeader::try_parse::<VerboseError<&[u8]>>(&header).unwrap();
+
+            assert_eq!(header.application_type, application_type);
+            assert_eq!(
+                header.flags,
+                ModuleFlags::from_bits_truncate(flags & 0x0002)
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_other_signatures() {
+        let mut header = vec![0u8; 0x40];
+        header[0..2].copy_from_slice(b"LE");
+        assert!(NEHeader::try_parse::<VerboseError<&[u8]>>(&header).is_err());
+    }
+}
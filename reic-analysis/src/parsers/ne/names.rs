@@ -0,0 +1,53 @@
+use nameof::name_of;
+use nom::{
+    bytes::complete::take,
+    combinator::{map, verify},
+    error::context,
+    error::ParseError,
+    multi::many_till,
+    number::complete::{le_u16, le_u8},
+    sequence::pair,
+    IResult,
+};
+
+/// An entry of the resident or non-resident name table.
+#[derive(Debug, PartialEq, Eq)]
+pub struct NameTableEntry {
+    pub name: String,
+    /// The ordinal of the entry table entry this name refers to.
+    /// This is zero for the first entry of each table (module name or description).
+    pub ordinal: u16,
+}
+
+/// Parses a length-prefixed string.
+pub fn pascal_string<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], String, E> {
+    let (i, length) = le_u8(i)?;
+    map(take(length), |raw: &[u8]| {
+        String::from_utf8_lossy(raw).into_owned()
+    })(i)
+}
+
+/// Parses a name table, up to and including its terminating zero-length entry.
+pub fn name_table<'a, E: ParseError<&'a [u8]>>(
+    i: &'a [u8],
+) -> IResult<&'a [u8], Vec<NameTableEntry>, E> {
+    context(
+        name_of!(type NameTableEntry),
+        map(
+            many_till(
+                map(
+                    pair(
+                        verify(pascal_string, |name: &String| !name.is_empty()),
+                        le_u16,
+                    ),
+                    |p| NameTableEntry {
+                        name: p.0,
+                        ordinal: p.1,
+                    },
+                ),
+                verify(le_u8, |&length| length == 0),
+            ),
+            |(entries, _)| entries,
+        ),
+    )(i)
+}
@@ -0,0 +1,345 @@
+use crate::parsers::le::expand_iterated_page;
+use crate::parsers::BinParsable;
+
+use bitflags::bitflags;
+use nameof::name_of;
+use nom::{
+    bytes::complete::take,
+    combinator::{map, map_opt},
+    error::context,
+    error::{ErrorKind, ParseError},
+    multi::count,
+    number::complete::{le_u16, le_u8},
+    sequence::tuple,
+    IResult,
+};
+use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::FromPrimitive;
+use std::fmt;
+
+/// The largest a segment can be.
+const MAX_SEGMENT_SIZE: usize = 0x10000;
+
+#[derive(PartialEq, Eq)]
+pub struct Segment {
+    pub header: SegmentHeader,
+    /// The segment data, with iterated data already expanded.
+    pub data: Vec<u8>,
+    pub relocations: Vec<SegmentRelocation>,
+}
+
+impl Segment {
+    pub fn from_file_and_header(
+        file: &[u8],
+        alignment_shift: u16,
+        header: SegmentHeader,
+    ) -> Option<Self> {
+        if header.sector == 0 {
+            return Some(Self {
+                header,
+                data: Vec::new(),
+                relocations: Vec::new(),
+            });
+        }
+
+        let start = (header.sector as usize).checked_shl(alignment_shift as u32)?;
+        let end = start + header.file_size();
+        let raw = file.get(start..end)?;
+        // Iterated segments use the same records as EXEPACK1 pages of LE images
+        let data = if header.flags.contains(SegmentFlags::ITERATED) {
+            expand_iterated_page(raw, MAX_SEGMENT_SIZE)
+        } else {
+            raw.to_vec()
+        };
+
+        let relocations = if header.flags.contains(SegmentFlags::RELOC_INFO) {
+            relocation_table::<(&[u8], ErrorKind)>(file.get(end..)?)
+                .ok()?
+                .1
+        } else {
+            Vec::new()
+        };
+
+        Some(Self {
+            header,
+            data,
+            relocations,
+        })
+    }
+}
+
+fn relocation_table<'a, E: ParseError<&'a [u8]>>(
+    i: &'a [u8],
+) -> IResult<&'a [u8], Vec<SegmentRelocation>, E> {
+    let (i, relocation_count) = le_u16(i)?;
+    count(SegmentRelocation::try_parse, relocation_count as usize)(i)
+}
+
+impl fmt::Debug for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct(name_of!(type Segment))
+            .field(name_of!(header in Segment), &self.header)
+            .field(
+                name_of!(data in Segment),
+                &format!("Vec<u8>, len: {:X}", self.data.len()),
+            )
+            .field(name_of!(relocations in Segment), &self.relocations)
+            .finish()
+    }
+}
+
+/// An entry of the segment table.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SegmentHeader {
+    /// The logical sector of the segment data within the file, or zero if there is no data.
+    pub sector: u16,
+    /// The size of the segment data in the file. Zero means 64K, unless `sector` is zero.
+    pub length: u16,
+    /// The flags that describe the characteristics of the segment.
+    pub flags: SegmentFlags,
+    /// The minimum allocation size of the segment in memory. Zero means 64K.
+    pub min_alloc: u16,
+}
+
+impl SegmentHeader {
+    /// The size of the segment data in the file, in bytes.
+    pub fn file_size(&self) -> usize {
+        match (self.sector, self.length) {
+            (0, _) => 0,
+            (_, 0) => 0x10000,
+            (_, length) => length as usize,
+        }
+    }
+
+    /// The size of the segment in memory, in bytes.
+    pub fn memory_size(&self) -> usize {
+        let min_alloc = match self.min_alloc {
+            0 => 0x10000,
+            min_alloc => min_alloc as usize,
+        };
+        min_alloc.max(self.file_size())
+    }
+
+    pub fn is_code(&self) -> bool {
+        !self.flags.contains(SegmentFlags::DATA)
+    }
+}
+
+impl BinParsable for SegmentHeader {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(
+            name_of!(type SegmentHeader),
+            map(
+                tuple((le_u16, le_u16, SegmentFlags::try_parse, le_u16)),
+                |p| Self {
+                    sector: p.0,
+                    length: p.1,
+                    flags: p.2,
+                    min_alloc: p.3,
+                },
+            ),
+        )(i)
+    }
+}
+
+bitflags! {
+    #[derive(Default)]
+    pub struct SegmentFlags: u16 {
+        /// The segment is a data segment. If not set, it is a code segment.
+        const DATA = 0x0001;
+        /// The loader has allocated memory for the segment.
+        const ALLOCATED = 0x0002;
+        /// The segment is loaded.
+        const LOADED = 0x0004;
+        /// The segment data is compressed with run-length encoding ("iterated").
+        const ITERATED = 0x0008;
+        /// The segment is not fixed and can be moved in memory.
+        const MOVABLE = 0x0010;
+        /// The segment can be shared between instances (pure code or read-only data).
+        const SHAREABLE = 0x0020;
+        /// The segment is loaded together with the module.
+        const PRELOAD = 0x0040;
+        /// Execute-only for code segments, read-only for data segments.
+        const READ_ONLY = 0x0080;
+        /// The segment data is followed by relocation records.
+        const RELOC_INFO = 0x0100;
+        /// The segment is a conforming code segment.
+        const CONFORMING = 0x0200;
+        /// The segment can be discarded when memory runs low.
+        const DISCARDABLE = 0x1000;
+    }
+}
+
+impl BinParsable for SegmentFlags {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(
+            name_of!(type SegmentFlags),
+            map(le_u16, Self::from_bits_truncate),
+        )(i)
+    }
+}
+
+/// A relocation record of a segment.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SegmentRelocation {
+    /// The kind of value that is patched.
+    pub source_type: RelocationSourceType,
+    /// Whether the target value is added to the value at the source location.
+    ///
+    /// If not set, the value at the source location is the offset of the next location
+    /// to patch with the same value (terminated by 0xFFFF).
+    pub additive: bool,
+    /// The offset of the (first) patched location within the segment.
+    pub offset: u16,
+    pub target: RelocationTarget,
+}
+
+impl SegmentRelocation {
+    /// The target type bits of the relocation flags.
+    const TARGET_MASK: u8 = 0x03;
+    const ADDITIVE: u8 = 0x04;
+}
+
+impl BinParsable for SegmentRelocation {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(
+            name_of!(type SegmentRelocation),
+            map_opt(
+                tuple((RelocationSourceType::try_parse, le_u8, le_u16, take(4usize))),
+                |(source_type, flags, offset, raw_target): (_, _, _, &[u8])| {
+                    let word = |index: usize| {
+                        u16::from_le_bytes([raw_target[index], raw_target[index + 1]])
+                    };
+                    let target = match flags & Self::TARGET_MASK {
+                        0 if raw_target[0] == 0xFF => {
+                            RelocationTarget::InternalMovable { ordinal: word(2) }
+                        }
+                        0 => RelocationTarget::InternalFixed {
+                            segment: raw_target[0],
+                            offset: word(2),
+                        },
+                        1 => RelocationTarget::ImportOrdinal {
+                            module: word(0),
+                            ordinal: word(2),
+                        },
+                        2 => RelocationTarget::ImportName {
+                            module: word(0),
+                            name_offset: word(2),
+                        },
+                        3 => RelocationTarget::OsFixup {
+                            fixup_type: word(0),
+                        },
+                        _ => return None,
+                    };
+
+                    Some(Self {
+                        source_type,
+                        additive: flags & Self::ADDITIVE != 0,
+                        offset,
+                        target,
+                    })
+                },
+            ),
+        )(i)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(u8)]
+pub enum RelocationSourceType {
+    /// The low byte of the target offset.
+    LowByte = 0,
+    /// The 16-bit segment selector of the target.
+    Segment = 2,
+    /// A 32-bit far pointer (offset followed by segment selector).
+    FarPointer = 3,
+    /// The 16-bit offset of the target.
+    Offset = 5,
+    /// A 48-bit far pointer (32-bit offset followed by segment selector).
+    FarPointer48 = 11,
+    /// The 32-bit offset of the target.
+    Offset32 = 13,
+}
+
+impl BinParsable for RelocationSourceType {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(
+            name_of!(type RelocationSourceType),
+            map_opt(le_u8, Self::from_u8),
+        )(i)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum RelocationTarget {
+    /// A location in a fixed segment of this module.
+    InternalFixed { segment: u8, offset: u16 },
+    /// An entry (in a movable segment) of this module, referenced by its entry table ordinal.
+    InternalMovable { ordinal: u16 },
+    /// An entry of an imported module, by ordinal. `module` is a 1-based index into the
+    /// module reference table.
+    ImportOrdinal { module: u16, ordinal: u16 },
+    /// An entry of an imported module, by name. `name_offset` is relative to the
+    /// imported names table.
+    ImportName { module: u16, name_offset: u16 },
+    /// A fixup for floating point emulation, which the loader patches depending on
+    /// the presence of a coprocessor.
+    OsFixup { fixup_type: u16 },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_relocation_table() {
+        let table = [
+            0x04, 0x00, // 4 relocations
+            0x02, 0x00, 0x10, 0x00, 0xFF, 0x00, 0x03, 0x00, // segment of entry 3
+            0x05, 0x04, 0x20, 0x00, 0x02, 0x00, 0x10, 0x00, // additive offset of 2:0010
+            0x03, 0x01, 0x30, 0x00, 0x01, 0x00, 0x7B, 0x00, // far pointer to module 1 #123
+            0x05, 0x03, 0x40, 0x00, 0x01, 0x00, 0x00, 0x00, // floating point fixup
+            0xCC,
+        ];
+        let (rest, relocations) = relocation_table::<(&[u8], ErrorKind)>(&table).unwrap();
+
+        assert_eq!(rest, &[0xCC]);
+        assert_eq!(
+            relocations
+                .iter()
+                .map(|relocation| (relocation.offset, relocation.additive, relocation.target))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    0x10,
+                    false,
+                    RelocationTarget::InternalMovable { ordinal: 3 }
+                ),
+                (
+                    0x20,
+                    true,
+                    RelocationTarget::InternalFixed {
+                        segment: 2,
+                        offset: 0x10
+                    }
+                ),
+                (
+                    0x30,
+                    false,
+                    RelocationTarget::ImportOrdinal {
+                        module: 1,
+                        ordinal: 123
+                    }
+                ),
+                (0x40, false, RelocationTarget::OsFixup { fixup_type: 1 }),
+            ]
+        );
+        assert_eq!(relocations[2].source_type, RelocationSourceType::FarPointer);
+    }
+
+    #[test]
+    fn rejects_unknown_source_types() {
+        let table = [0x01, 0x00, 0x07, 0x00, 0x10, 0x00, 0x01, 0x00, 0x00, 0x00];
+        assert!(relocation_table::<(&[u8], ErrorKind)>(&table).is_err());
+    }
+}
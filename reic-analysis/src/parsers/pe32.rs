@@ -1,74 +1,277 @@
+mod checksum;
+pub use checksum::*;
+
+mod exception;
+pub use exception::*;
+
+mod exports;
+pub use exports::*;
+
 mod headers;
 pub use headers::*;
 
+mod imports;
+pub use imports::*;
+
+mod inject;
+pub use inject::*;
+
+mod patch;
+pub use patch::*;
+
+mod rebuild;
+pub use rebuild::*;
+
+mod relocations;
+pub use relocations::*;
+
+mod rich;
+pub use rich::*;
+
 mod section;
 pub use section::*;
 
+mod writer;
+pub use writer::{CheckSumUpdate, WriteError, WriteOptions};
+
 use crate::parsers::coff::{
     COFFHeader, COFFImageOptionalHeaderType, COFFImageStandardOptionalHeader,
 };
 use crate::parsers::mz::MZHeader;
-use crate::parsers::BinParsable;
+use crate::parsers::{
+    BinParsable, Diagnostics, ParseMode, ParseRule, ParseWarning, Severity, SharedBytes,
+};
 
 use nameof::name_of;
-use nom::combinator::map_opt;
 use nom::{
-    bytes::complete::tag, bytes::complete::take, combinator::verify, error::context,
-    error::ParseError, multi::count, sequence::tuple, IResult,
+    bytes::complete::tag,
+    bytes::complete::take,
+    combinator::verify,
+    error::context,
+    error::{make_error, ErrorKind, ParseError},
+    multi::count,
+    sequence::tuple,
+    Err, IResult, Offset,
 };
-use num_traits::FromPrimitive;
 use std::collections::HashMap;
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct PE32Image {
     pub mz_header: MZHeader,
+    /// The data between the MZ header and the PE signature: the DOS stub program and the
+    /// Rich header, if present.
+    pub dos_stub: SharedBytes,
     pub coff_header: COFFHeader,
     pub coff_optional_header: COFFImageStandardOptionalHeader,
     pub pe32_optional_header: PE32OptionalHeader,
     pub data_directories: HashMap<DataDirectoryType, DataDirectory>,
     pub sections: Vec<Section>,
+    /// The data between the end of the section table and `size_of_headers`. Usually padding,
+    /// but linkers may place the bound import directory here.
+    pub header_padding: SharedBytes,
+    /// The data after the raw data of the last section, such as an Authenticode signature or
+    /// an installer payload. Always empty for mapped images.
+    pub overlay: SharedBytes,
+    /// Whether the image was parsed from a file or from a mapped image.
+    pub layout: ImageLayout,
+    /// The imports, grouped by DLL.
+    pub imports: Vec<ImportedModule>,
+    pub exports: Option<ExportDirectory>,
+    /// The function table of the exception directory of PE32+ images, sorted by address.
+    pub runtime_functions: Vec<RuntimeFunction>,
+    /// The base relocation table, empty if the relocations were stripped.
+    pub base_relocations: Vec<BaseRelocationBlock>,
+    /// The anomalies found while parsing. In strict mode, these are only the data directories
+    /// and function table entries that could not be parsed.
+    pub warnings: Vec<ParseWarning>,
+    /// The checksum of the file the image was parsed from, see `checksum`. `None` for mapped
+    /// images.
+    pub computed_check_sum: Option<u32>,
+}
+
+/// How the sections of an image are laid out in the parsed data.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImageLayout {
+    /// An image file, with section data at `pointer_to_raw_data`.
+    File,
+    /// A mapped image (such as a process memory dump), with section data at `virtual_address`.
+    Mapped,
+}
+
+impl PE32Image {
+    /// Returns the data directory of the given type, if it is present.
+    pub fn data_directory(&self, directory_type: KnownDataDirectoryType) -> Option<&DataDirectory> {
+        self.data_directories
+            .get(&DataDirectoryType::Known(directory_type))
+    }
+
+    /// Returns the section whose memory range contains the given RVA.
+    pub fn section_containing(&self, rva: u32) -> Option<&Section> {
+        self.sections.iter().find(|section| {
+            let header = &section.header;
+            let size = header.virtual_size.max(header.size_of_raw_data);
+            rva >= header.virtual_address && rva - header.virtual_address < size
+        })
+    }
+
+    /// Returns the raw section data from the entry point onwards.
+    pub fn entry_point_data(&self) -> Option<&[u8]> {
+        let entry_point = self.coff_optional_header.address_of_entry_point;
+        let section = self.section_containing(entry_point)?;
+        section
+            .data
+            .get((entry_point - section.header.virtual_address) as usize..)
+    }
+}
+
+/// The offset of the data directories in the optional header, including the standard fields.
+pub(crate) fn data_directories_offset(header: &PE32OptionalHeader) -> usize {
+    if header.is_pe32_plus() {
+        PE32_PLUS_DATA_DIRECTORIES_OFFSET
+    } else {
+        DATA_DIRECTORIES_OFFSET
+    }
+}
+
+/// Returns the file data from the given RVA onwards, if the RVA lies within the raw data
+/// of a section.
+pub(crate) fn rva_data<'a, E: ParseError<&'a [u8]>>(
+    file: &'a [u8],
+    sections: &[Section],
+    layout: ImageLayout,
+    rva: u32,
+) -> Result<&'a [u8], Err<E>> {
+    sections
+        .iter()
+        .find_map(|section| section.file_offset(rva, layout))
+        .and_then(|offset| file.get(offset as usize..))
+        .ok_or_else(|| Err::Error(make_error(file, ErrorKind::Eof)))
 }
 
 impl BinParsable for PE32Image {
     fn try_parse<'a, E: ParseError<&'a [u8]>>(image: &'a [u8]) -> IResult<&'a [u8], Self, E> {
-        context(name_of!(type PE32Image), |file: &'a [u8]| {
+        Self::try_parse_with_options(ParseOptions::default())(image)
+    }
+}
+
+/// Options for parsing a `PE32Image`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    pub layout: ImageLayout,
+    pub mode: ParseMode,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            layout: ImageLayout::File,
+            mode: ParseMode::Strict,
+        }
+    }
+}
+
+const MZ_HEADER_SIZE: usize = 0x40;
+/// The offsets of `section_alignment`, `file_alignment` and `check_sum` in the PE32 optional
+/// header.
+const SECTION_ALIGNMENT_OFFSET: usize = 32;
+const FILE_ALIGNMENT_OFFSET: usize = 36;
+const CHECK_SUM_OFFSET: usize = 64;
+/// The offset of the data directories in the PE32 optional header. The wider fields of PE32+
+/// move them back by 16 bytes.
+const DATA_DIRECTORIES_OFFSET: usize = 96;
+const PE32_PLUS_DATA_DIRECTORIES_OFFSET: usize = 112;
+const DATA_DIRECTORY_SIZE: usize = 8;
+const SECTION_HEADER_SIZE: usize = 40;
+
+impl PE32Image {
+    /// Parses an image with the given options.
+    ///
+    /// The parsed image owns its data, so the input is copied once its headers were parsed. Use
+    /// `try_parse_shared` to refer to a shared or memory-mapped buffer instead.
+    pub fn try_parse_with_options<'a, E: ParseError<&'a [u8]>>(
+        options: ParseOptions,
+    ) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], Self, E> {
+        move |image: &'a [u8]| Self::parse(image, None, options)
+    }
+
+    /// Parses an image from a shared buffer, such as a memory-mapped file. The section data
+    /// refers to the buffer instead of being copied.
+    pub fn try_parse_shared<'a, E: ParseError<&'a [u8]>>(
+        buffer: &'a SharedBytes,
+        options: ParseOptions,
+    ) -> IResult<&'a [u8], Self, E> {
+        Self::parse(buffer, Some(buffer), options)
+    }
+
+    /// Parses `image`, taking the section data from `shared`, which holds the same bytes. Without
+    /// it, the data is taken from a copy of `image`.
+    fn parse<'a, E: ParseError<&'a [u8]>>(
+        image: &'a [u8],
+        shared: Option<&SharedBytes>,
+        options: ParseOptions,
+    ) -> IResult<&'a [u8], Self, E> {
+        let layout = options.layout;
+        context(name_of!(type PE32Image), move |file: &'a [u8]| {
+            let mut diagnostics = Diagnostics::new(options.mode);
+
             let (_, mz_header) = context(
                 "PE header offset sanity check",
                 verify(MZHeader::try_parse, |mz| file.len() > mz.e_lfanew as usize),
             )(file)?;
 
-            let (i, (_, _, coff_header, coff_optional_header, pe32_optional_header)) =
-                tuple((
-                    take(mz_header.e_lfanew),
-                    tag(b"PE\0\0"),
-                    context(
-                        "Check size of PE32 optional COFF header",
-                        verify(COFFHeader::try_parse, |coff| {
-                            // COFF standard fields + PE32 base_of_data + windows/loader-specific fields
-                            coff.size_of_optional_header >= (24 + 4 + 64)
-                        }),
-                    ),
-                    context(
-                        "Check if optional header specifies PE32",
-                        verify(COFFImageStandardOptionalHeader::try_parse, |coff| {
-                            coff.magic == COFFImageOptionalHeaderType::PE32
-                        }),
-                    ),
-                    context(
-                        "Verify file and section alignment values",
-                        verify(PE32OptionalHeader::try_parse, |header| {
-                            let PE32OptionalHeaderWindowsSpecific {
-                                file_alignment,
-                                section_alignment,
-                                ..
-                            } = header.windows_specific;
-                            file_alignment >= 512
-                                && file_alignment <= 65536
-                                && file_alignment.is_power_of_two()
-                                && section_alignment >= file_alignment
-                        }),
-                    ),
-                ))(file)?;
+            let (optional_header, (_, _, coff_header, coff_optional_header)) = tuple((
+                take(mz_header.e_lfanew),
+                tag(b"PE\0\0"),
+                context(
+                    "Check size of PE32 optional COFF header",
+                    verify(COFFHeader::try_parse, |coff| {
+                        // COFF standard fields + PE32 base_of_data + windows/loader-specific fields
+                        coff.size_of_optional_header >= (24 + 4 + 64)
+                    }),
+                ),
+                context(
+                    "Check if optional header specifies PE32 or PE32+",
+                    verify(COFFImageStandardOptionalHeader::try_parse, |coff| {
+                        coff.magic == COFFImageOptionalHeaderType::PE32
+                            || coff.magic == COFFImageOptionalHeaderType::PE32Plus
+                    }),
+                ),
+            ))(file)?;
+            let (i, pe32_optional_header) =
+                PE32OptionalHeader::try_parse_for(coff_optional_header.magic)(optional_header)?;
+
+            // The standard fields precede the PE32 specific ones
+            let optional_header_offset = file.offset(optional_header) - 24;
+            let PE32OptionalHeaderWindowsSpecific {
+                file_alignment,
+                section_alignment,
+                ..
+            } = pe32_optional_header.windows_specific;
+            diagnostics.check(
+                file_alignment >= 512
+                    && file_alignment <= 65536
+                    && file_alignment.is_power_of_two(),
+                optional_header,
+                "Verify file and section alignment values",
+                ParseWarning {
+                    offset: optional_header_offset + FILE_ALIGNMENT_OFFSET,
+                    rule: ParseRule::FileAlignment { file_alignment },
+                    severity: Severity::Warning,
+                },
+            )?;
+            diagnostics.check(
+                section_alignment >= file_alignment,
+                optional_header,
+                "Verify file and section alignment values",
+                ParseWarning {
+                    offset: optional_header_offset + SECTION_ALIGNMENT_OFFSET,
+                    rule: ParseRule::SectionAlignment {
+                        section_alignment,
+                        file_alignment,
+                    },
+                    severity: Severity::Warning,
+                },
+            )?;
 
             let (i, data_dirs) = context(
                 "Parse data directories",
@@ -80,62 +283,666 @@ impl BinParsable for PE32Image {
                 ),
             )(i)?;
 
-            let data_directories = data_dirs
+            let data_directories: HashMap<_, _> = data_dirs
                 .into_iter()
                 .enumerate()
                 .filter(|(_, dir_entry)| dir_entry.size != 0)
-                .map(|(index, value)| {
-                    let key = KnownDataDirectoryType::from_usize(index)
-                        .map_or(DataDirectoryType::Unknown(index), |t| {
-                            DataDirectoryType::Known(t)
-                        });
-                    (key, value)
-                })
+                .map(|(index, value)| (DataDirectoryType::from_index(index), value))
                 .collect();
 
-            let (i, sections) = context(
-                "Sections",
-                context(
-                    "Verify sections",
-                    verify(
-                        count(
-                            context(
-                                "Get section data",
-                                map_opt(
-                                    context(
-                                        "Validate header",
-                                        verify(SectionHeader::try_parse, |header| {
-                                            header.verify(
-                                                pe32_optional_header
-                                                    .windows_specific
-                                                    .file_alignment,
-                                                pe32_optional_header
-                                                    .windows_specific
-                                                    .section_alignment,
-                                            )
-                                        }),
-                                    ),
-                                    |header| Section::from_file_and_header(image, header),
-                                ),
-                            ),
-                            coff_header.number_of_sections as usize,
+            // Only copy the input once it is known to be a PE image
+            let copy;
+            let shared = match shared {
+                Some(shared) => shared,
+                None => {
+                    copy = SharedBytes::copy_from_slice(file);
+                    &copy
+                }
+            };
+
+            let section_table = i;
+            let (i, sections) = Self::parse_sections(
+                file,
+                shared,
+                section_table,
+                &coff_header,
+                &pe32_optional_header,
+                layout,
+                &mut diagnostics,
+            )
+            .map_err(|e| e.map(|e| E::add_context(section_table, "Sections", e)))?;
+
+            let section_table_end = file.offset(i);
+            let size_of_headers = pe32_optional_header.windows_specific.size_of_headers as usize;
+            let header_padding = shared
+                .slice(section_table_end..size_of_headers.min(file.len()).max(section_table_end))
+                .unwrap_or_default();
+            let dos_stub = shared
+                .slice(MZ_HEADER_SIZE..(mz_header.e_lfanew as usize).max(MZ_HEADER_SIZE))
+                .unwrap_or_default();
+            let overlay = match layout {
+                ImageLayout::File => {
+                    let data_end = sections
+                        .iter()
+                        .filter(|section| section.header.size_of_raw_data != 0)
+                        .map(|section| {
+                            section.header.pointer_to_raw_data as usize + section.data.len()
+                        })
+                        .fold(size_of_headers, usize::max);
+                    shared
+                        .slice(data_end.min(file.len())..file.len())
+                        .unwrap_or_default()
+                }
+                ImageLayout::Mapped => SharedBytes::default(),
+            };
+
+            let computed_check_sum = match layout {
+                ImageLayout::File => Some(compute_checksum(
+                    file,
+                    optional_header_offset + CHECK_SUM_OFFSET,
+                )),
+                ImageLayout::Mapped => None,
+            };
+
+            let directory = |directory_type| {
+                data_directories
+                    .get(&DataDirectoryType::Known(directory_type))
+                    .filter(|directory: &&DataDirectory| directory.virtual_address != 0)
+            };
+
+            let directory_offset = |directory_type: KnownDataDirectoryType| {
+                optional_header_offset
+                    + data_directories_offset(&pe32_optional_header)
+                    + directory_type as usize * DATA_DIRECTORY_SIZE
+            };
+
+            // The data directories are not needed to use the rest of the image, so a malformed
+            // one is recorded as a warning in either mode
+            let imports = match directory(KnownDataDirectoryType::Import) {
+                Some(directory) => diagnostics
+                    .recover(
+                        import_directory::<E>(
+                            file,
+                            &sections,
+                            layout,
+                            directory.virtual_address,
+                            pe32_optional_header.is_pe32_plus(),
                         ),
-                        |sections| Section::verify_section_order(&sections),
+                        ParseWarning {
+                            offset: directory_offset(KnownDataDirectoryType::Import),
+                            rule: ParseRule::ImportDirectory,
+                            severity: Severity::Error,
+                        },
+                    )
+                    .unwrap_or_default(),
+                None => Vec::new(),
+            };
+
+            let exports = match directory(KnownDataDirectoryType::Export) {
+                Some(directory) => diagnostics.recover(
+                    export_directory::<E>(
+                        file,
+                        &sections,
+                        layout,
+                        directory.virtual_address,
+                        directory.size,
                     ),
+                    ParseWarning {
+                        offset: directory_offset(KnownDataDirectoryType::Export),
+                        rule: ParseRule::ExportDirectory,
+                        severity: Severity::Error,
+                    },
                 ),
-            )(i)?;
+                None => None,
+            };
+
+            // PE32 images use the exception directory only on non-x86 architectures
+            let exception = directory(KnownDataDirectoryType::Exception)
+                .filter(|_| pe32_optional_header.is_pe32_plus());
+            let runtime_functions = match exception {
+                Some(directory) => {
+                    let functions = exception_directory::<E>(
+                        file,
+                        &sections,
+                        layout,
+                        directory.virtual_address,
+                        directory.size,
+                        &mut diagnostics,
+                    );
+                    diagnostics
+                        .recover(
+                            functions,
+                            ParseWarning {
+                                offset: directory_offset(KnownDataDirectoryType::Exception),
+                                rule: ParseRule::ExceptionDirectory,
+                                severity: Severity::Error,
+                            },
+                        )
+                        .unwrap_or_default()
+                }
+                None => Vec::new(),
+            };
+
+            let base_relocations = match directory(KnownDataDirectoryType::Basereloc) {
+                Some(directory) => diagnostics
+                    .recover(
+                        base_relocation_directory::<E>(
+                            file,
+                            &sections,
+                            layout,
+                            directory.virtual_address,
+                            directory.size,
+                        ),
+                        ParseWarning {
+                            offset: directory_offset(KnownDataDirectoryType::Basereloc),
+                            rule: ParseRule::BaseRelocationDirectory,
+                            severity: Severity::Error,
+                        },
+                    )
+                    .unwrap_or_default(),
+                None => Vec::new(),
+            };
 
             Ok((
                 i,
                 Self {
                     mz_header,
+                    dos_stub,
                     coff_header,
                     coff_optional_header,
                     pe32_optional_header,
                     data_directories,
                     sections,
+                    header_padding,
+                    overlay,
+                    layout,
+                    imports,
+                    exports,
+                    runtime_functions,
+                    base_relocations,
+                    warnings: diagnostics.into_warnings(),
+                    computed_check_sum,
                 },
             ))
         })(image)
     }
+
+    fn parse_sections<'a, E: ParseError<&'a [u8]>>(
+        file: &'a [u8],
+        shared: &SharedBytes,
+        section_table: &'a [u8],
+        coff_header: &COFFHeader,
+        pe32_optional_header: &PE32OptionalHeader,
+        layout: ImageLayout,
+        diagnostics: &mut Diagnostics,
+    ) -> IResult<&'a [u8], Vec<Section>, E> {
+        let (i, headers) = count(
+            SectionHeader::try_parse,
+            coff_header.number_of_sections as usize,
+        )(section_table)?;
+
+        let windows_specific = &pe32_optional_header.windows_specific;
+        let mut sections = Vec::with_capacity(headers.len());
+        for (index, header) in headers.into_iter().enumerate() {
+            let header_input = &section_table[index * SECTION_HEADER_SIZE..];
+            let offset = file.offset(header_input);
+
+            diagnostics
+                .check(
+                    header.verify(
+                        windows_specific.file_alignment,
+                        windows_specific.section_alignment,
+                    ),
+                    header_input,
+                    "Validate header",
+                    ParseWarning {
+                        offset,
+                        rule: ParseRule::SectionHeaderAlignment { section: index },
+                        severity: Severity::Warning,
+                    },
+                )
+                .map_err(|e| e.map(|e| E::add_context(header_input, "Get section data", e)))?;
+
+            let section = match layout {
+                ImageLayout::File => Section::from_file_and_header(shared, header.clone()),
+                ImageLayout::Mapped => {
+                    Section::from_mapped_image_and_header(shared, header.clone())
+                }
+            };
+            let section = match section {
+                Some(section) => section,
+                None => {
+                    diagnostics.check(
+                        false,
+                        header_input,
+                        "Get section data",
+                        ParseWarning {
+                            offset,
+                            rule: ParseRule::SectionDataOutOfBounds { section: index },
+                            severity: Severity::Error,
+                        },
+                    )?;
+                    Section::from_available_data(shared, header, layout)
+                }
+            };
+            sections.push(section);
+        }
+
+        for index in Section::out_of_order_sections(&sections) {
+            let header_input = &section_table[index * SECTION_HEADER_SIZE..];
+            diagnostics.check(
+                false,
+                header_input,
+                "Verify sections",
+                ParseWarning {
+                    offset: file.offset(header_input),
+                    rule: ParseRule::SectionOrder { section: index },
+                    severity: Severity::Warning,
+                },
+            )?;
+        }
+
+        Ok((i, sections))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::parsers::pe32::writer::align;
+    use nom::error::VerboseError;
+
+    pub(crate) use crate::parsers::pe32::exception::tests::chained_function_table;
+
+    /// A section of a `TestImage`. The raw data is padded to the file alignment.
+    pub(crate) struct TestSection {
+        pub name: &'static str,
+        pub virtual_address: u32,
+        pub virtual_size: u32,
+        pub data: Vec<u8>,
+        pub characteristics: SectionCharacteristics,
+    }
+
+    impl TestSection {
+        pub(crate) fn new(
+            name: &'static str,
+            virtual_address: u32,
+            data: Vec<u8>,
+            characteristics: SectionCharacteristics,
+        ) -> Self {
+            Self {
+                name,
+                virtual_address,
+                virtual_size: data.len() as u32,
+                data,
+                characteristics,
+            }
+        }
+    }
+
+    /// Builds minimal PE32 and PE32+ image files, with 0x1000 section and 0x200 file alignment.
+    pub(crate) struct TestImage {
+        pub pe32_plus: bool,
+        /// The data between the MZ header and the PE signature.
+        pub dos_stub: Vec<u8>,
+        pub entry_point: u32,
+        /// The data directories by index, with their RVA and size.
+        pub directories: Vec<(usize, u32, u32)>,
+        pub sections: Vec<TestSection>,
+        pub overlay: Vec<u8>,
+    }
+
+    pub(crate) const TEST_IMAGE_BASE: u64 = 0x0040_0000;
+    pub(crate) const TEST_IMAGE_BASE_64: u64 = 0x1_4000_0000;
+    const TEST_FILE_ALIGNMENT: usize = 0x200;
+    const TEST_SECTION_ALIGNMENT: usize = 0x1000;
+
+    impl TestImage {
+        /// An executable calling `ExitProcess` from KERNEL32.dll through its import address
+        /// table, which also imports ordinal 17 from COMCTL32.dll:
+        ///
+        /// - `.text` at 0x1000: `push 0; call [ExitProcess]; ret`, the entry point
+        /// - `.idata` at 0x2000: the import directory, with the IAT at 0x2040 and the lookup
+        ///   tables at 0x2050
+        /// - `.reloc` at 0x3000: a base relocation for the IAT address in the call
+        pub(crate) fn executable() -> Self {
+            let mut idata = vec![0u8; 0xB0];
+            let mut put = |offset: usize, words: &[u32]| {
+                for (index, word) in words.iter().enumerate() {
+                    let offset = offset + index * 4;
+                    idata[offset..offset + 4].copy_from_slice(&word.to_le_bytes());
+                }
+            };
+            put(0x00, &[0x2050, 0, 0, 0x2090, 0x2040]);
+            put(0x14, &[0x2058, 0, 0, 0x20A0, 0x2048]);
+            put(0x40, &[0x2080, 0, IMPORT_BY_ORDINAL | 17, 0]);
+            put(0x50, &[0x2080, 0, IMPORT_BY_ORDINAL | 17, 0]);
+            idata[0x80..0x8E].copy_from_slice(b"\x00\x01ExitProcess\0");
+            idata[0x90..0x9D].copy_from_slice(b"KERNEL32.dll\0");
+            idata[0xA0..0xAD].copy_from_slice(b"COMCTL32.dll\0");
+
+            let text = vec![0x6A, 0x00, 0xFF, 0x15, 0x40, 0x20, 0x40, 0x00, 0xC3];
+            let mut reloc = Vec::new();
+            reloc.extend_from_slice(&0x1000u32.to_le_bytes());
+            reloc.extend_from_slice(&12u32.to_le_bytes());
+            reloc.extend_from_slice(&0x3004u16.to_le_bytes());
+            reloc.extend_from_slice(&0u16.to_le_bytes());
+
+            let code = SectionCharacteristics::CNT_CODE
+                | SectionCharacteristics::MEM_EXECUTE
+                | SectionCharacteristics::MEM_READ;
+            let data = SectionCharacteristics::CNT_INITIALIZED_DATA
+                | SectionCharacteristics::MEM_READ
+                | SectionCharacteristics::MEM_WRITE;
+            let discardable = SectionCharacteristics::CNT_INITIALIZED_DATA
+                | SectionCharacteristics::MEM_DISCARDABLE
+                | SectionCharacteristics::MEM_READ;
+            Self {
+                pe32_plus: false,
+                dos_stub: Vec::new(),
+                entry_point: 0x1000,
+                directories: vec![
+                    (KnownDataDirectoryType::Import as usize, 0x2000, 0x3C),
+                    (KnownDataDirectoryType::Basereloc as usize, 0x3000, 12),
+                    (KnownDataDirectoryType::Iat as usize, 0x2040, 0x10),
+                ],
+                sections: vec![
+                    TestSection::new(".text", 0x1000, text, code),
+                    TestSection::new(".idata", 0x2000, idata, data),
+                    TestSection::new(".reloc", 0x3000, reloc, discardable),
+                ],
+                overlay: Vec::new(),
+            }
+        }
+
+        pub(crate) fn image_base(&self) -> u64 {
+            if self.pe32_plus {
+                TEST_IMAGE_BASE_64
+            } else {
+                TEST_IMAGE_BASE
+            }
+        }
+
+        fn e_lfanew(&self) -> usize {
+            align(MZ_HEADER_SIZE + self.dos_stub.len(), 8)
+        }
+
+        fn size_of_headers(&self) -> usize {
+            let optional_header_size = if self.pe32_plus { 240 } else { 224 };
+            align(
+                self.e_lfanew()
+                    + 24
+                    + optional_header_size
+                    + self.sections.len() * SECTION_HEADER_SIZE,
+                TEST_FILE_ALIGNMENT,
+            )
+        }
+
+        fn size_of_image(&self) -> usize {
+            let end = self
+                .sections
+                .iter()
+                .map(|section| {
+                    section.virtual_address as usize
+                        + (section.virtual_size as usize).max(section.data.len())
+                })
+                .fold(self.size_of_headers(), usize::max);
+            align(end, TEST_SECTION_ALIGNMENT)
+        }
+
+        /// The image file, with the section data laid out in order after the headers.
+        pub(crate) fn build(&self) -> Vec<u8> {
+            let e_lfanew = self.e_lfanew();
+            let mut file = vec![0u8; MZ_HEADER_SIZE];
+            file[0..2].copy_from_slice(b"MZ");
+            file[0x3C..0x40].copy_from_slice(&(e_lfanew as u32).to_le_bytes());
+            file.extend_from_slice(&self.dos_stub);
+            file.resize(e_lfanew, 0);
+
+            let put_u16 =
+                |file: &mut Vec<u8>, value: u16| file.extend_from_slice(&value.to_le_bytes());
+            let put_u32 =
+                |file: &mut Vec<u8>, value: u32| file.extend_from_slice(&value.to_le_bytes());
+            let put_word = |file: &mut Vec<u8>, value: u64| {
+                if self.pe32_plus {
+                    file.extend_from_slice(&value.to_le_bytes())
+                } else {
+                    file.extend_from_slice(&(value as u32).to_le_bytes())
+                }
+            };
+
+            file.extend_from_slice(b"PE\0\0");
+            put_u16(&mut file, if self.pe32_plus { 0x8664 } else { 0x014C });
+            put_u16(&mut file, self.sections.len() as u16);
+            put_u32(&mut file, 0); // time_date_stamp
+            put_u32(&mut file, 0); // pointer_to_symbol_table
+            put_u32(&mut file, 0); // number_of_symbols
+            put_u16(&mut file, if self.pe32_plus { 240 } else { 224 });
+            put_u16(&mut file, 0x0102); // executable, 32-bit machine
+
+            put_u16(&mut file, if self.pe32_plus { 0x020B } else { 0x010B });
+            file.extend_from_slice(&[6, 0]); // linker version
+            put_u32(&mut file, 0); // size_of_code
+            put_u32(&mut file, 0); // size_of_initialized_data
+            put_u32(&mut file, 0); // size_of_uninitialized_data
+            put_u32(&mut file, self.entry_point);
+            put_u32(&mut file, 0x1000); // base_of_code
+            if !self.pe32_plus {
+                put_u32(&mut file, 0); // base_of_data
+            }
+            put_word(&mut file, self.image_base());
+            put_u32(&mut file, TEST_SECTION_ALIGNMENT as u32);
+            put_u32(&mut file, TEST_FILE_ALIGNMENT as u32);
+            for version in &[4, 0, 0, 0, 4, 0] {
+                put_u16(&mut file, *version);
+            }
+            put_u32(&mut file, 0); // win32_version_value
+            put_u32(&mut file, self.size_of_image() as u32);
+            put_u32(&mut file, self.size_of_headers() as u32);
+            put_u32(&mut file, 0); // check_sum
+            put_u16(&mut file, 2); // subsystem: Windows GUI
+            put_u16(&mut file, 0); // dll_characteristics
+            for size in &[0x10_0000, 0x1000, 0x10_0000, 0x1000] {
+                put_word(&mut file, *size);
+            }
+            put_u32(&mut file, 0); // loader_flags
+            put_u32(&mut file, 16); // number_of_rva_and_sizes
+            for index in 0..16 {
+                let (rva, size) = self
+                    .directories
+                    .iter()
+                    .find(|directory| directory.0 == index)
+                    .map_or((0, 0), |directory| (directory.1, directory.2));
+                put_u32(&mut file, rva);
+                put_u32(&mut file, size);
+            }
+
+            let mut pointer = self.size_of_headers();
+            for section in &self.sections {
+                let mut name = [0u8; 8];
+                name[..section.name.len()].copy_from_slice(section.name.as_bytes());
+                file.extend_from_slice(&name);
+                let raw_size = align(section.data.len(), TEST_FILE_ALIGNMENT);
+                put_u32(&mut file, section.virtual_size);
+                put_u32(&mut file, section.virtual_address);
+                put_u32(&mut file, raw_size as u32);
+                put_u32(&mut file, if raw_size == 0 { 0 } else { pointer as u32 });
+                file.extend_from_slice(&[0; 12]); // relocations and line numbers
+                put_u32(&mut file, section.characteristics.bits());
+                pointer += raw_size;
+            }
+
+            file.resize(self.size_of_headers(), 0);
+            for section in &self.sections {
+                let start = file.len();
+                file.extend_from_slice(&section.data);
+                file.resize(start + align(section.data.len(), TEST_FILE_ALIGNMENT), 0);
+            }
+            file.extend_from_slice(&self.overlay);
+            file
+        }
+
+        /// The image as the Windows loader maps it, with the section data at its RVA.
+        pub(crate) fn map(&self) -> Vec<u8> {
+            let mut image = self.build();
+            image.truncate(self.size_of_headers());
+            image.resize(self.size_of_image(), 0);
+            for section in &self.sections {
+                let start = section.virtual_address as usize;
+                image[start..start + section.data.len()].copy_from_slice(&section.data);
+            }
+            image
+        }
+    }
+
+    #[test]
+    fn parses_test_image() {
+        let file = TestImage::executable().build();
+        let (_, image) = PE32Image::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+
+        assert!(image.warnings.is_empty());
+        let names: Vec<&str> = image
+            .sections
+            .iter()
+            .map(|section| section.header.name.as_str())
+            .collect();
+        assert_eq!(names, vec![".text", ".idata", ".reloc"]);
+        assert_eq!(image.imports.len(), 2);
+        assert_eq!(image.imports[0].name, "KERNEL32.dll");
+        assert_eq!(
+            image.imports[0].imports,
+            vec![Import {
+                iat_rva: 0x2040,
+                lookup_entry: 0x2080,
+                name: ImportName::Name {
+                    hint: 0x100,
+                    name: "ExitProcess".to_owned()
+                },
+            }]
+        );
+        assert_eq!(image.imports[1].imports[0].name, ImportName::Ordinal(17));
+        assert_eq!(image.base_relocations.len(), 1);
+        assert_eq!(image.entry_point_data().unwrap()[..2], [0x6A, 0x00]);
+    }
+
+    #[test]
+    fn parses_malformed_image_leniently() {
+        let mut file = TestImage::executable().build();
+        // A file alignment below 512, an import descriptor naming a DLL outside of the image, and
+        // the raw data of .reloc cut short
+        file[0x7C..0x80].copy_from_slice(&0x100u32.to_le_bytes());
+        file[0x40C..0x410].copy_from_slice(&0x9000u32.to_le_bytes());
+        file.truncate(0x700);
+
+        assert!(PE32Image::try_parse::<VerboseError<&[u8]>>(&file).is_err());
+
+        let options = ParseOptions {
+            layout: ImageLayout::File,
+            mode: ParseMode::Lenient,
+        };
+        let (_, image) =
+            PE32Image::try_parse_with_options::<VerboseError<&[u8]>>(options)(&file).unwrap();
+        assert_eq!(
+            image.warnings,
+            vec![
+                ParseWarning {
+                    offset: 0x7C,
+                    rule: ParseRule::FileAlignment {
+                        file_alignment: 0x100
+                    },
+                    severity: Severity::Warning,
+                },
+                ParseWarning {
+                    offset: 0x188,
+                    rule: ParseRule::SectionDataOutOfBounds { section: 2 },
+                    severity: Severity::Error,
+                },
+                ParseWarning {
+                    offset: 0xC0,
+                    rule: ParseRule::ImportDirectory,
+                    severity: Severity::Error,
+                },
+            ]
+        );
+
+        assert_eq!(image.sections.len(), 3);
+        assert_eq!(image.sections[2].data.len(), 0x100);
+        assert_eq!(image.sections[2].uninitialized_data_size, 0);
+        assert!(image.imports.is_empty());
+        // The relocation block itself is still within the truncated data
+        assert_eq!(image.base_relocations.len(), 1);
+        assert_eq!(image.entry_point_data().unwrap()[..2], [0x6A, 0x00]);
+    }
+
+    #[test]
+    fn parses_zero_file_alignment_leniently() {
+        let mut file = TestImage::executable().build();
+        file[0x7C..0x80].copy_from_slice(&0u32.to_le_bytes());
+
+        let options = ParseOptions {
+            layout: ImageLayout::File,
+            mode: ParseMode::Lenient,
+        };
+        let (_, image) =
+            PE32Image::try_parse_with_options::<VerboseError<&[u8]>>(options)(&file).unwrap();
+        let rules: Vec<ParseRule> = image.warnings.iter().map(|warning| warning.rule).collect();
+        assert_eq!(
+            rules,
+            vec![
+                ParseRule::FileAlignment { file_alignment: 0 },
+                ParseRule::SectionHeaderAlignment { section: 0 },
+                ParseRule::SectionHeaderAlignment { section: 1 },
+                ParseRule::SectionHeaderAlignment { section: 2 },
+            ]
+        );
+        assert_eq!(image.sections.len(), 3);
+        assert_eq!(image.imports.len(), 2);
+    }
+
+    #[test]
+    fn warns_about_malformed_directories_in_strict_mode() {
+        let mut file = TestImage::executable().build();
+        file[0x40C..0x410].copy_from_slice(&0x9000u32.to_le_bytes());
+
+        let (_, image) = PE32Image::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+        assert_eq!(
+            image.warnings,
+            vec![ParseWarning {
+                offset: 0xC0,
+                rule: ParseRule::ImportDirectory,
+                severity: Severity::Error,
+            }]
+        );
+        assert!(image.imports.is_empty());
+        assert_eq!(image.base_relocations.len(), 1);
+    }
+
+    #[test]
+    fn parses_shared_buffer_without_copying() {
+        let buffer = SharedBytes::from(TestImage::executable().build());
+        let (_, image) =
+            PE32Image::try_parse_shared::<VerboseError<&[u8]>>(&buffer, ParseOptions::default())
+                .unwrap();
+
+        assert_eq!(image.sections[0].data.as_ptr(), buffer[0x200..].as_ptr());
+        assert_eq!(image.sections[2].data.as_ptr(), buffer[0x600..].as_ptr());
+    }
+
+    #[cfg(feature = "memmap2")]
+    #[test]
+    fn parses_memory_mapped_file() {
+        let file = TestImage::executable().build();
+        let path = std::env::temp_dir().join(format!("reic-pe32-{}.exe", std::process::id()));
+        std::fs::write(&path, &file).unwrap();
+        let buffer = SharedBytes::map_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let buffer = buffer.unwrap();
+        let (_, image) =
+            PE32Image::try_parse_shared::<VerboseError<&[u8]>>(&buffer, ParseOptions::default())
+                .unwrap();
+        let (_, copied) = PE32Image::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+        assert_eq!(image, copied);
+        assert_eq!(image.sections[1].data.as_ptr(), buffer[0x400..].as_ptr());
+    }
 }
@@ -0,0 +1,69 @@
+use crate::parsers::pe32::PE32Image;
+
+/// The stored and the actual checksum of an image file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    /// The `check_sum` field of the optional header.
+    pub stored: u32,
+    /// The checksum computed over the file.
+    pub computed: u32,
+}
+
+impl Checksum {
+    /// Whether the image carries a checksum. Linkers only emit one on request, and the loader
+    /// only verifies it for drivers and DLLs loaded into critical processes.
+    pub fn is_present(&self) -> bool {
+        self.stored != 0
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.stored == self.computed
+    }
+}
+
+/// Computes the checksum of an image file as `CheckSumMappedFile` does: the 16-bit one's
+/// complement sum of the file, plus the file length. The checksum field at `check_sum_offset`
+/// is skipped.
+pub fn compute_checksum(file: &[u8], check_sum_offset: usize) -> u32 {
+    let mut sum = 0u32;
+    for (index, word) in file.chunks(2).enumerate() {
+        let offset = index * 2;
+        if offset >= check_sum_offset && offset < check_sum_offset + 4 {
+            continue;
+        }
+
+        let word = match *word {
+            [low, high] => u16::from_le_bytes([low, high]),
+            [low] => u16::from(low),
+            _ => unreachable!(),
+        };
+        sum += u32::from(word);
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+
+    (sum & 0xFFFF).wrapping_add(file.len() as u32)
+}
+
+impl PE32Image {
+    /// The stored checksum and the checksum of the file the image was parsed from, or `None`
+    /// for mapped images. The computed checksum does not reflect changes made to the image,
+    /// see `to_bytes`.
+    pub fn checksum(&self) -> Option<Checksum> {
+        self.computed_check_sum.map(|computed| Checksum {
+            stored: self.pe32_optional_header.windows_specific.check_sum,
+            computed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum_skips_the_checksum_field() {
+        let file = [0x01, 0x02, 0xAA, 0xAA, 0xAA, 0xAA, 0xFF, 0xFF, 0x03];
+        // 0x0201 + 0xFFFF folds to 0x0201, plus the odd trailing byte and the file length
+        assert_eq!(compute_checksum(&file, 2), 0x0204 + 9);
+    }
+}
@@ -0,0 +1,541 @@
+use crate::parsers::pe32::{rva_data, ImageLayout, Section};
+use crate::parsers::{BinParsable, Diagnostics, ParseRule, ParseWarning, Severity};
+
+use bitflags::bitflags;
+use nameof::name_of;
+use nom::{
+    bytes::complete::take,
+    combinator::{map, verify},
+    error::context,
+    error::{make_error, ErrorKind, ParseError},
+    number::complete::{le_u16, le_u32, le_u8},
+    sequence::tuple,
+    Err, IResult, Offset,
+};
+
+/// An entry of the function table in the exception directory (`RUNTIME_FUNCTION`), describing
+/// how to unwind the stack frame of a function or a fragment of it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RuntimeFunctionEntry {
+    /// The RVA of the first byte of the function.
+    pub begin_address: u32,
+    /// The RVA after the last byte of the function.
+    pub end_address: u32,
+    /// The RVA of the unwind info.
+    pub unwind_info_address: u32,
+}
+
+impl BinParsable for RuntimeFunctionEntry {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(
+            name_of!(type RuntimeFunctionEntry),
+            map(
+                tuple((
+                    le_u32, // begin_address
+                    le_u32, // end_address
+                    le_u32, // unwind_info_address
+                )),
+                |p| Self {
+                    begin_address: p.0,
+                    end_address: p.1,
+                    unwind_info_address: p.2,
+                },
+            ),
+        )(i)
+    }
+}
+
+bitflags! {
+    #[derive(Default)]
+    pub struct UnwindFlags: u8 {
+        /// The function has an exception handler that is called when looking for a handler.
+        const EHANDLER = 0x01;
+        /// The function has a termination handler that is called when unwinding.
+        const UHANDLER = 0x02;
+        /// The unwind info continues the one of the primary function entry that follows the
+        /// unwind codes.
+        const CHAININFO = 0x04;
+    }
+}
+
+/// A register numbered as in the unwind codes: RAX, RCX, RDX, RBX, RSP, RBP, RSI, RDI, then R8
+/// to R15. For the XMM operations, the number of the XMM register.
+pub type UnwindRegister = u8;
+
+/// A prolog operation, in the reverse order of execution.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UnwindOperation {
+    /// `push reg` (`UWOP_PUSH_NONVOL`).
+    PushNonvolatile(UnwindRegister),
+    /// `sub rsp, size` for sizes up to 0x7FFF8 (`UWOP_ALLOC_LARGE` with 16-bit operand) or
+    /// larger (with 32-bit operand).
+    AllocLarge(u32),
+    /// `sub rsp, size` for sizes from 8 to 128 (`UWOP_ALLOC_SMALL`).
+    AllocSmall(u32),
+    /// Establishes the frame register at the frame offset from RSP (`UWOP_SET_FPREG`).
+    SetFramePointer,
+    /// `mov [rsp + offset], reg` (`UWOP_SAVE_NONVOL` and `UWOP_SAVE_NONVOL_FAR`).
+    SaveNonvolatile {
+        register: UnwindRegister,
+        offset: u32,
+    },
+    /// `movaps [rsp + offset], xmm` (`UWOP_SAVE_XMM128` and `UWOP_SAVE_XMM128_FAR`).
+    SaveXmm128 {
+        register: UnwindRegister,
+        offset: u32,
+    },
+    /// The frame of a hardware interrupt or exception, optionally with an error code
+    /// (`UWOP_PUSH_MACHFRAME`).
+    PushMachineFrame { error_code: bool },
+    /// Describes an epilog in version 2 unwind info (`UWOP_EPILOG`). The first one carries
+    /// the size of the epilogs, the following ones their offsets from the end of the function.
+    Epilog { offset: u8, info: u8 },
+}
+
+/// An unwind code: a prolog operation and the offset after it in the prolog.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct UnwindCode {
+    /// The offset from the start of the prolog to the end of the instruction.
+    pub code_offset: u8,
+    pub operation: UnwindOperation,
+}
+
+/// What follows the unwind codes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UnwindTrailer {
+    None,
+    /// The language specific handler and its data, such as a scope table for
+    /// `__C_specific_handler` or the `FuncInfo` RVA for `__CxxFrameHandler3`.
+    Handler {
+        /// The RVA of the handler.
+        handler: u32,
+        /// The RVA of the handler data, which directly follows the handler RVA.
+        data: u32,
+    },
+    /// The primary function entry that this unwind info continues.
+    Chained(RuntimeFunctionEntry),
+}
+
+/// The unwind info (`UNWIND_INFO`) of a function, describing its prolog.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnwindInfo {
+    /// 1, or 2 for unwind info with epilog codes.
+    pub version: u8,
+    pub flags: UnwindFlags,
+    /// The size of the prolog in bytes.
+    pub size_of_prolog: u8,
+    /// The nonvolatile register used as frame pointer, if nonzero.
+    pub frame_register: UnwindRegister,
+    /// The offset of the frame pointer from RSP when it is established, in 16 byte units.
+    pub frame_offset: u8,
+    /// The prolog operations, in the reverse order of execution.
+    pub codes: Vec<UnwindCode>,
+    pub trailer: UnwindTrailer,
+}
+
+impl UnwindInfo {
+    /// Parses the unwind info at the start of `i`. `rva` is the RVA of `i`, which the handler
+    /// data RVA is computed from.
+    pub fn try_parse_at<'a, E: ParseError<&'a [u8]>>(
+        rva: u32,
+    ) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], Self, E> {
+        move |input: &'a [u8]| {
+            context(name_of!(type UnwindInfo), |i: &'a [u8]| {
+                let (i, (version_and_flags, size_of_prolog, count_of_codes, frame)) =
+                    tuple((
+                        verify(le_u8, |v| matches!(v & 0x07, 1 | 2)),
+                        le_u8,
+                        le_u8,
+                        le_u8,
+                    ))(i)?;
+                let version = version_and_flags & 0x07;
+                let flags = UnwindFlags::from_bits_truncate(version_and_flags >> 3);
+
+                // The slots are padded to an even count, keeping the trailer dword-aligned
+                let slot_count = (count_of_codes as usize + 1) & !1;
+                let (i, slots) = take(slot_count * 2)(i)?;
+                let codes = unwind_codes(version, &slots[..count_of_codes as usize * 2])
+                    .ok_or_else(|| Err::Error(make_error(input, ErrorKind::Verify)))?;
+
+                let (i, trailer) = if flags.contains(UnwindFlags::CHAININFO) {
+                    map(RuntimeFunctionEntry::try_parse, UnwindTrailer::Chained)(i)?
+                } else if flags.intersects(UnwindFlags::EHANDLER | UnwindFlags::UHANDLER) {
+                    let (i, handler) = le_u32(i)?;
+                    let data = rva
+                        .checked_add((4 + slot_count * 2 + 4) as u32)
+                        .ok_or_else(|| Err::Error(make_error(input, ErrorKind::TooLarge)))?;
+                    (i, UnwindTrailer::Handler { handler, data })
+                } else {
+                    (i, UnwindTrailer::None)
+                };
+
+                Ok((
+                    i,
+                    Self {
+                        version,
+                        flags,
+                        size_of_prolog,
+                        frame_register: frame & 0x0F,
+                        frame_offset: frame >> 4,
+                        codes,
+                        trailer,
+                    },
+                ))
+            })(input)
+        }
+    }
+}
+
+/// Decodes the unwind code slots, some operations of which take up to two more slots for their
+/// operand.
+fn unwind_codes(version: u8, mut slots: &[u8]) -> Option<Vec<UnwindCode>> {
+    let mut codes = Vec::new();
+    while !slots.is_empty() {
+        let code_offset = slots[0];
+        let operation_code = slots[1] & 0x0F;
+        let info = slots[1] >> 4;
+        let operand16 = |slots: &[u8]| {
+            slots
+                .get(2..4)
+                .map(|operand| u32::from(u16::from_le_bytes([operand[0], operand[1]])))
+        };
+        let operand32 = |slots: &[u8]| {
+            slots
+                .get(2..6)
+                .map(|operand| u32::from_le_bytes([operand[0], operand[1], operand[2], operand[3]]))
+        };
+
+        let (operation, slot_count) = match operation_code {
+            0 => (UnwindOperation::PushNonvolatile(info), 1),
+            1 if info == 0 => (UnwindOperation::AllocLarge(operand16(slots)? * 8), 2),
+            1 if info == 1 => (UnwindOperation::AllocLarge(operand32(slots)?), 3),
+            2 => (UnwindOperation::AllocSmall(u32::from(info) * 8 + 8), 1),
+            3 => (UnwindOperation::SetFramePointer, 1),
+            4 => (
+                UnwindOperation::SaveNonvolatile {
+                    register: info,
+                    offset: operand16(slots)? * 8,
+                },
+                2,
+            ),
+            5 => (
+                UnwindOperation::SaveNonvolatile {
+                    register: info,
+                    offset: operand32(slots)?,
+                },
+                3,
+            ),
+            6 if version == 2 => (
+                UnwindOperation::Epilog {
+                    offset: code_offset,
+                    info,
+                },
+                1,
+            ),
+            8 => (
+                UnwindOperation::SaveXmm128 {
+                    register: info,
+                    offset: operand16(slots)? * 16,
+                },
+                2,
+            ),
+            9 => (
+                UnwindOperation::SaveXmm128 {
+                    register: info,
+                    offset: operand32(slots)?,
+                },
+                3,
+            ),
+            10 if info <= 1 => (
+                UnwindOperation::PushMachineFrame {
+                    error_code: info == 1,
+                },
+                1,
+            ),
+            _ => return None,
+        };
+        codes.push(UnwindCode {
+            code_offset,
+            operation,
+        });
+        slots = &slots[slot_count * 2..];
+    }
+    Some(codes)
+}
+
+/// A function table entry with its unwind info.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeFunction {
+    pub entry: RuntimeFunctionEntry,
+    pub unwind: UnwindInfo,
+    /// For chained unwind info, the primary function this entry is a fragment of, with its own
+    /// unwind info.
+    pub parent: Option<Box<RuntimeFunction>>,
+}
+
+impl RuntimeFunction {
+    /// The entry of the primary function at the end of the chain.
+    pub fn primary(&self) -> &RuntimeFunction {
+        let mut function = self;
+        while let Some(parent) = &function.parent {
+            function = parent;
+        }
+        function
+    }
+}
+
+/// The maximum length of a chain of unwind infos. The Windows unwinder limits it to 32.
+const MAX_CHAIN_LENGTH: usize = 32;
+
+/// Parses the function table of the exception directory spanning the given RVA range, with the
+/// unwind info of every entry. Entries whose unwind info cannot be parsed are left out with a
+/// warning.
+pub(crate) fn exception_directory<'a, E: ParseError<&'a [u8]>>(
+    file: &'a [u8],
+    sections: &[Section],
+    layout: ImageLayout,
+    rva: u32,
+    size: u32,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<RuntimeFunction>, Err<E>> {
+    let mut table = rva_data(file, sections, layout, rva)?;
+    let mut functions = Vec::new();
+    for index in 0..size as usize / 12 {
+        let offset = file.offset(table);
+        let (rest, entry) = RuntimeFunctionEntry::try_parse(table)?;
+        table = rest;
+        // Entries of all zeroes may pad the table
+        if entry.begin_address == 0 && entry.unwind_info_address == 0 {
+            continue;
+        }
+        let function = runtime_function::<E>(file, sections, layout, entry, 0);
+        functions.extend(diagnostics.recover(
+            function,
+            ParseWarning {
+                offset,
+                rule: ParseRule::RuntimeFunction { entry: index },
+                severity: Severity::Error,
+            },
+        ));
+    }
+    Ok(functions)
+}
+
+fn runtime_function<'a, E: ParseError<&'a [u8]>>(
+    file: &'a [u8],
+    sections: &[Section],
+    layout: ImageLayout,
+    entry: RuntimeFunctionEntry,
+    depth: usize,
+) -> Result<RuntimeFunction, Err<E>> {
+    let address = entry.unwind_info_address;
+    let data = rva_data(file, sections, layout, address)?;
+    let (_, unwind) = UnwindInfo::try_parse_at(address)(data)?;
+    let parent = match unwind.trailer {
+        UnwindTrailer::Chained(_) if depth >= MAX_CHAIN_LENGTH => {
+            return Err(Err::Error(make_error(data, ErrorKind::TooLarge)));
+        }
+        UnwindTrailer::Chained(parent) => Some(Box::new(runtime_function(
+            file,
+            sections,
+            layout,
+            parent,
+            depth + 1,
+        )?)),
+        _ => None,
+    };
+    Ok(RuntimeFunction {
+        entry,
+        unwind,
+        parent,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::parsers::pe32::tests::{TestImage, TestSection};
+    use crate::parsers::pe32::{KnownDataDirectoryType, PE32Image, SectionCharacteristics};
+    use nom::error::VerboseError;
+
+    /// A PE32+ image with the given function table in a `.pdata` section at 0x2000, followed by
+    /// the given unwind infos at 0x2100.
+    pub(crate) fn function_table_image(table: &[(u32, u32, u32)], unwind: &[u8]) -> TestImage {
+        let mut pdata = Vec::new();
+        for &(begin_address, end_address, unwind_info_address) in table {
+            for field in &[begin_address, end_address, unwind_info_address] {
+                pdata.extend_from_slice(&field.to_le_bytes());
+            }
+        }
+        pdata.resize(0x100, 0);
+        pdata.extend_from_slice(unwind);
+
+        let code = SectionCharacteristics::CNT_CODE
+            | SectionCharacteristics::MEM_EXECUTE
+            | SectionCharacteristics::MEM_READ;
+        let data = SectionCharacteristics::CNT_INITIALIZED_DATA | SectionCharacteristics::MEM_READ;
+        TestImage {
+            pe32_plus: true,
+            dos_stub: Vec::new(),
+            entry_point: 0x1000,
+            directories: vec![(
+                KnownDataDirectoryType::Exception as usize,
+                0x2000,
+                table.len() as u32 * 12,
+            )],
+            sections: vec![
+                TestSection::new(".text", 0x1000, vec![0xC3; 0x200], code),
+                TestSection::new(".pdata", 0x2000, pdata, data),
+            ],
+            overlay: Vec::new(),
+        }
+    }
+
+    /// A function at 0x1000 with a fragment at 0x1080 chained to it, an entry whose version 1
+    /// unwind info uses the version 2 epilog code, and a padding entry.
+    pub(crate) fn chained_function_table() -> TestImage {
+        let unwind = [
+            // 0x2100: sub rsp, 28h
+            0x01, 0x04, 0x01, 0x00, 0x04, 0x42, 0x00, 0x00, //
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+            // 0x2110: chained to the function at 0x1000
+            0x21, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, //
+            0x40, 0x10, 0x00, 0x00, 0x00, 0x21, 0x00, 0x00, //
+            // 0x2120: UWOP_EPILOG in version 1
+            0x01, 0x00, 0x01, 0x00, 0x00, 0x06, 0x00, 0x00,
+        ];
+        function_table_image(
+            &[
+                (0x1000, 0x1040, 0x2100),
+                (0x1080, 0x10A0, 0x2110),
+                (0x10A0, 0x10C0, 0x2120),
+                (0, 0, 0),
+            ],
+            &unwind,
+        )
+    }
+
+    #[test]
+    fn parses_unwind_info() {
+        let unwind = [
+            0x19, // version 1, EHANDLER | UHANDLER
+            0x0E, // size_of_prolog
+            0x05, // count_of_codes
+            0x35, // RBP at RSP + 0x30
+            0x0E, 0x53, // SET_FPREG
+            0x0A, 0x01, 0x12, 0x00, // ALLOC_LARGE 0x90
+            0x03, 0x60, // PUSH_NONVOL RSI
+            0x01, 0x50, // PUSH_NONVOL RBP
+            0x00, 0x00, // padding
+            0x00, 0x10, 0x00, 0x00, // handler
+            0x20, 0x30, 0x00, 0x00, // handler data
+        ];
+        let (rest, info) =
+            UnwindInfo::try_parse_at::<VerboseError<&[u8]>>(0x2000)(&unwind[..]).unwrap();
+        assert_eq!(rest.len(), 4);
+        assert_eq!(info.flags, UnwindFlags::EHANDLER | UnwindFlags::UHANDLER);
+        assert_eq!((info.frame_register, info.frame_offset), (5, 3));
+        assert_eq!(
+            info.codes
+                .iter()
+                .map(|code| (code.code_offset, code.operation))
+                .collect::<Vec<_>>(),
+            vec![
+                (0x0E, UnwindOperation::SetFramePointer),
+                (0x0A, UnwindOperation::AllocLarge(0x90)),
+                (0x03, UnwindOperation::PushNonvolatile(6)),
+                (0x01, UnwindOperation::PushNonvolatile(5)),
+            ]
+        );
+        assert_eq!(
+            info.trailer,
+            UnwindTrailer::Handler {
+                handler: 0x1000,
+                data: 0x2014,
+            }
+        );
+
+        let chained = [
+            0x21, 0x00, 0x00, 0x00, // version 1, CHAININFO
+            0x00, 0x10, 0x00, 0x00, 0x40, 0x10, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00,
+        ];
+        let (_, info) =
+            UnwindInfo::try_parse_at::<VerboseError<&[u8]>>(0x2100)(&chained[..]).unwrap();
+        assert_eq!(
+            info.trailer,
+            UnwindTrailer::Chained(RuntimeFunctionEntry {
+                begin_address: 0x1000,
+                end_address: 0x1040,
+                unwind_info_address: 0x2000,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_handler_data_beyond_the_address_space() {
+        let unwind = [
+            0x09, 0x00, 0x00, 0x00, // version 1, EHANDLER
+            0x00, 0x10, 0x00, 0x00, // handler
+        ];
+        let result = UnwindInfo::try_parse_at::<(&[u8], ErrorKind)>(0xFFFF_FFFC)(&unwind[..]);
+        assert_eq!(result, Err(Err::Error((&unwind[..], ErrorKind::TooLarge))));
+        assert!(UnwindInfo::try_parse_at::<VerboseError<&[u8]>>(0xFFFF_FFF0)(&unwind[..]).is_ok());
+    }
+
+    #[test]
+    fn parses_chained_entries_and_skips_malformed_ones() {
+        let file = chained_function_table().build();
+        let (_, image) = PE32Image::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+        let functions = &image.runtime_functions;
+        assert_eq!(functions.len(), 2);
+        assert_eq!(functions[0].parent, None);
+        assert_eq!(
+            functions[0].unwind.codes[0].operation,
+            UnwindOperation::AllocSmall(0x28)
+        );
+        assert_eq!(functions[1].entry.begin_address, 0x1080);
+        assert_eq!(functions[1].primary(), &functions[0]);
+        // The raw data of .pdata starts at 0x400
+        assert_eq!(
+            image.warnings,
+            vec![ParseWarning {
+                offset: 0x418,
+                rule: ParseRule::RuntimeFunction { entry: 2 },
+                severity: Severity::Error,
+            }]
+        );
+    }
+
+    #[test]
+    fn limits_chain_length() {
+        // Unwind info 0 to 32 are each chained to the next one, 33 is a primary one
+        let mut unwind = Vec::new();
+        for index in 0..=MAX_CHAIN_LENGTH as u32 {
+            unwind.extend_from_slice(&[0x21, 0x00, 0x00, 0x00]);
+            for field in &[0x1000, 0x1010, 0x2100 + (index + 1) * 0x10] {
+                unwind.extend_from_slice(&field.to_le_bytes());
+            }
+        }
+        unwind.extend_from_slice(&[0x01, 0x00, 0x00, 0x00]);
+        let file = function_table_image(
+            &[(0x1100, 0x1110, 0x2100), (0x1120, 0x1130, 0x2110)],
+            &unwind,
+        )
+        .build();
+
+        let (_, image) = PE32Image::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+        // Only the chain starting at the second unwind info is short enough
+        assert_eq!(image.runtime_functions.len(), 1);
+        let mut function = &image.runtime_functions[0];
+        let mut length = 0;
+        while let Some(parent) = &function.parent {
+            function = parent;
+            length += 1;
+        }
+        assert_eq!(length, MAX_CHAIN_LENGTH);
+        assert_eq!(
+            image.warnings[0].rule,
+            ParseRule::RuntimeFunction { entry: 0 }
+        );
+    }
+}
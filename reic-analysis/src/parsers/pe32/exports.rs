@@ -0,0 +1,160 @@
+use crate::parsers::pe32::{rva_data, string_at_rva, ImageLayout, Section};
+use crate::parsers::BinParsable;
+
+use nameof::name_of;
+use nom::{
+    combinator::map,
+    error::context,
+    error::ParseError,
+    multi::count,
+    number::complete::{le_u16, le_u32},
+    sequence::tuple,
+    Err, IResult,
+};
+
+/// The export directory table, describing the symbols exported by the image.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExportDirectoryTable {
+    /// Reserved, must be 0.
+    pub export_flags: u32,
+    /// The time and date that the export data was created.
+    pub time_date_stamp: u32,
+    pub major_version: u16,
+    pub minor_version: u16,
+    /// The RVA of the name of the DLL.
+    pub name_rva: u32,
+    /// The starting ordinal number for exports in this image.
+    pub ordinal_base: u32,
+    /// The number of entries in the export address table.
+    pub address_table_entries: u32,
+    /// The number of entries in the name pointer table (and the ordinal table).
+    pub number_of_name_pointers: u32,
+    /// The RVA of the export address table.
+    pub export_address_table_rva: u32,
+    /// The RVA of the export name pointer table.
+    pub name_pointer_rva: u32,
+    /// The RVA of the ordinal table.
+    pub ordinal_table_rva: u32,
+}
+
+impl BinParsable for ExportDirectoryTable {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(
+            name_of!(type ExportDirectoryTable),
+            map(
+                tuple((
+                    le_u32, // export_flags
+                    le_u32, // time_date_stamp
+                    le_u16, // major_version
+                    le_u16, // minor_version
+                    le_u32, // name_rva
+                    le_u32, // ordinal_base
+                    le_u32, // address_table_entries
+                    le_u32, // number_of_name_pointers
+                    le_u32, // export_address_table_rva
+                    le_u32, // name_pointer_rva
+                    le_u32, // ordinal_table_rva
+                )),
+                |p| Self {
+                    export_flags: p.0,
+                    time_date_stamp: p.1,
+                    major_version: p.2,
+                    minor_version: p.3,
+                    name_rva: p.4,
+                    ordinal_base: p.5,
+                    address_table_entries: p.6,
+                    number_of_name_pointers: p.7,
+                    export_address_table_rva: p.8,
+                    name_pointer_rva: p.9,
+                    ordinal_table_rva: p.10,
+                },
+            ),
+        )(i)
+    }
+}
+
+/// The exports of the image.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExportDirectory {
+    pub table: ExportDirectoryTable,
+    /// The name of the DLL.
+    pub name: String,
+    pub exports: Vec<Export>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Export {
+    pub ordinal: u32,
+    pub name: Option<String>,
+    pub target: ExportTarget,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExportTarget {
+    /// The RVA of the exported code or data.
+    Rva(u32),
+    /// The export is forwarded to another DLL (`DLL.Name` or `DLL.#Ordinal`).
+    Forwarder(String),
+}
+
+/// Parses the export directory spanning the given RVA range.
+pub fn export_directory<'a, E: ParseError<&'a [u8]>>(
+    file: &'a [u8],
+    sections: &[Section],
+    layout: ImageLayout,
+    rva: u32,
+    size: u32,
+) -> Result<ExportDirectory, Err<E>> {
+    let (_, table) = ExportDirectoryTable::try_parse(rva_data(file, sections, layout, rva)?)?;
+    let name = string_at_rva(file, sections, layout, table.name_rva)?;
+
+    let (_, addresses) = context(
+        "Export address table",
+        count(le_u32, table.address_table_entries as usize),
+    )(rva_data(
+        file,
+        sections,
+        layout,
+        table.export_address_table_rva,
+    )?)?;
+    let (_, name_pointers) = context(
+        "Export name pointer table",
+        count(le_u32, table.number_of_name_pointers as usize),
+    )(rva_data(file, sections, layout, table.name_pointer_rva)?)?;
+    let (_, name_ordinals) = context(
+        "Export ordinal table",
+        count(le_u16, table.number_of_name_pointers as usize),
+    )(rva_data(file, sections, layout, table.ordinal_table_rva)?)?;
+
+    let mut names = vec![None; addresses.len()];
+    for (&name_rva, &index) in name_pointers.iter().zip(&name_ordinals) {
+        if let Some(name) = names.get_mut(index as usize) {
+            *name = Some(string_at_rva(file, sections, layout, name_rva)?);
+        }
+    }
+
+    let mut exports = Vec::new();
+    for (index, (address, name)) in addresses.into_iter().zip(names).enumerate() {
+        if address == 0 {
+            continue;
+        }
+
+        // Addresses pointing into the export directory itself are forwarder strings.
+        let target = if address >= rva && address < rva.wrapping_add(size) {
+            ExportTarget::Forwarder(string_at_rva(file, sections, layout, address)?)
+        } else {
+            ExportTarget::Rva(address)
+        };
+        exports.push(Export {
+            ordinal: table.ordinal_base + index as u32,
+            name,
+            target,
+        });
+    }
+
+    Ok(ExportDirectory {
+        table,
+        name,
+        exports,
+    })
+}
@@ -1,4 +1,5 @@
-use crate::parsers::BinParsable;
+use crate::parsers::coff::COFFImageOptionalHeaderType;
+use crate::parsers::{BinParsable, BinWritable};
 
 use bitflags::bitflags;
 use nameof::name_of;
@@ -7,7 +8,7 @@ use nom::{
     combinator::map_opt,
     error::context,
     error::ParseError,
-    number::complete::{le_u16, le_u32},
+    number::complete::{le_u16, le_u32, le_u64},
     sequence::tuple,
     IResult,
 };
@@ -18,25 +19,57 @@ use num_traits::FromPrimitive;
 pub struct PE32OptionalHeader {
     /// The address that is relative to the image base of the
     /// beginning-of-data section when it is loaded into memory.
-    pub base_of_data: u32,
+    ///
+    /// PE32+ images do not have this field.
+    pub base_of_data: Option<u32>,
 
     /// The next 21 fields are an extension to the COFF optional header format.
     /// They contain additional information that is required by the linker and loader in Windows.
     pub windows_specific: PE32OptionalHeaderWindowsSpecific,
 }
 
+impl PE32OptionalHeader {
+    /// Parses the fields following the standard fields, in the PE32 or PE32+ layout.
+    pub fn try_parse_for<'a, E: ParseError<&'a [u8]>>(
+        magic: COFFImageOptionalHeaderType,
+    ) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], Self, E> {
+        let is_pe32_plus = magic == COFFImageOptionalHeaderType::PE32Plus;
+        context(name_of!(type PE32OptionalHeader), move |i: &'a [u8]| {
+            let (i, base_of_data) = if is_pe32_plus {
+                (i, None)
+            } else {
+                map(le_u32, Some)(i)?
+            };
+            let (i, windows_specific) =
+                PE32OptionalHeaderWindowsSpecific::try_parse_for(is_pe32_plus)(i)?;
+            Ok((
+                i,
+                Self {
+                    base_of_data,
+                    windows_specific,
+                },
+            ))
+        })
+    }
+
+    /// Whether the header has the PE32+ layout of 64-bit images.
+    pub fn is_pe32_plus(&self) -> bool {
+        self.base_of_data.is_none()
+    }
+}
+
 impl BinParsable for PE32OptionalHeader {
     fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
-        context(
-            name_of!(type PE32OptionalHeader),
-            map(
-                tuple((le_u32, PE32OptionalHeaderWindowsSpecific::try_parse)),
-                |p| Self {
-                    base_of_data: p.0,
-                    windows_specific: p.1,
-                },
-            ),
-        )(i)
+        Self::try_parse_for(COFFImageOptionalHeaderType::PE32)(i)
+    }
+}
+
+impl BinWritable for PE32OptionalHeader {
+    fn write_to(&self, out: &mut Vec<u8>) {
+        if let Some(base_of_data) = self.base_of_data {
+            out.extend_from_slice(&base_of_data.to_le_bytes());
+        }
+        self.windows_specific.write_fields(out, self.is_pe32_plus());
     }
 }
 
@@ -49,7 +82,9 @@ pub struct PE32OptionalHeaderWindowsSpecific {
     /// The default for Windows CE EXEs is 0x00010000.
     /// The default for Windows NT, Windows 2000, Windows XP,
     /// Windows 95, Windows 98, and Windows Me is 0x00400000.
-    pub image_base: u32,
+    ///
+    /// 64 bits wide in PE32+ images, like the stack and heap sizes.
+    pub image_base: u64,
     /// The alignment (in bytes) of sections when they are loaded into memory.
     /// It must be greater than or equal to FileAlignment.
     /// The default is the page size for the architecture.
@@ -96,15 +131,15 @@ pub struct PE32OptionalHeaderWindowsSpecific {
     pub dll_characteristics: DllCharacteristics,
     /// The size of the stack to reserve. Only SizeOfStackCommit is committed;
     /// the rest is made available one page at a time until the reserve size is reached.
-    pub size_of_stack_reserve: u32,
+    pub size_of_stack_reserve: u64,
     /// The size of the stack to commit.
-    pub size_of_stack_commit: u32,
+    pub size_of_stack_commit: u64,
     /// The size of the local heap space to reserve.
     /// Only SizeOfHeapCommit is committed; the rest is made available one page at a time
     /// until the reserve size is reached.
-    pub size_of_heap_reserve: u32,
+    pub size_of_heap_reserve: u64,
     /// The size of the local heap space to commit.
-    pub size_of_heap_commit: u32,
+    pub size_of_heap_commit: u64,
     /// Reserved, must be zero.
     pub loader_flags: u32,
     /// The number of data-directory entries in the remainder of the optional header.
@@ -112,13 +147,37 @@ pub struct PE32OptionalHeaderWindowsSpecific {
     pub number_of_rva_and_sizes: u32,
 }
 
-impl BinParsable for PE32OptionalHeaderWindowsSpecific {
-    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+/// Parses a field that is 32 bits wide in PE32 images and 64 bits wide in PE32+ images.
+fn address_field<'a, E: ParseError<&'a [u8]>>(
+    is_pe32_plus: bool,
+) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], u64, E> {
+    move |i: &'a [u8]| {
+        if is_pe32_plus {
+            le_u64(i)
+        } else {
+            map(le_u32, u64::from)(i)
+        }
+    }
+}
+
+/// Writes a field that is 32 bits wide in PE32 images and 64 bits wide in PE32+ images.
+fn write_address_field(out: &mut Vec<u8>, value: u64, is_pe32_plus: bool) {
+    if is_pe32_plus {
+        out.extend_from_slice(&value.to_le_bytes());
+    } else {
+        out.extend_from_slice(&(value as u32).to_le_bytes());
+    }
+}
+
+impl PE32OptionalHeaderWindowsSpecific {
+    fn try_parse_for<'a, E: ParseError<&'a [u8]>>(
+        is_pe32_plus: bool,
+    ) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], Self, E> {
         context(
             name_of!(type PE32OptionalHeaderWindowsSpecific),
             map(
                 tuple((
-                    le_u32,                        // image_base
+                    address_field(is_pe32_plus),   // image_base
                     le_u32,                        // section_alignment
                     le_u32,                        // file_alignment
                     le_u16,                        // major_operating_system_version
@@ -133,10 +192,10 @@ impl BinParsable for PE32OptionalHeaderWindowsSpecific {
                     le_u32,                        // check_sum
                     ImageSubsystem::try_parse,     // subsystem
                     DllCharacteristics::try_parse, // dll_characteristics
-                    le_u32,                        // size_of_stack_reserve
-                    le_u32,                        // size_of_stack_commit
-                    le_u32,                        // size_of_heap_reserve
-                    le_u32,                        // size_of_heap_commit
+                    address_field(is_pe32_plus),   // size_of_stack_reserve
+                    address_field(is_pe32_plus),   // size_of_stack_commit
+                    address_field(is_pe32_plus),   // size_of_heap_reserve
+                    address_field(is_pe32_plus),   // size_of_heap_commit
                     le_u32,                        // loader_flags
                     le_u32,                        // number_of_rva_and_sizes
                 )),
@@ -164,11 +223,35 @@ impl BinParsable for PE32OptionalHeaderWindowsSpecific {
                     number_of_rva_and_sizes: p.20,
                 },
             ),
-        )(i)
+        )
+    }
+
+    fn write_fields(&self, out: &mut Vec<u8>, is_pe32_plus: bool) {
+        write_address_field(out, self.image_base, is_pe32_plus);
+        out.extend_from_slice(&self.section_alignment.to_le_bytes());
+        out.extend_from_slice(&self.file_alignment.to_le_bytes());
+        out.extend_from_slice(&self.major_operating_system_version.to_le_bytes());
+        out.extend_from_slice(&self.minor_operating_system_version.to_le_bytes());
+        out.extend_from_slice(&self.major_image_version.to_le_bytes());
+        out.extend_from_slice(&self.minor_image_version.to_le_bytes());
+        out.extend_from_slice(&self.major_subsystem_version.to_le_bytes());
+        out.extend_from_slice(&self.minor_subsystem_version.to_le_bytes());
+        out.extend_from_slice(&self.win32_version_value.to_le_bytes());
+        out.extend_from_slice(&self.size_of_image.to_le_bytes());
+        out.extend_from_slice(&self.size_of_headers.to_le_bytes());
+        out.extend_from_slice(&self.check_sum.to_le_bytes());
+        out.extend_from_slice(&(self.subsystem as u16).to_le_bytes());
+        out.extend_from_slice(&self.dll_characteristics.bits().to_le_bytes());
+        write_address_field(out, self.size_of_stack_reserve, is_pe32_plus);
+        write_address_field(out, self.size_of_stack_commit, is_pe32_plus);
+        write_address_field(out, self.size_of_heap_reserve, is_pe32_plus);
+        write_address_field(out, self.size_of_heap_commit, is_pe32_plus);
+        out.extend_from_slice(&self.loader_flags.to_le_bytes());
+        out.extend_from_slice(&self.number_of_rva_and_sizes.to_le_bytes());
     }
 }
 
-#[derive(Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromPrimitive, ToPrimitive)]
 #[repr(u16)]
 pub enum ImageSubsystem {
     /// An unknown subsystem
@@ -288,12 +371,27 @@ impl BinParsable for DataDirectory {
     }
 }
 
+impl BinWritable for DataDirectory {
+    fn write_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.virtual_address.to_le_bytes());
+        out.extend_from_slice(&self.size.to_le_bytes());
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash)]
 pub enum DataDirectoryType {
     Known(KnownDataDirectoryType),
     Unknown(usize),
 }
 
+impl DataDirectoryType {
+    /// The type of the data directory at `index` in the optional header.
+    pub fn from_index(index: usize) -> Self {
+        KnownDataDirectoryType::from_usize(index)
+            .map_or(DataDirectoryType::Unknown(index), DataDirectoryType::Known)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, FromPrimitive, ToPrimitive, Hash)]
 pub enum KnownDataDirectoryType {
     /// Export Directory
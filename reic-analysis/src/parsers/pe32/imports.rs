@@ -0,0 +1,216 @@
+use crate::parsers::pe32::{rva_data, ImageLayout, Section};
+use crate::parsers::{BinParsable, BinWritable};
+
+use nameof::name_of;
+use nom::{
+    bytes::complete::take_till,
+    combinator::map,
+    error::context,
+    error::{make_error, ErrorKind, ParseError},
+    number::complete::{le_u16, le_u32, le_u64},
+    sequence::tuple,
+    Err, IResult,
+};
+
+/// An entry of the import directory table, describing the imports from a single DLL.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ImportDescriptor {
+    /// The RVA of the import lookup table, which contains a name or ordinal for each import.
+    pub original_first_thunk: u32,
+    /// Zero until the image is bound.
+    pub time_date_stamp: u32,
+    /// The index of the first forwarder reference.
+    pub forwarder_chain: u32,
+    /// The RVA of the name of the DLL.
+    pub name_rva: u32,
+    /// The RVA of the import address table. Until the image is bound, its contents are
+    /// identical to the import lookup table.
+    pub first_thunk: u32,
+}
+
+impl ImportDescriptor {
+    fn is_null(&self) -> bool {
+        self.original_first_thunk == 0 && self.name_rva == 0 && self.first_thunk == 0
+    }
+}
+
+impl BinParsable for ImportDescriptor {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(
+            name_of!(type ImportDescriptor),
+            map(
+                tuple((
+                    le_u32, // original_first_thunk
+                    le_u32, // time_date_stamp
+                    le_u32, // forwarder_chain
+                    le_u32, // name_rva
+                    le_u32, // first_thunk
+                )),
+                |p| Self {
+                    original_first_thunk: p.0,
+                    time_date_stamp: p.1,
+                    forwarder_chain: p.2,
+                    name_rva: p.3,
+                    first_thunk: p.4,
+                },
+            ),
+        )(i)
+    }
+}
+
+impl BinWritable for ImportDescriptor {
+    fn write_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.original_first_thunk.to_le_bytes());
+        out.extend_from_slice(&self.time_date_stamp.to_le_bytes());
+        out.extend_from_slice(&self.forwarder_chain.to_le_bytes());
+        out.extend_from_slice(&self.name_rva.to_le_bytes());
+        out.extend_from_slice(&self.first_thunk.to_le_bytes());
+    }
+}
+
+/// The imports of the image from a single DLL.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ImportedModule {
+    pub descriptor: ImportDescriptor,
+    /// The name of the DLL.
+    pub name: String,
+    pub imports: Vec<Import>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Import {
+    /// The RVA of the import address table slot the loader writes the address of the import to.
+    pub iat_rva: u32,
+    /// The raw import lookup table entry: the ordinal with the high bit set, or the RVA of the
+    /// hint/name entry. 64 bits wide in PE32+ images.
+    pub lookup_entry: u64,
+    pub name: ImportName,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ImportName {
+    Ordinal(u16),
+    Name { hint: u16, name: String },
+}
+
+impl ImportName {
+    /// The name of the import, or `#<ordinal>` for imports by ordinal.
+    pub fn to_display_name(&self) -> String {
+        match self {
+            ImportName::Ordinal(ordinal) => format!("#{}", ordinal),
+            ImportName::Name { name, .. } => name.clone(),
+        }
+    }
+}
+
+/// `IMAGE_ORDINAL_FLAG32`: the import lookup table entry is an ordinal.
+pub(crate) const IMPORT_BY_ORDINAL: u32 = 0x8000_0000;
+/// `IMAGE_ORDINAL_FLAG64`, the same for PE32+ images.
+pub(crate) const IMPORT_BY_ORDINAL_64: u64 = 0x8000_0000_0000_0000;
+
+/// Reads the null-terminated string at the given RVA.
+pub(crate) fn string_at_rva<'a, E: ParseError<&'a [u8]>>(
+    file: &'a [u8],
+    sections: &[Section],
+    layout: ImageLayout,
+    rva: u32,
+) -> Result<String, Err<E>> {
+    let i = rva_data(file, sections, layout, rva)?;
+    let (_, name) = take_till(|b| b == 0)(i)?;
+    Ok(String::from_utf8_lossy(name).into_owned())
+}
+
+/// Parses the import directory table at the given RVA, resolving every imported name. The
+/// lookup and address tables of PE32+ images have 64-bit entries.
+pub fn import_directory<'a, E: ParseError<&'a [u8]>>(
+    file: &'a [u8],
+    sections: &[Section],
+    layout: ImageLayout,
+    rva: u32,
+    is_pe32_plus: bool,
+) -> Result<Vec<ImportedModule>, Err<E>> {
+    let mut modules = Vec::new();
+    let mut descriptors = rva_data(file, sections, layout, rva)?;
+    loop {
+        let (rest, descriptor) = ImportDescriptor::try_parse(descriptors)?;
+        descriptors = rest;
+        if descriptor.is_null() {
+            return Ok(modules);
+        }
+
+        let name = string_at_rva(file, sections, layout, descriptor.name_rva)?;
+        let lookup_table = |rva| {
+            import_lookup_table(
+                file,
+                sections,
+                layout,
+                rva,
+                descriptor.first_thunk,
+                is_pe32_plus,
+            )
+        };
+        let imports = match (descriptor.original_first_thunk, layout) {
+            (0, ImageLayout::File) => lookup_table(descriptor.first_thunk)?,
+            // In a mapped image, the import address table usually holds the resolved addresses,
+            // so the names are lost without a separate lookup table. Images dumped before their
+            // imports were resolved still hold the lookup entries, though.
+            (0, ImageLayout::Mapped) => lookup_table(descriptor.first_thunk).unwrap_or_default(),
+            (original_first_thunk, _) => lookup_table(original_first_thunk)?,
+        };
+
+        modules.push(ImportedModule {
+            descriptor,
+            name,
+            imports,
+        });
+    }
+}
+
+/// Parses the import lookup table at the given RVA, for the import address table at
+/// `first_thunk`.
+fn import_lookup_table<'a, E: ParseError<&'a [u8]>>(
+    file: &'a [u8],
+    sections: &[Section],
+    layout: ImageLayout,
+    rva: u32,
+    first_thunk: u32,
+    is_pe32_plus: bool,
+) -> Result<Vec<Import>, Err<E>> {
+    let (thunk_size, ordinal_flag) = if is_pe32_plus {
+        (8, IMPORT_BY_ORDINAL_64)
+    } else {
+        (4, u64::from(IMPORT_BY_ORDINAL))
+    };
+
+    let mut imports = Vec::new();
+    let mut thunks = rva_data(file, sections, layout, rva)?;
+    loop {
+        let (rest, thunk) = if is_pe32_plus {
+            le_u64(thunks)?
+        } else {
+            map(le_u32, u64::from)(thunks)?
+        };
+        thunks = rest;
+        if thunk == 0 {
+            return Ok(imports);
+        }
+
+        let name = if thunk & ordinal_flag != 0 {
+            ImportName::Ordinal(thunk as u16)
+        } else {
+            let hint_name = thunk as u32;
+            let (_, hint) = le_u16(rva_data(file, sections, layout, hint_name)?)?;
+            let name = string_at_rva(file, sections, layout, hint_name.wrapping_add(2))?;
+            ImportName::Name { hint, name }
+        };
+        let iat_rva = (imports.len() as u32)
+            .checked_mul(thunk_size)
+            .and_then(|offset| first_thunk.checked_add(offset))
+            .ok_or_else(|| Err::Error(make_error(thunks, ErrorKind::TooLarge)))?;
+        imports.push(Import {
+            iat_rva,
+            lookup_entry: thunk,
+            name,
+        });
+    }
+}
@@ -0,0 +1,535 @@
+use crate::parsers::pe32::writer::{align, OPTIONAL_HEADER_OFFSET};
+use crate::parsers::pe32::{
+    DataDirectory, DataDirectoryType, Import, ImportDescriptor, ImportName, ImportedModule,
+    KnownDataDirectoryType, PE32Image, Section, SectionCharacteristics, SectionHeader,
+    IMPORT_BY_ORDINAL, IMPORT_BY_ORDINAL_64, SECTION_HEADER_SIZE,
+};
+use crate::parsers::{BinWritable, ParseRule};
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+/// The maximum number of sections the Windows loader accepts.
+const MAX_SECTIONS: usize = 96;
+const IMPORT_DESCRIPTOR_SIZE: usize = 20;
+const DEBUG_DIRECTORY_SIZE: usize = 28;
+/// The offset of `PointerToRawData` in a debug directory entry.
+const DEBUG_POINTER_TO_RAW_DATA_OFFSET: usize = 24;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SectionInjectionError {
+    /// The section name is longer than 8 bytes.
+    InvalidName(String),
+    TooManySections,
+    /// The header area cannot grow without overlapping the first section in memory.
+    NoHeaderSpace,
+    /// The new section would lie beyond the 4 GiB the headers can address.
+    ImageTooLarge,
+    /// The import directory could not be parsed, so it cannot be rebuilt with new imports.
+    MalformedImports,
+}
+
+impl fmt::Display for SectionInjectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SectionInjectionError::InvalidName(name) => {
+                write!(f, "section name `{}` is longer than 8 bytes", name)
+            }
+            SectionInjectionError::TooManySections => {
+                write!(f, "the image already has {} sections", MAX_SECTIONS)
+            }
+            SectionInjectionError::NoHeaderSpace => {
+                write!(f, "there is no room to grow the header area")
+            }
+            SectionInjectionError::ImageTooLarge => {
+                write!(f, "the image would grow beyond 4 GiB")
+            }
+            SectionInjectionError::MalformedImports => {
+                write!(f, "the import directory of the image is malformed")
+            }
+        }
+    }
+}
+
+impl Error for SectionInjectionError {}
+
+impl PE32Image {
+    /// The address after the last section in memory, aligned to `section_alignment`.
+    fn next_section_address(&self) -> Result<u32, SectionInjectionError> {
+        let windows_specific = &self.pe32_optional_header.windows_specific;
+        let end = self
+            .sections
+            .iter()
+            .map(|section| {
+                let header = &section.header;
+                header.virtual_address as usize
+                    + header.virtual_size.max(header.size_of_raw_data) as usize
+            })
+            .fold(windows_specific.size_of_headers as usize, usize::max);
+        to_u32(align(end, windows_specific.section_alignment as usize))
+    }
+
+    /// Makes room for another section header, growing the header area by whole
+    /// `file_alignment` units if the header padding is too small.
+    fn reserve_section_header(&mut self) -> Result<(), SectionInjectionError> {
+        let windows_specific = &mut self.pe32_optional_header.windows_specific;
+        let size_of_headers = windows_specific.size_of_headers as usize;
+        let table_end = self.mz_header.e_lfanew as usize
+            + OPTIONAL_HEADER_OFFSET
+            + self.coff_header.size_of_optional_header as usize
+            + (self.sections.len() + 1) * SECTION_HEADER_SIZE;
+
+        if table_end <= size_of_headers {
+            self.header_padding = vec![0; size_of_headers - table_end].into();
+            return Ok(());
+        }
+
+        let new_size_of_headers = align(table_end, windows_specific.file_alignment as usize);
+        let first_section = self
+            .sections
+            .iter()
+            .map(|section| section.header.virtual_address as usize)
+            .min()
+            .unwrap_or(usize::MAX);
+        if new_size_of_headers > first_section {
+            return Err(SectionInjectionError::NoHeaderSpace);
+        }
+
+        let growth = (new_size_of_headers - size_of_headers) as u32;
+        for section in &mut self.sections {
+            if section.header.size_of_raw_data != 0 {
+                section.header.pointer_to_raw_data = section
+                    .header
+                    .pointer_to_raw_data
+                    .checked_add(growth)
+                    .ok_or(SectionInjectionError::ImageTooLarge)?;
+            }
+        }
+        windows_specific.size_of_headers = new_size_of_headers as u32;
+        self.header_padding = vec![0; new_size_of_headers - table_end].into();
+        self.move_debug_data(size_of_headers as u32, growth)
+    }
+
+    /// Moves the file pointers of the debug directory entries at or after `start` by
+    /// `distance`, as the raw data there moved.
+    fn move_debug_data(&mut self, start: u32, distance: u32) -> Result<(), SectionInjectionError> {
+        let (rva, size) = match self.data_directory(KnownDataDirectoryType::Debug) {
+            Some(directory) => (directory.virtual_address, directory.size),
+            None => return Ok(()),
+        };
+        let section = match self.sections.iter_mut().find(|section| {
+            let start = section.header.virtual_address;
+            rva >= start && ((rva - start) as usize) < section.data.len()
+        }) {
+            Some(section) => section,
+            None => return Ok(()),
+        };
+
+        let mut data = section.data.to_vec();
+        let offset = (rva - section.header.virtual_address) as usize;
+        let end = (offset + size as usize).min(data.len());
+        for entry in data[offset..end].chunks_exact_mut(DEBUG_DIRECTORY_SIZE) {
+            let field = &mut entry[DEBUG_POINTER_TO_RAW_DATA_OFFSET..];
+            let pointer = u32::from_le_bytes([field[0], field[1], field[2], field[3]]);
+            if pointer >= start {
+                let pointer = pointer
+                    .checked_add(distance)
+                    .ok_or(SectionInjectionError::ImageTooLarge)?;
+                field.copy_from_slice(&pointer.to_le_bytes());
+            }
+        }
+        section.data = data.into();
+        Ok(())
+    }
+
+    /// Appends a section after the last section, both in the file and in memory.
+    ///
+    /// The data is zero-padded to `file_alignment`, and the section spans at least
+    /// `virtual_size` bytes in memory. The section header goes into the header padding,
+    /// or, if there is no room, the header area grows and the raw data of all sections moves.
+    /// The file pointers of the debug directory follow the data they point to.
+    /// `number_of_sections`, `size_of_image`, `size_of_headers` and the section size totals of
+    /// the optional header are updated.
+    ///
+    /// Bound imports and the Authenticode signature are dropped, as they would no longer be
+    /// valid. The header padding is cleared.
+    pub fn add_section(
+        &mut self,
+        name: &str,
+        characteristics: SectionCharacteristics,
+        mut data: Vec<u8>,
+        virtual_size: u32,
+    ) -> Result<&Section, SectionInjectionError> {
+        if name.len() > 8 {
+            return Err(SectionInjectionError::InvalidName(name.to_owned()));
+        }
+        if self.sections.len() >= MAX_SECTIONS {
+            return Err(SectionInjectionError::TooManySections);
+        }
+
+        self.reserve_section_header()?;
+        self.data_directories.remove(&DataDirectoryType::Known(
+            KnownDataDirectoryType::BoundImport,
+        ));
+        self.data_directories
+            .remove(&DataDirectoryType::Known(KnownDataDirectoryType::Security));
+
+        let windows_specific = &self.pe32_optional_header.windows_specific;
+        let file_alignment = windows_specific.file_alignment as usize;
+        let raw_data_end = self
+            .sections
+            .iter()
+            .filter(|section| section.header.size_of_raw_data != 0)
+            .map(|section| {
+                section.header.pointer_to_raw_data as usize
+                    + section.header.size_of_raw_data as usize
+            })
+            .fold(windows_specific.size_of_headers as usize, usize::max);
+        let pointer_to_raw_data = to_u32(align(raw_data_end, file_alignment))?;
+        let virtual_address = self.next_section_address()?;
+
+        let virtual_size = virtual_size.max(to_u32(data.len())?);
+        data.resize(align(data.len(), file_alignment), 0);
+        let size_of_raw_data = to_u32(data.len())?;
+        let new_raw_data_end = pointer_to_raw_data
+            .checked_add(size_of_raw_data)
+            .filter(|_| virtual_address.checked_add(virtual_size).is_some())
+            .ok_or(SectionInjectionError::ImageTooLarge)?;
+        // The overlay follows the new section
+        self.move_debug_data(raw_data_end as u32, new_raw_data_end - raw_data_end as u32)?;
+
+        // The totals are informational, and are often wrong in the parsed image already
+        let standard = &mut self.coff_optional_header;
+        if characteristics.contains(SectionCharacteristics::CNT_CODE) {
+            standard.size_of_code = standard.size_of_code.saturating_add(size_of_raw_data);
+        }
+        if characteristics.contains(SectionCharacteristics::CNT_INITIALIZED_DATA) {
+            standard.size_of_initialized_data = standard
+                .size_of_initialized_data
+                .saturating_add(size_of_raw_data);
+        }
+        if characteristics.contains(SectionCharacteristics::CNT_UNINITIALIZED_DATA) {
+            standard.size_of_uninitialized_data = standard
+                .size_of_uninitialized_data
+                .saturating_add(virtual_size);
+        }
+
+        self.sections.push(Section {
+            header: SectionHeader {
+                name: name.to_owned(),
+                virtual_size,
+                virtual_address,
+                size_of_raw_data,
+                pointer_to_raw_data: if size_of_raw_data == 0 {
+                    0
+                } else {
+                    pointer_to_raw_data
+                },
+                pointer_to_relocations: 0,
+                pointer_to_linenumbers: 0,
+                number_of_relocations: 0,
+                number_of_linenumbers: 0,
+                characteristics,
+            },
+            data: data.into(),
+            uninitialized_data_size: virtual_size.saturating_sub(size_of_raw_data),
+        });
+        self.coff_header.number_of_sections = self.sections.len() as u16;
+        self.pe32_optional_header.windows_specific.size_of_image = self.next_section_address()?;
+
+        Ok(self.sections.last().unwrap())
+    }
+
+    /// Adds imports from the given DLLs in a new data section named `section_name`.
+    ///
+    /// The import directory is rebuilt in the new section, keeping the existing descriptors
+    /// and their tables in place. The new modules are appended to `imports`, with the import
+    /// address table slots to call the imports through.
+    ///
+    /// Images whose import directory could not be parsed are rejected, as their imports would
+    /// be lost.
+    pub fn add_imports(
+        &mut self,
+        section_name: &str,
+        modules: Vec<(String, Vec<ImportName>)>,
+    ) -> Result<(), SectionInjectionError> {
+        if self
+            .warnings
+            .iter()
+            .any(|warning| warning.rule == ParseRule::ImportDirectory)
+        {
+            return Err(SectionInjectionError::MalformedImports);
+        }
+
+        // Growing the header area only moves raw data, so the section ends up at this address
+        let base = self.next_section_address()?;
+        let rva = |offset: usize| {
+            to_u32(offset)?
+                .checked_add(base)
+                .ok_or(SectionInjectionError::ImageTooLarge)
+        };
+        let descriptor_count = self.imports.len() + modules.len() + 1;
+        let mut data = vec![0; descriptor_count * IMPORT_DESCRIPTOR_SIZE];
+        let is_pe32_plus = self.pe32_optional_header.is_pe32_plus();
+        let thunk_size = if is_pe32_plus { 8 } else { 4 };
+
+        // The import lookup and address tables of the new modules, followed by the names
+        let tables: Vec<usize> = modules
+            .iter()
+            .map(|(_, imports)| {
+                let lookup_table = data.len();
+                data.resize(lookup_table + (imports.len() + 1) * thunk_size * 2, 0);
+                lookup_table
+            })
+            .collect();
+
+        let mut descriptors = Vec::new();
+        for module in &self.imports {
+            module.descriptor.write_to(&mut descriptors);
+        }
+        let mut new_modules = Vec::new();
+        for ((name, imports), lookup_table) in modules.into_iter().zip(tables) {
+            let address_table = lookup_table + (imports.len() + 1) * thunk_size;
+            let mut module_imports = Vec::new();
+            for (index, import) in imports.into_iter().enumerate() {
+                let lookup_entry = match &import {
+                    ImportName::Ordinal(ordinal) if is_pe32_plus => {
+                        IMPORT_BY_ORDINAL_64 | u64::from(*ordinal)
+                    }
+                    ImportName::Ordinal(ordinal) => {
+                        u64::from(IMPORT_BY_ORDINAL | u32::from(*ordinal))
+                    }
+                    ImportName::Name { hint, name } => {
+                        data.resize(align(data.len(), 2), 0);
+                        let entry = rva(data.len())?;
+                        data.extend_from_slice(&hint.to_le_bytes());
+                        data.extend_from_slice(name.as_bytes());
+                        data.push(0);
+                        u64::from(entry)
+                    }
+                };
+
+                for table in &[lookup_table, address_table] {
+                    let slot = table + index * thunk_size;
+                    data[slot..slot + thunk_size]
+                        .copy_from_slice(&lookup_entry.to_le_bytes()[..thunk_size]);
+                }
+                module_imports.push(Import {
+                    iat_rva: rva(address_table + index * thunk_size)?,
+                    lookup_entry,
+                    name: import,
+                });
+            }
+
+            let name_rva = rva(data.len())?;
+            data.extend_from_slice(name.as_bytes());
+            data.push(0);
+
+            let descriptor = ImportDescriptor {
+                original_first_thunk: rva(lookup_table)?,
+                time_date_stamp: 0,
+                forwarder_chain: 0,
+                name_rva,
+                first_thunk: rva(address_table)?,
+            };
+            descriptor.write_to(&mut descriptors);
+            new_modules.push(ImportedModule {
+                descriptor,
+                name,
+                imports: module_imports,
+            });
+        }
+        data[..descriptors.len()].copy_from_slice(&descriptors);
+
+        let section = self.add_section(
+            section_name,
+            SectionCharacteristics::CNT_INITIALIZED_DATA
+                | SectionCharacteristics::MEM_READ
+                | SectionCharacteristics::MEM_WRITE,
+            data,
+            0,
+        )?;
+        debug_assert_eq!(section.header.virtual_address, base);
+
+        self.data_directories.insert(
+            DataDirectoryType::Known(KnownDataDirectoryType::Import),
+            DataDirectory {
+                virtual_address: base,
+                size: (descriptor_count * IMPORT_DESCRIPTOR_SIZE) as u32,
+            },
+        );
+        self.imports.extend(new_modules);
+        Ok(())
+    }
+}
+
+fn to_u32(value: usize) -> Result<u32, SectionInjectionError> {
+    u32::try_from(value).map_err(|_| SectionInjectionError::ImageTooLarge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::pe32::tests::TestImage;
+    use crate::parsers::BinParsable;
+    use nom::error::VerboseError;
+
+    fn parse(file: &[u8]) -> PE32Image {
+        PE32Image::try_parse::<VerboseError<&[u8]>>(file).unwrap().1
+    }
+
+    fn code() -> SectionCharacteristics {
+        SectionCharacteristics::CNT_CODE
+            | SectionCharacteristics::MEM_EXECUTE
+            | SectionCharacteristics::MEM_READ
+    }
+
+    #[test]
+    fn injects_sections_and_imports() {
+        let mut image = parse(&TestImage::executable().build());
+        let cave = image
+            .add_section(".cave", code(), vec![0xCC; 0x10], 0x100)
+            .unwrap();
+        assert_eq!(cave.header.virtual_address, 0x4000);
+        assert_eq!(cave.header.pointer_to_raw_data, 0x800);
+        image
+            .add_imports(
+                ".imports",
+                vec![(
+                    "USER32.dll".to_owned(),
+                    vec![
+                        ImportName::Name {
+                            hint: 0,
+                            name: "MessageBoxA".to_owned(),
+                        },
+                        ImportName::Ordinal(0x7F),
+                    ],
+                )],
+            )
+            .unwrap();
+        let iat_rva = image.imports[2].imports[0].iat_rva;
+
+        let injected = parse(&image.to_bytes().unwrap());
+        assert_eq!(injected.coff_header.number_of_sections, 5);
+        assert_eq!(injected.coff_optional_header.size_of_code, 0x200);
+        assert_eq!(
+            injected.pe32_optional_header.windows_specific.size_of_image,
+            0x6000
+        );
+        let cave = &injected.sections[3];
+        assert_eq!(cave.header.virtual_size, 0x100);
+        assert_eq!(&cave.data[..0x10], &[0xCC; 0x10][..]);
+
+        assert_eq!(injected.imports.len(), 3);
+        assert_eq!(injected.imports[0], image.imports[0]);
+        let names: Vec<String> = injected.imports[2]
+            .imports
+            .iter()
+            .map(|import| import.name.to_display_name())
+            .collect();
+        assert_eq!(names, vec!["MessageBoxA", "#127"]);
+        assert_eq!(injected.imports[2].imports[0].iat_rva, iat_rva);
+        assert_eq!(
+            injected.section_containing(iat_rva).unwrap().header.name,
+            ".imports"
+        );
+    }
+
+    #[test]
+    fn grows_header_area() {
+        // The section table ends right at `size_of_headers`
+        let mut test_image = TestImage::executable();
+        test_image.dos_stub = vec![0; 0x50];
+        let file = test_image.build();
+        let mut image = parse(&file);
+        let text = image.sections[0].data.clone();
+
+        image
+            .add_section(".cave", code(), vec![0xCC; 0x10], 0)
+            .unwrap();
+        assert_eq!(
+            image.pe32_optional_header.windows_specific.size_of_headers,
+            0x400
+        );
+
+        let written = image.to_bytes().unwrap();
+        assert_eq!(written.len(), file.len() + 0x400);
+        let grown = parse(&written);
+        assert_eq!(grown.sections[0].header.pointer_to_raw_data, 0x400);
+        assert_eq!(grown.sections[0].data, text);
+        assert_eq!(grown.sections[3].header.pointer_to_raw_data, 0xA00);
+        assert_eq!(grown.imports, parse(&file).imports);
+    }
+
+    #[test]
+    fn moves_debug_data_with_the_header_area() {
+        let mut test_image = TestImage::executable();
+        test_image.dos_stub = vec![0; 0x50];
+        // A CodeView debug directory entry after the import directory, for data in the overlay
+        let idata = &mut test_image.sections[1];
+        idata.data.resize(0xCC, 0);
+        idata.data[0xBC..0xC0].copy_from_slice(&2u32.to_le_bytes());
+        idata.data[0xC0..0xC4].copy_from_slice(&0x10u32.to_le_bytes());
+        idata.data[0xC8..0xCC].copy_from_slice(&0x800u32.to_le_bytes());
+        idata.virtual_size = 0xCC;
+        test_image
+            .directories
+            .push((KnownDataDirectoryType::Debug as usize, 0x20B0, 0x1C));
+        test_image.overlay = b"RSDS debug data.".to_vec();
+        let mut image = parse(&test_image.build());
+
+        image
+            .add_section(".cave", code(), vec![0xCC; 0x10], 0)
+            .unwrap();
+        let written = image.to_bytes().unwrap();
+        let grown = parse(&written);
+        assert_eq!(&grown.sections[1].data[0xC8..0xCC], &0xC00u32.to_le_bytes());
+        assert_eq!(&written[0xC00..0xC10], b"RSDS debug data.");
+    }
+
+    #[test]
+    fn rejects_invalid_sections() {
+        let file = TestImage::executable().build();
+        let mut image = parse(&file);
+        assert_eq!(
+            image
+                .add_section(".toolong1", code(), Vec::new(), 0x10)
+                .err(),
+            Some(SectionInjectionError::InvalidName(".toolong1".to_owned()))
+        );
+
+        // The first section starts right after the headers, so they cannot grow
+        let mut test_image = TestImage::executable();
+        test_image.dos_stub = vec![0; 0x50];
+        let mut image = parse(&test_image.build());
+        image.sections[0].header.virtual_address = 0x200;
+        assert_eq!(
+            image.add_section(".cave", code(), Vec::new(), 0x10).err(),
+            Some(SectionInjectionError::NoHeaderSpace)
+        );
+
+        // The last section ends at 4 GiB
+        let mut image = parse(&file);
+        image.sections[2].header.virtual_address = 0xFFFF_F000;
+        assert_eq!(
+            image.add_section(".cave", code(), Vec::new(), 0x10).err(),
+            Some(SectionInjectionError::ImageTooLarge)
+        );
+    }
+
+    #[test]
+    fn rejects_imports_of_malformed_directories() {
+        let mut file = TestImage::executable().build();
+        // An import descriptor naming a DLL outside of the image
+        file[0x40C..0x410].copy_from_slice(&0x9000u32.to_le_bytes());
+        let mut image = parse(&file);
+        assert!(image.imports.is_empty());
+
+        assert_eq!(
+            image.add_imports(".imports", vec![("USER32.dll".to_owned(), Vec::new())]),
+            Err(SectionInjectionError::MalformedImports)
+        );
+        assert_eq!(image.sections.len(), 3);
+    }
+}
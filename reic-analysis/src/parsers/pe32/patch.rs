@@ -0,0 +1,46 @@
+use crate::parsers::pe32::PE32Image;
+
+impl PE32Image {
+    /// Returns the index of the section and the range in its data that holds `length` bytes at
+    /// the given linear address, if they lie within the raw data of a single section.
+    fn section_range(&self, address: u64, length: usize) -> Option<(usize, usize, usize)> {
+        let image_base = self.pe32_optional_header.windows_specific.image_base;
+        let rva = address.checked_sub(image_base)?;
+
+        self.sections
+            .iter()
+            .enumerate()
+            .find_map(|(index, section)| {
+                let start = rva.checked_sub(u64::from(section.header.virtual_address))?;
+                let end = start.checked_add(length as u64)?;
+                if end <= section.data.len() as u64 {
+                    Some((index, start as usize, end as usize))
+                } else {
+                    None
+                }
+            })
+    }
+
+    /// Returns the raw section data at the given linear address.
+    pub fn bytes_at(&self, address: u64, length: usize) -> Option<&[u8]> {
+        self.section_range(address, length)
+            .map(|(index, start, end)| &self.sections[index].data[start..end])
+    }
+
+    /// Overwrites the raw section data at the given linear address. Returns `false`, leaving the
+    /// image unchanged, if the bytes do not lie within the raw data of a single section.
+    ///
+    /// The section data is copied, as it shares the buffer of the parsed file.
+    pub fn write_bytes(&mut self, address: u64, bytes: &[u8]) -> bool {
+        match self.section_range(address, bytes.len()) {
+            Some((index, start, end)) => {
+                let section = &mut self.sections[index];
+                let mut data = section.data.to_vec();
+                data[start..end].copy_from_slice(bytes);
+                section.data = data.into();
+                true
+            }
+            None => false,
+        }
+    }
+}
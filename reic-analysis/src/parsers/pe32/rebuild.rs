@@ -0,0 +1,181 @@
+use crate::parsers::pe32::writer::{align, write_u32, OPTIONAL_HEADER_OFFSET};
+use crate::parsers::pe32::{
+    data_directories_offset, KnownDataDirectoryType, PE32Image, CHECK_SUM_OFFSET,
+};
+
+use std::error::Error;
+use std::fmt;
+
+const DATA_DIRECTORY_SIZE: usize = 8;
+const SECTION_HEADER_SIZE: usize = 40;
+/// The offsets of `size_of_raw_data` and `pointer_to_raw_data` in a section header.
+const SIZE_OF_RAW_DATA_OFFSET: usize = 16;
+const POINTER_TO_RAW_DATA_OFFSET: usize = 20;
+const IMPORT_DESCRIPTOR_SIZE: usize = 20;
+/// The offsets of `time_date_stamp` and `forwarder_chain` in an import descriptor.
+const IMPORT_TIME_DATE_STAMP_OFFSET: usize = 4;
+const IMPORT_FORWARDER_CHAIN_OFFSET: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RebuildError {
+    /// The import address table of the named DLL holds resolved addresses, and there is no
+    /// import lookup table to restore it from.
+    ResolvedImports(String),
+}
+
+impl fmt::Display for RebuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RebuildError::ResolvedImports(module) => write!(
+                f,
+                "the imports from `{}` are resolved and have no lookup table",
+                module
+            ),
+        }
+    }
+}
+
+impl Error for RebuildError {}
+
+impl PE32Image {
+    /// Rebuilds an image file from the mapped image (such as a process memory dump) this image
+    /// was parsed from, see `ImageLayout::Mapped`.
+    ///
+    /// The section data is laid out back to back at `file_alignment`, and the section headers are
+    /// fixed up to match. The import address table is restored from the import lookup table and
+    /// bound imports are dropped, so the Windows loader resolves every import again. The checksum
+    /// is cleared.
+    ///
+    /// Without a lookup table, the import address table is kept if it still holds the lookup
+    /// entries (see `import_directory`). If it holds resolved addresses instead, the imports of
+    /// that DLL cannot be restored, and rebuilding fails.
+    pub fn rebuild_from_mapped(&self, image: &[u8]) -> Result<Vec<u8>, RebuildError> {
+        let windows_specific = &self.pe32_optional_header.windows_specific;
+        let mut mapped = image.to_vec();
+
+        if let Some(directory) = self.data_directory(KnownDataDirectoryType::Import) {
+            for (index, module) in self.imports.iter().enumerate() {
+                let first_slot = module.descriptor.first_thunk as usize;
+                let is_table_empty = mapped
+                    .get(first_slot..first_slot + 4)
+                    .map(|slot| slot.iter().all(|&b| b == 0))
+                    .unwrap_or(true);
+                if module.imports.is_empty() && !is_table_empty {
+                    return Err(RebuildError::ResolvedImports(module.name.clone()));
+                }
+
+                let descriptor =
+                    directory.virtual_address as usize + index * IMPORT_DESCRIPTOR_SIZE;
+                write_u32(&mut mapped, descriptor + IMPORT_TIME_DATE_STAMP_OFFSET, 0);
+                write_u32(&mut mapped, descriptor + IMPORT_FORWARDER_CHAIN_OFFSET, 0);
+                for import in &module.imports {
+                    let slot = import.iat_rva as usize;
+                    write_u32(&mut mapped, slot, import.lookup_entry as u32);
+                    if self.pe32_optional_header.is_pe32_plus() {
+                        write_u32(&mut mapped, slot + 4, (import.lookup_entry >> 32) as u32);
+                    }
+                }
+            }
+        }
+
+        let size_of_headers = (windows_specific.size_of_headers as usize).min(mapped.len());
+        let mut file = mapped[..size_of_headers].to_vec();
+
+        let optional_header = self.mz_header.e_lfanew as usize + OPTIONAL_HEADER_OFFSET;
+        write_u32(&mut file, optional_header + CHECK_SUM_OFFSET, 0);
+        let bound_import = optional_header
+            + data_directories_offset(&self.pe32_optional_header)
+            + KnownDataDirectoryType::BoundImport as usize * DATA_DIRECTORY_SIZE;
+        if (KnownDataDirectoryType::BoundImport as u32) < windows_specific.number_of_rva_and_sizes {
+            write_u32(&mut file, bound_import, 0);
+            write_u32(&mut file, bound_import + 4, 0);
+        }
+
+        let file_alignment = windows_specific.file_alignment as usize;
+        let section_table = optional_header + self.coff_header.size_of_optional_header as usize;
+        file.resize(align(size_of_headers, file_alignment), 0);
+        for (index, section) in self.sections.iter().enumerate() {
+            let start = section.header.virtual_address as usize;
+            let data =
+                &mapped[start.min(mapped.len())..(start + section.data.len()).min(mapped.len())];
+            let raw_size = align(data.len(), file_alignment);
+            let pointer = if raw_size == 0 { 0 } else { file.len() };
+
+            let header = section_table + index * SECTION_HEADER_SIZE;
+            write_u32(&mut file, header + SIZE_OF_RAW_DATA_OFFSET, raw_size as u32);
+            write_u32(
+                &mut file,
+                header + POINTER_TO_RAW_DATA_OFFSET,
+                pointer as u32,
+            );
+
+            if raw_size != 0 {
+                file.extend_from_slice(data);
+                file.resize(pointer + raw_size, 0);
+            }
+        }
+
+        Ok(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::pe32::tests::TestImage;
+    use crate::parsers::pe32::{ImageLayout, ParseOptions};
+    use crate::parsers::{BinParsable, ParseMode};
+    use nom::error::VerboseError;
+
+    fn parse_mapped(mapped: &[u8]) -> PE32Image {
+        let options = ParseOptions {
+            layout: ImageLayout::Mapped,
+            mode: ParseMode::Strict,
+        };
+        PE32Image::try_parse_with_options::<VerboseError<&[u8]>>(options)(mapped)
+            .unwrap()
+            .1
+    }
+
+    #[test]
+    fn rebuilds_file_from_dump() {
+        let test_image = TestImage::executable();
+        let file = test_image.build();
+        let mut mapped = test_image.map();
+        // The loader resolved both imports
+        write_u32(&mut mapped, 0x2040, 0x7C81_CAFA);
+        write_u32(&mut mapped, 0x2048, 0x773D_1234);
+
+        let rebuilt = parse_mapped(&mapped).rebuild_from_mapped(&mapped).unwrap();
+        assert_eq!(rebuilt, file);
+
+        let (_, original) = PE32Image::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+        let (_, reparsed) = PE32Image::try_parse::<VerboseError<&[u8]>>(&rebuilt).unwrap();
+        assert_eq!(reparsed.imports, original.imports);
+        assert_eq!(reparsed.sections, original.sections);
+    }
+
+    #[test]
+    fn restores_imports_without_lookup_table() {
+        let test_image = TestImage::executable();
+        let mut mapped = test_image.map();
+        // KERNEL32.dll has no lookup table, but was dumped before its imports were resolved
+        write_u32(&mut mapped, 0x2000, 0);
+
+        let rebuilt = parse_mapped(&mapped).rebuild_from_mapped(&mapped).unwrap();
+        let (_, reparsed) = PE32Image::try_parse::<VerboseError<&[u8]>>(&rebuilt).unwrap();
+        assert_eq!(reparsed.imports[0].descriptor.original_first_thunk, 0);
+        assert_eq!(
+            reparsed.imports[0].imports[0].name.to_display_name(),
+            "ExitProcess"
+        );
+
+        write_u32(&mut mapped, 0x2040, 0x7C81_CAFA);
+        let image = parse_mapped(&mapped);
+        assert!(image.imports[0].imports.is_empty());
+        assert_eq!(
+            image.rebuild_from_mapped(&mapped),
+            Err(RebuildError::ResolvedImports("KERNEL32.dll".to_owned()))
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use crate::parsers::pe32::{rva_data, ImageLayout, Section};
+use crate::parsers::BinParsable;
+
+use nameof::name_of;
+use nom::{
+    bytes::complete::take,
+    combinator::{map, verify},
+    error::context,
+    error::ParseError,
+    multi::count,
+    number::complete::{le_u16, le_u32},
+    sequence::tuple,
+    Err, IResult,
+};
+
+/// `IMAGE_REL_BASED_ABSOLUTE`: no relocation, pads a block to a multiple of 4 bytes.
+pub const BASE_RELOCATION_ABSOLUTE: u8 = 0;
+/// `IMAGE_REL_BASED_HIGHLOW`: the 32-bit address at the location is rebased.
+pub const BASE_RELOCATION_HIGHLOW: u8 = 3;
+/// `IMAGE_REL_BASED_DIR64`: the 64-bit address at the location is rebased.
+pub const BASE_RELOCATION_DIR64: u8 = 10;
+
+/// A block of the base relocation table, covering a 4K page.
+#[derive(Debug, PartialEq, Eq)]
+pub struct BaseRelocationBlock {
+    /// The RVA of the page.
+    pub page_rva: u32,
+    pub relocations: Vec<BaseRelocation>,
+}
+
+/// A location the loader patches when the image is not loaded at its preferred base address.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BaseRelocation {
+    /// The kind of patch, such as `BASE_RELOCATION_HIGHLOW`.
+    pub relocation_type: u8,
+    /// The offset of the location in the page.
+    pub offset: u16,
+}
+
+impl BinParsable for BaseRelocation {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(
+            name_of!(type BaseRelocation),
+            map(le_u16, |entry| Self {
+                relocation_type: (entry >> 12) as u8,
+                offset: entry & 0x0FFF,
+            }),
+        )(i)
+    }
+}
+
+impl BinParsable for BaseRelocationBlock {
+    fn try_parse<'a, E: ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], Self, E> {
+        context(name_of!(type BaseRelocationBlock), |i: &'a [u8]| {
+            let (i, (page_rva, block_size)) = tuple((
+                le_u32,                            // page_rva
+                verify(le_u32, |&size| size >= 8), // block_size
+            ))(i)?;
+            // The next block follows `block_size` bytes after the start, even if it is odd
+            let (i, entries) = take(block_size as usize - 8)(i)?;
+            let (_, relocations) = count(BaseRelocation::try_parse, entries.len() / 2)(entries)?;
+            Ok((
+                i,
+                Self {
+                    page_rva,
+                    relocations,
+                },
+            ))
+        })(i)
+    }
+}
+
+impl BaseRelocationBlock {
+    /// The RVAs of the addresses the block rebases, skipping padding.
+    pub fn relocated_addresses(&self) -> impl Iterator<Item = u32> + '_ {
+        self.relocations
+            .iter()
+            .filter(|relocation| {
+                relocation.relocation_type == BASE_RELOCATION_HIGHLOW
+                    || relocation.relocation_type == BASE_RELOCATION_DIR64
+            })
+            .map(move |relocation| self.page_rva + u32::from(relocation.offset))
+    }
+}
+
+/// Parses the base relocation table spanning the given RVA range.
+pub fn base_relocation_directory<'a, E: ParseError<&'a [u8]>>(
+    file: &'a [u8],
+    sections: &[Section],
+    layout: ImageLayout,
+    rva: u32,
+    size: u32,
+) -> Result<Vec<BaseRelocationBlock>, Err<E>> {
+    let data = rva_data(file, sections, layout, rva)?;
+    let mut table = &data[..(size as usize).min(data.len())];
+    let mut blocks = Vec::new();
+    // Blocks are at least 8 bytes, anything shorter is padding
+    while table.len() >= 8 {
+        let (rest, block) = BaseRelocationBlock::try_parse(table)?;
+        table = rest;
+        blocks.push(block);
+    }
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::pe32::tests::TestImage;
+    use crate::parsers::pe32::{KnownDataDirectoryType, PE32Image};
+    use crate::parsers::{ParseRule, ParseWarning, Severity};
+    use nom::error::VerboseError;
+
+    /// The test executable with the given base relocation table.
+    fn relocated_image(reloc: Vec<u8>) -> Vec<u8> {
+        let mut test_image = TestImage::executable();
+        for directory in &mut test_image.directories {
+            if directory.0 == KnownDataDirectoryType::Basereloc as usize {
+                directory.2 = reloc.len() as u32;
+            }
+        }
+        test_image.sections[2].virtual_size = reloc.len() as u32;
+        test_image.sections[2].data = reloc;
+        test_image.build()
+    }
+
+    #[test]
+    fn follows_odd_block_sizes() {
+        let file = relocated_image(vec![
+            0x00, 0x10, 0x00, 0x00, 0x0D, 0x00, 0x00, 0x00, // page 0x1000, 13 bytes
+            0x04, 0x30, 0x08, 0x30, 0x00, // two relocations and a stray byte
+            0x00, 0x20, 0x00, 0x00, 0x0A, 0x00, 0x00, 0x00, // page 0x2000, 10 bytes
+            0x10, 0x30,
+        ]);
+        let (_, image) = PE32Image::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+        assert!(image.warnings.is_empty());
+        assert_eq!(
+            image
+                .base_relocations
+                .iter()
+                .flat_map(BaseRelocationBlock::relocated_addresses)
+                .collect::<Vec<_>>(),
+            vec![0x1004, 0x1008, 0x2010]
+        );
+    }
+
+    #[test]
+    fn warns_about_blocks_past_the_directory() {
+        let file = relocated_image(vec![
+            0x00, 0x10, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, // page 0x1000, 32 bytes
+            0x04, 0x30, 0x00, 0x00,
+        ]);
+        let (_, image) = PE32Image::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+        assert_eq!(
+            image.warnings,
+            vec![ParseWarning {
+                offset: 0xE0,
+                rule: ParseRule::BaseRelocationDirectory,
+                severity: Severity::Error,
+            }]
+        );
+        assert!(image.base_relocations.is_empty());
+    }
+}
@@ -0,0 +1,94 @@
+use crate::parsers::pe32::PE32Image;
+
+use std::convert::TryInto;
+
+pub(crate) const RICH_MAGIC: u32 = 0x6863_6952; // "Rich"
+pub(crate) const DANS_MAGIC: u32 = 0x536E_6144; // "DanS"
+
+/// The undocumented header Microsoft linkers since Visual C++ 6.0 place in the DOS stub. It
+/// counts the object files each tool (compiler, assembler, linker, ...) contributed to the image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RichHeader {
+    /// The key the header is XOR-encrypted with, a checksum over the DOS header and the
+    /// entries.
+    pub key: u32,
+    pub entries: Vec<RichEntry>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RichEntry {
+    /// Identifies the tool and its version, such as the Visual C++ 6.0 C++ compiler.
+    pub product_id: u16,
+    /// The build number of the tool.
+    pub build: u16,
+    /// The number of object files built by the tool.
+    pub count: u32,
+}
+
+impl RichHeader {
+    /// Finds and decrypts the Rich header in the DOS stub, the data following the MZ header.
+    pub fn from_dos_stub(dos_stub: &[u8]) -> Option<Self> {
+        let dwords: Vec<u32> = dos_stub
+            .chunks_exact(4)
+            .map(|dword| u32::from_le_bytes(dword.try_into().unwrap()))
+            .collect();
+        let rich = dwords.iter().position(|&dword| dword == RICH_MAGIC)?;
+        let key = *dwords.get(rich + 1)?;
+        let dans = dwords[..rich]
+            .iter()
+            .rposition(|&dword| dword ^ key == DANS_MAGIC)?;
+
+        // "DanS" is followed by three encrypted zero dwords, then by pairs of a tool id and an
+        // object count
+        let entries = dwords.get(dans + 4..rich)?;
+        let entries = entries
+            .chunks_exact(2)
+            .map(|entry| {
+                let id = entry[0] ^ key;
+                RichEntry {
+                    product_id: (id >> 16) as u16,
+                    build: id as u16,
+                    count: entry[1] ^ key,
+                }
+            })
+            .collect();
+
+        Some(Self { key, entries })
+    }
+}
+
+impl PE32Image {
+    pub fn rich_header(&self) -> Option<RichHeader> {
+        // The header is dword-aligned in the file, as is the start of the DOS stub
+        RichHeader::from_dos_stub(&self.dos_stub)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decrypts_entries() {
+        let key = 0x1234_5678;
+        let mut stub = vec![0u8; 8];
+        for dword in &[DANS_MAGIC ^ key, key, key, key, 0x000B_2264 ^ key, 3 ^ key] {
+            stub.extend_from_slice(&dword.to_le_bytes());
+        }
+        stub.extend_from_slice(&RICH_MAGIC.to_le_bytes());
+        stub.extend_from_slice(&key.to_le_bytes());
+
+        assert_eq!(
+            RichHeader::from_dos_stub(&stub),
+            Some(RichHeader {
+                key,
+                entries: vec![RichEntry {
+                    product_id: 0x000B,
+                    build: 8804,
+                    count: 3,
+                }],
+            })
+        );
+        assert_eq!(RichHeader::from_dos_stub(&stub[8..stub.len() - 4]), None);
+    }
+}
@@ -1,4 +1,5 @@
-use crate::parsers::BinParsable;
+use crate::parsers::pe32::ImageLayout;
+use crate::parsers::{BinParsable, BinWritable, SharedBytes};
 
 use bitflags::bitflags;
 use nameof::name_of;
@@ -18,13 +19,14 @@ use std::fmt;
 #[derive(PartialEq, Eq)]
 pub struct Section {
     pub header: SectionHeader,
-    pub data: Vec<u8>,
+    /// The raw data of the section, sharing the buffer of the parsed file.
+    pub data: SharedBytes,
     pub uninitialized_data_size: u32,
 }
 
 impl Section {
-    pub fn from_file_and_header(file: &[u8], header: SectionHeader) -> Option<Self> {
-        file.get(
+    pub fn from_file_and_header(file: &SharedBytes, header: SectionHeader) -> Option<Self> {
+        file.slice(
             header.pointer_to_raw_data as usize
                 ..(header.pointer_to_raw_data as usize) + (header.size_of_raw_data as usize),
         )
@@ -34,22 +36,82 @@ impl Section {
             } else {
                 0
             },
-            data: data.to_vec(),
+            data,
             header,
         })
     }
 
-    pub fn verify_section_order(sections: &[Section]) -> bool {
+    /// Reads the section from a mapped image (such as a process memory dump), where the section
+    /// data is located at its virtual address.
+    pub fn from_mapped_image_and_header(
+        image: &SharedBytes,
+        header: SectionHeader,
+    ) -> Option<Self> {
+        let virtual_size = if header.virtual_size == 0 {
+            header.size_of_raw_data
+        } else {
+            header.virtual_size
+        };
+        let start = header.virtual_address as usize;
+        let end = (start + virtual_size as usize).min(image.len());
+        image.slice(start..end).map(|data| Self {
+            uninitialized_data_size: virtual_size - data.len() as u32,
+            data,
+            header,
+        })
+    }
+
+    /// Reads as much of the section data as is present in the given file or mapped image,
+    /// for sections whose data extends beyond its end.
+    pub fn from_available_data(
+        image: &SharedBytes,
+        header: SectionHeader,
+        layout: ImageLayout,
+    ) -> Self {
+        let (start, size) = match layout {
+            ImageLayout::File => (header.pointer_to_raw_data, header.size_of_raw_data),
+            ImageLayout::Mapped => (header.virtual_address, header.virtual_size),
+        };
+        let start = (start as usize).min(image.len());
+        let end = start + (size as usize).min(image.len() - start);
+        let data = image.slice(start..end).unwrap_or_default();
+
+        Self {
+            uninitialized_data_size: header.virtual_size.saturating_sub(data.len() as u32),
+            data,
+            header,
+        }
+    }
+
+    /// Converts an RVA within the data of this section to an offset in the parsed image.
+    pub fn file_offset(&self, rva: u32, layout: ImageLayout) -> Option<u32> {
+        let offset = rva.checked_sub(self.header.virtual_address)?;
+        if offset >= self.data.len() as u32 {
+            return None;
+        }
+
+        match layout {
+            ImageLayout::File => Some(self.header.pointer_to_raw_data + offset),
+            ImageLayout::Mapped => Some(rva),
+        }
+    }
+
+    /// Returns the indices of all sections that start before the end of the previous section.
+    pub fn out_of_order_sections(sections: &[Section]) -> Vec<usize> {
+        let mut out_of_order = Vec::new();
         let mut earliest_section_start = 0;
-        for section in sections {
+        for (index, section) in sections.iter().enumerate() {
             if section.header.virtual_address < earliest_section_start {
-                return false;
+                out_of_order.push(index);
             }
 
-            earliest_section_start = section.header.virtual_address + section.header.virtual_size
+            earliest_section_start = section
+                .header
+                .virtual_address
+                .saturating_add(section.header.virtual_size);
         }
 
-        true
+        out_of_order
     }
 }
 
@@ -60,7 +122,7 @@ impl fmt::Debug for Section {
             .field(
                 name_of!(data in Section),
                 &(
-                    format!("Vec<u8>, len: {:X}", self.data.len()),
+                    format!("SharedBytes, len: {:X}", self.data.len()),
                     if self.data.len() > 16 {
                         &self.data[..16]
                     } else {
@@ -76,7 +138,7 @@ impl fmt::Debug for Section {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SectionHeader {
     /// An 8-byte, null-padded UTF-8 encoded string. If the string is exactly 8 characters long,
     /// there is no terminating null.
@@ -147,10 +209,13 @@ pub struct SectionHeader {
 }
 
 impl SectionHeader {
+    /// Checks that the section is aligned as the optional header requires. Nothing is aligned
+    /// to a zero alignment, which lenient parsing lets through.
     pub fn verify(&self, file_alignment: u32, section_alignment: u32) -> bool {
-        self.virtual_address % section_alignment == 0
-            && self.size_of_raw_data % file_alignment == 0
-            && self.pointer_to_raw_data % file_alignment == 0
+        let is_aligned = |value: u32, alignment: u32| value.checked_rem(alignment) == Some(0);
+        is_aligned(self.virtual_address, section_alignment)
+            && is_aligned(self.size_of_raw_data, file_alignment)
+            && is_aligned(self.pointer_to_raw_data, file_alignment)
     }
 }
 
@@ -200,6 +265,26 @@ impl BinParsable for SectionHeader {
     }
 }
 
+impl BinWritable for SectionHeader {
+    fn write_to(&self, out: &mut Vec<u8>) {
+        // Longer names are truncated, as the linker does for images
+        let mut name = [0u8; 8];
+        let length = self.name.len().min(name.len());
+        name[..length].copy_from_slice(&self.name.as_bytes()[..length]);
+        out.extend_from_slice(&name);
+
+        out.extend_from_slice(&self.virtual_size.to_le_bytes());
+        out.extend_from_slice(&self.virtual_address.to_le_bytes());
+        out.extend_from_slice(&self.size_of_raw_data.to_le_bytes());
+        out.extend_from_slice(&self.pointer_to_raw_data.to_le_bytes());
+        out.extend_from_slice(&self.pointer_to_relocations.to_le_bytes());
+        out.extend_from_slice(&self.pointer_to_linenumbers.to_le_bytes());
+        out.extend_from_slice(&self.number_of_relocations.to_le_bytes());
+        out.extend_from_slice(&self.number_of_linenumbers.to_le_bytes());
+        out.extend_from_slice(&self.characteristics.bits().to_le_bytes());
+    }
+}
+
 bitflags! {
     #[derive(Default)]
     pub struct SectionCharacteristics: u32 {
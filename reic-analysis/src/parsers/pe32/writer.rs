@@ -0,0 +1,284 @@
+use crate::parsers::pe32::{
+    compute_checksum, DataDirectory, DataDirectoryType, PE32Image, CHECK_SUM_OFFSET,
+};
+use crate::parsers::BinWritable;
+
+use std::error::Error;
+use std::fmt;
+
+/// The offset of the optional header from the PE signature (signature and COFF header).
+pub(crate) const OPTIONAL_HEADER_OFFSET: usize = 4 + 20;
+/// The offsets of `size_of_image` and `size_of_headers` in the PE32 optional header.
+const SIZE_OF_IMAGE_OFFSET: usize = 56;
+const SIZE_OF_HEADERS_OFFSET: usize = 60;
+
+pub(crate) fn write_u32(data: &mut [u8], offset: usize, value: u32) {
+    if let Some(location) = data.get_mut(offset..offset + 4) {
+        location.copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+pub(crate) fn align(value: usize, alignment: usize) -> usize {
+    // Images parsed in lenient mode may have a zero alignment
+    let alignment = alignment.max(1);
+    value + (alignment - value % alignment) % alignment
+}
+
+/// How `to_bytes_with_options` updates the checksum in the optional header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckSumUpdate {
+    /// The checksum is written as parsed.
+    Keep,
+    /// The checksum is recomputed unless it is zero. Most images besides drivers carry none, and
+    /// the loader only verifies it for drivers and boot-time DLLs.
+    IfPresent,
+    /// The checksum is always recomputed.
+    Always,
+}
+
+#[derive(Debug, Clone)]
+pub struct WriteOptions {
+    pub check_sum: CheckSumUpdate,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            check_sum: CheckSumUpdate::IfPresent,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteError {
+    /// The raw data of the named section starts within the headers, and would overwrite them.
+    SectionInHeaders(String),
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteError::SectionInHeaders(section) => write!(
+                f,
+                "the raw data of section `{}` starts within the headers",
+                section
+            ),
+        }
+    }
+}
+
+impl Error for WriteError {}
+
+impl PE32Image {
+    /// Serializes the image to an image file, with the default options.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, WriteError> {
+        self.to_bytes_with_options(&WriteOptions::default())
+    }
+
+    /// Serializes the image to an image file.
+    ///
+    /// The headers are written as parsed, with the section table at `size_of_optional_header`
+    /// after the standard optional header. Section data is written at `pointer_to_raw_data`,
+    /// truncated or zero-padded to `size_of_raw_data`, and gaps between sections are
+    /// zero-filled. The overlay is appended after the last section.
+    ///
+    /// `size_of_image` and `size_of_headers` are recomputed from the section table, and the
+    /// checksum is updated as set in the options. Section data starting within the headers is
+    /// rejected.
+    ///
+    /// An unmodified image parsed from a file is written back byte for byte.
+    pub fn to_bytes_with_options(&self, options: &WriteOptions) -> Result<Vec<u8>, WriteError> {
+        let windows_specific = &self.pe32_optional_header.windows_specific;
+        let mut file = Vec::new();
+
+        self.mz_header.write_to(&mut file);
+        file.extend_from_slice(&self.dos_stub);
+        let e_lfanew = self.mz_header.e_lfanew as usize;
+        if file.len() < e_lfanew {
+            file.resize(e_lfanew, 0);
+        }
+
+        let mut headers = Vec::new();
+        headers.extend_from_slice(b"PE\0\0");
+        self.coff_header.write_to(&mut headers);
+        let optional_header = e_lfanew + headers.len();
+        self.coff_optional_header.write_to(&mut headers);
+        self.pe32_optional_header.write_to(&mut headers);
+        for index in 0..windows_specific.number_of_rva_and_sizes as usize {
+            match self
+                .data_directories
+                .get(&DataDirectoryType::from_index(index))
+            {
+                Some(directory) => directory.write_to(&mut headers),
+                None => DataDirectory {
+                    virtual_address: 0,
+                    size: 0,
+                }
+                .write_to(&mut headers),
+            }
+        }
+        let section_table = optional_header + self.coff_header.size_of_optional_header as usize;
+        headers.resize(headers.len().max(section_table - e_lfanew), 0);
+
+        for section in &self.sections {
+            section.header.write_to(&mut headers);
+        }
+        headers.extend_from_slice(&self.header_padding);
+
+        // The PE headers of tiny images overlap the MZ header. They were parsed from the same
+        // bytes, so they are written over it.
+        let headers_end = e_lfanew + headers.len();
+        file.resize(file.len().max(headers_end), 0);
+        file[e_lfanew..headers_end].copy_from_slice(&headers);
+        let size_of_headers = align(file.len(), windows_specific.file_alignment as usize);
+        file.resize(size_of_headers, 0);
+
+        for section in &self.sections {
+            let header = &section.header;
+            if header.size_of_raw_data == 0 {
+                continue;
+            }
+
+            let start = header.pointer_to_raw_data as usize;
+            if start < size_of_headers {
+                return Err(WriteError::SectionInHeaders(header.name.clone()));
+            }
+            let end = start + header.size_of_raw_data as usize;
+            if file.len() < end {
+                file.resize(end, 0);
+            }
+            let length = section.data.len().min(end - start);
+            file[start..start + length].copy_from_slice(&section.data[..length]);
+        }
+        file.extend_from_slice(&self.overlay);
+
+        let image_end = self
+            .sections
+            .iter()
+            .map(|section| {
+                let header = &section.header;
+                let size = match header.virtual_size {
+                    0 => header.size_of_raw_data,
+                    virtual_size => virtual_size,
+                };
+                header.virtual_address as usize + size as usize
+            })
+            .fold(size_of_headers, usize::max);
+        let size_of_image = align(image_end, windows_specific.section_alignment as usize);
+
+        write_u32(
+            &mut file,
+            optional_header + SIZE_OF_IMAGE_OFFSET,
+            size_of_image as u32,
+        );
+        write_u32(
+            &mut file,
+            optional_header + SIZE_OF_HEADERS_OFFSET,
+            size_of_headers as u32,
+        );
+        let update_check_sum = match options.check_sum {
+            CheckSumUpdate::Keep => false,
+            CheckSumUpdate::IfPresent => windows_specific.check_sum != 0,
+            CheckSumUpdate::Always => true,
+        };
+        if update_check_sum {
+            let check_sum_offset = optional_header + CHECK_SUM_OFFSET;
+            let check_sum = compute_checksum(&file, check_sum_offset);
+            write_u32(&mut file, check_sum_offset, check_sum);
+        }
+
+        Ok(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::pe32::tests::TestImage;
+    use crate::parsers::BinParsable;
+    use nom::error::VerboseError;
+
+    #[test]
+    fn writes_back_unmodified_image() {
+        let mut test_image = TestImage::executable();
+        test_image.dos_stub = b"This program cannot be run in DOS mode.\r\n$".to_vec();
+        test_image.overlay = b"appended data".to_vec();
+        let file = test_image.build();
+
+        let (_, image) = PE32Image::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+        assert_eq!(&image.overlay[..], b"appended data");
+        assert!(image.to_bytes().unwrap() == file);
+    }
+
+    #[test]
+    fn writes_back_headers_overlapping_the_mz_header() {
+        // The PE headers start at 0x0C, and `base_of_data` doubles as `e_lfanew`
+        let mut file = TestImage::executable().build();
+        let headers = file[0x40..0x200].to_vec();
+        file[0x02..0x200].iter_mut().for_each(|byte| *byte = 0);
+        file[0x0C..0x0C + headers.len() - 0x34].copy_from_slice(&headers[..headers.len() - 0x34]);
+        file[0x3C..0x40].copy_from_slice(&0x0Cu32.to_le_bytes());
+
+        let (_, image) = PE32Image::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+        assert_eq!(image.mz_header.e_lfanew, 0x0C);
+        assert_eq!(image.pe32_optional_header.base_of_data, Some(0x0C));
+        assert!(image.to_bytes().unwrap() == file);
+    }
+
+    #[test]
+    fn recomputes_image_layout() {
+        let file = TestImage::executable().build();
+        let (_, mut image) = PE32Image::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+        image.sections[2].header.virtual_size = 0x1800;
+        image.pe32_optional_header.windows_specific.size_of_image = 0;
+        image.pe32_optional_header.windows_specific.size_of_headers = 0;
+
+        let written = image.to_bytes().unwrap();
+        assert_eq!(written.len(), file.len());
+        let (_, rewritten) = PE32Image::try_parse::<VerboseError<&[u8]>>(&written).unwrap();
+        let windows_specific = &rewritten.pe32_optional_header.windows_specific;
+        assert_eq!(windows_specific.size_of_image, 0x5000);
+        assert_eq!(windows_specific.size_of_headers, 0x200);
+        assert_eq!(rewritten.sections[2].header, image.sections[2].header);
+        assert_eq!(rewritten.sections[2].uninitialized_data_size, 0x1600);
+    }
+
+    #[test]
+    fn updates_check_sum_as_requested() {
+        let file = TestImage::executable().build();
+        let (_, image) = PE32Image::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+        assert_eq!(image.pe32_optional_header.windows_specific.check_sum, 0);
+
+        let written = image
+            .to_bytes_with_options(&WriteOptions {
+                check_sum: CheckSumUpdate::Always,
+            })
+            .unwrap();
+        let (_, rewritten) = PE32Image::try_parse::<VerboseError<&[u8]>>(&written).unwrap();
+        let checksum = rewritten.checksum().unwrap();
+        assert_ne!(checksum.stored, 0);
+        assert_eq!(checksum.stored, checksum.computed);
+
+        let (_, mut modified) = PE32Image::try_parse::<VerboseError<&[u8]>>(&written).unwrap();
+        modified.write_bytes(0x40_1000, &[0xCC]);
+        let kept = modified
+            .to_bytes_with_options(&WriteOptions {
+                check_sum: CheckSumUpdate::Keep,
+            })
+            .unwrap();
+        let (_, kept) = PE32Image::try_parse::<VerboseError<&[u8]>>(&kept).unwrap();
+        assert_eq!(kept.checksum().unwrap().stored, checksum.stored);
+        assert_ne!(kept.checksum().unwrap().computed, checksum.stored);
+    }
+
+    #[test]
+    fn rejects_section_data_in_headers() {
+        let file = TestImage::executable().build();
+        let (_, mut image) = PE32Image::try_parse::<VerboseError<&[u8]>>(&file).unwrap();
+        image.sections[0].header.pointer_to_raw_data = 0x100;
+        assert_eq!(
+            image.to_bytes(),
+            Err(WriteError::SectionInHeaders(".text".to_owned()))
+        );
+    }
+}
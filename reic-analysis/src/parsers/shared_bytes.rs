@@ -0,0 +1,121 @@
+use std::fmt;
+use std::ops::{Deref, Range};
+use std::sync::Arc;
+
+/// A reference-counted view into a byte buffer, such as a memory-mapped file.
+///
+/// Cloning and slicing share the underlying buffer instead of copying it, so parsed structures
+/// can refer to the file data cheaply and be shared across threads.
+#[derive(Clone)]
+pub struct SharedBytes {
+    buffer: Arc<dyn AsRef<[u8]> + Send + Sync>,
+    range: Range<usize>,
+}
+
+impl SharedBytes {
+    pub fn new<T: AsRef<[u8]> + Send + Sync + 'static>(buffer: T) -> Self {
+        let length = buffer.as_ref().len();
+        Self {
+            buffer: Arc::new(buffer),
+            range: 0..length,
+        }
+    }
+
+    pub fn copy_from_slice(data: &[u8]) -> Self {
+        Self::new(data.to_vec())
+    }
+
+    /// Memory-maps the given file.
+    ///
+    /// The file must not be modified while it is mapped, or parsed data may change underneath.
+    #[cfg(feature = "memmap2")]
+    pub fn map_file<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        // Safety: see above, modifying the file while it is mapped is undefined behaviour.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        Ok(Self::new(map))
+    }
+
+    /// Returns a view of the given range of this view, sharing the underlying buffer.
+    pub fn slice(&self, range: Range<usize>) -> Option<Self> {
+        if range.start > range.end || range.end > self.range.len() {
+            return None;
+        }
+
+        Some(Self {
+            buffer: Arc::clone(&self.buffer),
+            range: self.range.start + range.start..self.range.start + range.end,
+        })
+    }
+}
+
+impl Deref for SharedBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &(*self.buffer).as_ref()[self.range.clone()]
+    }
+}
+
+impl AsRef<[u8]> for SharedBytes {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Default for SharedBytes {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl From<Vec<u8>> for SharedBytes {
+    fn from(data: Vec<u8>) -> Self {
+        Self::new(data)
+    }
+}
+
+impl PartialEq for SharedBytes {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for SharedBytes {}
+
+impl fmt::Debug for SharedBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SharedBytes, len: {:X}", self.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slices_share_the_buffer() {
+        let bytes = SharedBytes::from((0u8..16).collect::<Vec<_>>());
+        let slice = bytes.slice(4..12).unwrap();
+        assert_eq!(&slice[..], &[4, 5, 6, 7, 8, 9, 10, 11]);
+        assert_eq!(slice.as_ptr(), bytes[4..].as_ptr());
+
+        let nested = slice.slice(2..4).unwrap();
+        assert_eq!(&nested[..], &[6, 7]);
+        assert!(slice.slice(4..9).is_none());
+    }
+
+    #[cfg(feature = "memmap2")]
+    #[test]
+    fn maps_files() {
+        let path = std::env::temp_dir().join(format!("reic-shared-bytes-{}", std::process::id()));
+        std::fs::write(&path, b"mapped file").unwrap();
+        let bytes = SharedBytes::map_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let bytes = bytes.unwrap();
+        assert_eq!(&bytes[..], b"mapped file");
+        assert_eq!(&bytes.slice(7..11).unwrap()[..], b"file");
+        assert!(SharedBytes::map_file(&path).is_err());
+    }
+}
@@ -0,0 +1,267 @@
+mod assembler;
+pub use assembler::*;
+
+use crate::parsers::pe32::{PE32Image, WriteError};
+use crate::parsers::{BinParsable, ParseErrorReport};
+
+use nom::error::VerboseError;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+/// A named group of patches, such as a single fix or feature of a mod, that is applied or
+/// skipped as a whole.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PatchSet {
+    pub name: String,
+    /// Whether the patch set is applied to the output file.
+    pub enabled: bool,
+    pub patches: Vec<Patch>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Patch {
+    /// The linear address of the first patched byte.
+    pub address: u64,
+    pub content: PatchContent,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PatchContent {
+    Bytes(Vec<u8>),
+    /// Assembly source, see `assemble`. It is assembled at the address of the patch.
+    Assembly(String),
+}
+
+impl Patch {
+    /// The bytes written by this patch.
+    pub fn bytes(&self) -> Result<Vec<u8>, PatchError> {
+        match &self.content {
+            PatchContent::Bytes(bytes) => Ok(bytes.clone()),
+            PatchContent::Assembly(source) => {
+                assemble(source, self.address).map_err(|error| PatchError::Assembly {
+                    address: self.address,
+                    error,
+                })
+            }
+        }
+    }
+}
+
+/// The bytes at the address of a patch, before and after patching.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchComparison {
+    /// The name of the patch set the patch belongs to.
+    pub patch_set: String,
+    pub address: u64,
+    pub original: Vec<u8>,
+    pub patched: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    /// The file to patch could not be parsed.
+    Parse(ParseErrorReport),
+    Assembly {
+        address: u64,
+        error: AssemblyError,
+    },
+    /// The patched bytes do not lie within the raw data of a single section.
+    OutOfBounds {
+        address: u64,
+        length: usize,
+    },
+    /// The patch at the address modifies bytes that an earlier enabled patch modifies as well.
+    Overlap {
+        address: u64,
+    },
+    /// The patched image could not be written.
+    Write(WriteError),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::Parse(report) => write!(f, "failed to parse the file: {}", report),
+            PatchError::Assembly { address, error } => {
+                write!(f, "patch at 0x{:X}: {}", address, error)
+            }
+            PatchError::OutOfBounds { address, length } => write!(
+                f,
+                "patch at 0x{:X} (0x{:X} bytes) is outside the raw data of the sections",
+                address, length
+            ),
+            PatchError::Overlap { address } => {
+                write!(f, "patch at 0x{:X} overlaps another patch", address)
+            }
+            PatchError::Write(error) => write!(f, "failed to write the patched file: {}", error),
+        }
+    }
+}
+
+impl Error for PatchError {}
+
+fn parse(file: &[u8]) -> Result<PE32Image, PatchError> {
+    PE32Image::try_parse::<VerboseError<&[u8]>>(file)
+        .map(|(_, image)| image)
+        .map_err(|error| PatchError::Parse(ParseErrorReport::from_nom_error(file, &error)))
+}
+
+fn compare(image: &PE32Image, patch_sets: &[PatchSet]) -> Result<Vec<PatchComparison>, PatchError> {
+    let mut comparisons: Vec<PatchComparison> = Vec::new();
+    for patch_set in patch_sets.iter().filter(|patch_set| patch_set.enabled) {
+        for patch in &patch_set.patches {
+            let patched = patch.bytes()?;
+            let original =
+                image
+                    .bytes_at(patch.address, patched.len())
+                    .ok_or(PatchError::OutOfBounds {
+                        address: patch.address,
+                        length: patched.len(),
+                    })?;
+
+            let end = patch.address + patched.len() as u64;
+            if comparisons.iter().any(|comparison| {
+                patch.address < comparison.address + comparison.patched.len() as u64
+                    && comparison.address < end
+            }) {
+                return Err(PatchError::Overlap {
+                    address: patch.address,
+                });
+            }
+
+            comparisons.push(PatchComparison {
+                patch_set: patch_set.name.clone(),
+                address: patch.address,
+                original: original.to_vec(),
+                patched,
+            });
+        }
+    }
+
+    Ok(comparisons)
+}
+
+/// Compares the original and patched bytes of every patch in the enabled patch sets, in order.
+pub fn compare_patches(
+    file: &[u8],
+    patch_sets: &[PatchSet],
+) -> Result<Vec<PatchComparison>, PatchError> {
+    compare(&parse(file)?, patch_sets)
+}
+
+/// Applies the enabled patch sets to the PE image file, and writes the patched file.
+pub fn apply_patch_sets(file: &[u8], patch_sets: &[PatchSet]) -> Result<Vec<u8>, PatchError> {
+    let mut image = parse(file)?;
+    for comparison in compare(&image, patch_sets)? {
+        image.write_bytes(comparison.address, &comparison.patched);
+    }
+
+    image.to_bytes().map_err(PatchError::Write)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::pe32::compute_checksum;
+    use crate::parsers::pe32::tests::{TestImage, TEST_IMAGE_BASE};
+
+    /// The offset of the checksum in the test image: the optional header follows the PE
+    /// signature at 0x40.
+    const CHECK_SUM_OFFSET: usize = 0x98;
+
+    fn patch_set(name: &str, patches: Vec<Patch>) -> PatchSet {
+        PatchSet {
+            name: name.to_owned(),
+            enabled: true,
+            patches,
+        }
+    }
+
+    #[test]
+    fn applies_patch_sets() {
+        let mut file = TestImage::executable().build();
+        let check_sum = compute_checksum(&file, CHECK_SUM_OFFSET);
+        file[CHECK_SUM_OFFSET..CHECK_SUM_OFFSET + 4].copy_from_slice(&check_sum.to_le_bytes());
+
+        let entry_point = TEST_IMAGE_BASE + 0x1000;
+        let mut patch_sets = vec![
+            patch_set(
+                "Skip entry point",
+                vec![Patch {
+                    address: entry_point,
+                    content: PatchContent::Assembly("xor eax, eax; ret".to_owned()),
+                }],
+            ),
+            patch_set(
+                "Import by name",
+                vec![Patch {
+                    address: TEST_IMAGE_BASE + 0x2058,
+                    content: PatchContent::Bytes(vec![0x80, 0x20, 0x00, 0x00]),
+                }],
+            ),
+        ];
+
+        let comparisons = compare_patches(&file, &patch_sets).unwrap();
+        assert_eq!(
+            comparisons[0],
+            PatchComparison {
+                patch_set: "Skip entry point".to_owned(),
+                address: entry_point,
+                original: vec![0x6A, 0x00, 0xFF],
+                patched: vec![0x31, 0xC0, 0xC3],
+            }
+        );
+        assert_eq!(comparisons[1].original, vec![0x11, 0x00, 0x00, 0x80]);
+
+        let patched = apply_patch_sets(&file, &patch_sets).unwrap();
+        let (_, image) = PE32Image::try_parse::<VerboseError<&[u8]>>(&patched).unwrap();
+        assert_eq!(
+            image.bytes_at(entry_point, 3),
+            Some(&[0x31, 0xC0, 0xC3][..])
+        );
+        let checksum = image.checksum().unwrap();
+        assert!(checksum.is_present() && checksum.is_valid());
+        assert_eq!(
+            image.imports[1].imports[0].name.to_display_name(),
+            "ExitProcess"
+        );
+
+        patch_sets[0].enabled = false;
+        patch_sets[1].enabled = false;
+        assert!(apply_patch_sets(&file, &patch_sets).unwrap() == file);
+    }
+
+    #[test]
+    fn rejects_invalid_patches() {
+        let file = TestImage::executable().build();
+        let patch = |address, bytes: &[u8]| Patch {
+            address,
+            content: PatchContent::Bytes(bytes.to_vec()),
+        };
+
+        // The .text raw data ends at 0x401200
+        let out_of_bounds = patch_set("Out of bounds", vec![patch(0x0040_11FE, &[0x90; 4])]);
+        assert_eq!(
+            apply_patch_sets(&file, &[out_of_bounds]),
+            Err(PatchError::OutOfBounds {
+                address: 0x0040_11FE,
+                length: 4,
+            })
+        );
+
+        let first = patch_set("First", vec![patch(0x0040_1000, &[0x90; 4])]);
+        let second = patch_set("Second", vec![patch(0x0040_1003, &[0xCC])]);
+        assert_eq!(
+            apply_patch_sets(&file, &[first, second.clone()]),
+            Err(PatchError::Overlap {
+                address: 0x0040_1003
+            })
+        );
+
+        match apply_patch_sets(&file[..0x40], &[second]) {
+            Err(PatchError::Parse(report)) => assert_eq!(report.context[0], "PE32Image"),
+            result => panic!("expected a parse error, got {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+/// The 32-bit general purpose registers, in encoding order.
+const REGISTERS: [&str; 8] = ["eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi"];
+
+/// The condition codes of `jcc`, in encoding order, with their aliases.
+const CONDITIONS: [&[&str]; 16] = [
+    &["jo"],
+    &["jno"],
+    &["jb", "jc", "jnae"],
+    &["jae", "jnc", "jnb"],
+    &["je", "jz"],
+    &["jne", "jnz"],
+    &["jbe", "jna"],
+    &["ja", "jnbe"],
+    &["js"],
+    &["jns"],
+    &["jp", "jpe"],
+    &["jnp", "jpo"],
+    &["jl", "jnge"],
+    &["jge", "jnl"],
+    &["jle", "jng"],
+    &["jg", "jnle"],
+];
+
+/// Arithmetic instructions with their `op r/m32, r32` opcode and `81 /digit` extension.
+const ARITHMETIC: [(&str, u8, u8); 6] = [
+    ("add", 0x01, 0),
+    ("or", 0x09, 1),
+    ("and", 0x21, 4),
+    ("sub", 0x29, 5),
+    ("xor", 0x31, 6),
+    ("cmp", 0x39, 7),
+];
+
+/// Instructions without operands.
+const SINGLE_BYTE: [(&str, u8); 8] = [
+    ("nop", 0x90),
+    ("int3", 0xCC),
+    ("ret", 0xC3),
+    ("leave", 0xC9),
+    ("pushad", 0x60),
+    ("popad", 0x61),
+    ("pushfd", 0x9C),
+    ("popfd", 0x9D),
+];
+
+/// The other supported instructions.
+const OTHER_MNEMONICS: [&str; 9] = [
+    "db", "jmp", "call", "push", "pop", "inc", "dec", "mov", "test",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssemblyError {
+    UnknownInstruction(String),
+    /// The operands are not supported for the instruction.
+    InvalidOperands(String),
+    /// The target of a relative jump or call is out of range.
+    TargetOutOfRange(String),
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssemblyError::UnknownInstruction(instruction) => {
+                write!(f, "unknown instruction `{}`", instruction)
+            }
+            AssemblyError::InvalidOperands(instruction) => {
+                write!(f, "invalid operands in `{}`", instruction)
+            }
+            AssemblyError::TargetOutOfRange(instruction) => {
+                write!(f, "target of `{}` is out of range", instruction)
+            }
+        }
+    }
+}
+
+impl Error for AssemblyError {}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Operand {
+    Register(u8),
+    Immediate(i64),
+    /// A dword at an absolute address, such as an import address table slot.
+    Memory(i64),
+}
+
+impl Operand {
+    fn parse(operand: &str) -> Option<Self> {
+        if let Some(register) = REGISTERS.iter().position(|&name| name == operand) {
+            return Some(Operand::Register(register as u8));
+        }
+        let operand = operand.trim_start_matches("dword ptr").trim();
+        if operand.starts_with('[') && operand.ends_with(']') {
+            return match Operand::parse(operand[1..operand.len() - 1].trim())? {
+                Operand::Immediate(address) => Some(Operand::Memory(address)),
+                _ => None,
+            };
+        }
+
+        let (negative, number) = match operand.strip_prefix('-') {
+            Some(number) => (true, number),
+            None => (false, operand),
+        };
+        let value = if let Some(hex) = number.strip_prefix("0x") {
+            i64::from_str_radix(hex, 16).ok()?
+        } else if let Some(hex) = number.strip_suffix('h') {
+            i64::from_str_radix(hex, 16).ok()?
+        } else {
+            number.parse().ok()?
+        };
+        Some(Operand::Immediate(if negative { -value } else { value }))
+    }
+}
+
+/// Assembles 32-bit x86 code to be placed at `address`.
+///
+/// Instructions are separated by newlines or `;`, and use Intel syntax with register and
+/// immediate operands. Besides a few common single-byte instructions, this supports `jmp`,
+/// `call` and `jcc` to absolute targets (always encoded with a 32-bit displacement), `push`,
+/// `pop`, `mov`, `inc`, `dec`, `test` and basic arithmetic. `jmp`, `call` and `push` also take
+/// an absolute memory operand, like `call [0x402004]`. `db` emits raw bytes.
+pub fn assemble(source: &str, address: u64) -> Result<Vec<u8>, AssemblyError> {
+    let mut code = Vec::new();
+    for instruction in source
+        .lines()
+        .flat_map(|line| line.split(';'))
+        .map(str::trim)
+        .filter(|instruction| !instruction.is_empty())
+    {
+        let instruction = instruction.to_ascii_lowercase();
+        let instruction_address = address + code.len() as u64;
+        code.extend(assemble_instruction(&instruction, instruction_address)?);
+    }
+
+    Ok(code)
+}
+
+fn assemble_instruction(instruction: &str, address: u64) -> Result<Vec<u8>, AssemblyError> {
+    let invalid = || AssemblyError::InvalidOperands(instruction.to_owned());
+
+    let (mnemonic, operands) = match instruction.find(char::is_whitespace) {
+        Some(index) => (&instruction[..index], instruction[index..].trim()),
+        None => (instruction, ""),
+    };
+    let operands = if operands.is_empty() {
+        Vec::new()
+    } else {
+        operands
+            .split(',')
+            .map(|operand| Operand::parse(operand.trim()))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(invalid)?
+    };
+
+    let immediate32 = |value: i64| {
+        if value >= i64::from(i32::MIN) && value <= i64::from(u32::MAX) {
+            Ok((value as u32).to_le_bytes())
+        } else {
+            Err(invalid())
+        }
+    };
+    let relative = |opcode: &[u8], target: i64| {
+        let end = address as i64 + opcode.len() as i64 + 4;
+        let displacement = i32::try_from(target - end)
+            .map_err(|_| AssemblyError::TargetOutOfRange(instruction.to_owned()))?;
+        let mut code = opcode.to_vec();
+        code.extend_from_slice(&displacement.to_le_bytes());
+        Ok(code)
+    };
+    let code_with_immediate = |opcode: &[u8], value: i64| {
+        let mut code = opcode.to_vec();
+        code.extend_from_slice(&immediate32(value)?);
+        Ok(code)
+    };
+
+    let single_byte = SINGLE_BYTE.iter().find(|(name, _)| *name == mnemonic);
+    if let (Some((_, opcode)), []) = (single_byte, &operands[..]) {
+        return Ok(vec![*opcode]);
+    }
+    if let Some(condition) = CONDITIONS
+        .iter()
+        .position(|aliases| aliases.contains(&mnemonic))
+    {
+        return match operands[..] {
+            [Operand::Immediate(target)] => relative(&[0x0F, 0x80 | condition as u8], target),
+            _ => Err(invalid()),
+        };
+    }
+    if let Some((_, opcode, extension)) = ARITHMETIC.iter().find(|(name, _, _)| *name == mnemonic) {
+        return match operands[..] {
+            [Operand::Register(destination), Operand::Register(source)] => {
+                Ok(vec![*opcode, 0xC0 | source << 3 | destination])
+            }
+            [Operand::Register(destination), Operand::Immediate(value)] => {
+                code_with_immediate(&[0x81, 0xC0 | extension << 3 | destination], value)
+            }
+            _ => Err(invalid()),
+        };
+    }
+
+    match (mnemonic, &operands[..]) {
+        ("ret", [Operand::Immediate(value)]) => {
+            let value = u16::try_from(*value).map_err(|_| invalid())?;
+            let mut code = vec![0xC2];
+            code.extend_from_slice(&value.to_le_bytes());
+            Ok(code)
+        }
+        ("db", bytes) if !bytes.is_empty() => bytes
+            .iter()
+            .map(|byte| match *byte {
+                Operand::Immediate(value) => u8::try_from(value).map_err(|_| invalid()),
+                _ => Err(invalid()),
+            })
+            .collect(),
+        ("jmp", [Operand::Immediate(target)]) => relative(&[0xE9], *target),
+        ("jmp", [Operand::Register(register)]) => Ok(vec![0xFF, 0xE0 | register]),
+        ("jmp", [Operand::Memory(address)]) => code_with_immediate(&[0xFF, 0x25], *address),
+        ("call", [Operand::Immediate(target)]) => relative(&[0xE8], *target),
+        ("call", [Operand::Register(register)]) => Ok(vec![0xFF, 0xD0 | register]),
+        ("call", [Operand::Memory(address)]) => code_with_immediate(&[0xFF, 0x15], *address),
+        ("push", [Operand::Immediate(value)]) => code_with_immediate(&[0x68], *value),
+        ("push", [Operand::Register(register)]) => Ok(vec![0x50 | register]),
+        ("push", [Operand::Memory(address)]) => code_with_immediate(&[0xFF, 0x35], *address),
+        ("pop", [Operand::Register(register)]) => Ok(vec![0x58 | register]),
+        ("inc", [Operand::Register(register)]) => Ok(vec![0x40 | register]),
+        ("dec", [Operand::Register(register)]) => Ok(vec![0x48 | register]),
+        ("mov", [Operand::Register(register), Operand::Immediate(value)]) => {
+            code_with_immediate(&[0xB8 | register], *value)
+        }
+        ("mov", [Operand::Register(destination), Operand::Register(source)]) => {
+            Ok(vec![0x89, 0xC0 | source << 3 | destination])
+        }
+        ("test", [Operand::Register(destination), Operand::Register(source)]) => {
+            Ok(vec![0x85, 0xC0 | source << 3 | destination])
+        }
+        _ if single_byte.is_some() || OTHER_MNEMONICS.contains(&mnemonic) => Err(invalid()),
+        _ => Err(AssemblyError::UnknownInstruction(instruction.to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assembles_code_cave_jump() {
+        let code = assemble(
+            "push 0x10; call 0x401000\nadd esp, 4\njnz 0x402000; jmp 402100h",
+            0x402000,
+        )
+        .unwrap();
+        assert_eq!(
+            code,
+            vec![
+                0x68, 0x10, 0x00, 0x00, 0x00, // push 0x10
+                0xE8, 0xF6, 0xEF, 0xFF, 0xFF, // call 0x401000
+                0x81, 0xC4, 0x04, 0x00, 0x00, 0x00, // add esp, 4
+                0x0F, 0x85, 0xEA, 0xFF, 0xFF, 0xFF, // jnz 0x402000
+                0xE9, 0xE5, 0x00, 0x00, 0x00, // jmp 0x402100
+            ]
+        );
+
+        assert_eq!(assemble("mov eax, ebx", 0), Ok(vec![0x89, 0xD8]));
+        assert_eq!(
+            assemble("call dword ptr [0x402004]", 0),
+            Ok(vec![0xFF, 0x15, 0x04, 0x20, 0x40, 0x00])
+        );
+        assert_eq!(
+            assemble("mov eax, [ebx]", 0),
+            Err(AssemblyError::InvalidOperands("mov eax, [ebx]".to_owned()))
+        );
+        assert_eq!(
+            assemble("lea eax, ebx", 0),
+            Err(AssemblyError::UnknownInstruction("lea eax, ebx".to_owned()))
+        );
+    }
+}
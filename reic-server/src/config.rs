@@ -1 +1,66 @@
-pub struct ServerConfiguration {}
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default)]
+pub struct ServerConfiguration {
+    /// The files the server offers to clients, identified by their index.
+    pub files: Vec<PathBuf>,
+    /// The object files and `.lib` archives whose functions are recognized in opened files.
+    pub signature_files: Vec<PathBuf>,
+}
+
+/// Whether the file holds library code to generate signatures from, judging by its extension.
+fn is_signature_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map_or(false, |extension| {
+            extension.eq_ignore_ascii_case("lib") || extension.eq_ignore_ascii_case("obj")
+        })
+}
+
+impl ServerConfiguration {
+    /// Reads the configuration from the command line arguments, which name the files to offer
+    /// and the object files and archives to recognize library functions by.
+    pub fn from_args<I: IntoIterator<Item = OsString>>(args: I) -> Self {
+        let (signature_files, files) = args
+            .into_iter()
+            .map(PathBuf::from)
+            .partition(|path| is_signature_file(path));
+        Self {
+            files,
+            signature_files,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offers_files_named_by_arguments() {
+        let configuration = ServerConfiguration::from_args(vec![
+            OsString::from("GAME.EXE"),
+            OsString::from("data/EDITOR.EXE"),
+        ]);
+        assert_eq!(
+            configuration.files,
+            vec![PathBuf::from("GAME.EXE"), PathBuf::from("data/EDITOR.EXE")]
+        );
+        assert!(configuration.signature_files.is_empty());
+    }
+
+    #[test]
+    fn reads_signatures_from_libraries_and_objects() {
+        let configuration = ServerConfiguration::from_args(vec![
+            OsString::from("LIBC.LIB"),
+            OsString::from("GAME.EXE"),
+            OsString::from("crt0.obj"),
+        ]);
+        assert_eq!(configuration.files, vec![PathBuf::from("GAME.EXE")]);
+        assert_eq!(
+            configuration.signature_files,
+            vec![PathBuf::from("LIBC.LIB"), PathBuf::from("crt0.obj")]
+        );
+    }
+}
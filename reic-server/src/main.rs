@@ -3,11 +3,19 @@ mod pb;
 mod project;
 mod server;
 
+use config::ServerConfiguration;
+use pb::reic_server::ReicServer;
+use server::ReicService;
+use std::env;
+use tonic::transport::Server;
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let address = "[::1]:10000".parse().unwrap();
 
-    let route_guide = ReicService {};
+    // The files to offer and the libraries to recognize functions of are given on the command line
+    let configuration = ServerConfiguration::from_args(env::args_os().skip(1));
+    let route_guide = ReicService::new(configuration)?;
 
     let svc = ReicServer::new(route_guide);
 
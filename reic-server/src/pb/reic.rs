@@ -17,6 +17,96 @@ pub struct FileList {
     #[prost(message, repeated, tag = "1")]
     pub files: ::std::vec::Vec<File>,
 }
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ClassHierarchyRequest {
+    #[prost(uint64, tag = "1")]
+    pub file_id: u64,
+}
+/// The C++ classes recovered from the run-time type information of a file.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ClassHierarchy {
+    #[prost(message, repeated, tag = "1")]
+    pub classes: ::std::vec::Vec<Class>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Class {
+    /// The address of the type descriptor, identifying the class.
+    #[prost(uint64, tag = "1")]
+    pub type_descriptor: u64,
+    #[prost(string, tag = "2")]
+    pub name: std::string::String,
+    #[prost(string, tag = "3")]
+    pub mangled_name: std::string::String,
+    /// The type descriptors of the classes this class directly derives from.
+    #[prost(uint64, repeated, tag = "4")]
+    pub direct_bases: ::std::vec::Vec<u64>,
+    #[prost(message, repeated, tag = "5")]
+    pub vtables: ::std::vec::Vec<VirtualTable>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VirtualTable {
+    #[prost(uint64, tag = "1")]
+    pub address: u64,
+    /// The offset of the table's pointer within the object.
+    #[prost(uint32, tag = "2")]
+    pub offset: u32,
+    #[prost(uint64, repeated, tag = "3")]
+    pub functions: ::std::vec::Vec<u64>,
+    /// The names of the functions, or empty strings for the functions that are not named.
+    #[prost(string, repeated, tag = "4")]
+    pub function_names: ::std::vec::Vec<std::string::String>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ComparePatchesRequest {
+    #[prost(uint64, tag = "1")]
+    pub file_id: u64,
+}
+/// The bytes at the patches of the enabled patch sets in the project of a file.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PatchComparisons {
+    #[prost(message, repeated, tag = "1")]
+    pub comparisons: ::std::vec::Vec<PatchComparison>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PatchComparison {
+    /// The name of the patch set the patch belongs to.
+    #[prost(string, tag = "1")]
+    pub patch_set: std::string::String,
+    #[prost(uint64, tag = "2")]
+    pub address: u64,
+    /// The bytes at the address before and after patching.
+    #[prost(bytes, tag = "3")]
+    pub original: std::vec::Vec<u8>,
+    #[prost(bytes, tag = "4")]
+    pub patched: std::vec::Vec<u8>,
+}
+/// A file that could not be parsed. Attached as details to the status of the failed call.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ParseError {
+    /// The contexts the error occurred in, outermost first.
+    #[prost(string, repeated, tag = "1")]
+    pub context: ::std::vec::Vec<std::string::String>,
+    /// What the parser expected to find at the offset.
+    #[prost(string, tag = "3")]
+    pub expected: std::string::String,
+    /// The bytes found at the offset.
+    #[prost(bytes, tag = "4")]
+    pub actual: std::vec::Vec<u8>,
+    /// A hex dump of the data around the offset.
+    #[prost(string, tag = "5")]
+    pub hexdump: std::string::String,
+    /// The file offset of the error, unless it lies outside of the file.
+    #[prost(oneof = "parse_error::Location", tags = "2")]
+    pub location: ::std::option::Option<parse_error::Location>,
+}
+pub mod parse_error {
+    /// The file offset of the error, unless it lies outside of the file.
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Location {
+        #[prost(uint64, tag = "2")]
+        Offset(u64),
+    }
+}
 #[doc = r" Generated server implementations."]
 pub mod reic_server {
     #![allow(unused_variables, dead_code, missing_docs)]
@@ -49,6 +139,14 @@ pub mod reic_server {
             tonic::Response<super::super::reic_commands::SampleCommandResponse>,
             tonic::Status,
         >;
+        async fn get_class_hierarchy(
+            &self,
+            request: tonic::Request<super::ClassHierarchyRequest>,
+        ) -> Result<tonic::Response<super::ClassHierarchy>, tonic::Status>;
+        async fn compare_patches(
+            &self,
+            request: tonic::Request<super::ComparePatchesRequest>,
+        ) -> Result<tonic::Response<super::PatchComparisons>, tonic::Status>;
     }
     #[derive(Debug)]
     #[doc(hidden)]
@@ -183,6 +281,70 @@ pub mod reic_server {
                     };
                     Box::pin(fut)
                 }
+                "/reic.Reic/GetClassHierarchy" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetClassHierarchySvc<T: Reic>(pub Arc<T>);
+                    impl<T: Reic> tonic::server::UnaryService<super::ClassHierarchyRequest>
+                        for GetClassHierarchySvc<T>
+                    {
+                        type Response = super::ClassHierarchy;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ClassHierarchyRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { inner.get_class_hierarchy(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = GetClassHierarchySvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/reic.Reic/ComparePatches" => {
+                    #[allow(non_camel_case_types)]
+                    struct ComparePatchesSvc<T: Reic>(pub Arc<T>);
+                    impl<T: Reic> tonic::server::UnaryService<super::ComparePatchesRequest> for ComparePatchesSvc<T> {
+                        type Response = super::PatchComparisons;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ComparePatchesRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { inner.compare_patches(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = ComparePatchesSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     Ok(http::Response::builder()
                         .status(200)
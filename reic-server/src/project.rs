@@ -1,11 +1,484 @@
-use std::path::PathBuf;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
 
-use sled::Db;
+use reic_analysis::patching::{
+    apply_patch_sets, compare_patches, PatchComparison, PatchError, PatchSet,
+};
+use reic_analysis::{CallingConventionOverride, StackVariableOverride, TypeLibrary};
+use sled::{Db, Tree};
+
+/// The tree holding the patch sets of the project, keyed by name.
+const PATCH_SETS_TREE: &str = "patch_sets";
+/// The tree holding the names and types the user gave to stack variables, keyed by function
+/// and offset, see `stack_variable_key`.
+const STACK_VARIABLES_TREE: &str = "stack_variables";
+
+/// The tree holding the calling conventions the user set, keyed by the big-endian function
+/// address.
+const CALLING_CONVENTIONS_TREE: &str = "calling_conventions";
+
+/// The key of the prototypes and constants the user added to the project, in the default tree.
+const TYPE_LIBRARY_KEY: &str = "type_library";
+
+/// The database of the project of a file, stored next to it with `.reic` appended to its name.
+pub(crate) fn project_path(file: &Path) -> PathBuf {
+    let mut path = OsString::from(file);
+    path.push(".reic");
+    PathBuf::from(path)
+}
+
+/// The big-endian function address followed by the offset, with the sign bit flipped so the
+/// variables of a function are ordered by offset.
+fn stack_variable_key(function: u64, offset: i32) -> [u8; 12] {
+    let mut key = [0; 12];
+    key[..8].copy_from_slice(&function.to_be_bytes());
+    key[8..].copy_from_slice(&(offset as u32 ^ 0x8000_0000).to_be_bytes());
+    key
+}
 
 #[derive(Debug)]
-struct PersistedProject {
+pub enum ProjectError {
+    Database(sled::Error),
+    Serialization(bincode::Error),
+    UnknownPatchSet(String),
+    UnknownStackVariable {
+        function: u64,
+        offset: i32,
+    },
+    /// No calling convention was set for the function.
+    UnknownCallingConvention(u64),
+    Patch(PatchError),
+}
+
+impl fmt::Display for ProjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProjectError::Database(error) => write!(f, "database error: {}", error),
+            ProjectError::Serialization(error) => write!(f, "serialization error: {}", error),
+            ProjectError::UnknownPatchSet(name) => write!(f, "unknown patch set `{}`", name),
+            ProjectError::UnknownStackVariable { function, offset } => write!(
+                f,
+                "no stack variable at offset {} of function {:08X} was edited",
+                offset, function
+            ),
+            ProjectError::UnknownCallingConvention(function) => write!(
+                f,
+                "no calling convention was set for function {:08X}",
+                function
+            ),
+            ProjectError::Patch(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for ProjectError {}
+
+impl From<sled::Error> for ProjectError {
+    fn from(error: sled::Error) -> Self {
+        ProjectError::Database(error)
+    }
+}
+
+impl From<bincode::Error> for ProjectError {
+    fn from(error: bincode::Error) -> Self {
+        ProjectError::Serialization(error)
+    }
+}
+
+impl From<PatchError> for ProjectError {
+    fn from(error: PatchError) -> Self {
+        ProjectError::Patch(error)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct PersistedProject {
     db_path: PathBuf,
     db: Db,
 }
 
-impl PersistedProject {}
+impl PersistedProject {
+    pub fn open(db_path: PathBuf) -> Result<Self, ProjectError> {
+        let db = sled::open(&db_path)?;
+        Ok(Self { db_path, db })
+    }
+
+    fn patch_set_tree(&self) -> Result<Tree, ProjectError> {
+        Ok(self.db.open_tree(PATCH_SETS_TREE)?)
+    }
+
+    /// All patch sets of the project, ordered by name.
+    pub fn patch_sets(&self) -> Result<Vec<PatchSet>, ProjectError> {
+        self.patch_set_tree()?
+            .iter()
+            .values()
+            .map(|value| Ok(bincode::deserialize(&value?)?))
+            .collect()
+    }
+
+    /// Stores a patch set, replacing the patch set with the same name.
+    pub fn save_patch_set(&self, patch_set: &PatchSet) -> Result<(), ProjectError> {
+        self.patch_set_tree()?
+            .insert(patch_set.name.as_bytes(), bincode::serialize(patch_set)?)?;
+        Ok(())
+    }
+
+    pub fn remove_patch_set(&self, name: &str) -> Result<(), ProjectError> {
+        match self.patch_set_tree()?.remove(name.as_bytes())? {
+            Some(_) => Ok(()),
+            None => Err(ProjectError::UnknownPatchSet(name.to_owned())),
+        }
+    }
+
+    pub fn set_patch_set_enabled(&self, name: &str, enabled: bool) -> Result<(), ProjectError> {
+        let tree = self.patch_set_tree()?;
+        let mut patch_set: PatchSet = match tree.get(name.as_bytes())? {
+            Some(value) => bincode::deserialize(&value)?,
+            None => return Err(ProjectError::UnknownPatchSet(name.to_owned())),
+        };
+
+        patch_set.enabled = enabled;
+        tree.insert(name.as_bytes(), bincode::serialize(&patch_set)?)?;
+        Ok(())
+    }
+
+    fn stack_variable_tree(&self) -> Result<Tree, ProjectError> {
+        Ok(self.db.open_tree(STACK_VARIABLES_TREE)?)
+    }
+
+    /// The edited stack variables of a function, ordered by offset.
+    pub fn stack_variable_overrides(
+        &self,
+        function: u64,
+    ) -> Result<Vec<StackVariableOverride>, ProjectError> {
+        self.stack_variable_tree()?
+            .scan_prefix(function.to_be_bytes())
+            .values()
+            .map(|value| Ok(bincode::deserialize(&value?)?))
+            .collect()
+    }
+
+    /// Stores the name and type of a stack variable, replacing an earlier edit of it.
+    pub fn save_stack_variable_override(
+        &self,
+        stack_override: &StackVariableOverride,
+    ) -> Result<(), ProjectError> {
+        self.stack_variable_tree()?.insert(
+            stack_variable_key(stack_override.function, stack_override.offset),
+            bincode::serialize(stack_override)?,
+        )?;
+        Ok(())
+    }
+
+    /// Reverts a stack variable to the name and type found by the analysis.
+    pub fn remove_stack_variable_override(
+        &self,
+        function: u64,
+        offset: i32,
+    ) -> Result<(), ProjectError> {
+        match self
+            .stack_variable_tree()?
+            .remove(stack_variable_key(function, offset))?
+        {
+            Some(_) => Ok(()),
+            None => Err(ProjectError::UnknownStackVariable { function, offset }),
+        }
+    }
+
+    fn calling_convention_tree(&self) -> Result<Tree, ProjectError> {
+        Ok(self.db.open_tree(CALLING_CONVENTIONS_TREE)?)
+    }
+
+    /// All calling conventions set by the user, ordered by function.
+    pub fn calling_convention_overrides(
+        &self,
+    ) -> Result<Vec<CallingConventionOverride>, ProjectError> {
+        self.calling_convention_tree()?
+            .iter()
+            .values()
+            .map(|value| Ok(bincode::deserialize(&value?)?))
+            .collect()
+    }
+
+    /// Stores the calling convention of a function, replacing an earlier one.
+    pub fn save_calling_convention_override(
+        &self,
+        convention_override: &CallingConventionOverride,
+    ) -> Result<(), ProjectError> {
+        self.calling_convention_tree()?.insert(
+            convention_override.function.to_be_bytes(),
+            bincode::serialize(convention_override)?,
+        )?;
+        Ok(())
+    }
+
+    /// Reverts a function to the inferred calling convention.
+    pub fn remove_calling_convention_override(&self, function: u64) -> Result<(), ProjectError> {
+        match self
+            .calling_convention_tree()?
+            .remove(function.to_be_bytes())?
+        {
+            Some(_) => Ok(()),
+            None => Err(ProjectError::UnknownCallingConvention(function)),
+        }
+    }
+
+    /// The bundled type library, extended with the prototypes and constants the user added to
+    /// the project.
+    pub fn type_library(&self) -> Result<TypeLibrary, ProjectError> {
+        let mut library = TypeLibrary::bundled();
+        if let Some(value) = self.db.get(TYPE_LIBRARY_KEY)? {
+            library.extend(bincode::deserialize(&value)?);
+        }
+        Ok(library)
+    }
+
+    /// Stores the prototypes and constants the user added, replacing the earlier ones. They
+    /// take precedence over bundled entries with the same name.
+    pub fn save_type_library(&self, library: &TypeLibrary) -> Result<(), ProjectError> {
+        self.db
+            .insert(TYPE_LIBRARY_KEY, bincode::serialize(library)?)?;
+        Ok(())
+    }
+
+    /// Compares the original and patched bytes of every patch in the enabled patch sets, with
+    /// `original` being the file the project was created from.
+    pub fn compare_patches(&self, original: &[u8]) -> Result<Vec<PatchComparison>, ProjectError> {
+        Ok(compare_patches(original, &self.patch_sets()?)?)
+    }
+
+    /// Applies the enabled patch sets to `original`, the file the project was created from, and
+    /// returns the patched file.
+    pub fn write_patched_file(&self, original: &[u8]) -> Result<Vec<u8>, ProjectError> {
+        Ok(apply_patch_sets(original, &self.patch_sets()?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reic_analysis::patching::{Patch, PatchContent};
+    use reic_analysis::{CallingConvention, CallingConventionOverride, StackVariableOverride};
+
+    fn temporary_project() -> PersistedProject {
+        PersistedProject {
+            db_path: PathBuf::new(),
+            db: sled::Config::new().temporary(true).open().unwrap(),
+        }
+    }
+
+    /// A PE32 executable with a single `.text` section at 0x401000, holding
+    /// `push 0; pop eax; ret`.
+    fn executable() -> Vec<u8> {
+        let mut file = vec![0u8; 0x400];
+        let mut put = |offset: usize, bytes: &[u8]| {
+            file[offset..offset + bytes.len()].copy_from_slice(bytes);
+        };
+        put(0x00, b"MZ");
+        put(0x3C, &0x40u32.to_le_bytes()); // e_lfanew
+        put(0x40, b"PE\0\0");
+        put(0x44, &[0x4C, 0x01, 0x01, 0x00]); // i386, one section
+        put(0x54, &[0xE0, 0x00, 0x02, 0x01]); // optional header size, executable
+        put(0x58, &[0x0B, 0x01, 0x06, 0x00]); // PE32, linker version
+        put(0x5C, &0x200u32.to_le_bytes()); // size_of_code
+        put(0x68, &0x1000u32.to_le_bytes()); // address_of_entry_point
+        put(0x6C, &0x1000u32.to_le_bytes()); // base_of_code
+        put(0x74, &0x0040_0000u32.to_le_bytes()); // image_base
+        put(0x78, &0x1000u32.to_le_bytes()); // section_alignment
+        put(0x7C, &0x200u32.to_le_bytes()); // file_alignment
+        put(0x80, &[4, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0]); // OS and subsystem versions
+        put(0x90, &0x2000u32.to_le_bytes()); // size_of_image
+        put(0x94, &0x200u32.to_le_bytes()); // size_of_headers
+        put(0x9C, &[2, 0]); // Windows GUI subsystem
+        put(0xB4, &16u32.to_le_bytes()); // number_of_rva_and_sizes
+        put(0x138, b".text\0\0\0");
+        put(
+            0x140,
+            &[0x10, 0, 0, 0, 0, 0x10, 0, 0, 0, 0x02, 0, 0, 0, 0x02, 0, 0],
+        );
+        put(0x15C, &0x6000_0020u32.to_le_bytes()); // code, executable, readable
+        put(0x200, &[0x6A, 0x00, 0x58, 0xC3]);
+        file
+    }
+
+    fn patch_set(name: &str, address: u64, bytes: &[u8]) -> PatchSet {
+        PatchSet {
+            name: name.to_owned(),
+            enabled: true,
+            patches: vec![Patch {
+                address,
+                content: PatchContent::Bytes(bytes.to_vec()),
+            }],
+        }
+    }
+
+    #[test]
+    fn names_projects_after_their_file() {
+        assert_eq!(
+            project_path(Path::new("games/GAME.EXE")),
+            PathBuf::from("games/GAME.EXE.reic")
+        );
+    }
+
+    #[test]
+    fn stores_and_applies_patch_sets() {
+        let project = temporary_project();
+        let original = executable();
+        let mut return_one = patch_set("Return one", 0x401001, &[0x01]);
+        project.save_patch_set(&return_one).unwrap();
+        project
+            .save_patch_set(&patch_set("Skip", 0x401000, &[0xC3]))
+            .unwrap();
+        // Saving a patch set again replaces it
+        return_one.patches[0].address = 0x401000;
+        return_one.patches[0].content = PatchContent::Assembly("xor eax, eax; inc eax".to_owned());
+        project.save_patch_set(&return_one).unwrap();
+        assert_eq!(
+            project.patch_sets().unwrap(),
+            vec![return_one, patch_set("Skip", 0x401000, &[0xC3])]
+        );
+
+        // Both enabled patch sets patch the entry point
+        assert!(matches!(
+            project.write_patched_file(&original),
+            Err(ProjectError::Patch(PatchError::Overlap {
+                address: 0x401000
+            }))
+        ));
+
+        project.set_patch_set_enabled("Skip", false).unwrap();
+        assert_eq!(
+            project.compare_patches(&original).unwrap(),
+            vec![PatchComparison {
+                patch_set: "Return one".to_owned(),
+                address: 0x401000,
+                original: vec![0x6A, 0x00, 0x58],
+                patched: vec![0x31, 0xC0, 0x40],
+            }]
+        );
+        let patched = project.write_patched_file(&original).unwrap();
+        assert_eq!(patched[0x200..0x204], [0x31, 0xC0, 0x40, 0xC3]);
+        assert_eq!(patched[..0x200], original[..0x200]);
+
+        project.remove_patch_set("Return one").unwrap();
+        assert!(project.compare_patches(&original).unwrap().is_empty());
+        assert!(project.write_patched_file(&original).unwrap() == original);
+        for result in &[
+            project.remove_patch_set("Return one"),
+            project.set_patch_set_enabled("Return one", true),
+        ] {
+            match result {
+                Err(ProjectError::UnknownPatchSet(name)) => assert_eq!(name, "Return one"),
+                result => panic!("unexpected result {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn stores_type_library() {
+        let project = temporary_project();
+        let bundled = TypeLibrary::bundled();
+        assert_eq!(project.type_library().unwrap(), bundled);
+
+        let added = TypeLibrary::parse(
+            "module GAME.dll\nint __stdcall LoadLevel(const char *name, int index);\n",
+        )
+        .unwrap();
+        project.save_type_library(&added).unwrap();
+        let library = project.type_library().unwrap();
+        let load_level = library.prototype("game", "LoadLevel").unwrap();
+        assert_eq!(load_level.calling_convention, CallingConvention::Stdcall);
+        assert_eq!(library.prototypes.len(), bundled.prototypes.len() + 1);
+    }
+
+    fn stack_override(function: u64, offset: i32, name: &str) -> StackVariableOverride {
+        StackVariableOverride {
+            function,
+            offset,
+            name: Some(name.to_owned()),
+            type_name: None,
+        }
+    }
+
+    #[test]
+    fn orders_stack_variables_by_offset() {
+        assert!(stack_variable_key(0x401000, -0x30) < stack_variable_key(0x401000, -4));
+        assert!(stack_variable_key(0x401000, -4) < stack_variable_key(0x401000, 8));
+        assert!(stack_variable_key(0x401000, i32::MAX) < stack_variable_key(0x401001, i32::MIN));
+
+        let project = temporary_project();
+        for stack_override in &[
+            stack_override(0x401000, 8, "count"),
+            stack_override(0x401000, -0x30, "buffer"),
+            stack_override(0x401000, -4, "index"),
+            stack_override(0x402000, 4, "other"),
+        ] {
+            project
+                .save_stack_variable_override(stack_override)
+                .unwrap();
+        }
+        // Saving a variable again replaces it
+        let mut retyped = stack_override(0x401000, -4, "i");
+        retyped.type_name = Some("int".to_owned());
+        project.save_stack_variable_override(&retyped).unwrap();
+
+        assert_eq!(
+            project.stack_variable_overrides(0x401000).unwrap(),
+            vec![
+                stack_override(0x401000, -0x30, "buffer"),
+                retyped,
+                stack_override(0x401000, 8, "count"),
+            ]
+        );
+
+        project.remove_stack_variable_override(0x401000, 8).unwrap();
+        assert_eq!(project.stack_variable_overrides(0x401000).unwrap().len(), 2);
+        match project.remove_stack_variable_override(0x401000, 8) {
+            Err(ProjectError::UnknownStackVariable { function, offset }) => {
+                assert_eq!((function, offset), (0x401000, 8))
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert_eq!(
+            project.stack_variable_overrides(0x402000).unwrap(),
+            vec![stack_override(0x402000, 4, "other")]
+        );
+    }
+
+    #[test]
+    fn stores_calling_conventions() {
+        let project = temporary_project();
+        let thiscall = CallingConventionOverride {
+            function: 0x402000,
+            convention: CallingConvention::Thiscall,
+            stack_arguments: Some(8),
+        };
+        let mut fastcall = CallingConventionOverride {
+            function: 0x401000,
+            convention: CallingConvention::Fastcall,
+            stack_arguments: None,
+        };
+        project.save_calling_convention_override(&thiscall).unwrap();
+        project.save_calling_convention_override(&fastcall).unwrap();
+        fastcall.stack_arguments = Some(4);
+        project.save_calling_convention_override(&fastcall).unwrap();
+        assert_eq!(
+            project.calling_convention_overrides().unwrap(),
+            vec![fastcall, thiscall.clone()]
+        );
+
+        project
+            .remove_calling_convention_override(0x401000)
+            .unwrap();
+        assert_eq!(
+            project.calling_convention_overrides().unwrap(),
+            vec![thiscall]
+        );
+        match project.remove_calling_convention_override(0x401000) {
+            Err(ProjectError::UnknownCallingConvention(function)) => assert_eq!(function, 0x401000),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+}
@@ -1,14 +1,134 @@
+use crate::config::ServerConfiguration;
 use crate::pb::{
+    parse_error,
     reic_server::{Reic, ReicServer},
-    FileChanges, FileList, FileListRequest, OpenFileRequest, SampleCommand, SampleCommandResponse,
+    Class, ClassHierarchy, ClassHierarchyRequest, ComparePatchesRequest, FileChanges, FileList,
+    FileListRequest, OpenFileRequest, ParseError, PatchComparison, PatchComparisons, SampleCommand,
+    SampleCommandResponse, VirtualTable,
 };
+use crate::project::{project_path, PersistedProject, ProjectError};
 use futures_core::Stream;
+use prost::Message;
+use reic_analysis::patching::PatchError;
+use reic_analysis::{
+    load_pe_buffer, Disassembler, Disassembly, ImageLayout, LoadedImage, ParseErrorReport,
+    ParseMode, ParseOptions, SharedBytes, SignatureLibrary,
+};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use tokio::task;
 use tonic::transport::Server;
-use tonic::{Request, Response, Status};
+use tonic::{Code, Request, Response, Status};
+
+pub(crate) struct ReicService {
+    configuration: ServerConfiguration,
+    /// The signatures of the configured library files, applied to every opened file.
+    signatures: Arc<SignatureLibrary>,
+    /// The files opened by clients, by file ID.
+    opened_files: Mutex<HashMap<u64, Arc<OpenedFile>>>,
+}
+
+/// A loaded file, the code found in it and the project holding the user's changes to it.
+struct OpenedFile {
+    file: SharedBytes,
+    image: LoadedImage,
+    disassembly: Disassembly,
+    project: PersistedProject,
+}
+
+impl ReicService {
+    /// Creates the service, generating the signatures of the configured library files.
+    pub(crate) fn new(configuration: ServerConfiguration) -> Result<Self, Box<dyn Error>> {
+        let mut signatures = SignatureLibrary::new("server");
+        for path in &configuration.signature_files {
+            let file = fs::read(path)
+                .map_err(|error| format!("cannot read {}: {}", path.display(), error))?;
+            signatures
+                .add_file(&file)
+                .map_err(|report| format!("cannot parse {}: {}", path.display(), report))?;
+        }
+
+        Ok(Self {
+            configuration,
+            signatures: Arc::new(signatures),
+            opened_files: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn opened_file(&self, file_id: u64) -> Result<Arc<OpenedFile>, Status> {
+        self.opened_files
+            .lock()
+            .unwrap()
+            .get(&file_id)
+            .cloned()
+            .ok_or_else(|| Status::failed_precondition(format!("file {} is not opened", file_id)))
+    }
+}
+
+/// The status of a call that failed because the file could not be parsed, with the report
+/// attached as `ParseError` details.
+fn parse_error_status(report: &ParseErrorReport) -> Status {
+    let mut details = Vec::new();
+    ParseError::from(report)
+        .encode(&mut details)
+        .expect("the buffer grows as needed");
+    Status::with_details(
+        Code::InvalidArgument,
+        format!("failed to parse the file: {}", report.context_chain()),
+        details.into(),
+    )
+}
+
+/// The status of a call that failed because the project of the file could not be read or
+/// its changes could not be applied.
+fn project_error_status(error: &ProjectError) -> Status {
+    match error {
+        ProjectError::Patch(PatchError::Parse(report)) => parse_error_status(report),
+        ProjectError::Patch(_) => Status::failed_precondition(error.to_string()),
+        ProjectError::UnknownPatchSet(_)
+        | ProjectError::UnknownStackVariable { .. }
+        | ProjectError::UnknownCallingConvention(_) => Status::not_found(error.to_string()),
+        ProjectError::Database(_) | ProjectError::Serialization(_) => {
+            Status::internal(error.to_string())
+        }
+    }
+}
+
+/// Loads the mapped file. The image is parsed leniently, so that malformed files, which are
+/// common among old and protected executables, can still be analyzed.
+fn load_file(file: &SharedBytes) -> Result<LoadedImage, Status> {
+    let options = ParseOptions {
+        layout: ImageLayout::File,
+        mode: ParseMode::Lenient,
+    };
+    load_pe_buffer(file, options).map_err(|report| parse_error_status(&report))
+}
 
-#[derive(Debug)]
-struct ReicService;
+/// Maps and loads the given file, disassembles it, naming the library functions the
+/// signatures recognize, and opens its project.
+fn open(path: &Path, signatures: &SignatureLibrary) -> Result<OpenedFile, Status> {
+    let file = SharedBytes::map_file(path).map_err(|error| {
+        Status::unavailable(format!("cannot read {}: {}", path.display(), error))
+    })?;
+    let image = load_file(&file)?;
+    let mut disassembly = Disassembler::new(&image)
+        .map_err(|error| Status::internal(format!("cannot disassemble the file: {:?}", error)))?
+        .run();
+    signatures.apply(&image, &mut disassembly);
+    let project =
+        PersistedProject::open(project_path(path)).map_err(|error| project_error_status(&error))?;
+
+    Ok(OpenedFile {
+        file,
+        image,
+        disassembly,
+        project,
+    })
+}
 
 #[tonic::async_trait]
 impl Reic for ReicService {
@@ -25,11 +145,36 @@ impl Reic for ReicService {
     type OpenFileStream =
         Pin<Box<dyn Stream<Item = Result<FileChanges, Status>> + Send + Sync + 'static>>;
 
+    /// Parses, loads and disassembles the file and opens its project. A file that cannot be
+    /// parsed fails the call with a `ParseError`.
     async fn open_file(
         &self,
         request: Request<OpenFileRequest>,
     ) -> Result<Response<Self::OpenFileStream>, Status> {
-        Err(Status::unimplemented("not implemented"))
+        let file_id = request.into_inner().file_id;
+        let path = self
+            .configuration
+            .files
+            .get(file_id as usize)
+            .cloned()
+            .ok_or_else(|| Status::not_found(format!("unknown file {}", file_id)))?;
+        // The project of an opened file stays locked, so the file is only opened once
+        if !self.opened_files.lock().unwrap().contains_key(&file_id) {
+            // Reading and analyzing a large image would stall the other requests on this thread
+            let signatures = Arc::clone(&self.signatures);
+            let file = task::spawn_blocking(move || open(&path, &signatures))
+                .await
+                .map_err(|error| {
+                    Status::internal(format!("loading the file failed: {}", error))
+                })??;
+            self.opened_files
+                .lock()
+                .unwrap()
+                .insert(file_id, Arc::new(file));
+        }
+
+        // Changes to the file are not tracked yet, so the stream ends right away.
+        Ok(Response::new(Box::pin(futures_util::stream::empty())))
     }
 
     async fn sample_command(
@@ -38,4 +183,99 @@ impl Reic for ReicService {
     ) -> Result<Response<SampleCommandResponse>, Status> {
         Err(Status::unimplemented("not implemented"))
     }
+
+    /// Recovers the C++ classes from the run-time type information of an opened file.
+    async fn get_class_hierarchy(
+        &self,
+        request: Request<ClassHierarchyRequest>,
+    ) -> Result<Response<ClassHierarchy>, Status> {
+        let file = self.opened_file(request.into_inner().file_id)?;
+        let hierarchy = reic_analysis::ClassHierarchy::scan(&file.image);
+        Ok(Response::new(class_hierarchy(
+            &hierarchy,
+            &file.disassembly,
+        )))
+    }
+
+    /// Compares the original and patched bytes of the enabled patch sets in the project of an
+    /// opened file.
+    async fn compare_patches(
+        &self,
+        request: Request<ComparePatchesRequest>,
+    ) -> Result<Response<PatchComparisons>, Status> {
+        let file = self.opened_file(request.into_inner().file_id)?;
+        // Parsing the file and assembling the patches would stall the other requests
+        let comparisons = task::spawn_blocking(move || file.project.compare_patches(&file.file))
+            .await
+            .map_err(|error| Status::internal(format!("comparing failed: {}", error)))?
+            .map_err(|error| project_error_status(&error))?;
+        Ok(Response::new(PatchComparisons {
+            comparisons: comparisons.into_iter().map(PatchComparison::from).collect(),
+        }))
+    }
+}
+
+/// Converts the class hierarchy into its message, naming the virtual functions the disassembly
+/// knows the names of.
+fn class_hierarchy(
+    hierarchy: &reic_analysis::ClassHierarchy,
+    disassembly: &Disassembly,
+) -> ClassHierarchy {
+    let function_name = |address: &u64| {
+        disassembly
+            .functions
+            .get(address)
+            .and_then(|function| function.name.clone())
+            .unwrap_or_default()
+    };
+    let classes = hierarchy
+        .classes
+        .values()
+        .map(|class| Class {
+            type_descriptor: class.type_descriptor,
+            name: class.name.clone(),
+            mangled_name: class.mangled_name.clone(),
+            direct_bases: class
+                .direct_bases()
+                .iter()
+                .map(|base| base.type_descriptor)
+                .collect(),
+            vtables: class
+                .vtables
+                .iter()
+                .map(|vtable| VirtualTable {
+                    address: vtable.address,
+                    offset: vtable.offset,
+                    functions: vtable.functions.clone(),
+                    function_names: vtable.functions.iter().map(function_name).collect(),
+                })
+                .collect(),
+        })
+        .collect();
+    ClassHierarchy { classes }
+}
+
+impl From<reic_analysis::patching::PatchComparison> for PatchComparison {
+    fn from(comparison: reic_analysis::patching::PatchComparison) -> Self {
+        PatchComparison {
+            patch_set: comparison.patch_set,
+            address: comparison.address,
+            original: comparison.original,
+            patched: comparison.patched,
+        }
+    }
+}
+
+impl From<&ParseErrorReport> for ParseError {
+    fn from(report: &ParseErrorReport) -> Self {
+        ParseError {
+            context: report.context.clone(),
+            expected: report.expected.clone(),
+            actual: report.actual.clone(),
+            hexdump: report.hexdump.clone(),
+            location: report
+                .offset
+                .map(|offset| parse_error::Location::Offset(offset as u64)),
+        }
+    }
 }